@@ -1,36 +1,204 @@
-use std::{
-    fs::File,
-    io::{Cursor, Read},
-};
+use std::io::Cursor;
 
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use tinyvg::Decoder;
+use tinyvg::decode::DecodeOptions;
+use tinyvg::format::{
+    Color, Command, Image, Point, Rect, Segment, SegmentCommand, SegmentCommandKind, Style,
+};
+use tinyvg::{Decoder, RenderOptions, RenderStrategy};
 
-pub fn criterion_benchmark(c: &mut Criterion) {
-    let mut g = c.benchmark_group("TinyVG");
+/// Encoded files to benchmark, by name: every file in `data/` and the
+/// generated stress files
+fn inputs() -> Vec<(String, Vec<u8>)> {
+    let mut inputs: Vec<(String, Vec<u8>)> = glob::glob("data/*.tvg")
+        .unwrap()
+        .map(|path| {
+            let path = path.unwrap();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, std::fs::read(&path).unwrap())
+        })
+        .collect();
+    inputs.sort();
+
+    for (name, image) in [
+        ("many-commands", many_commands()),
+        ("huge-palette", huge_palette()),
+        ("deep-path", deep_path()),
+    ] {
+        let mut data = Vec::new();
+        image.encode(&mut data).unwrap();
+        inputs.push((name.to_string(), data));
+    }
+
+    inputs
+}
+
+/// everything.tvg repeated many times, so that the same handful of gradients
+/// is used by hundreds of commands
+fn many_commands() -> Image {
+    let data = std::fs::read("data/everything.tvg").unwrap();
+    let mut image = Decoder::new(Cursor::new(data)).decode().unwrap();
+    let commands = image.commands.clone();
+    image.commands = (0..50).flat_map(|_| commands.clone()).collect();
+
+    image
+}
+
+/// A grid of squares, each with its own color
+fn huge_palette() -> Image {
+    const SIDE: u32 = 64;
+
+    let mut image = Image::empty(SIDE * 4, SIDE * 4);
+    for index in 0..SIDE * SIDE {
+        let (x, y) = ((index % SIDE) as f64 * 4.0, (index / SIDE) as f64 * 4.0);
+
+        image
+            .color_table
+            .push(Color::rgb8((index % 256) as u8, (index / 256) as u8, 0x80));
+        image.commands.push(Command::FillRectangles {
+            fill_style: Style::FlatColor {
+                color_index: index as usize,
+            },
+            rectangles: vec![Rect::new(x, y, x + 4.0, y + 4.0)],
+            outline: None,
+        });
+    }
+
+    image
+}
+
+/// A single path with thousands of curves, spiralling into the center
+fn deep_path() -> Image {
+    const CURVES: usize = 5_000;
 
-    let mut f = File::open("data/tiger.tvg").unwrap();
-    let mut data = Vec::new();
-    f.read_to_end(&mut data).unwrap();
+    let mut image = Image::empty(256, 256);
+    image.color_table.push(Color::rgb8(0x20, 0x40, 0xc0));
 
-    g.bench_function(BenchmarkId::new("decode", "tiger.tvg"), |b| {
-        b.iter(|| {
-            let p = Decoder::new(Cursor::new(&data));
+    let center = Point::new(128.0, 128.0);
+    let at = |t: f64| {
+        let radius = 120.0 * (1.0 - t);
+        let angle = t * 200.0;
+        center + radius * kurbo::Vec2::from_angle(angle)
+    };
 
-            black_box(p.decode().unwrap());
+    let commands = (1..=CURVES)
+        .map(|i| {
+            let t = i as f64 / CURVES as f64;
+            let mid = (i as f64 - 0.5) / CURVES as f64;
+
+            SegmentCommand {
+                kind: SegmentCommandKind::QuadraticBezier {
+                    control: at(mid),
+                    point_1: at(t),
+                },
+                line_width: None,
+            }
         })
+        .collect();
+
+    image.commands.push(Command::DrawLinePath {
+        line_style: Style::FlatColor { color_index: 0 },
+        line_width: 1.0,
+        path: vec![Segment {
+            start: at(0.0),
+            commands,
+        }],
     });
 
-    g.bench_function(BenchmarkId::new("render", "tiger.tvg"), |b| {
-        let p = Decoder::new(Cursor::new(&data));
-        let image = p.decode().unwrap();
-        let mut data = Vec::new();
+    image
+}
 
-        b.iter(|| {
-            black_box(image.render_png(&mut data).unwrap());
-            data.clear();
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let inputs = inputs();
+    let images: Vec<(&str, Image)> = inputs
+        .iter()
+        .map(|(name, data)| {
+            let image = Decoder::new(Cursor::new(data)).decode().unwrap();
+            (name.as_str(), image)
         })
-    });
+        .collect();
+
+    let mut g = c.benchmark_group("decode");
+    for (name, data) in &inputs {
+        g.bench_with_input(BenchmarkId::new("default", name), data, |b, data| {
+            b.iter(|| black_box(Decoder::new(Cursor::new(data)).decode().unwrap()))
+        });
+
+        g.bench_with_input(BenchmarkId::new("from-slice", name), data, |b, data| {
+            b.iter(|| black_box(Decoder::from_slice(data).decode().unwrap()))
+        });
+
+        g.bench_with_input(BenchmarkId::new("with-bounds", name), data, |b, data| {
+            let options = DecodeOptions {
+                compute_bounds: true,
+                ..Default::default()
+            };
+
+            b.iter(|| {
+                black_box(
+                    Decoder::new(Cursor::new(data))
+                        .with_options(options)
+                        .decode()
+                        .unwrap(),
+                )
+            })
+        });
+    }
+    g.finish();
+
+    let mut g = c.benchmark_group("render");
+    g.sample_size(20);
+    for (name, image) in &images {
+        for (strategy_name, strategy) in [
+            ("sequential", RenderStrategy::Sequential),
+            ("batched", RenderStrategy::Batched),
+        ] {
+            let options = RenderOptions {
+                strategy,
+                ..Default::default()
+            };
+
+            g.bench_with_input(BenchmarkId::new(strategy_name, name), image, |b, image| {
+                let mut data = Vec::new();
+
+                b.iter(|| {
+                    image.render_png_with_options(&mut data, &options).unwrap();
+                    black_box(&data);
+                    data.clear();
+                })
+            });
+        }
+    }
+    g.finish();
+
+    let mut g = c.benchmark_group("encode");
+    for (name, image) in &images {
+        g.bench_with_input(BenchmarkId::from_parameter(name), image, |b, image| {
+            let mut data = Vec::new();
+
+            b.iter(|| {
+                image.encode(&mut data).unwrap();
+                black_box(&data);
+                data.clear();
+            })
+        });
+    }
+    g.finish();
+
+    let mut g = c.benchmark_group("round-trip");
+    for (name, data) in &inputs {
+        g.bench_with_input(BenchmarkId::from_parameter(name), data, |b, data| {
+            let mut encoded = Vec::new();
+
+            b.iter(|| {
+                let image = Decoder::new(Cursor::new(data)).decode().unwrap();
+                image.encode(&mut encoded).unwrap();
+                black_box(&encoded);
+                encoded.clear();
+            })
+        });
+    }
+    g.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,112 @@
+//! Build the library with each combination of cargo features and report the
+//! size of the linked library, to help choose features for size-constrained
+//! targets.
+//!
+//! Each configuration is linked as a stripped `dylib` optimized for size, so
+//! that the sizes include the dependencies the features pull in. A `cdylib`
+//! would be smaller, but only keeps the code reachable from `extern "C"`
+//! functions, which is none without the `ffi` feature. Configurations that
+//! fail to build, e.g. for lack of system libraries, are reported and
+//! skipped.
+//!
+//! ```text
+//! $ cargo run --example features-report
+//! ```
+
+use std::path::Path;
+use std::process::Command;
+
+use eyre::{Context, Result};
+
+/// Name of each configuration and the features it is built with, on top of
+/// `--no-default-features`
+const CONFIGURATIONS: &[(&str, &[&str])] = &[
+    ("decode-only", &[]),
+    ("+text", &["text"]),
+    ("+bytes", &["bytes"]),
+    ("+decode-stats", &["decode-stats"]),
+    ("+piet", &["piet"]),
+    ("+render-png", &["render-png"]),
+    ("+render-skia", &["render-skia"]),
+    ("+render-gpu", &["render-gpu"]),
+    ("+raster-formats", &["raster-formats"]),
+    ("+animation", &["animation"]),
+    ("+wasm", &["wasm"]),
+    ("+ffi", &["ffi"]),
+    ("+async", &["async"]),
+    ("+python", &["python"]),
+    ("+chart", &["chart"]),
+    ("+import-svg", &["import-svg"]),
+    ("+generate", &["generate"]),
+    ("+icon-theme", &["icon-theme"]),
+];
+
+fn main() -> Result<()> {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+
+    let mut sizes = Vec::new();
+
+    for (name, features) in CONFIGURATIONS {
+        // A target directory per configuration, so that the configurations
+        // don't invalidate each other's builds
+        let target_dir = manifest_dir
+            .join("target")
+            .join("features-report")
+            .join(name);
+
+        eprintln!("building {}", name);
+
+        let status = Command::new(&cargo)
+            .current_dir(manifest_dir)
+            .args(["rustc", "--release", "--lib", "--no-default-features"])
+            .args(["--crate-type", "dylib"])
+            .arg("--features")
+            .arg(features.join(","))
+            .arg("--target-dir")
+            .arg(&target_dir)
+            // Environment rather than rustc flags, so that the dependencies
+            // are optimized for size too
+            .env("CARGO_PROFILE_RELEASE_OPT_LEVEL", "z")
+            .env("CARGO_PROFILE_RELEASE_STRIP", "symbols")
+            .status()
+            .wrap_err("failed to run cargo")?;
+
+        if !status.success() {
+            eprintln!("building {} failed, skipping it", name);
+            sizes.push((name, None));
+            continue;
+        }
+
+        let library = target_dir.join("release").join(format!(
+            "{}tinyvg{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        ));
+        let size = std::fs::metadata(&library)
+            .wrap_err_with(|| format!("failed to read {}", library.display()))?
+            .len();
+
+        sizes.push((name, Some(size)));
+    }
+
+    // Sizes relative to the first configuration, which has no features
+    let base = sizes.first().and_then(|(_, size)| *size);
+
+    println!("{:<20} {:>12} {:>12}", "configuration", "size", "added");
+
+    for (name, size) in sizes {
+        match (size, base) {
+            (Some(size), Some(base)) => println!(
+                "{:<20} {:>8} KiB {:>8} KiB",
+                name,
+                size / 1024,
+                (size as i64 - base as i64) / 1024
+            ),
+            (Some(size), None) => println!("{:<20} {:>8} KiB", name, size / 1024),
+            (None, _) => println!("{:<20} {:>12}", name, "build failed"),
+        }
+    }
+
+    Ok(())
+}
@@ -3,7 +3,7 @@ use std::time::Instant;
 use eyre::Result;
 
 fn main() -> Result<()> {
-    eprintln!("{:<20} {}", "path", "render time");
+    eprintln!("{:<20} render time", "path");
 
     for path in glob::glob("./data/*.tvg")? {
         let path = path?;
@@ -0,0 +1,14 @@
+//! Decoding with the default limits must fail with an error rather than
+//! panic or run out of memory, and whatever decodes must encode again.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tinyvg::Decoder;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(image) = Decoder::new(data).decode() {
+        let mut bytes = Vec::new();
+        let _ = image.encode(&mut bytes);
+    }
+});
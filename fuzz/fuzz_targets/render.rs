@@ -0,0 +1,31 @@
+//! Decoded images must render to RGBA pixels without panicking.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tinyvg::{Decoder, PrimitiveLimit, RenderOptions};
+
+/// Images with more pixels are skipped, so that allocations of huge surfaces
+/// aren't reported as crashes
+const MAX_PIXELS: u64 = 1024 * 1024;
+
+fuzz_target!(|data: &[u8]| {
+    let image = match Decoder::new(data).decode() {
+        Ok(image) => image,
+        Err(_) => return,
+    };
+
+    if image.header.width as u64 * image.header.height as u64 > MAX_PIXELS {
+        return;
+    }
+
+    let options = RenderOptions {
+        max_primitives: Some(PrimitiveLimit {
+            count: 10_000,
+            truncate: true,
+        }),
+        ..Default::default()
+    };
+
+    let _ = image.render_pixmap_with_options(&options);
+});
@@ -0,0 +1,15 @@
+//! Parsing the text format must fail with an error rather than panic, and
+//! whatever parses must be written back as text.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tinyvg::text_format::{parse_text, write_text};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let Ok(image) = parse_text(text) {
+            let _ = write_text(&image);
+        }
+    }
+});
@@ -0,0 +1,87 @@
+#ifndef TINYVG_H
+#define TINYVG_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// A decoded image, only handled through pointers
+typedef struct TinyvgImage TinyvgImage;
+
+// Straight (not premultiplied) RGBA pixels, row by row without padding
+typedef struct TinyvgPixmap {
+  // Width in pixels
+  uint32_t width;
+  // Height in pixels
+  uint32_t height;
+  // `width * height * 4` bytes, owned by this crate
+  uint8_t *data;
+  // Number of bytes in `data`
+  size_t len;
+} TinyvgPixmap;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Decode a TinyVG file, with the limits of `DecodeOptions::default`. Returns
+// null on failure. Free the image with `tinyvg_free`.
+//
+// # Safety
+//
+// `data` must point to `len` readable bytes.
+TinyvgImage *tinyvg_decode(const uint8_t *data, size_t len);
+
+// Width of an image from its header, in image units
+//
+// # Safety
+//
+// `image` must be a pointer returned by `tinyvg_decode`.
+uint32_t tinyvg_width(const TinyvgImage *image);
+
+// Height of an image from its header, in image units
+//
+// # Safety
+//
+// `image` must be a pointer returned by `tinyvg_decode`.
+uint32_t tinyvg_height(const TinyvgImage *image);
+
+// Render an image at `scale` times the size in its header into `pixmap`.
+// Returns false on failure, in which case `pixmap` is left untouched. Free
+// the pixels with `tinyvg_pixmap_free`. Fails if the crate was built
+// without a renderer (`render-png` or `render-skia`).
+//
+// # Safety
+//
+// `image` must be a pointer returned by `tinyvg_decode`, and `pixmap` must
+// point to writable memory for a `TinyvgPixmap`.
+bool tinyvg_render_rgba(const TinyvgImage *image, double scale, TinyvgPixmap *pixmap);
+
+// Free the pixels of a pixmap filled by `tinyvg_render_rgba`, and reset it
+// to an empty pixmap
+//
+// # Safety
+//
+// `pixmap` must be null or point to a pixmap filled by
+// `tinyvg_render_rgba` that hasn't been freed.
+void tinyvg_pixmap_free(TinyvgPixmap *pixmap);
+
+// Free an image returned by `tinyvg_decode`
+//
+// # Safety
+//
+// `image` must be null or a pointer returned by `tinyvg_decode` that hasn't
+// been freed.
+void tinyvg_free(TinyvgImage *image);
+
+// Message of the last failure on this thread, or null if nothing failed
+// yet. The string is valid until the next failing call on this thread.
+const char *tinyvg_last_error(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* TINYVG_H */
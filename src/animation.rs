@@ -0,0 +1,361 @@
+//! Animated PNG and GIF output from a sequence of images, e.g. icon animations
+//! whose frames are kept as separate TinyVG files, or frames interpolated
+//! between keyframes with a `Timeline`. Enabled by the `animation` feature.
+//!
+//! ```
+//! # use std::fs::File;
+//! # use std::time::Duration;
+//! # use tinyvg::animation::{write_animation, AnimationFormat};
+//! # use tinyvg::Decoder;
+//! let frame = Decoder::new(File::open("data/shield.tvg").unwrap())
+//!     .decode()
+//!     .unwrap();
+//!
+//! let mut dimmed = frame.clone();
+//! dimmed.color_table[0] = dimmed.color_table[0].with_alpha(0.5);
+//!
+//! let frames = [frame, dimmed];
+//!
+//! let mut apng = Vec::new();
+//! write_animation(&frames, Duration::from_millis(250), AnimationFormat::Png, &mut apng).unwrap();
+//!
+//! let mut gif = Vec::new();
+//! write_animation(&frames, Duration::from_millis(250), AnimationFormat::Gif, &mut gif).unwrap();
+//! assert!(gif.starts_with(b"GIF89a"));
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{ensure, format_err, Context, Error, Result};
+use crate::format::Image;
+
+/// Quality of GIF color quantization, from 1 (best, slowest) to 30
+const GIF_SPEED: i32 = 10;
+
+/// File format of an animation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    /// Animated PNG, with full color and alpha. Viewers without APNG support
+    /// show the first frame.
+    Png,
+
+    /// GIF, with at most 256 colors per frame and transparency without
+    /// partial alpha
+    Gif,
+}
+
+impl AnimationFormat {
+    /// File extension for this format, without the leading dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            AnimationFormat::Png => "png",
+            AnimationFormat::Gif => "gif",
+        }
+    }
+}
+
+impl std::str::FromStr for AnimationFormat {
+    type Err = Error;
+
+    /// Parse a format from its file extension
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "png" | "apng" => Ok(AnimationFormat::Png),
+            "gif" => Ok(AnimationFormat::Gif),
+            _ => Err(format_err!("unknown animation format {}", s)),
+        }
+    }
+}
+
+/// Render every frame at the size in its header and write them as an
+/// animation that loops forever, showing each frame for `delay`. Every frame
+/// must have the same size. Delays are rounded to milliseconds for PNG and to
+/// hundredths of a second for GIF.
+pub fn write_animation(
+    frames: &[Image],
+    delay: Duration,
+    format: AnimationFormat,
+    writer: &mut impl std::io::Write,
+) -> Result<()> {
+    let first = frames
+        .first()
+        .ok_or_else(|| format_err!("an animation needs at least one frame"))?;
+    let (width, height) = (first.header.width, first.header.height);
+
+    for (index, frame) in frames.iter().enumerate() {
+        ensure!(
+            (frame.header.width, frame.header.height) == (width, height),
+            "frame {} is {}x{}, but the first frame is {}x{}",
+            index,
+            frame.header.width,
+            frame.header.height,
+            width,
+            height
+        );
+    }
+
+    let pixmaps = frames
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            frame
+                .render_pixmap()
+                .wrap_err_with(|| format!("failed to render frame {}", index))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    match format {
+        AnimationFormat::Png => {
+            let delay_ms = u16::try_from(delay.as_millis()).unwrap_or(u16::MAX);
+
+            let mut encoder = png::Encoder::new(writer, width, height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder
+                .set_animated(pixmaps.len() as u32, 0)
+                .wrap_err("failed to set up animated PNG")?;
+            encoder
+                .set_frame_delay(delay_ms, 1000)
+                .wrap_err("failed to set frame delay")?;
+
+            let mut writer = encoder
+                .write_header()
+                .wrap_err("failed to write PNG header")?;
+            for pixmap in &pixmaps {
+                writer
+                    .write_image_data(&pixmap.as_rgba8())
+                    .wrap_err("failed to write frame")?;
+            }
+            writer.finish().wrap_err("failed to finish PNG")?;
+        }
+        AnimationFormat::Gif => {
+            ensure!(
+                width <= u16::MAX as u32 && height <= u16::MAX as u32,
+                "GIF images can be at most {} pixels wide",
+                u16::MAX
+            );
+
+            let delay_cs = u16::try_from((delay.as_millis() + 5) / 10).unwrap_or(u16::MAX);
+
+            let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &[])
+                .wrap_err("failed to write GIF header")?;
+            encoder
+                .set_repeat(gif::Repeat::Infinite)
+                .wrap_err("failed to set GIF repetition")?;
+
+            for pixmap in pixmaps {
+                let mut rgba = pixmap.as_rgba8().into_owned();
+                let mut frame =
+                    gif::Frame::from_rgba_speed(width as u16, height as u16, &mut rgba, GIF_SPEED);
+                frame.delay = delay_cs;
+                // Transparent pixels of a frame would show the previous frame
+                frame.dispose = gif::DisposalMethod::Background;
+
+                encoder
+                    .write_frame(&frame)
+                    .wrap_err("failed to write frame")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Pace of the interpolation from one keyframe to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    /// Constant speed
+    Linear,
+
+    /// Starts slow and speeds up
+    EaseIn,
+
+    /// Starts fast and slows down
+    EaseOut,
+
+    /// Starts and ends slow
+    EaseInOut,
+
+    /// Holds the previous keyframe, then jumps to the next one
+    Step,
+}
+
+impl Easing {
+    /// Progress of the interpolation at `t` between 0 and 1
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Step => {
+                if t < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+/// An image at a point in time of a `Timeline`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    /// Time from the start of the animation
+    pub time: Duration,
+
+    /// Image shown at `time`
+    pub image: Image,
+
+    /// Pace of the interpolation from the previous keyframe to this one
+    pub easing: Easing,
+}
+
+/// Keyframes of an animation. Frames between keyframes are interpolated with
+/// `Image::lerp`, so consecutive keyframes must have the same structure.
+///
+/// ```
+/// # use std::fs::File;
+/// # use std::time::Duration;
+/// # use tinyvg::animation::{AnimationFormat, Easing, Timeline};
+/// # use tinyvg::Decoder;
+/// let icon = Decoder::new(File::open("data/shield.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let mut faded = icon.clone();
+/// faded.color_table[0] = faded.color_table[0].with_alpha(0.0);
+///
+/// let timeline = Timeline::new(icon.clone())
+///     .then(Duration::from_millis(300), faded, Easing::EaseOut)
+///     .then(Duration::from_millis(300), icon, Easing::EaseIn);
+///
+/// assert_eq!(timeline.duration(), Duration::from_millis(600));
+///
+/// let frames = timeline.frames(30).unwrap();
+/// assert_eq!(frames.len(), 19);
+///
+/// let mut apng = Vec::new();
+/// timeline.write_animation(30, AnimationFormat::Png, &mut apng).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+    /// Keyframes ordered by time, the first one at zero
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Timeline {
+    /// Timeline that starts with `image`
+    pub fn new(image: Image) -> Self {
+        Self {
+            keyframes: vec![Keyframe {
+                time: Duration::ZERO,
+                image,
+                easing: Easing::Linear,
+            }],
+        }
+    }
+
+    /// Add a keyframe `after` the last one, interpolated towards with
+    /// `easing`
+    pub fn then(mut self, after: Duration, image: Image, easing: Easing) -> Self {
+        let time = self.duration() + after;
+        self.keyframes.push(Keyframe {
+            time,
+            image,
+            easing,
+        });
+
+        self
+    }
+
+    /// Time of the last keyframe
+    pub fn duration(&self) -> Duration {
+        self.keyframes
+            .last()
+            .map_or(Duration::ZERO, |keyframe| keyframe.time)
+    }
+
+    /// Image at `time`, interpolated between the keyframes around it.
+    /// Before the first keyframe and after the last one, the image of that
+    /// keyframe is shown.
+    pub fn frame_at(&self, time: Duration) -> Result<Image> {
+        let first = self
+            .keyframes
+            .first()
+            .ok_or_else(|| format_err!("a timeline needs at least one keyframe"))?;
+
+        let next = match self.keyframes.iter().position(|k| k.time > time) {
+            Some(0) => return Ok(first.image.clone()),
+            Some(next) => next,
+            None => return Ok(self.keyframes[self.keyframes.len() - 1].image.clone()),
+        };
+
+        let (from, to) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - from.time).as_secs_f64() / (to.time - from.time).as_secs_f64();
+
+        Image::lerp(&from.image, &to.image, to.easing.apply(t))
+            .wrap_err_with(|| format!("failed to interpolate towards keyframe {}", next))
+    }
+
+    /// Every frame of the animation at `fps` frames per second, from the
+    /// first keyframe up to and including the last one
+    pub fn frames(&self, fps: u32) -> Result<Vec<Image>> {
+        ensure!(fps > 0, "frame rate must be at least 1");
+
+        let count = (self.duration().as_secs_f64() * fps as f64).round() as u32 + 1;
+
+        (0..count)
+            .map(|frame| self.frame_at(Duration::from_secs_f64(frame as f64 / fps as f64)))
+            .collect()
+    }
+
+    /// Render the frames at `fps` frames per second as an animation, see
+    /// `write_animation`
+    pub fn write_animation(
+        &self,
+        fps: u32,
+        format: AnimationFormat,
+        writer: &mut impl std::io::Write,
+    ) -> Result<()> {
+        let frames = self.frames(fps)?;
+
+        write_animation(
+            &frames,
+            Duration::from_secs_f64(1.0 / fps as f64),
+            format,
+            writer,
+        )
+    }
+
+    /// Render the frames at `fps` frames per second to numbered PNG files in
+    /// `directory`, e.g. `frame_00000.png`, as video encoders take them.
+    /// Returns the paths of the files.
+    pub fn write_png_sequence(&self, fps: u32, directory: &Path) -> Result<Vec<PathBuf>> {
+        let frames = self.frames(fps)?;
+
+        std::fs::create_dir_all(directory)
+            .wrap_err_with(|| format!("failed to create {}", directory.display()))?;
+
+        frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                let path = directory.join(format!("frame_{:05}.png", index));
+
+                let mut png = Vec::new();
+                frame
+                    .render_png(&mut png)
+                    .wrap_err_with(|| format!("failed to render frame {}", index))?;
+                std::fs::write(&path, png)
+                    .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+
+                Ok(path)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,627 @@
+//! Replacement of gradients with bands of flat color, for renderers and
+//! exporters that can only fill with flat colors, e.g. rasterizers for
+//! embedded displays. Each command drawn with a gradient is split into one
+//! command per band, clipped to the part of its geometry where the gradient
+//! is within the band, and filled or stroked with the gradient's color at the
+//! band. More bands approximate the gradient more closely, at the cost of
+//! more commands.
+//!
+//! ```
+//! # use tinyvg::format::{Color, Command, Image, Point, Rect, Style};
+//! let mut image = Image::empty(64, 16);
+//! image.color_table = vec![Color::BLACK, Color::WHITE];
+//! image.commands.push(Command::FillRectangles {
+//!     fill_style: Style::LinearGradient {
+//!         point_0: Point::new(0.0, 0.0),
+//!         point_1: Point::new(64.0, 0.0),
+//!         color_index_0: 0,
+//!         color_index_1: 1,
+//!     },
+//!     rectangles: vec![Rect::new(0.0, 0.0, 64.0, 16.0)],
+//!     outline: None,
+//! });
+//!
+//! image.band_gradients(8, 0.25).unwrap();
+//!
+//! assert_eq!(image.commands.len(), 8);
+//! assert!(image
+//!     .styles()
+//!     .all(|style| matches!(style, Style::FlatColor { .. })));
+//! ```
+
+use std::f64::consts::PI;
+
+use kurbo::{BezPath, PathEl, Point, Rect, Shape, Vec2};
+
+use crate::error::{ensure, Result};
+use crate::format::{
+    Color, Command, Image, OutlineStyle, Segment, SegmentCommand, SegmentCommandKind, Style,
+};
+use crate::geometry::{has_area, rect_segment};
+use crate::render::{bez_path, path_strokes, polygon_path, ARC_TOLERANCE};
+
+/// Most pieces a stroke edge or a circle is split into, so that a tiny
+/// tolerance or band can't make banding run out of memory
+const MAX_PIECES: usize = 4096;
+
+impl Image {
+    /// Replace every gradient of this image with `bands` bands of flat color,
+    /// see the module documentation. Curves of banded commands are flattened
+    /// into lines no further than `tolerance` units from the curve. Commands
+    /// drawn with flat colors are left unchanged, and degenerate gradients
+    /// are replaced by their first color.
+    pub fn band_gradients(&mut self, bands: usize, tolerance: f64) -> Result<()> {
+        ensure!(bands > 0, "number of gradient bands must be at least 1");
+        ensure!(
+            tolerance > 0.0,
+            "banding tolerance must be positive, got {}",
+            tolerance
+        );
+
+        if !self
+            .styles()
+            .any(|style| !matches!(style, Style::FlatColor { .. }))
+        {
+            return Ok(());
+        }
+
+        let mut banded = self.clone();
+        banded.commands.clear();
+        banded.bounds = None;
+
+        for command in &self.commands {
+            let fill = match command {
+                Command::FillPolygon { fill_style, .. }
+                | Command::FillRectangles { fill_style, .. }
+                | Command::FillPath { fill_style, .. } => Some(fill_style),
+                _ => None,
+            };
+
+            let stroke = match command {
+                Command::FillPolygon { outline, .. }
+                | Command::FillRectangles { outline, .. }
+                | Command::FillPath { outline, .. } => outline.as_ref().map(|o| &o.line_style),
+                Command::DrawLines { line_style, .. }
+                | Command::DrawLineLoop { line_style, .. }
+                | Command::DrawLinePath { line_style, .. } => Some(line_style),
+            };
+
+            let is_gradient = |style: Option<&Style>| {
+                style.is_some_and(|style| !matches!(style, Style::FlatColor { .. }))
+            };
+
+            if !is_gradient(fill) && !is_gradient(stroke) {
+                banded.commands.push(command.clone());
+                continue;
+            }
+
+            if let Some(fill_style) = fill {
+                if is_gradient(fill) {
+                    let bands = Bands::new(&mut banded, fill_style, bands)?;
+                    banded.band_fill(command, &bands, tolerance)?;
+                } else {
+                    banded.commands.push(without_outline(command));
+                }
+            }
+
+            if let Some(line_style) = stroke {
+                if is_gradient(stroke) {
+                    let bands = Bands::new(&mut banded, line_style, bands)?;
+                    banded.band_strokes(command, &bands, tolerance)?;
+                } else if fill.is_some() {
+                    banded.commands.push(outline_command(command));
+                } else {
+                    banded.commands.push(command.clone());
+                }
+            }
+        }
+
+        *self = banded;
+
+        Ok(())
+    }
+
+    /// Push one fill command per band, with the geometry of `command` clipped
+    /// to the band
+    fn band_fill(&mut self, command: &Command, bands: &Bands, tolerance: f64) -> Result<()> {
+        let shape = match command {
+            Command::FillPolygon { polygon, .. } if !polygon.is_empty() => polygon_path(polygon),
+            Command::FillRectangles { rectangles, .. } => {
+                let mut shape = BezPath::new();
+                for rect in rectangles.iter().filter(|r| has_area(r)) {
+                    for element in rect.path_elements(ARC_TOLERANCE) {
+                        shape.push(element);
+                    }
+                }
+                shape
+            }
+            Command::FillPath { path, .. } => bez_path(path)?,
+            _ => return Ok(()),
+        };
+
+        let polygons = flatten(&shape, tolerance, true);
+        let bounds = shape.bounding_box();
+
+        for (band, color_index) in bands.colors.iter().enumerate() {
+            let mut path = Vec::new();
+
+            for region in bands.regions(band, bounds, tolerance) {
+                for polygon in &polygons {
+                    let clipped = clip_convex(polygon, &region);
+
+                    if clipped.len() >= 3 {
+                        path.push(polygon_segment(&clipped));
+                    }
+                }
+            }
+
+            if !path.is_empty() {
+                self.commands.push(Command::FillPath {
+                    fill_style: Style::FlatColor {
+                        color_index: *color_index,
+                    },
+                    path,
+                    outline: None,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Push one line strip for every run of a stroke of `command` that stays
+    /// in the same band
+    fn band_strokes(&mut self, command: &Command, bands: &Bands, tolerance: f64) -> Result<()> {
+        let mut strokes: Vec<(Vec<Point>, f64)> = Vec::new();
+
+        match command {
+            Command::FillPolygon {
+                polygon,
+                outline: Some(outline),
+                ..
+            } if !polygon.is_empty() => {
+                let mut points = polygon.clone();
+                points.push(polygon[0]);
+                strokes.push((points, outline.line_width));
+            }
+            Command::FillRectangles {
+                rectangles,
+                outline: Some(outline),
+                ..
+            } => {
+                for rect in rectangles.iter().filter(|r| has_area(r)) {
+                    for points in flatten(&rect.to_path(ARC_TOLERANCE), tolerance, false) {
+                        strokes.push((points, outline.line_width));
+                    }
+                }
+            }
+            Command::FillPath {
+                path,
+                outline: Some(outline),
+                ..
+            } => {
+                for (stroke, line_width) in path_strokes(outline.line_width, path)? {
+                    for points in flatten(&stroke, tolerance, false) {
+                        strokes.push((points, line_width));
+                    }
+                }
+            }
+            Command::DrawLines {
+                line_width, lines, ..
+            } => {
+                for line in lines {
+                    strokes.push((vec![line.p0, line.p1], *line_width));
+                }
+            }
+            Command::DrawLineLoop {
+                line_width,
+                close_path,
+                points,
+                ..
+            } if !points.is_empty() => {
+                let mut points = points.clone();
+                if *close_path {
+                    points.push(points[0]);
+                }
+                strokes.push((points, *line_width));
+            }
+            Command::DrawLinePath {
+                line_width, path, ..
+            } => {
+                for (stroke, line_width) in path_strokes(*line_width, path)? {
+                    for points in flatten(&stroke, tolerance, false) {
+                        strokes.push((points, line_width));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        for (points, line_width) in strokes {
+            for (band, run) in bands.runs(&points) {
+                self.commands.push(Command::DrawLineLoop {
+                    line_style: Style::FlatColor {
+                        color_index: bands.colors[band],
+                    },
+                    line_width,
+                    close_path: false,
+                    points: run,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A gradient divided into bands
+struct Bands {
+    radial: bool,
+    point_0: Point,
+    point_1: Point,
+
+    /// Index into the color table of the color of each band
+    colors: Vec<usize>,
+}
+
+impl Bands {
+    /// Bands of `style`, adding their colors to the color table of `image`.
+    /// Flat colors and degenerate gradients have a single band.
+    fn new(image: &mut Image, style: &Style, bands: usize) -> Result<Self> {
+        let (radial, point_0, point_1, color_0, color_1) = match *style {
+            Style::LinearGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => (false, point_0, point_1, color_index_0, color_index_1),
+            Style::RadialGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => (true, point_0, point_1, color_index_0, color_index_1),
+            Style::FlatColor { color_index } => {
+                (false, Point::ZERO, Point::ZERO, color_index, color_index)
+            }
+        };
+
+        if point_0 == point_1 {
+            image.color(color_0)?;
+
+            return Ok(Self {
+                radial,
+                point_0,
+                point_1,
+                colors: vec![color_0],
+            });
+        }
+
+        let (color_0, color_1) = (image.color(color_0)?, image.color(color_1)?);
+
+        // The first and last bands get the end colors, as the areas beyond
+        // the ends of the gradient do
+        let colors = (0..bands)
+            .map(|band| {
+                let t = if bands == 1 {
+                    0.5
+                } else {
+                    band as f64 / (bands - 1) as f64
+                };
+
+                color_index(image, lerp_color(&color_0, &color_1, t))
+            })
+            .collect();
+
+        Ok(Self {
+            radial,
+            point_0,
+            point_1,
+            colors,
+        })
+    }
+
+    /// Position of `point` along the gradient, clamped to `0..=1`
+    fn offset(&self, point: Point) -> f64 {
+        let axis = self.point_1 - self.point_0;
+        let offset = point - self.point_0;
+
+        if axis.hypot2() == 0.0 {
+            return 0.0;
+        }
+
+        let t = if self.radial {
+            offset.hypot() / axis.hypot()
+        } else {
+            offset.dot(axis) / axis.hypot2()
+        };
+
+        t.clamp(0.0, 1.0)
+    }
+
+    fn band(&self, point: Point) -> usize {
+        let bands = self.colors.len();
+        ((self.offset(point) * bands as f64) as usize).min(bands - 1)
+    }
+
+    /// Convex polygons covering the part of the plane where the gradient is
+    /// within `band`, as far as geometry within `bounds` can reach
+    fn regions(&self, band: usize, bounds: Rect, tolerance: f64) -> Vec<Vec<Point>> {
+        let bands = self.colors.len();
+
+        // Farther from the gradient than any geometry
+        let far = [bounds.origin(), Point::new(bounds.x1, bounds.y1)]
+            .iter()
+            .chain(&[
+                Point::new(bounds.x0, bounds.y1),
+                Point::new(bounds.x1, bounds.y0),
+            ])
+            .map(|corner| (*corner - self.point_0).hypot())
+            .fold(0.0, f64::max)
+            * 2.0
+            + 1.0;
+
+        let axis = self.point_1 - self.point_0;
+        let length = axis.hypot();
+
+        if bands == 1 || length == 0.0 {
+            let r = Vec2::new(far, far);
+            let (min, max) = (self.point_0 - r, self.point_0 + r);
+            return vec![vec![
+                min,
+                Point::new(max.x, min.y),
+                max,
+                Point::new(min.x, max.y),
+            ]];
+        }
+
+        let inner = band as f64 / bands as f64 * length;
+        let outer = if band + 1 == bands {
+            far.max(length)
+        } else {
+            (band + 1) as f64 / bands as f64 * length
+        };
+
+        if self.radial {
+            let inner = if band == 0 { 0.0 } else { inner };
+
+            // Chords of the outer circle stay within the tolerance of it
+            let step = 2.0 * (1.0 - (tolerance / outer).min(1.0)).acos();
+            let sectors = ((2.0 * PI / step).ceil() as usize).clamp(8, MAX_PIECES);
+            let at = |radius: f64, sector: usize| {
+                let angle = 2.0 * PI * sector as f64 / sectors as f64;
+                self.point_0 + Vec2::from_angle(angle) * radius
+            };
+
+            (0..sectors)
+                .map(|sector| {
+                    let mut region = vec![at(outer, sector), at(outer, sector + 1)];
+                    if inner > 0.0 {
+                        region.push(at(inner, sector + 1));
+                        region.push(at(inner, sector));
+                    } else {
+                        region.push(self.point_0);
+                    }
+                    region
+                })
+                .collect()
+        } else {
+            let along = axis / length;
+            let across = Vec2::new(-along.y, along.x) * far;
+            let start = if band == 0 { -far } else { inner };
+
+            let (from, to) = (self.point_0 + along * start, self.point_0 + along * outer);
+            vec![vec![from - across, to - across, to + across, from + across]]
+        }
+    }
+
+    /// Split a polyline into runs of consecutive points in the same band.
+    /// Edges are split into pieces of at most half a band, and each piece
+    /// belongs to the band of its middle.
+    fn runs(&self, points: &[Point]) -> Vec<(usize, Vec<Point>)> {
+        let bands = self.colors.len();
+        let step = (self.point_1 - self.point_0).hypot() / bands as f64 / 2.0;
+        let mut runs: Vec<(usize, Vec<Point>)> = Vec::new();
+
+        for edge in points.windows(2) {
+            let (from, to) = (edge[0], edge[1]);
+            let pieces = if step > 0.0 {
+                ((to - from).hypot() / step).ceil() as usize
+            } else {
+                1
+            };
+            let pieces = pieces.clamp(1, MAX_PIECES);
+
+            // The last piece ends exactly on the next edge's start, so that
+            // runs continue across corners
+            let at = |piece: usize| {
+                if piece == pieces {
+                    to
+                } else {
+                    from.lerp(to, piece as f64 / pieces as f64)
+                }
+            };
+
+            for piece in 0..pieces {
+                let (start, end) = (at(piece), at(piece + 1));
+                let band = self.band(start.midpoint(end));
+
+                match runs.last_mut() {
+                    Some((run_band, run)) if *run_band == band && run.last() == Some(&start) => {
+                        run.push(end)
+                    }
+                    _ => runs.push((band, vec![start, end])),
+                }
+            }
+        }
+
+        runs
+    }
+}
+
+/// Index of `color` in the color table, adding it if it isn't there
+fn color_index(image: &mut Image, color: Color) -> usize {
+    let existing = image
+        .color_table
+        .iter()
+        .position(|c| c.as_rgba_u32() == color.as_rgba_u32());
+
+    existing.unwrap_or_else(|| {
+        image.color_table.push(color);
+        image.color_table.len() - 1
+    })
+}
+
+fn lerp_color(a: &Color, b: &Color, t: f64) -> Color {
+    let (r0, g0, b0, a0) = a.as_rgba();
+    let (r1, g1, b1, a1) = b.as_rgba();
+    let lerp = |a: f64, b: f64| a + (b - a) * t;
+
+    Color::rgba(lerp(r0, r1), lerp(g0, g1), lerp(b0, b1), lerp(a0, a1))
+}
+
+/// The points of every subpath of a flattened path. With `closed`, every
+/// subpath is treated as a polygon, otherwise closed subpaths end on their
+/// first point.
+fn flatten(path: &BezPath, tolerance: f64, closed: bool) -> Vec<Vec<Point>> {
+    let mut polylines: Vec<Vec<Point>> = Vec::new();
+
+    path.flatten(tolerance, |element| match element {
+        PathEl::MoveTo(point) => polylines.push(vec![point]),
+        PathEl::LineTo(point) => {
+            if let Some(polyline) = polylines.last_mut() {
+                polyline.push(point);
+            }
+        }
+        PathEl::ClosePath if !closed => {
+            if let Some(polyline) = polylines.last_mut() {
+                polyline.push(polyline[0]);
+            }
+        }
+        _ => {}
+    });
+
+    polylines
+}
+
+/// Clip a polygon to a convex polygon with the Sutherland–Hodgman algorithm.
+/// Concave polygons can come out with edges running back and forth along the
+/// clip boundary, which enclose no area and don't change the fill.
+fn clip_convex(polygon: &[Point], clip: &[Point]) -> Vec<Point> {
+    // Which side of the clip edges is inside depends on the clip polygon's
+    // orientation
+    let orientation = clip
+        .iter()
+        .zip(clip.iter().cycle().skip(1))
+        .map(|(a, b)| a.to_vec2().cross(b.to_vec2()))
+        .sum::<f64>()
+        .signum();
+
+    let mut output = polygon.to_vec();
+
+    for (a, b) in clip.iter().zip(clip.iter().cycle().skip(1)) {
+        let side = |p: Point| (*b - *a).cross(p - *a) * orientation;
+        let input = std::mem::take(&mut output);
+
+        for (index, current) in input.iter().enumerate() {
+            let previous = input[(index + input.len() - 1) % input.len()];
+            let (current_side, previous_side) = (side(*current), side(previous));
+
+            if (current_side >= 0.0) != (previous_side >= 0.0) {
+                let t = previous_side / (previous_side - current_side);
+                output.push(previous.lerp(*current, t));
+            }
+
+            if current_side >= 0.0 {
+                output.push(*current);
+            }
+        }
+
+        if output.is_empty() {
+            break;
+        }
+    }
+
+    output
+}
+
+/// A closed path segment along a polygon
+fn polygon_segment(polygon: &[Point]) -> Segment {
+    let mut commands: Vec<_> = polygon[1..]
+        .iter()
+        .map(|end| SegmentCommand {
+            kind: SegmentCommandKind::Line { end: *end },
+            line_width: None,
+        })
+        .collect();
+    commands.push(SegmentCommand {
+        kind: SegmentCommandKind::ClosePath,
+        line_width: None,
+    });
+
+    Segment {
+        start: polygon[0],
+        commands,
+    }
+}
+
+/// A fill command without its outline
+fn without_outline(command: &Command) -> Command {
+    let mut command = command.clone();
+
+    if let Command::FillPolygon { outline, .. }
+    | Command::FillRectangles { outline, .. }
+    | Command::FillPath { outline, .. } = &mut command
+    {
+        *outline = None;
+    }
+
+    command
+}
+
+/// A draw command stroking the outline of a fill command the same way
+fn outline_command(command: &Command) -> Command {
+    match command.clone() {
+        Command::FillPolygon {
+            polygon,
+            outline:
+                Some(OutlineStyle {
+                    line_width,
+                    line_style,
+                }),
+            ..
+        } => Command::DrawLineLoop {
+            line_style,
+            line_width,
+            close_path: true,
+            points: polygon,
+        },
+        Command::FillRectangles {
+            rectangles,
+            outline:
+                Some(OutlineStyle {
+                    line_width,
+                    line_style,
+                }),
+            ..
+        } => Command::DrawLinePath {
+            line_style,
+            line_width,
+            path: rectangles.iter().map(rect_segment).collect(),
+        },
+        Command::FillPath {
+            path,
+            outline:
+                Some(OutlineStyle {
+                    line_width,
+                    line_style,
+                }),
+            ..
+        } => Command::DrawLinePath {
+            line_style,
+            line_width,
+            path,
+        },
+        command => command,
+    }
+}
@@ -0,0 +1,254 @@
+//! Write small TinyVG files covering every combination of command, style,
+//! color encoding and coordinate range to `data/generated/`, as reproducible
+//! inputs for parser and renderer edge cases.
+//!
+//! ```text
+//! $ cargo run --bin gen-fixtures
+//! ```
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use eyre::{Context, Result};
+use tinyvg::format::{
+    Color, ColorEncoding, Command, CoordinateRange, Image, Line, OutlineStyle, Point, Rect,
+    Segment, SegmentCommand, SegmentCommandKind, Style,
+};
+
+/// Size of every fixture. With `SCALE`, every unit fits in
+/// `CoordinateRange::Reduced`, so that every range can be used.
+const SIZE: u32 = 24;
+
+/// Fractional bits of the units, so that fixtures also cover fractional values
+const SCALE: u8 = 2;
+
+const COMMANDS: &[&str] = &[
+    "fill_polygon",
+    "fill_rectangles",
+    "fill_path",
+    "draw_lines",
+    "draw_line_loop",
+    "draw_line_strip",
+    "draw_line_path",
+    "outline_fill_polygon",
+    "outline_fill_rectangles",
+    "outline_fill_path",
+];
+
+const STYLES: &[&str] = &["flat", "linear", "radial"];
+
+const ENCODINGS: &[(&str, ColorEncoding)] = &[
+    ("rgba8888", ColorEncoding::Rgba8888),
+    ("rgb565", ColorEncoding::Rgb565),
+    ("rgbaf32", ColorEncoding::RgbaF32),
+];
+
+const RANGES: &[(&str, CoordinateRange)] = &[
+    ("reduced", CoordinateRange::Reduced),
+    ("default", CoordinateRange::Default),
+    ("enhanced", CoordinateRange::Enhanced),
+];
+
+fn main() -> Result<()> {
+    let output_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("data")
+        .join("generated");
+    std::fs::create_dir_all(&output_dir).wrap_err("failed to create output directory")?;
+
+    let mut count = 0;
+
+    for command in COMMANDS {
+        for style in STYLES {
+            for (encoding_name, encoding) in ENCODINGS {
+                for (range_name, range) in RANGES {
+                    let mut image = Image::empty(SIZE, SIZE);
+                    image.header.scale = SCALE;
+                    image.header.color_encoding = *encoding;
+                    image.header.coordinate_range = *range;
+                    image.color_table = vec![
+                        Color::rgba8(0xe0, 0x40, 0x20, 0xff),
+                        Color::rgba8(0x20, 0x60, 0xe0, 0xc0),
+                        Color::rgba8(0x10, 0x10, 0x10, 0xff),
+                    ];
+                    image.commands.push(fixture_command(command, style));
+
+                    let path = output_dir.join(format!(
+                        "{}-{}-{}-{}.tvg",
+                        command, style, encoding_name, range_name
+                    ));
+                    let mut file = BufWriter::new(
+                        File::create(&path)
+                            .wrap_err_with(|| format!("failed to create {}", path.display()))?,
+                    );
+                    image
+                        .encode(&mut file)
+                        .wrap_err_with(|| format!("failed to encode {}", path.display()))?;
+
+                    count += 1;
+                }
+            }
+        }
+    }
+
+    println!("wrote {} fixtures to {}", count, output_dir.display());
+
+    Ok(())
+}
+
+fn fixture_style(name: &str) -> Style {
+    match name {
+        "flat" => Style::FlatColor { color_index: 0 },
+        "linear" => Style::LinearGradient {
+            point_0: Point::new(2.0, 2.0),
+            point_1: Point::new(22.0, 22.0),
+            color_index_0: 0,
+            color_index_1: 1,
+        },
+        "radial" => Style::RadialGradient {
+            point_0: Point::new(12.0, 12.0),
+            point_1: Point::new(22.0, 12.0),
+            color_index_0: 0,
+            color_index_1: 1,
+        },
+        _ => unreachable!("unknown style {}", name),
+    }
+}
+
+fn fixture_command(name: &str, style: &str) -> Command {
+    let style = fixture_style(style);
+    let outline = || OutlineStyle {
+        line_width: 1.5,
+        line_style: Style::FlatColor { color_index: 2 },
+    };
+
+    let polygon = vec![
+        Point::new(2.0, 4.0),
+        Point::new(20.0, 2.0),
+        Point::new(22.5, 20.0),
+        Point::new(6.25, 22.0),
+    ];
+    let rectangles = vec![
+        Rect::new(2.0, 2.0, 10.0, 10.0),
+        Rect::new(12.5, 3.0, 22.0, 11.75),
+        Rect::new(4.0, 14.0, 20.0, 21.0),
+    ];
+
+    match name {
+        "fill_polygon" => Command::FillPolygon {
+            fill_style: style,
+            polygon,
+            outline: None,
+        },
+        "fill_rectangles" => Command::FillRectangles {
+            fill_style: style,
+            rectangles,
+            outline: None,
+        },
+        "fill_path" => Command::FillPath {
+            fill_style: style,
+            path: fixture_path(),
+            outline: None,
+        },
+        "draw_lines" => Command::DrawLines {
+            line_style: style,
+            line_width: 1.25,
+            lines: vec![
+                Line::new((2.0, 2.0), (22.0, 22.0)),
+                Line::new((2.0, 22.0), (22.0, 2.0)),
+                Line::new((12.0, 1.0), (12.0, 23.0)),
+            ],
+        },
+        "draw_line_loop" | "draw_line_strip" => Command::DrawLineLoop {
+            line_style: style,
+            line_width: 2.0,
+            close_path: name == "draw_line_loop",
+            points: polygon,
+        },
+        "draw_line_path" => Command::DrawLinePath {
+            line_style: style,
+            line_width: 1.0,
+            path: fixture_path(),
+        },
+        "outline_fill_polygon" => Command::FillPolygon {
+            fill_style: style,
+            polygon,
+            outline: Some(outline()),
+        },
+        "outline_fill_rectangles" => Command::FillRectangles {
+            fill_style: style,
+            rectangles,
+            outline: Some(outline()),
+        },
+        "outline_fill_path" => Command::FillPath {
+            fill_style: style,
+            path: fixture_path(),
+            outline: Some(outline()),
+        },
+        _ => unreachable!("unknown command {}", name),
+    }
+}
+
+/// Two segments using every segment command, one of them changing the line
+/// width partway through
+fn fixture_path() -> Vec<Segment> {
+    let command = |kind, line_width| SegmentCommand { kind, line_width };
+
+    vec![
+        Segment {
+            start: Point::new(2.0, 2.0),
+            commands: vec![
+                command(SegmentCommandKind::HorizontalLine { x: 10.0 }, None),
+                command(
+                    SegmentCommandKind::QuadraticBezier {
+                        control: Point::new(14.0, 2.0),
+                        point_1: Point::new(14.0, 6.0),
+                    },
+                    None,
+                ),
+                command(SegmentCommandKind::VerticalLine { y: 10.0 }, Some(2.5)),
+                command(
+                    SegmentCommandKind::CubicBezier {
+                        control_0: Point::new(12.0, 12.0),
+                        control_1: Point::new(6.0, 12.0),
+                        point_1: Point::new(4.0, 10.0),
+                    },
+                    None,
+                ),
+                command(SegmentCommandKind::ClosePath, None),
+            ],
+        },
+        Segment {
+            start: Point::new(14.0, 14.0),
+            commands: vec![
+                command(
+                    SegmentCommandKind::ArcCircle {
+                        large: false,
+                        sweep: true,
+                        radius: 4.0,
+                        target: Point::new(22.0, 14.0),
+                    },
+                    None,
+                ),
+                command(
+                    SegmentCommandKind::ArcEllipse {
+                        large: true,
+                        sweep: false,
+                        radius_x: 5.0,
+                        radius_y: 3.0,
+                        rotation: 30.0,
+                        target: Point::new(18.0, 22.0),
+                    },
+                    None,
+                ),
+                command(
+                    SegmentCommandKind::Line {
+                        end: Point::new(14.0, 20.0),
+                    },
+                    None,
+                ),
+                command(SegmentCommandKind::ClosePath, None),
+            ],
+        },
+    ]
+}
@@ -0,0 +1,228 @@
+//! A cache of rendered images for programs that show the same images over and
+//! over, such as icon loaders, preview servers and thumbnailers. Renders are
+//! keyed on the content hash of the image (see `Image::content_hash`) and the
+//! output size, so equal images loaded from different files share renders,
+//! and an edited image never gets a stale render. The least recently used
+//! renders are dropped to stay within a budget in bytes.
+//!
+//! ```
+//! # use tinyvg::cache::RenderCache;
+//! # use tinyvg::RenderSize;
+//! let mut cache = RenderCache::new(1024 * 1024);
+//!
+//! let first = cache.load("data/shield.tvg", RenderSize::Scale(2.0)).unwrap();
+//! let second = cache.load("data/shield.tvg", RenderSize::Scale(2.0)).unwrap();
+//! assert!(std::sync::Arc::ptr_eq(&first, &second));
+//! assert_eq!(cache.size_bytes(), 48 * 48 * 4);
+//!
+//! // After the file changed on disk
+//! assert!(cache.invalidate("data/shield.tvg"));
+//! assert_eq!(cache.size_bytes(), 0);
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::decode::Decoder;
+use crate::error::{Context, Result};
+use crate::format::Image;
+use crate::render::{RasterImage, RenderOptions, RenderSize};
+
+/// Renders of images by content hash and size, and the images of files by
+/// path. See the module documentation.
+pub struct RenderCache {
+    options: RenderOptions,
+    budget: usize,
+    used: usize,
+    renders: HashMap<RenderKey, CachedRender>,
+    files: HashMap<PathBuf, (u64, Arc<Image>)>,
+    clock: u64,
+}
+
+/// Content hash, width and height of a render
+type RenderKey = (u64, u32, u32);
+
+struct CachedRender {
+    pixmap: Arc<RasterImage>,
+    last_used: u64,
+}
+
+impl RenderCache {
+    /// Create an empty cache that keeps at most `budget` bytes of pixels.
+    /// Renders are made with the default `RenderOptions`.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            options: RenderOptions::default(),
+            budget,
+            used: 0,
+            renders: HashMap::new(),
+            files: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Render with `options` instead of the defaults. Clears the cache, since
+    /// the renders it holds were made with other options.
+    pub fn with_options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self.clear();
+        self
+    }
+
+    /// The render of `image` at `size`, rendering it if it isn't cached. A
+    /// render larger than the whole budget is returned without caching it.
+    pub fn render(&mut self, image: &Image, size: RenderSize) -> Result<Arc<RasterImage>> {
+        let hash = image.content_hash()?;
+        self.render_hashed(image, hash, size)
+    }
+
+    /// The render of the TinyVG file at `path` at `size`. The file is only
+    /// read and decoded the first time, call `RenderCache::invalidate` when
+    /// it changes.
+    pub fn load(&mut self, path: impl AsRef<Path>, size: RenderSize) -> Result<Arc<RasterImage>> {
+        let (hash, image) = self.load_image(path.as_ref())?;
+        self.render_hashed(&image, hash, size)
+    }
+
+    /// The decoded image of the TinyVG file at `path`, see
+    /// `RenderCache::load`
+    pub fn load_image(&mut self, path: impl AsRef<Path>) -> Result<(u64, Arc<Image>)> {
+        let path = path.as_ref();
+
+        if let Some((hash, image)) = self.files.get(path) {
+            return Ok((*hash, image.clone()));
+        }
+
+        let bytes =
+            std::fs::read(path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
+        let image = Decoder::from_slice(&bytes)
+            .decode()
+            .wrap_err_with(|| format!("failed to decode {}", path.display()))?;
+        let hash = image.content_hash()?;
+        let image = Arc::new(image);
+
+        self.files.insert(path.to_path_buf(), (hash, image.clone()));
+
+        Ok((hash, image))
+    }
+
+    /// Forget the file at `path` and drop its renders, e.g. after it changed
+    /// on disk. Renders of other files with the same content are dropped too.
+    /// Returns whether the file was cached.
+    pub fn invalidate(&mut self, path: impl AsRef<Path>) -> bool {
+        match self.files.remove(path.as_ref()) {
+            Some((hash, _)) => {
+                self.remove_renders(|key| key.0 == hash);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop every render of `image`, e.g. after an editor changed it in place
+    pub fn invalidate_image(&mut self, image: &Image) -> Result<()> {
+        let hash = image.content_hash()?;
+        self.remove_renders(|key| key.0 == hash);
+
+        Ok(())
+    }
+
+    /// Drop every file and render
+    pub fn clear(&mut self) {
+        self.files.clear();
+        self.renders.clear();
+        self.used = 0;
+    }
+
+    /// Bytes of pixels of the cached renders
+    pub fn size_bytes(&self) -> usize {
+        self.used
+    }
+
+    /// Maximum bytes of pixels kept, see `RenderCache::new`
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Change the maximum bytes of pixels kept, dropping the least recently
+    /// used renders if the cache is now over budget
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict(0);
+    }
+
+    /// Number of cached renders
+    pub fn len(&self) -> usize {
+        self.renders.len()
+    }
+
+    /// Whether no renders are cached
+    pub fn is_empty(&self) -> bool {
+        self.renders.is_empty()
+    }
+
+    fn render_hashed(
+        &mut self,
+        image: &Image,
+        hash: u64,
+        size: RenderSize,
+    ) -> Result<Arc<RasterImage>> {
+        let (width, height) = size.output_size(image.header.width, image.header.height)?;
+        let key = (hash, width, height);
+
+        self.clock += 1;
+        if let Some(cached) = self.renders.get_mut(&key) {
+            cached.last_used = self.clock;
+            return Ok(cached.pixmap.clone());
+        }
+
+        let pixmap = Arc::new(image.render_pixmap_scaled(size, &self.options)?);
+        let len = pixmap.data.len();
+
+        if len <= self.budget {
+            self.evict(len);
+            self.used += len;
+            self.renders.insert(
+                key,
+                CachedRender {
+                    pixmap: pixmap.clone(),
+                    last_used: self.clock,
+                },
+            );
+        }
+
+        Ok(pixmap)
+    }
+
+    /// Drop the least recently used renders until `len` more bytes fit in
+    /// the budget. Finding the oldest render walks every render, which is
+    /// cheap next to rendering for caches of up to thousands of renders.
+    fn evict(&mut self, len: usize) {
+        while self.used + len > self.budget {
+            let oldest = match self
+                .renders
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+            {
+                Some((key, _)) => *key,
+                None => break,
+            };
+
+            self.remove_renders(|key| *key == oldest);
+        }
+    }
+
+    fn remove_renders(&mut self, mut remove: impl FnMut(&RenderKey) -> bool) {
+        let used = &mut self.used;
+
+        self.renders.retain(|key, cached| {
+            if remove(key) {
+                *used -= cached.pixmap.data.len();
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
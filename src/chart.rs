@@ -0,0 +1,412 @@
+//! Line, bar and pie charts built from data series, e.g. for dashboard
+//! sprites generated on a server. Charts are regular images, so they can be
+//! encoded, rendered or exported like any other.
+
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+use crate::error::{ensure, Result};
+use crate::format::{
+    Color, Command, Image, Line, Point, Rect, Segment, SegmentCommand, SegmentCommandKind, Style,
+};
+
+/// Fractional bits of chart coordinates, enough for smooth lines at any size
+const CHART_SCALE: u8 = 4;
+
+/// Fraction of a bar group's width covered by its bars, the rest separates
+/// neighbouring groups
+const BAR_GROUP_FILL: f64 = 0.8;
+
+/// A sequence of values drawn in one color
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    /// Values in the order they are plotted
+    pub values: Vec<f64>,
+
+    /// Color of the series' line, bars or slice
+    pub color: Color,
+}
+
+impl Series {
+    /// Create a series from its values and color
+    pub fn new(values: impl Into<Vec<f64>>, color: Color) -> Self {
+        Self {
+            values: values.into(),
+            color,
+        }
+    }
+}
+
+/// Layout and colors shared by all charts
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartOptions {
+    /// Width of the image in units
+    pub width: u32,
+
+    /// Height of the image in units
+    pub height: u32,
+
+    /// Space between the edges of the image and the plot area
+    pub padding: f64,
+
+    /// Color filling the whole image behind the chart, transparent if `None`
+    pub background: Option<Color>,
+
+    /// Color of the axes of line and bar charts, no axes are drawn if `None`
+    pub axis_color: Option<Color>,
+
+    /// Width of the lines of line charts and of the axes
+    pub line_width: f64,
+}
+
+impl Default for ChartOptions {
+    fn default() -> Self {
+        Self {
+            width: 256,
+            height: 128,
+            padding: 8.0,
+            background: None,
+            axis_color: Some(Color::grey8(0x80)),
+            line_width: 2.0,
+        }
+    }
+}
+
+/// Plot each series as a line through its values, spread evenly over the
+/// width of the plot area. The value axis spans all values of all series and
+/// always includes zero.
+///
+/// ```
+/// # use tinyvg::chart::{line_chart, ChartOptions, Series};
+/// # use tinyvg::format::Color;
+/// let chart = line_chart(
+///     &[
+///         Series::new([3.0, 5.0, 4.0, 8.0], Color::rgb8(0x1f, 0x77, 0xb4)),
+///         Series::new([1.0, -2.0, 2.5], Color::rgb8(0xff, 0x7f, 0x0e)),
+///     ],
+///     &ChartOptions::default(),
+/// )
+/// .unwrap();
+///
+/// // The axes and one line per series
+/// assert_eq!(chart.commands.len(), 3);
+///
+/// let mut bytes = Vec::new();
+/// chart.encode(&mut bytes).unwrap();
+/// ```
+pub fn line_chart(series: &[Series], options: &ChartOptions) -> Result<Image> {
+    let mut chart = Chart::new(series, options)?;
+    let count = series.iter().map(|s| s.values.len()).max().unwrap_or(0);
+
+    chart.axes();
+
+    for s in series {
+        if s.values.is_empty() {
+            continue;
+        }
+
+        let points = s
+            .values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let x = if count > 1 {
+                    chart.plot.x0 + chart.plot.width() * index as f64 / (count - 1) as f64
+                } else {
+                    chart.plot.center().x
+                };
+
+                Point::new(x, chart.y(*value))
+            })
+            .collect();
+
+        let line_style = chart.style(&s.color);
+        chart.image.commands.push(Command::DrawLineLoop {
+            line_style,
+            line_width: options.line_width,
+            close_path: false,
+            points,
+        });
+    }
+
+    chart.finish()
+}
+
+/// Plot the values of the series as groups of bars, one group per index with
+/// a bar per series. Bars grow from zero, so negative values point down.
+/// Values of zero have no bar.
+///
+/// ```
+/// # use tinyvg::chart::{bar_chart, ChartOptions, Series};
+/// # use tinyvg::format::Color;
+/// let chart = bar_chart(
+///     &[
+///         Series::new([3.0, 5.0, 0.0, 8.0], Color::rgb8(0x2c, 0xa0, 0x2c)),
+///         Series::new([1.0, -2.0, 2.5, 4.0], Color::rgb8(0xd6, 0x27, 0x28)),
+///     ],
+///     &ChartOptions {
+///         width: 100_000,
+///         height: 100_000,
+///         ..Default::default()
+///     },
+/// )
+/// .unwrap();
+///
+/// // Too large to render at full size, but the chart is only geometry
+/// let mut thumbnail = chart.clone();
+/// thumbnail.scale(0.005).unwrap();
+///
+/// let mut png = Vec::new();
+/// thumbnail.render_png(&mut png).unwrap();
+/// ```
+pub fn bar_chart(series: &[Series], options: &ChartOptions) -> Result<Image> {
+    let mut chart = Chart::new(series, options)?;
+    let groups = series.iter().map(|s| s.values.len()).max().unwrap_or(0);
+
+    if groups > 0 && !series.is_empty() {
+        let group_width = chart.plot.width() / groups as f64;
+        let bar_width = group_width * BAR_GROUP_FILL / series.len() as f64;
+        let zero = chart.y(0.0);
+
+        for (index, s) in series.iter().enumerate() {
+            let rectangles = s
+                .values
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| **value != 0.0)
+                .map(|(group, value)| {
+                    let x = chart.plot.x0
+                        + group_width * (group as f64 + (1.0 - BAR_GROUP_FILL) / 2.0)
+                        + bar_width * index as f64;
+
+                    Rect::new(x, zero, x + bar_width, chart.y(*value)).abs()
+                })
+                .collect::<Vec<_>>();
+
+            if rectangles.is_empty() {
+                continue;
+            }
+
+            let fill_style = chart.style(&s.color);
+            chart.image.commands.push(Command::FillRectangles {
+                fill_style,
+                rectangles,
+                outline: None,
+            });
+        }
+    }
+
+    // Axes on top, so that bars don't cover the zero line
+    chart.axes();
+
+    chart.finish()
+}
+
+/// Plot the first value of each series as a slice of a pie, clockwise from
+/// the top. Slices of zero are left out, negative values are an error.
+///
+/// ```
+/// # use tinyvg::chart::{pie_chart, ChartOptions, Series};
+/// # use tinyvg::format::Color;
+/// let chart = pie_chart(
+///     &[
+///         Series::new([60.0], Color::rgb8(0x94, 0x67, 0xbd)),
+///         Series::new([30.0], Color::rgb8(0x8c, 0x56, 0x4b)),
+///         Series::new([10.0], Color::rgb8(0xe3, 0x77, 0xc2)),
+///     ],
+///     &ChartOptions {
+///         width: 64,
+///         height: 64,
+///         ..Default::default()
+///     },
+/// )
+/// .unwrap();
+///
+/// assert_eq!(chart.commands.len(), 3);
+///
+/// let mut png = Vec::new();
+/// chart.render_png(&mut png).unwrap();
+/// ```
+pub fn pie_chart(series: &[Series], options: &ChartOptions) -> Result<Image> {
+    let mut chart = Chart::new(series, options)?;
+
+    let values = series
+        .iter()
+        .map(|s| s.values.first().copied().unwrap_or(0.0))
+        .collect::<Vec<_>>();
+    ensure!(
+        values.iter().all(|v| *v >= 0.0),
+        "pie chart values must not be negative"
+    );
+
+    let total = values.iter().sum::<f64>();
+    let center = chart.plot.center();
+    let radius = chart.plot.width().min(chart.plot.height()) / 2.0;
+    let at = |angle: f64| center + kurbo::Vec2::from_angle(angle) * radius;
+
+    let mut angle = -FRAC_PI_2;
+    for (s, value) in series.iter().zip(values) {
+        if value == 0.0 {
+            continue;
+        }
+
+        let sweep = value / total * TAU;
+
+        // Each arc covers at most half of the circle, so that a slice of the
+        // whole pie doesn't end where it starts
+        let pieces = if sweep > PI { 2 } else { 1 };
+        let mut commands = vec![SegmentCommand {
+            kind: SegmentCommandKind::Line { end: at(angle) },
+            line_width: None,
+        }];
+        for piece in 1..=pieces {
+            commands.push(SegmentCommand {
+                kind: SegmentCommandKind::ArcCircle {
+                    large: false,
+                    sweep: true,
+                    radius,
+                    target: at(angle + sweep * piece as f64 / pieces as f64),
+                },
+                line_width: None,
+            });
+        }
+        commands.push(SegmentCommand {
+            kind: SegmentCommandKind::ClosePath,
+            line_width: None,
+        });
+
+        let fill_style = chart.style(&s.color);
+        chart.image.commands.push(Command::FillPath {
+            fill_style,
+            path: vec![Segment {
+                start: center,
+                commands,
+            }],
+            outline: None,
+        });
+
+        angle += sweep;
+    }
+
+    chart.finish()
+}
+
+/// An image being built into a chart, with the mapping from values to units
+struct Chart<'a> {
+    image: Image,
+    options: &'a ChartOptions,
+
+    /// Area that the values are plotted in
+    plot: Rect,
+
+    /// Smallest and largest value on the value axis
+    min: f64,
+    max: f64,
+}
+
+impl<'a> Chart<'a> {
+    fn new(series: &[Series], options: &'a ChartOptions) -> Result<Self> {
+        ensure!(
+            options.width > 0 && options.height > 0,
+            "charts must be at least 1 unit wide and high"
+        );
+        ensure!(
+            series.iter().flat_map(|s| &s.values).all(|v| v.is_finite()),
+            "chart values must be finite"
+        );
+
+        let plot = Rect::new(
+            options.padding,
+            options.padding,
+            options.width as f64 - options.padding,
+            options.height as f64 - options.padding,
+        );
+        ensure!(
+            plot.width() > 0.0 && plot.height() > 0.0,
+            "chart padding of {} leaves no room to plot in",
+            options.padding
+        );
+
+        let values = series.iter().flat_map(|s| &s.values);
+        let min = values.clone().fold(0.0, |min: f64, v| min.min(*v));
+        let max = values.fold(0.0, |max: f64, v| max.max(*v));
+
+        let mut image = Image::empty(options.width, options.height);
+        image.header.scale = CHART_SCALE;
+
+        let mut chart = Self {
+            image,
+            options,
+            plot,
+            min,
+            max,
+        };
+
+        if let Some(background) = &options.background {
+            let fill_style = chart.style(background);
+            chart.image.commands.push(Command::FillRectangles {
+                fill_style,
+                rectangles: vec![Rect::new(
+                    0.0,
+                    0.0,
+                    options.width as f64,
+                    options.height as f64,
+                )],
+                outline: None,
+            });
+        }
+
+        Ok(chart)
+    }
+
+    /// Vertical position of a value in the plot area
+    fn y(&self, value: f64) -> f64 {
+        let span = self.max - self.min;
+        if span == 0.0 {
+            return self.plot.y1;
+        }
+
+        self.plot.y1 - (value - self.min) / span * self.plot.height()
+    }
+
+    /// Flat style of a color, reusing the color table entry of equal colors
+    fn style(&mut self, color: &Color) -> Style {
+        let color_table = &mut self.image.color_table;
+        let color_index = match color_table
+            .iter()
+            .position(|c| c.as_rgba_u32() == color.as_rgba_u32())
+        {
+            Some(index) => index,
+            None => {
+                color_table.push(color.clone());
+                color_table.len() - 1
+            }
+        };
+
+        Style::FlatColor { color_index }
+    }
+
+    /// The value axis on the left of the plot area and the zero line
+    fn axes(&mut self) {
+        let axis_color = match &self.options.axis_color {
+            Some(color) => color.clone(),
+            None => return,
+        };
+
+        let zero = self.y(0.0);
+        let line_style = self.style(&axis_color);
+        self.image.commands.push(Command::DrawLines {
+            line_style,
+            line_width: self.options.line_width,
+            lines: vec![
+                Line::new((self.plot.x0, self.plot.y0), (self.plot.x0, self.plot.y1)),
+                Line::new((self.plot.x0, zero), (self.plot.x1, zero)),
+            ],
+        });
+    }
+
+    fn finish(mut self) -> Result<Image> {
+        self.image.fit_coordinate_range()?;
+
+        Ok(self.image)
+    }
+}
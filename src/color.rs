@@ -0,0 +1,305 @@
+//! Conversions between `Color` and the binary color encodings of TinyVG. The
+//! decoder and encoder both go through these functions, so the two can't
+//! disagree on bit layout or rounding.
+//!
+//! `Rgb565` colors store red in the lowest 5 bits, green in the middle 6 bits
+//! and blue in the highest 5 bits of a little-endian `u16`, matching the
+//! reference implementation:
+//!
+//! ```
+//! # use tinyvg::color::{from_rgb565, to_rgb565};
+//! # use tinyvg::format::Color;
+//! assert_eq!(from_rgb565(0x001F), Color::rgb8(0xFF, 0x00, 0x00));
+//! assert_eq!(from_rgb565(0x07E0), Color::rgb8(0x00, 0xFF, 0x00));
+//! assert_eq!(from_rgb565(0xF800), Color::rgb8(0x00, 0x00, 0xFF));
+//! assert_eq!(from_rgb565(0x0000), Color::rgb8(0x00, 0x00, 0x00));
+//! assert_eq!(from_rgb565(0xFFFF), Color::rgb8(0xFF, 0xFF, 0xFF));
+//! assert_eq!(from_rgb565(0x0010), Color::rgb8(0x84, 0x00, 0x00));
+//! assert_eq!(from_rgb565(0x0400), Color::rgb8(0x00, 0x82, 0x00));
+//!
+//! assert_eq!(to_rgb565(&Color::rgb8(0xFF, 0x00, 0x00)), 0x001F);
+//! assert_eq!(to_rgb565(&Color::rgb8(0x00, 0xFF, 0x00)), 0x07E0);
+//! assert_eq!(to_rgb565(&Color::rgb8(0x00, 0x00, 0xFF)), 0xF800);
+//!
+//! // Every 565 value survives a round trip through `Color`
+//! for raw in 0..=u16::MAX {
+//!     assert_eq!(to_rgb565(&from_rgb565(raw)), raw);
+//! }
+//! ```
+
+/// Color of this crate's images. This is `piet::Color` if the `piet` feature
+/// is enabled (it is by `render-png`), otherwise a type with the same
+/// methods, so that decoding and encoding don't depend on piet.
+#[cfg(feature = "piet")]
+pub use piet::Color;
+
+/// Order of the channels in a packed 16 bit color, starting from the lowest
+/// bits. TinyVG files always use `ChannelOrder::Rgb`, `ChannelOrder::Bgr` is
+/// provided for converting data from other sources.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelOrder {
+    /// Red in the lowest bits, blue in the highest bits
+    Rgb,
+
+    /// Blue in the lowest bits, red in the highest bits
+    Bgr,
+}
+
+/// Convert a TinyVG `Rgb565` value to a color
+pub fn from_rgb565(raw: u16) -> Color {
+    from_565(raw, ChannelOrder::Rgb)
+}
+
+/// Convert a color to a TinyVG `Rgb565` value. Alpha is dropped, and each
+/// channel is rounded to the nearest representable value.
+pub fn to_rgb565(color: &Color) -> u16 {
+    to_565(color, ChannelOrder::Rgb)
+}
+
+/// Convert a packed 5-6-5 bit color with the given channel order to a color
+///
+/// ```
+/// # use tinyvg::color::{from_565, ChannelOrder};
+/// # use tinyvg::format::Color;
+/// assert_eq!(from_565(0x001F, ChannelOrder::Bgr), Color::rgb8(0x00, 0x00, 0xFF));
+/// ```
+pub fn from_565(raw: u16, order: ChannelOrder) -> Color {
+    let low = (raw & 0x001F) as f64 / 31.0;
+    let green = ((raw & 0x07E0) >> 5) as f64 / 63.0;
+    let high = ((raw & 0xF800) >> 11) as f64 / 31.0;
+
+    match order {
+        ChannelOrder::Rgb => Color::rgb(low, green, high),
+        ChannelOrder::Bgr => Color::rgb(high, green, low),
+    }
+}
+
+/// Convert a color to a packed 5-6-5 bit color with the given channel order
+pub fn to_565(color: &Color, order: ChannelOrder) -> u16 {
+    let (red, green, blue, _) = color.as_rgba();
+
+    let (low, high) = match order {
+        ChannelOrder::Rgb => (red, blue),
+        ChannelOrder::Bgr => (blue, red),
+    };
+
+    let low = (low * 31.0).round() as u16;
+    let green = (green * 63.0).round() as u16;
+    let high = (high * 31.0).round() as u16;
+
+    low | (green << 5) | (high << 11)
+}
+
+/// Convert TinyVG `Rgba8888` channel values to a color
+pub fn from_rgba8888(raw: [u8; 4]) -> Color {
+    Color::rgba8(raw[0], raw[1], raw[2], raw[3])
+}
+
+/// Convert a color to TinyVG `Rgba8888` channel values
+pub fn to_rgba8888(color: &Color) -> [u8; 4] {
+    let (red, green, blue, alpha) = color.as_rgba8();
+
+    [red, green, blue, alpha]
+}
+
+/// Convert TinyVG `RgbaF32` channel values to a color. Channels are clamped to
+/// the range 0 to 1, and NaN channels become 0, see `is_valid_rgba_f32`.
+///
+/// ```
+/// # use tinyvg::color::from_rgba_f32;
+/// # use tinyvg::format::Color;
+/// assert_eq!(
+///     from_rgba_f32([f32::NAN, 2.0, f32::NEG_INFINITY, 1.0]),
+///     Color::rgba(0.0, 1.0, 0.0, 1.0)
+/// );
+/// ```
+pub fn from_rgba_f32(raw: [f32; 4]) -> Color {
+    let [red, green, blue, alpha] = raw.map(|channel| {
+        if channel.is_nan() {
+            0.0
+        } else {
+            channel.clamp(0.0, 1.0) as f64
+        }
+    });
+
+    Color::rgba(red, green, blue, alpha)
+}
+
+/// Whether all `RgbaF32` channel values are numbers in the range 0 to 1. Other
+/// values are clamped when decoding and rejected when encoding.
+///
+/// ```
+/// # use tinyvg::decode::{Decoder, DecodeWarning};
+/// # use tinyvg::format::Color;
+/// // One color of NaN red, green of 2, no blue and full alpha
+/// let bytes = [
+///     0x72, 0x56, 1, 0x60, 16, 16, 1,
+///     0, 0, 0xC0, 0x7F, 0, 0, 0, 0x40, 0, 0, 0, 0, 0, 0, 0x80, 0x3F,
+///     0,
+/// ];
+///
+/// let mut decoder = Decoder::new(&bytes[..]);
+/// let image = decoder.decode_header().unwrap();
+///
+/// assert_eq!(image.color_table, [Color::rgb(0.0, 1.0, 0.0)]);
+/// assert_eq!(decoder.warnings(), [DecodeWarning::ColorOutOfRange { index: 0 }]);
+///
+/// // The clamped color encodes without problems
+/// let mut encoded = Vec::new();
+/// image.encode(&mut encoded).unwrap();
+/// ```
+pub fn is_valid_rgba_f32(raw: [f32; 4]) -> bool {
+    raw.iter().all(|channel| (0.0..=1.0).contains(channel))
+}
+
+/// Convert a color to TinyVG `RgbaF32` channel values
+pub fn to_rgba_f32(color: &Color) -> [f32; 4] {
+    let (red, green, blue, alpha) = color.as_rgba();
+
+    [red as f32, green as f32, blue as f32, alpha as f32]
+}
+
+/// Color with 8 bits per channel, standing in for `piet::Color` if the `piet`
+/// feature is disabled. Its methods are the subset of `piet::Color` used by
+/// this crate, and behave the same, so code written against it also compiles
+/// with piet.
+#[cfg(not(feature = "piet"))]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Color(u32);
+
+#[cfg(not(feature = "piet"))]
+impl Color {
+    /// Opaque black
+    pub const BLACK: Color = Color::rgb8(0, 0, 0);
+
+    /// Opaque white
+    pub const WHITE: Color = Color::rgb8(255, 255, 255);
+
+    /// Fully transparent black
+    pub const TRANSPARENT: Color = Color::rgba8(0, 0, 0, 0);
+
+    /// Create an opaque color from 8 bit channels
+    pub const fn rgb8(r: u8, g: u8, b: u8) -> Color {
+        Color::rgba8(r, g, b, 255)
+    }
+
+    /// Create a color from 8 bit channels
+    pub const fn rgba8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color::from_rgba32_u32(
+            ((r as u32) << 24) | ((g as u32) << 16) | ((b as u32) << 8) | a as u32,
+        )
+    }
+
+    /// Create an opaque grey from an 8 bit value
+    pub const fn grey8(grey: u8) -> Color {
+        Color::rgb8(grey, grey, grey)
+    }
+
+    /// Create a color from red in the highest 8 bits to alpha in the lowest
+    pub const fn from_rgba32_u32(rgba: u32) -> Color {
+        Color(rgba)
+    }
+
+    /// Create an opaque color from channels between 0 and 1, values outside
+    /// are clamped
+    pub fn rgb<F: Into<f64>>(r: F, g: F, b: F) -> Color {
+        Color::rgba(r.into(), g.into(), b.into(), 1.0)
+    }
+
+    /// Create a color from channels between 0 and 1, values outside are
+    /// clamped
+    pub fn rgba<F: Into<f64>>(r: F, g: F, b: F, a: F) -> Color {
+        let channel = |value: F| (value.into().clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        Color::rgba8(channel(r), channel(g), channel(b), channel(a))
+    }
+
+    /// Create an opaque grey from a value between 0 and 1
+    pub fn grey<F: Into<f64>>(grey: F) -> Color {
+        let grey = grey.into();
+
+        Color::rgb(grey, grey, grey)
+    }
+
+    /// Create an opaque color from CIE LCh coordinates: a hue angle in
+    /// degrees, a lightness between 0 and 100 and a chroma
+    pub fn hlc<F: Into<f64>>(h: F, l: F, c: F) -> Color {
+        // Lab to XYZ, see https://en.wikipedia.org/wiki/CIELAB_color_space
+        fn f_inv(t: f64) -> f64 {
+            let d = 6.0 / 29.0;
+            if t > d {
+                t.powi(3)
+            } else {
+                3.0 * d * d * (t - 4.0 / 29.0)
+            }
+        }
+
+        // Linear to sRGB transfer function
+        fn gamma(u: f64) -> f64 {
+            if u <= 0.0031308 {
+                12.92 * u
+            } else {
+                1.055 * u.powf(1.0 / 2.4) - 0.055
+            }
+        }
+
+        let hue = h.into().to_radians();
+        let chroma = c.into();
+        let a = chroma * hue.cos();
+        let b = chroma * hue.sin();
+        let ll = (l.into() + 16.0) / 116.0;
+
+        let x = f_inv(ll + a / 500.0);
+        let y = f_inv(ll);
+        let z = f_inv(ll - b / 200.0);
+
+        // XYZ relative to D50 to linear sRGB, the same matrix as piet's
+        let r = 3.02172918 * x - 1.61692294 * y - 0.40480625 * z;
+        let g = -0.94339358 * x + 1.91584267 * y + 0.02755094 * z;
+        let b = 0.06945666 * x - 0.22903204 * y + 1.15957526 * z;
+
+        Color::rgb(gamma(r), gamma(g), gamma(b))
+    }
+
+    /// This color with its alpha replaced by a value between 0 and 1
+    pub fn with_alpha(self, a: f64) -> Color {
+        let (r, g, b, _) = self.as_rgba8();
+
+        Color::rgba8(r, g, b, (a.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    /// The color with red in the highest 8 bits to alpha in the lowest
+    pub const fn as_rgba_u32(&self) -> u32 {
+        self.0
+    }
+
+    /// The 8 bit red, green, blue and alpha channels
+    pub const fn as_rgba8(&self) -> (u8, u8, u8, u8) {
+        (
+            (self.0 >> 24) as u8,
+            (self.0 >> 16) as u8,
+            (self.0 >> 8) as u8,
+            self.0 as u8,
+        )
+    }
+
+    /// The red, green, blue and alpha channels between 0 and 1
+    pub fn as_rgba(&self) -> (f64, f64, f64, f64) {
+        let (r, g, b, a) = self.as_rgba8();
+
+        (
+            r as f64 / 255.0,
+            g as f64 / 255.0,
+            b as f64 / 255.0,
+            a as f64 / 255.0,
+        )
+    }
+}
+
+/// Formatted like `piet::Color`, as `#rrggbbaa`
+#[cfg(not(feature = "piet"))]
+impl std::fmt::Debug for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#{:08x}", self.as_rgba_u32())
+    }
+}
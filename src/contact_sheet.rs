@@ -0,0 +1,95 @@
+//! Contact sheets: many images laid out in a grid as a single TinyVG image,
+//! e.g. as an overview of an icon set. The sheet is a regular image, so it can
+//! be rendered to PNG or exported like any other.
+
+use crate::error::{ensure, Result};
+use crate::format::{ColorEncoding, Image};
+
+/// Lay out `images` in a grid with `columns` columns of square cells,
+/// `cell_size` units wide. Each image is scaled uniformly to fit its cell and
+/// centered in it. Color tables are concatenated, so every image keeps its
+/// colors.
+///
+/// ```
+/// # use std::fs::File;
+/// # use tinyvg::Decoder;
+/// # use tinyvg::contact_sheet::contact_sheet;
+/// let images = ["data/shield.tvg", "data/app_icon.tvg", "data/chart.tvg"]
+///     .iter()
+///     .map(|path| Decoder::new(File::open(path).unwrap()).decode().unwrap())
+///     .collect::<Vec<_>>();
+///
+/// let sheet = contact_sheet(&images, 2, 64).unwrap();
+///
+/// assert_eq!((sheet.header.width, sheet.header.height), (128, 128));
+/// assert_eq!(
+///     sheet.commands.len(),
+///     images.iter().map(|i| i.commands.len()).sum::<usize>()
+/// );
+///
+/// let mut png = Vec::new();
+/// sheet.render_png(&mut png).unwrap();
+/// ```
+pub fn contact_sheet(images: &[Image], columns: u32, cell_size: u32) -> Result<Image> {
+    ensure!(columns > 0, "contact sheet must have at least one column");
+    ensure!(
+        cell_size > 0,
+        "contact sheet cells must be at least 1 unit wide"
+    );
+
+    let rows = (images.len() as u32).div_ceil(columns);
+    let mut sheet = Image::empty(columns * cell_size, rows * cell_size);
+
+    // Keep the precision of the most precise input
+    sheet.header.scale = images.iter().map(|i| i.header.scale).max().unwrap_or(0);
+    if images
+        .iter()
+        .any(|i| i.header.color_encoding == ColorEncoding::RgbaF32)
+    {
+        sheet.header.color_encoding = ColorEncoding::RgbaF32;
+    }
+
+    for (index, image) in images.iter().enumerate() {
+        let column = index as u32 % columns;
+        let row = index as u32 / columns;
+
+        let width = image.header.width as f64;
+        let height = image.header.height as f64;
+        let cell = cell_size as f64;
+
+        let scale = if width > 0.0 && height > 0.0 {
+            (cell / width).min(cell / height)
+        } else {
+            1.0
+        };
+        let offset_x = column as f64 * cell + (cell - width * scale) / 2.0;
+        let offset_y = row as f64 * cell + (cell - height * scale) / 2.0;
+
+        let mut placed = image.clone();
+        placed.visit_geometry_mut(
+            |p| {
+                p.x = p.x * scale + offset_x;
+                p.y = p.y * scale + offset_y;
+            },
+            |length| *length *= scale,
+        );
+
+        let color_offset = sheet.color_table.len();
+        placed.visit_styles_mut(|style| {
+            for index in style.color_indices_mut() {
+                *index += color_offset;
+            }
+        });
+
+        sheet.color_table.extend(placed.color_table);
+        sheet.commands.extend(placed.commands);
+    }
+
+    // Lower the precision until the sheet fits in the binary format
+    while sheet.required_coordinate_range().is_none() && sheet.header.scale > 0 {
+        sheet.header.scale -= 1;
+    }
+    sheet.fit_coordinate_range()?;
+
+    Ok(sheet)
+}
@@ -2,16 +2,20 @@
 //! to its in-memory representation.
 
 use std::io::Read;
+use std::ops::Range;
 
 use byteorder::{LittleEndian, ReadBytesExt};
-use eyre::{bail, ensure, eyre, Context, Result};
-use kurbo::{Rect, Size};
+use kurbo::Rect;
 use packed_struct::prelude::*;
 
+use crate::color;
+use crate::error::{bail, ensure, format_err, full_message, Context, Error, Result};
 use crate::format::{
     Color, ColorEncoding, Command, CoordinateRange, Header, Image, Line, OutlineStyle, Point,
     Segment, SegmentCommand, SegmentCommandKind, Style,
 };
+use crate::geometry::has_area;
+use crate::validate::{check_color_indices, ValidationError};
 
 struct ByteCountReader<R> {
     inner: R,
@@ -38,6 +42,16 @@ where
 
         Ok(bytes_read)
     }
+
+    // Most reads are a few bytes, forward them whole so that readers with a
+    // fast `read_exact` (like `SliceReader`) don't go through a `read` loop
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), std::io::Error> {
+        self.inner.read_exact(buffer)?;
+
+        self.bytes_read += buffer.len();
+
+        Ok(())
+    }
 }
 
 /// Used to perform the TinyVG parsing pass from an arbitrary `std::io::Read`.
@@ -57,6 +71,275 @@ pub struct Decoder<R> {
     color_count: u32,
     color_encoding: ColorEncoding,
     scale: u32,
+    trailer_check: TrailerCheck,
+    max_trailer_len: Option<usize>,
+    warnings: Vec<DecodeWarning>,
+    source_map: Vec<Range<usize>>,
+    options: DecodeOptions,
+    strict: bool,
+}
+
+/// Limits on the sizes and counts read from a file, which are checked before
+/// anything is allocated for them. Counts come straight from the input, so
+/// without limits a few bytes of untrusted input can make the decoder
+/// allocate gigabytes or loop for a very long time. Exceeding a limit fails
+/// decoding with `Error::LimitExceeded`.
+///
+/// The defaults are far above what real images use, but valid files can
+/// exceed them, e.g. plots on canvases wider than 16384 units. Use
+/// `DecodeOptions::unlimited` for trusted input that needs more.
+///
+/// ```
+/// # use tinyvg::decode::{DecodeOptions, Decoder};
+/// # use tinyvg::Error;
+/// let bytes = std::fs::read("data/everything.tvg").unwrap();
+///
+/// let options = DecodeOptions {
+///     max_commands: Some(1),
+///     ..Default::default()
+/// };
+/// let error = Decoder::new(bytes.as_slice())
+///     .with_options(options)
+///     .decode()
+///     .unwrap_err();
+///
+/// assert!(matches!(
+///     error.root_cause(),
+///     Error::LimitExceeded { limit: "max_commands", max: 1, .. }
+/// ));
+///
+/// let image = Decoder::new(bytes.as_slice())
+///     .with_options(DecodeOptions::unlimited())
+///     .decode()
+///     .unwrap();
+/// assert!(image.commands.len() > 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// Maximum width of the image in the header. Defaults to 16384.
+    pub max_width: Option<u32>,
+
+    /// Maximum height of the image in the header. Defaults to 16384.
+    pub max_height: Option<u32>,
+
+    /// Maximum number of colors in the color table. Defaults to 65536.
+    pub max_colors: Option<u32>,
+
+    /// Maximum number of commands. Defaults to 1000000.
+    pub max_commands: Option<u32>,
+
+    /// Maximum number of segment commands in the path of one command, and of
+    /// points, rectangles or lines of any other command. Defaults to 1000000.
+    pub max_segment_commands: Option<u32>,
+
+    /// Keep the commands decoded before a command that can't be decoded,
+    /// e.g. because the file is cut off, instead of failing. The failure is
+    /// recorded as a `DecodeWarning::UndecodableCommand` and the trailer is
+    /// left empty. Errors in the header still fail decoding. Defaults to
+    /// `false`.
+    pub recover: bool,
+
+    /// Compute the bounding box of every command while decoding, and store
+    /// them in `Image::bounds`. Useful for hit testing, culling and spatial
+    /// indexes, which would otherwise walk every command again. Defaults to
+    /// `false`.
+    ///
+    /// ```
+    /// # use tinyvg::decode::{DecodeOptions, Decoder};
+    /// let bytes = std::fs::read("data/everything.tvg").unwrap();
+    ///
+    /// let options = DecodeOptions {
+    ///     compute_bounds: true,
+    ///     ..Default::default()
+    /// };
+    /// let image = Decoder::new(bytes.as_slice())
+    ///     .with_options(options)
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let bounds = image.bounds.as_ref().unwrap();
+    /// assert_eq!(bounds.len(), image.commands.len());
+    /// assert_eq!(bounds[0], image.commands[0].bounds());
+    ///
+    /// // Bounds are a cache, they don't take part in comparisons
+    /// let plain = Decoder::new(bytes.as_slice()).decode().unwrap();
+    /// assert_eq!(plain, image);
+    ///
+    /// // and are cleared when the geometry changes
+    /// let mut scaled = image.clone();
+    /// scaled.scale(2.0).unwrap();
+    /// assert!(scaled.bounds.is_none());
+    /// ```
+    pub compute_bounds: bool,
+}
+
+impl DecodeOptions {
+    /// No limits at all, for input that is trusted
+    pub fn unlimited() -> Self {
+        Self {
+            max_width: None,
+            max_height: None,
+            max_colors: None,
+            max_commands: None,
+            max_segment_commands: None,
+            recover: false,
+            compute_bounds: false,
+        }
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            max_width: Some(16_384),
+            max_height: Some(16_384),
+            max_colors: Some(65_536),
+            max_commands: Some(1_000_000),
+            max_segment_commands: Some(1_000_000),
+            recover: false,
+            compute_bounds: false,
+        }
+    }
+}
+
+/// Fail with `Error::LimitExceeded` if `value` is above `max`
+fn check_limit(limit: &'static str, value: u64, max: Option<u32>) -> Result<()> {
+    match max {
+        Some(max) if value > max as u64 => Err(Error::LimitExceeded {
+            limit,
+            value,
+            max: max as u64,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// What the decoder does if the trailer starts with bytes that decode as a
+/// valid command. This usually means the file was produced by concatenating
+/// images, or by a producer that wrote the end-of-document marker too early.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrailerCheck {
+    /// The trailer is not inspected
+    Ignore,
+
+    /// A `DecodeWarning::TrailerLooksLikeCommands` is recorded, see
+    /// `Decoder::warnings`
+    Warn,
+
+    /// Decoding fails
+    Error,
+}
+
+/// Problem found while decoding that did not stop decoding
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeWarning {
+    /// The trailer starts with bytes that decode as a valid command
+    TrailerLooksLikeCommands {
+        /// Length of the trailer in bytes
+        len: usize,
+    },
+
+    /// A fill rectangles command has rectangles with a width or height of
+    /// zero or less, which are not drawn
+    EmptyRectangles {
+        /// Index of the command
+        command: usize,
+
+        /// Number of empty rectangles in the command
+        count: usize,
+    },
+
+    /// A command uses a gradient whose two points are the same, see
+    /// `Style::is_degenerate_gradient`
+    DegenerateGradient {
+        /// Index of the command
+        command: usize,
+    },
+
+    /// An `RgbaF32` color has channels which are NaN, infinite or outside of
+    /// the range 0 to 1. The channels were clamped, see
+    /// `color::from_rgba_f32`.
+    ColorOutOfRange {
+        /// Index of the color in the color table
+        index: usize,
+    },
+
+    /// A command could not be decoded, and decoding stopped before it with
+    /// `DecodeOptions::recover`. The image holds the commands before it.
+    UndecodableCommand {
+        /// Index of the command
+        command: usize,
+
+        /// Offset of the command's first byte from the start of the file
+        offset: usize,
+
+        /// Why the command could not be decoded, with all causes
+        error: String,
+    },
+}
+
+/// A decoding failure after the header was decoded successfully, with all
+/// commands decoded before the failure. Returned by `Decoder::decode_partial`.
+#[derive(Debug)]
+pub struct PartialDecode {
+    /// Header, color table and every command decoded before the failure
+    pub image: Image,
+
+    /// The error that stopped decoding
+    pub error: Error,
+}
+
+impl std::fmt::Display for PartialDecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "decoding failed after {} commands: {}",
+            self.image.commands.len(),
+            full_message(&self.error)
+        )
+    }
+}
+
+impl std::error::Error for PartialDecode {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl std::fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeWarning::TrailerLooksLikeCommands { len } => write!(
+                f,
+                "the {} byte trailer starts with what looks like another command",
+                len
+            ),
+            DecodeWarning::EmptyRectangles { command, count } => write!(
+                f,
+                "command {} has {} rectangles without area, they are not drawn",
+                command, count
+            ),
+            DecodeWarning::DegenerateGradient { command } => write!(
+                f,
+                "command {} uses a gradient with identical start and end points",
+                command
+            ),
+            DecodeWarning::ColorOutOfRange { index } => write!(
+                f,
+                "color {} has channels outside of the range 0 to 1, they were clamped",
+                index
+            ),
+            DecodeWarning::UndecodableCommand {
+                command,
+                offset,
+                error,
+            } => write!(
+                f,
+                "decoding stopped at command {} at byte {}: {}",
+                command, offset, error
+            ),
+        }
+    }
 }
 
 enum StyleVariant {
@@ -66,7 +349,7 @@ enum StyleVariant {
 }
 
 impl TryFrom<u8> for StyleVariant {
-    type Error = eyre::Error;
+    type Error = Error;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         Ok(match value {
@@ -108,19 +391,151 @@ where
             color_count: 0,
             color_encoding: ColorEncoding::Rgb565,
             scale: 0,
+            trailer_check: TrailerCheck::Ignore,
+            max_trailer_len: None,
+            warnings: Vec::new(),
+            source_map: Vec::new(),
+            options: DecodeOptions::default(),
+            strict: false,
         }
     }
 
+    /// Replace the limits on sizes and counts read from the file, see
+    /// `DecodeOptions`. By default, `DecodeOptions::default` is used.
+    pub fn with_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Inspect the data after the end-of-document marker, and warn or fail if
+    /// it looks like more commands. Defaults to `TrailerCheck::Ignore`.
+    ///
+    /// ```
+    /// # use tinyvg::decode::{Decoder, DecodeWarning, TrailerCheck};
+    /// # use tinyvg::Image;
+    /// // An empty image, followed by a flat filled rectangle
+    /// let bytes = [
+    ///     0x72, 0x56, 1, 0x40, 16, 16, 1, 0, 0, 0, 255, 0,
+    ///     0x02, 0, 0, 0, 0, 16, 16, 0,
+    /// ];
+    ///
+    /// let mut decoder = Decoder::new(&bytes[..]).with_trailer_check(TrailerCheck::Warn);
+    /// let mut image = decoder.decode_header().unwrap();
+    /// decoder.decode_commands(&mut image).unwrap();
+    ///
+    /// assert_eq!(
+    ///     decoder.warnings(),
+    ///     [DecodeWarning::TrailerLooksLikeCommands { len: 8 }]
+    /// );
+    ///
+    /// let decoder = Decoder::new(&bytes[..]).with_trailer_check(TrailerCheck::Error);
+    /// assert!(decoder.decode().is_err());
+    /// ```
+    pub fn with_trailer_check(mut self, check: TrailerCheck) -> Self {
+        self.trailer_check = check;
+        self
+    }
+
+    /// Fail if the data after the end-of-document marker is longer than `len`
+    /// bytes. By default the trailer is unlimited.
+    pub fn with_max_trailer_len(mut self, len: usize) -> Self {
+        self.max_trailer_len = Some(len);
+        self
+    }
+
+    /// Fail on input that doesn't conform to the TinyVG specification, even
+    /// where the decoder could work around it. The root cause of the error
+    /// is an `Error::Validation`, see the `validate` module.
+    ///
+    /// ```
+    /// # use tinyvg::{Decoder, Error};
+    /// # use tinyvg::validate::ValidationError;
+    /// // An empty image, with the color count encoded in two bytes
+    /// let bytes = [0x72, 0x56, 1, 0x40, 16, 16, 0x80, 0x00, 0];
+    /// assert!(Decoder::new(&bytes[..]).decode().is_ok());
+    ///
+    /// let error = Decoder::new(&bytes[..]).with_strict().decode().unwrap_err();
+    /// assert!(matches!(
+    ///     error.root_cause(),
+    ///     Error::Validation(ValidationError::NonCanonicalVarUint { offset: 6 })
+    /// ));
+    ///
+    /// // An empty image without the end-of-document marker
+    /// let bytes = [0x72, 0x56, 1, 0x40, 16, 16, 0];
+    /// let error = Decoder::new(&bytes[..]).with_strict().decode().unwrap_err();
+    /// assert!(matches!(
+    ///     error.root_cause(),
+    ///     Error::Validation(ValidationError::MissingEndOfDocument)
+    /// ));
+    /// ```
+    pub fn with_strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Byte range in the input of every command decoded so far, indexed like
+    /// `Image::commands`
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// let bytes = std::fs::read("data/everything.tvg").unwrap();
+    ///
+    /// let mut decoder = Decoder::new(bytes.as_slice());
+    /// let mut image = decoder.decode_header().unwrap();
+    /// decoder.decode_commands(&mut image).unwrap();
+    ///
+    /// assert_eq!(decoder.source_map().len(), image.commands.len());
+    ///
+    /// // The first command's bytes decode to the same command
+    /// let first = decoder.source_map()[0].clone();
+    /// let mut single = bytes[..first.start].to_vec();
+    /// single.extend(&bytes[first]);
+    /// single.push(0);
+    ///
+    /// let decoded = Decoder::new(single.as_slice()).decode().unwrap();
+    /// assert_eq!(decoded.commands, image.commands[..1]);
+    /// ```
+    pub fn source_map(&self) -> &[Range<usize>] {
+        &self.source_map
+    }
+
+    /// Warnings recorded so far. Only useful together with
+    /// `Decoder::decode_header` and `Decoder::decode_commands`, since
+    /// `Decoder::decode` consumes the decoder.
+    ///
+    /// ```
+    /// # use tinyvg::decode::{Decoder, DecodeWarning};
+    /// // A rectangle with a height of zero, and one with a width of -8
+    /// let bytes = [
+    ///     0x72, 0x56, 1, 0x40, 16, 16, 1, 0, 0, 0, 255,
+    ///     0x02, 1, 0, 0, 0, 16, 0, 8, 0, 0xF8, 8, 0,
+    /// ];
+    ///
+    /// let mut decoder = Decoder::new(&bytes[..]);
+    /// let mut image = decoder.decode_header().unwrap();
+    /// decoder.decode_commands(&mut image).unwrap();
+    ///
+    /// assert_eq!(
+    ///     decoder.warnings(),
+    ///     [DecodeWarning::EmptyRectangles {
+    ///         command: 0,
+    ///         count: 2
+    ///     }]
+    /// );
+    ///
+    /// // Empty rectangles are skipped when rendering
+    /// let mut png = Vec::new();
+    /// image.render_png(&mut png).unwrap();
+    /// ```
+    pub fn warnings(&self) -> &[DecodeWarning] {
+        &self.warnings
+    }
+
     fn magic_number(&mut self) -> Result<()> {
         let b0 = self.reader.read_u8()?;
         let b1 = self.reader.read_u8()?;
 
-        ensure!(
-            b0 == 0x72 && b1 == 0x56,
-            "tinyvg file must begin with magic number 0x72 0x56, found {:x} {:x}",
-            b0,
-            b1
-        );
+        ensure!(b0 == 0x72 && b1 == 0x56, Error::BadMagic([b0, b1]));
 
         Ok(())
     }
@@ -128,11 +543,7 @@ where
     fn version(&mut self) -> Result<u8> {
         let version = self.reader.read_u8()?;
 
-        ensure!(
-            version == 1,
-            "Tried to parse TinyVG file with version {} - only version 1 files are supported",
-            version,
-        );
+        ensure!(version == 1, Error::UnsupportedVersion(version));
 
         Ok(version)
     }
@@ -160,7 +571,7 @@ where
             1 => CoordinateRange::Reduced,
             2 => CoordinateRange::Enhanced,
             x => {
-                bail!("unrecognized coordinate type {}", x);
+                bail!(Error::UnsupportedCoordinateRange(x));
             }
         };
 
@@ -168,11 +579,8 @@ where
             0 => ColorEncoding::Rgba8888,
             1 => ColorEncoding::Rgb565,
             2 => ColorEncoding::RgbaF32,
-            3 => {
-                bail!("custom color encodings are not supported");
-            }
             x => {
-                bail!("unrecognized color encoding {}", x);
+                bail!(Error::UnsupportedColorEncoding(x));
             }
         };
 
@@ -195,21 +603,48 @@ where
             }
             CoordinateRange::Enhanced => {
                 let x = self.reader.read_u32::<LittleEndian>()?;
-                Ok(x as u32)
+                Ok(x)
+            }
+        }
+    }
+
+    /// Units are signed, unlike the image dimensions which share their width
+    fn read_signed_with_coordinate_range(&mut self) -> Result<i32> {
+        match self.coordinate_range {
+            CoordinateRange::Reduced => {
+                let x = self.reader.read_i8()?;
+                Ok(x as i32)
+            }
+            CoordinateRange::Default => {
+                let x = self.reader.read_i16::<LittleEndian>()?;
+                Ok(x as i32)
+            }
+            CoordinateRange::Enhanced => {
+                let x = self.reader.read_i32::<LittleEndian>()?;
+                Ok(x)
             }
         }
     }
 
     fn read_var_uint(&mut self) -> Result<u32> {
+        let offset = self.reader.bytes_read;
         let mut result = 0u32;
         let mut count = 0;
 
         loop {
+            // 5 bytes carry 35 bits, more can't be a u32
+            ensure!(count < 5, "variable-length integer is longer than 5 bytes");
+
             let b = self.reader.read_u8()? as u32;
 
             result |= (b & 0x7F) << (7 * count);
 
             if (b & 0x80) == 0 {
+                // A last byte of zero adds nothing to the value
+                if self.strict && count > 0 && b == 0 {
+                    bail!(ValidationError::NonCanonicalVarUint { offset });
+                }
+
                 break;
             }
 
@@ -219,51 +654,62 @@ where
         Ok(result)
     }
 
+    /// Read the number of items of a command, which is stored minus one
+    fn read_count(&mut self) -> Result<u32> {
+        let count = self.read_var_uint()? as u64 + 1;
+        check_limit(
+            "max_segment_commands",
+            count,
+            self.options.max_segment_commands,
+        )?;
+
+        u32::try_from(count).map_err(|_| format_err!("item count {} is too large", count))
+    }
+
     fn decode_color_table(&mut self) -> Result<Vec<Color>> {
         let mut colors = Vec::new();
+        self.decode_color_table_into(&mut colors)?;
 
-        for _ in 0..self.color_count {
+        Ok(colors)
+    }
+
+    fn decode_color_table_into(&mut self, colors: &mut Vec<Color>) -> Result<()> {
+        // Colors are pushed one at a time instead of reserving the count from
+        // the header up front, so that a huge count in a short file fails at
+        // the end of the input instead of allocating
+        for index in 0..self.color_count as usize {
             colors.push(match self.color_encoding {
                 ColorEncoding::Rgba8888 => self.color_8888()?,
-                ColorEncoding::RgbaF32 => self.color_f32()?,
+                ColorEncoding::RgbaF32 => self.color_f32(index)?,
                 ColorEncoding::Rgb565 => self.color_565()?,
             })
         }
 
-        Ok(colors)
+        Ok(())
     }
 
     fn color_8888(&mut self) -> Result<Color> {
-        let red = self.reader.read_u8()?;
-        let green = self.reader.read_u8()?;
-        let blue = self.reader.read_u8()?;
-        let alpha = self.reader.read_u8()?;
+        let mut raw = [0; 4];
+        self.reader.read_exact(&mut raw)?;
 
-        Ok(Color::rgba8(red, green, blue, alpha))
+        Ok(color::from_rgba8888(raw))
     }
 
-    fn color_f32(&mut self) -> Result<Color> {
-        let red = self.reader.read_f32::<LittleEndian>()?;
-        let green = self.reader.read_f32::<LittleEndian>()?;
-        let blue = self.reader.read_f32::<LittleEndian>()?;
-        let alpha = self.reader.read_f32::<LittleEndian>()?;
+    fn color_f32(&mut self, index: usize) -> Result<Color> {
+        let mut raw = [0.0; 4];
+        self.reader.read_f32_into::<LittleEndian>(&mut raw)?;
+
+        if !color::is_valid_rgba_f32(raw) {
+            self.warnings.push(DecodeWarning::ColorOutOfRange { index });
+        }
 
-        Ok(Color::rgba(
-            red as f64,
-            green as f64,
-            blue as f64,
-            alpha as f64,
-        ))
+        Ok(color::from_rgba_f32(raw))
     }
 
     fn color_565(&mut self) -> Result<Color> {
-        let rgb = self.reader.read_u16::<LittleEndian>()?;
+        let raw = self.reader.read_u16::<LittleEndian>()?;
 
-        let red = (((rgb & 0x001F) >> 0) as f64) / 31.0;
-        let green = (((rgb & 0x07E0) >> 5) as f64) / 63.0;
-        let blue = (((rgb & 0xF800) >> 11) as f64) / 31.0;
-
-        Ok(Color::rgb(red, green, blue))
+        Ok(color::from_rgb565(raw))
     }
 
     fn header(&mut self) -> Result<Header> {
@@ -274,8 +720,14 @@ where
         self.coordinate_range = scale_properties.coordinate_range;
         let width = self.read_with_coordinate_range()?;
         let height = self.read_with_coordinate_range()?;
+        if self.strict && (width == 0 || height == 0) {
+            bail!(ValidationError::EmptySize { width, height });
+        }
+        check_limit("max_width", width as u64, self.options.max_width)?;
+        check_limit("max_height", height as u64, self.options.max_height)?;
 
         let color_count = self.read_var_uint()?;
+        check_limit("max_colors", color_count as u64, self.options.max_colors)?;
 
         self.color_count = color_count;
         self.color_encoding = scale_properties.color_encoding;
@@ -288,7 +740,6 @@ where
             coordinate_range: scale_properties.coordinate_range,
             width,
             height,
-            color_count,
         })
     }
 
@@ -303,7 +754,7 @@ where
     }
 
     fn read_unit(&mut self) -> Result<f64> {
-        let raw = self.read_with_coordinate_range()?;
+        let raw = self.read_signed_with_coordinate_range()?;
 
         let scale_factor = 1u32 << self.scale;
         let result = (raw as f64) / (scale_factor as f64);
@@ -317,10 +768,9 @@ where
         let width = self.read_unit()?;
         let height = self.read_unit()?;
 
-        Ok(Rect::from_origin_size(
-            Point { x, y },
-            Size { width, height },
-        ))
+        // Not `Rect::from_origin_size`, which would flip negative extents
+        // into rectangles with area
+        Ok(Rect::new(x, y, x + width, y + height))
     }
 
     fn fill_rectangles(&mut self, style_variant: StyleVariant) -> Result<Command> {
@@ -416,12 +866,10 @@ where
         let radius = self.read_unit()?;
         let target = self.point()?;
 
-        Ok(SegmentCommandKind::ArcEllipse {
+        Ok(SegmentCommandKind::ArcCircle {
             large,
             sweep,
-            radius_x: radius,
-            radius_y: radius,
-            rotation: 0.0,
+            radius,
             target,
         })
     }
@@ -488,8 +936,13 @@ where
     }
 
     fn segment_command_tag(&mut self) -> Result<SegmentCommandTag> {
+        let offset = self.reader.bytes_read;
         let raw = self.reader.read_u8()?;
 
+        if self.strict && raw & 0b1110_1000 != 0 {
+            bail!(ValidationError::ReservedBitsSet { offset });
+        }
+
         let instruction = raw & 0b0000_0111;
 
         let has_line_width = (raw & 0b001_0000) > 0;
@@ -522,7 +975,7 @@ where
         variant: StyleVariant,
         f: impl Fn(&mut Self) -> Result<T>,
     ) -> Result<(Style, Vec<T>)> {
-        let count = self.read_var_uint()? + 1;
+        let count = self.read_count()?;
         let style = self.style(variant)?;
 
         let mut items = Vec::new();
@@ -535,8 +988,19 @@ where
 
     fn read_path(&mut self, count: u32) -> Result<Vec<Segment>> {
         let mut segment_lengths = Vec::new();
+        let mut total = 0;
         for _ in 0..count {
-            segment_lengths.push(self.read_var_uint()? + 1);
+            let len = self.read_count()?;
+
+            // Each segment is within the limit, but the path may not be
+            total += len as u64;
+            check_limit(
+                "max_segment_commands",
+                total,
+                self.options.max_segment_commands,
+            )?;
+
+            segment_lengths.push(len);
         }
 
         let mut items = Vec::new();
@@ -548,7 +1012,7 @@ where
     }
 
     fn fill_path(&mut self, style_variant: StyleVariant) -> Result<Command> {
-        let count = self.read_var_uint()? + 1;
+        let count = self.read_count()?;
         let fill_style = self.style(style_variant)?;
 
         let path = self.read_path(count)?;
@@ -577,7 +1041,7 @@ where
     }
 
     fn draw_lines(&mut self, style_variant: StyleVariant) -> Result<Command> {
-        let count = self.read_var_uint()? + 1;
+        let count = self.read_count()?;
         let line_style = self.style(style_variant)?;
         let line_width = self.read_unit()?;
 
@@ -594,7 +1058,7 @@ where
     }
 
     fn draw_line_loop(&mut self, style_variant: StyleVariant) -> Result<Command> {
-        let count = self.read_var_uint()? + 1;
+        let count = self.read_count()?;
         let line_style = self.style(style_variant)?;
         let line_width = self.read_unit()?;
 
@@ -612,7 +1076,7 @@ where
     }
 
     fn draw_line_strip(&mut self, style_variant: StyleVariant) -> Result<Command> {
-        let count = self.read_var_uint()? + 1;
+        let count = self.read_count()?;
         let line_style = self.style(style_variant)?;
         let line_width = self.read_unit()?;
 
@@ -630,7 +1094,7 @@ where
     }
 
     fn draw_line_path(&mut self, style_variant: StyleVariant) -> Result<Command> {
-        let count = self.read_var_uint()? + 1;
+        let count = self.read_count()?;
         let line_style = self.style(style_variant)?;
         let line_width = self.read_unit()?;
 
@@ -713,12 +1177,27 @@ where
     }
 
     fn command(&mut self) -> Result<Option<Command>> {
-        let (command_index, primary_style) = self.u6_u2()?;
+        let offset = self.reader.bytes_read;
+        let (command_index, primary_style) = match self.u6_u2() {
+            Err(error) if self.strict && ran_out_of_data(&error) => {
+                bail!(ValidationError::MissingEndOfDocument)
+            }
+            result => result?,
+        };
+
+        // The end-of-document marker carries no style, so it is handled before
+        // the style bits are interpreted
+        if command_index == 0 {
+            if self.strict && primary_style != 0 {
+                bail!(ValidationError::ReservedBitsSet { offset });
+            }
+
+            return Ok(None);
+        }
 
         let primary_style = primary_style.try_into()?;
 
         let command = match command_index {
-            0 => return Ok(None),
             1 => self.fill_polygon(primary_style)?,
             2 => self.fill_rectangles(primary_style)?,
             3 => self.fill_path(primary_style)?,
@@ -744,6 +1223,41 @@ where
     ///
     /// let image = decoder.decode().unwrap();
     /// ```
+    ///
+    /// Units are signed, so geometry and gradient anchors can lie above or
+    /// left of the image:
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::format::{Command, Point, Style};
+    /// let bytes = std::fs::read("data/negative.tvg").unwrap();
+    /// let image = Decoder::new(bytes.as_slice()).decode().unwrap();
+    ///
+    /// match &image.commands[0] {
+    ///     Command::FillPolygon { polygon, .. } => {
+    ///         assert_eq!(polygon[0], Point::new(-8.0, -8.0));
+    ///         assert_eq!(polygon[1], Point::new(16.0, -4.0));
+    ///     }
+    ///     command => panic!("unexpected command {:?}", command),
+    /// }
+    ///
+    /// match &image.commands[2] {
+    ///     Command::FillRectangles {
+    ///         fill_style: Style::LinearGradient { point_0, .. },
+    ///         rectangles,
+    ///         ..
+    ///     } => {
+    ///         assert_eq!(*point_0, Point::new(-10.0, 0.0));
+    ///         assert_eq!(rectangles[0].origin(), Point::new(-10.0, 10.0));
+    ///     }
+    ///     command => panic!("unexpected command {:?}", command),
+    /// }
+    ///
+    /// // Encoding writes the same signed values back
+    /// let mut encoded = Vec::new();
+    /// image.encode(&mut encoded).unwrap();
+    /// assert_eq!(encoded, bytes);
+    /// ```
     pub fn decode(mut self) -> Result<Image> {
         let mut image = self.decode_header()?;
 
@@ -752,6 +1266,68 @@ where
         Ok(image)
     }
 
+    /// Decode a TinyVG image from the reader into an existing image, replacing
+    /// its contents. The buffers of the image's color table, command list and
+    /// trailer are reused, so a service that decodes many files into the same
+    /// image only grows them to the largest file instead of allocating them
+    /// per file. The geometry of each command is still allocated per command.
+    /// If decoding fails, the image holds whatever was decoded before the
+    /// failure.
+    ///
+    /// ```
+    /// # use tinyvg::{Decoder, Image};
+    /// let mut image = Image::empty(0, 0);
+    ///
+    /// for path in ["data/tiger.tvg", "data/shield.tvg"] {
+    ///     let bytes = std::fs::read(path).unwrap();
+    ///     Decoder::new(bytes.as_slice()).decode_into(&mut image).unwrap();
+    /// }
+    ///
+    /// assert_eq!(image.header.width, 24);
+    /// // The command list kept the capacity it grew to for the tiger
+    /// assert!(image.commands.capacity() > image.commands.len());
+    /// ```
+    pub fn decode_into(mut self, image: &mut Image) -> Result<()> {
+        image.header = self.header().wrap_err("error parsing header")?;
+
+        image.color_table.clear();
+        image.commands.clear();
+        image.trailer.clear();
+        image.labels.clear();
+        image.bounds = self.options.compute_bounds.then(Vec::new);
+
+        self.decode_color_table_into(&mut image.color_table)
+            .wrap_err("error parsing color table")?;
+        self.decode_commands(image)
+    }
+
+    /// Decode a TinyVG image from the reader, keeping everything that was
+    /// decoded if the input ends or is corrupt partway through the commands.
+    /// Errors in the header are returned by the outer result, since there is
+    /// no image without one.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// let bytes = std::fs::read("data/shield.tvg").unwrap();
+    /// let truncated = &bytes[..bytes.len() - 10];
+    ///
+    /// let partial = Decoder::new(truncated)
+    ///     .decode_partial()
+    ///     .unwrap()
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(partial.image.header.width, 24);
+    /// assert!(partial.image.commands.is_empty());
+    /// ```
+    pub fn decode_partial(mut self) -> Result<Result<Image, PartialDecode>> {
+        let mut image = self.decode_header()?;
+
+        Ok(match self.decode_commands(&mut image) {
+            Ok(()) => Ok(image),
+            Err(error) => Err(PartialDecode { image, error }),
+        })
+    }
+
     /// Decode a TinyVG image header file from the reader. Does not decode any
     /// commands from the file. To get commands, you must use
     /// `Decoder::decode_commands` after calling this function. Calling these two
@@ -778,9 +1354,33 @@ where
             color_table,
             commands: Vec::new(),
             trailer: Vec::new(),
+            labels: Default::default(),
+            bounds: self.options.compute_bounds.then(Vec::new),
         })
     }
 
+    /// Decode only the color table of a TinyVG image from the reader. The
+    /// header is parsed to find the color encoding, then decoding stops before
+    /// the commands, so that tools which only need the palette don't pay for
+    /// the geometry.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// # use std::fs::File;
+    /// let palette = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode_palette()
+    ///     .unwrap();
+    ///
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(palette, image.color_table);
+    /// ```
+    pub fn decode_palette(mut self) -> Result<Vec<Color>> {
+        Ok(self.decode_header()?.color_table)
+    }
+
     /// Decode TinyVG image commands from the reader. The Image can be obtained
     /// by calling `Decoder::decode_header`. Calling these two functions together
     /// is basically the same as calling `Decoder::decode`. This function exists
@@ -797,7 +1397,7 @@ where
     /// ```
     pub fn decode_commands(&mut self, file: &mut Image) -> Result<()> {
         self.decode_inner(file).wrap_err_with(|| {
-            eyre!(
+            format!(
                 "parsing failed after reading {} bytes",
                 self.reader.bytes_read
             )
@@ -806,17 +1406,413 @@ where
         Ok(())
     }
 
+    /// Decode a TinyVG image like `Decoder::decode`, and return the warnings
+    /// about the input along with it, see `Decoder::warnings`. Together with
+    /// `DecodeOptions::recover`, this gives as much of a damaged file as can
+    /// be decoded, e.g. for a thumbnailer or viewer.
+    ///
+    /// ```
+    /// # use tinyvg::decode::{DecodeOptions, DecodeWarning, Decoder};
+    /// let bytes = std::fs::read("data/everything.tvg").unwrap();
+    /// let complete = Decoder::new(bytes.as_slice()).decode().unwrap();
+    /// let truncated = &bytes[..bytes.len() / 2];
+    ///
+    /// assert!(Decoder::new(truncated).decode().is_err());
+    ///
+    /// let options = DecodeOptions {
+    ///     recover: true,
+    ///     ..Default::default()
+    /// };
+    /// let (image, warnings) = Decoder::new(truncated)
+    ///     .with_options(options)
+    ///     .decode_with_warnings()
+    ///     .unwrap();
+    ///
+    /// assert!(!image.commands.is_empty());
+    /// assert_eq!(
+    ///     image.commands[..],
+    ///     complete.commands[..image.commands.len()]
+    /// );
+    /// assert!(matches!(
+    ///     warnings.last(),
+    ///     Some(DecodeWarning::UndecodableCommand { command, .. })
+    ///         if *command == image.commands.len()
+    /// ));
+    /// ```
+    pub fn decode_with_warnings(mut self) -> Result<(Image, Vec<DecodeWarning>)> {
+        let mut image = self.decode_header()?;
+
+        self.decode_commands(&mut image)?;
+
+        Ok((image, self.warnings))
+    }
+
+    /// Decode a TinyVG image like `Decoder::decode`, and report counters about
+    /// the work done while decoding. See the `stats` module for how to count
+    /// allocations.
+    #[cfg(feature = "decode-stats")]
+    pub fn decode_with_stats(mut self) -> Result<(Image, crate::stats::DecodeStats)> {
+        let (allocations_before, bytes_before) = crate::stats::allocation_counters();
+
+        let mut image = self.decode_header()?;
+        self.decode_commands(&mut image)?;
+
+        let (allocations_after, bytes_after) = crate::stats::allocation_counters();
+
+        let stats = crate::stats::DecodeStats {
+            allocations: allocations_after - allocations_before,
+            allocated_bytes: bytes_after - bytes_before,
+            commands: image.commands.len(),
+            bytes_read: self.reader.bytes_read,
+        };
+
+        Ok((image, stats))
+    }
+
+    /// Iterate over the commands of a TinyVG image, decoding each command
+    /// only when it is requested, so that consumers that process commands
+    /// one at a time (e.g. translating them to another format) don't need to
+    /// hold all of them in memory. Call `Decoder::decode_header` first. The
+    /// iterator ends at the end-of-document marker, without reading the
+    /// trailer, or after the first error. Warnings, bounds and the source map
+    /// are recorded as commands are decoded.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// # use std::fs::File;
+    /// let mut decoder = Decoder::new(File::open("data/tiger.tvg").unwrap());
+    /// decoder.decode_header().unwrap();
+    ///
+    /// let mut fills = 0;
+    /// for command in decoder.commands() {
+    ///     if command.unwrap().name().contains("fill") {
+    ///         fills += 1;
+    ///     }
+    /// }
+    ///
+    /// assert!(fills > 0);
+    /// assert!(decoder.source_map().len() >= fills);
+    /// ```
+    pub fn commands(&mut self) -> Commands<'_, R> {
+        Commands {
+            decoder: self,
+            done: false,
+        }
+    }
+
+    /// Decode the next command and record what is known about it, or `None`
+    /// at the end-of-document marker
+    pub(crate) fn next_command(&mut self) -> Result<Option<Command>> {
+        let start = self.reader.bytes_read;
+        let index = self.source_map.len();
+
+        let command = match self.command().map_err(|source| Error::InvalidCommand {
+            index,
+            offset: start,
+            source: Box::new(source),
+        })? {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+        check_limit("max_commands", index as u64 + 1, self.options.max_commands)?;
+
+        if self.strict {
+            if let Some(error) = check_color_indices(index, &command, self.color_count as usize) {
+                bail!(error);
+            }
+        }
+
+        if let Command::FillRectangles { rectangles, .. } = &command {
+            let count = rectangles.iter().filter(|r| !has_area(r)).count();
+
+            if count > 0 {
+                self.warnings.push(DecodeWarning::EmptyRectangles {
+                    command: index,
+                    count,
+                });
+            }
+        }
+
+        if command.styles().any(Style::is_degenerate_gradient) {
+            self.warnings
+                .push(DecodeWarning::DegenerateGradient { command: index });
+        }
+
+        self.source_map.push(start..self.reader.bytes_read);
+
+        Ok(Some(command))
+    }
+
     fn decode_inner(&mut self, file: &mut Image) -> Result<()> {
-        while let Some(command) = self.command().wrap_err("error parsing command")? {
-            file.commands.push(command);
+        loop {
+            let offset = self.reader.bytes_read;
+
+            match self.next_command() {
+                Ok(Some(command)) => self.push_command(file, command),
+                Ok(None) => break,
+                Err(error) => return self.recover(error, offset),
+            }
+        }
+
+        self.decode_trailer(file)
+    }
+
+    /// Append a decoded command to `file`, along with its bounding box with
+    /// `DecodeOptions::compute_bounds`
+    pub(crate) fn push_command(&self, file: &mut Image, command: Command) {
+        if self.options.compute_bounds {
+            file.bounds
+                .get_or_insert_with(Vec::new)
+                .push(command.bounds());
+        }
+
+        file.commands.push(command);
+    }
+
+    /// Handle a command at `offset` that failed to decode: record a warning
+    /// and stop decoding with `DecodeOptions::recover`, or fail
+    pub(crate) fn recover(&mut self, error: Error, offset: usize) -> Result<()> {
+        if !self.options.recover {
+            return Err(error);
         }
 
-        self.reader
+        self.warnings.push(DecodeWarning::UndecodableCommand {
+            command: self.source_map.len(),
+            offset,
+            error: full_message(&error),
+        });
+
+        Ok(())
+    }
+
+    /// Read everything after the end-of-document marker into the trailer of
+    /// `file`, and check it
+    pub(crate) fn decode_trailer(&mut self, file: &mut Image) -> Result<()> {
+        // Read one byte more than allowed, to tell a trailer of exactly the
+        // maximum length from a longer one
+        let limit = self.max_trailer_len.map_or(u64::MAX, |max| max as u64 + 1);
+
+        (&mut self.reader)
+            .take(limit)
             .read_to_end(&mut file.trailer)
             .wrap_err("error reading trailing bytes")?;
 
+        if let Some(max) = self.max_trailer_len {
+            ensure!(
+                file.trailer.len() <= max,
+                "trailer is longer than the maximum of {} bytes",
+                max
+            );
+        }
+
+        if self.trailer_check != TrailerCheck::Ignore
+            && self.trailer_looks_like_commands(&file.trailer)
+        {
+            let warning = DecodeWarning::TrailerLooksLikeCommands {
+                len: file.trailer.len(),
+            };
+
+            if self.trailer_check == TrailerCheck::Error {
+                bail!("{}", warning);
+            }
+
+            self.warnings.push(warning);
+        }
+
         Ok(())
     }
+
+    /// The wrapped reader, for decoders that feed it more data as it arrives
+    #[cfg(feature = "async")]
+    pub(crate) fn reader_mut(&mut self) -> &mut R {
+        &mut self.reader.inner
+    }
+
+    /// Number of bytes read from the wrapped reader so far
+    #[cfg(feature = "async")]
+    pub(crate) fn bytes_read(&self) -> usize {
+        self.reader.bytes_read
+    }
+
+    /// Number of warnings found so far, to rewind to with `Decoder::rewind`
+    #[cfg(feature = "async")]
+    pub(crate) fn warning_count(&self) -> usize {
+        self.warnings.len()
+    }
+
+    /// Move the count of bytes read back to `bytes_read` and drop the
+    /// warnings found after the first `warnings`, after the wrapped reader was
+    /// moved back to the same position to retry a read that ran out of data
+    #[cfg(feature = "async")]
+    pub(crate) fn rewind(&mut self, bytes_read: usize, warnings: usize) {
+        self.reader.bytes_read = bytes_read;
+        self.warnings.truncate(warnings);
+    }
+
+    /// Whether the trailer starts with a command that decodes with the
+    /// settings of this image and only references existing colors
+    fn trailer_looks_like_commands(&self, trailer: &[u8]) -> bool {
+        let mut decoder = Decoder::new(trailer);
+        decoder.coordinate_range = self.coordinate_range;
+        decoder.color_count = self.color_count;
+        decoder.color_encoding = self.color_encoding;
+        decoder.scale = self.scale;
+
+        match decoder.command() {
+            Ok(Some(command)) => command
+                .styles()
+                .flat_map(|style| style.color_indices())
+                .all(|index| index < self.color_count as usize),
+            _ => false,
+        }
+    }
+}
+
+/// Iterator over the commands of an image, see `Decoder::commands`
+pub struct Commands<'a, R> {
+    decoder: &'a mut Decoder<R>,
+    done: bool,
+}
+
+impl<R: Read> Iterator for Commands<'_, R> {
+    type Item = Result<Command>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.decoder.next_command() {
+            Ok(Some(command)) => Some(Ok(command)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+impl<R: Read> std::iter::FusedIterator for Commands<'_, R> {}
+
+/// Reader over a byte slice, see `Decoder::from_slice`
+pub struct SliceReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Read for SliceReader<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, std::io::Error> {
+        let remaining = &self.bytes[self.position..];
+        let len = remaining.len().min(buffer.len());
+
+        buffer[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+
+        Ok(len)
+    }
+
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), std::io::Error> {
+        let end = self.position + buffer.len();
+        let source = self.bytes.get(self.position..end).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "unexpected end of input")
+        })?;
+
+        buffer.copy_from_slice(source);
+        self.position = end;
+
+        Ok(())
+    }
+}
+
+impl<'a> Decoder<SliceReader<'a>> {
+    /// Create a decoder reading from a byte slice. This is faster than
+    /// `Decoder::new` with a `&[u8]`, since every read is a single bounds
+    /// check and copy, and the bytes of each decoded command can be retrieved
+    /// without copying with `Decoder::command_slices`.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// let bytes = std::fs::read("data/shield.tvg").unwrap();
+    ///
+    /// let mut decoder = Decoder::from_slice(&bytes);
+    /// let mut image = decoder.decode_header().unwrap();
+    /// decoder.decode_commands(&mut image).unwrap();
+    ///
+    /// assert_eq!(image, Decoder::new(bytes.as_slice()).decode().unwrap());
+    /// assert_eq!(decoder.command_slices().len(), image.commands.len());
+    /// ```
+    pub fn from_slice(bytes: &'a [u8]) -> Self {
+        Decoder::new(SliceReader { bytes, position: 0 })
+    }
+
+    /// The input bytes of every command decoded so far, indexed like
+    /// `Image::commands`. The slices borrow the slice passed to
+    /// `Decoder::from_slice`.
+    pub fn command_slices(&self) -> Vec<&'a [u8]> {
+        let bytes = self.reader.inner.bytes;
+
+        self.source_map
+            .iter()
+            .map(|range| &bytes[range.clone()])
+            .collect()
+    }
+}
+
+/// Reader over a `bytes::Bytes` buffer, see `Decoder::from_bytes`
+#[cfg(feature = "bytes")]
+pub struct BytesReader {
+    bytes: bytes::Bytes,
+    position: usize,
+}
+
+#[cfg(feature = "bytes")]
+impl Read for BytesReader {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, std::io::Error> {
+        let remaining = &self.bytes[self.position..];
+        let len = remaining.len().min(buffer.len());
+
+        buffer[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Decoder<BytesReader> {
+    /// Create a decoder reading from a shared `bytes::Bytes` buffer without
+    /// copying it. Only the bytes of each decoded command can be retrieved as
+    /// cheap sub-slices of it, with `Decoder::command_bytes`. The decoded
+    /// `Image`, including its color table and path segments, owns its data as
+    /// with any other reader.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// let bytes = bytes::Bytes::from(std::fs::read("data/shield.tvg").unwrap());
+    ///
+    /// let mut decoder = Decoder::from_bytes(bytes);
+    /// let mut image = decoder.decode_header().unwrap();
+    /// decoder.decode_commands(&mut image).unwrap();
+    ///
+    /// assert_eq!(decoder.command_bytes().len(), image.commands.len());
+    /// ```
+    pub fn from_bytes(bytes: bytes::Bytes) -> Self {
+        Decoder::new(BytesReader { bytes, position: 0 })
+    }
+
+    /// The input bytes of every command decoded so far, indexed like
+    /// `Image::commands`. The slices share the buffer passed to
+    /// `Decoder::from_bytes`, and include the segments of path commands.
+    pub fn command_bytes(&self) -> Vec<bytes::Bytes> {
+        self.source_map
+            .iter()
+            .map(|range| self.reader.inner.bytes.slice(range.clone()))
+            .collect()
+    }
 }
 
 struct OutlineFill<T> {
@@ -831,3 +1827,14 @@ struct ScaleProperties {
     color_encoding: ColorEncoding,
     coordinate_range: CoordinateRange,
 }
+
+/// Whether an error was caused by the input ending. A missing end-of-document
+/// marker counts, so that the async decoder still sees that it ran out of
+/// data in strict mode.
+pub(crate) fn ran_out_of_data(error: &Error) -> bool {
+    match error.root_cause() {
+        Error::Io(error) => error.kind() == std::io::ErrorKind::UnexpectedEof,
+        Error::Validation(ValidationError::MissingEndOfDocument) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,237 @@
+//! Decoding from a `tokio::io::AsyncRead`, so that servers can decode assets
+//! while they arrive over the network instead of buffering the whole body
+//! first. Enabled by the `async` feature.
+//!
+//! The binary format is parsed by the same code as `Decoder`. Data is read in
+//! chunks into a buffer, and a header or command that is cut off by the end
+//! of the buffer is parsed again once at least as much data again has
+//! arrived, so that a large command is parsed a logarithmic number of times
+//! rather than once per chunk. Only the unparsed part of the input is kept in
+//! memory.
+
+use std::io::Read;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::decode::{ran_out_of_data, DecodeOptions, DecodeWarning, Decoder};
+use crate::error::{Context, Result};
+use crate::format::{Command, Image};
+
+/// Number of bytes requested from the reader at a time
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Decoder for TinyVG images from a `tokio::io::AsyncRead`, with the same
+/// methods as `Decoder`
+///
+/// ```
+/// # use tinyvg::decode_async::AsyncDecoder;
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let bytes = std::fs::read("data/tiger.tvg").unwrap();
+///
+/// // Any AsyncRead, e.g. the body of an HTTP response
+/// let mut decoder = AsyncDecoder::new(bytes.as_slice());
+///
+/// let mut image = decoder.decode_header().await.unwrap();
+/// decoder.decode_commands(&mut image).await.unwrap();
+///
+/// let sync = tinyvg::Decoder::new(bytes.as_slice()).decode().unwrap();
+/// assert_eq!(image, sync);
+/// # });
+/// ```
+pub struct AsyncDecoder<R> {
+    reader: R,
+    decoder: Decoder<Buffer>,
+    eof: bool,
+}
+
+/// Bytes received from the async reader that haven't been parsed yet
+#[derive(Default)]
+struct Buffer {
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl Read for Buffer {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = &self.data[self.position..];
+        let len = remaining.len().min(buffer.len());
+
+        buffer[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+
+        Ok(len)
+    }
+}
+
+impl<R> AsyncDecoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Create a new decoder wrapping a `tokio::io::AsyncRead`
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            decoder: Decoder::new(Buffer::default()),
+            eof: false,
+        }
+    }
+
+    /// Replace the limits on sizes and counts read from the input, see
+    /// `Decoder::with_options`
+    pub fn with_options(mut self, options: DecodeOptions) -> Self {
+        self.decoder = self.decoder.with_options(options);
+        self
+    }
+
+    /// Fail on input that doesn't conform to the TinyVG specification, see
+    /// `Decoder::with_strict`
+    pub fn with_strict(mut self) -> Self {
+        self.decoder = self.decoder.with_strict();
+        self
+    }
+
+    /// Warnings about the input found so far, see `Decoder::warnings`
+    ///
+    /// ```
+    /// # use tinyvg::decode::DecodeWarning;
+    /// # use tinyvg::decode_async::AsyncDecoder;
+    /// # tokio::runtime::Builder::new_current_thread()
+    /// #     .build()
+    /// #     .unwrap()
+    /// #     .block_on(async {
+    /// // 1024 RgbaF32 colors, more than fits in a chunk, the first of them too
+    /// // bright
+    /// let mut bytes = vec![0x72, 0x56, 1, 0x60, 16, 16, 0x80, 0x08];
+    /// for index in 0..1024 {
+    ///     let red: f32 = if index == 0 { 2.0 } else { 1.0 };
+    ///     for channel in [red, 1.0, 1.0, 1.0] {
+    ///         bytes.extend(channel.to_le_bytes());
+    ///     }
+    /// }
+    /// bytes.push(0);
+    ///
+    /// let mut decoder = AsyncDecoder::new(bytes.as_slice());
+    /// decoder.decode_header().await.unwrap();
+    ///
+    /// // Parsing the color table again after more data arrived doesn't
+    /// // repeat the warning
+    /// assert_eq!(
+    ///     decoder.warnings(),
+    ///     [DecodeWarning::ColorOutOfRange { index: 0 }]
+    /// );
+    /// # });
+    /// ```
+    pub fn warnings(&self) -> &[DecodeWarning] {
+        self.decoder.warnings()
+    }
+
+    /// Decode a TinyVG image from the reader, see `Decoder::decode`
+    pub async fn decode(mut self) -> Result<Image> {
+        let mut image = self.decode_header().await?;
+
+        self.decode_commands(&mut image).await?;
+
+        Ok(image)
+    }
+
+    /// Decode the header and color table of a TinyVG image, see
+    /// `Decoder::decode_header`
+    pub async fn decode_header(&mut self) -> Result<Image> {
+        self.resumable(|decoder| decoder.decode_header()).await
+    }
+
+    /// Decode the next command, or `None` at the end-of-document marker, see
+    /// `Decoder::commands`
+    pub async fn next_command(&mut self) -> Result<Option<Command>> {
+        self.resumable(|decoder| decoder.next_command()).await
+    }
+
+    /// Decode the commands and the trailer of a TinyVG image, see
+    /// `Decoder::decode_commands`
+    pub async fn decode_commands(&mut self, file: &mut Image) -> Result<()> {
+        loop {
+            let offset = self.decoder.bytes_read();
+
+            match self.next_command().await {
+                Ok(Some(command)) => self.decoder.push_command(file, command),
+                Ok(None) => break,
+                Err(error) => return self.decoder.recover(error, offset),
+            }
+        }
+
+        // The trailer is everything up to the end of the input
+        while !self.eof {
+            self.fill().await?;
+        }
+
+        self.decoder.decode_trailer(file).wrap_err_with(|| {
+            format!(
+                "parsing failed after reading {} bytes",
+                self.decoder.bytes_read()
+            )
+        })
+    }
+
+    /// Run `parse` on the buffered data, reading more data and parsing again
+    /// for as long as it runs out of data before the end of the input
+    async fn resumable<T>(
+        &mut self,
+        mut parse: impl FnMut(&mut Decoder<Buffer>) -> Result<T>,
+    ) -> Result<T> {
+        loop {
+            let position = self.decoder.reader_mut().position;
+            let bytes_read = self.decoder.bytes_read();
+            let warnings = self.decoder.warning_count();
+
+            match parse(&mut self.decoder) {
+                Ok(value) => {
+                    // Parsed data is never read again
+                    let buffer = self.decoder.reader_mut();
+                    buffer.data.drain(..buffer.position);
+                    buffer.position = 0;
+
+                    return Ok(value);
+                }
+                Err(error) if !self.eof && ran_out_of_data(&error) => {
+                    let buffer = self.decoder.reader_mut();
+                    buffer.position = position;
+                    let buffered = buffer.data.len() - position;
+                    self.decoder.rewind(bytes_read, warnings);
+
+                    // Doubling the buffered data before every retry keeps
+                    // the total parsing work linear in the size of the input
+                    let target = buffered + buffered.max(CHUNK_SIZE);
+                    while !self.eof && self.decoder.reader_mut().data.len() - position < target {
+                        self.fill().await?;
+                    }
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Append the next chunk of the input to the buffer
+    async fn fill(&mut self) -> Result<()> {
+        let buffer = &mut self.decoder.reader_mut().data;
+        let len = buffer.len();
+
+        buffer.resize(len + CHUNK_SIZE, 0);
+        let read = match self.reader.read(&mut buffer[len..]).await {
+            Ok(read) => read,
+            Err(error) => {
+                buffer.truncate(len);
+                return Err(error).wrap_err("failed to read input");
+            }
+        };
+        buffer.truncate(len + read);
+
+        if read == 0 {
+            self.eof = true;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,803 @@
+//! The `encode` module includes the code required to encode the in-memory
+//! representation of a TinyVG image to the binary TinyVG format.
+
+use std::io::Write;
+use std::ops::Range;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::color;
+use crate::decode::Decoder;
+use crate::error::{bail, ensure, format_err, Context, Result};
+use crate::format::{
+    Color, ColorEncoding, Command, CoordinateRange, Image, OutlineStyle, Point, Rect, Segment,
+    SegmentCommandKind, Style,
+};
+use crate::geometry::{has_area, CoordinateOverflow};
+
+/// Used to write a TinyVG image to an arbitrary `std::io::Write`. Every unit
+/// value is written with the scale and `CoordinateRange` of the image header.
+/// If the geometry or the dimensions of the image don't fit in the header's
+/// range, the narrowest range they fit in is written instead, so the range is
+/// only ever widened (see `Image::required_coordinate_range`).
+///
+/// ```
+/// # use std::fs::File;
+/// # use tinyvg::Decoder;
+/// # use tinyvg::encode::Encoder;
+/// for path in ["data/shield.tvg", "data/everything.tvg", "data/flowchart.tvg"] {
+///     let image = Decoder::new(File::open(path).unwrap())
+///         .decode()
+///         .unwrap();
+///
+///     let mut bytes = Vec::new();
+///     Encoder::new(&mut bytes).encode(&image).unwrap();
+///
+///     let decoded = Decoder::new(bytes.as_slice()).decode().unwrap();
+///     assert_eq!(decoded, image);
+/// }
+/// ```
+///
+/// Commands keep the instruction they were decoded from, e.g. line strips and
+/// line loops or circle and ellipse arcs, so decoding and encoding a file
+/// gives back the same bytes:
+///
+/// ```
+/// # use tinyvg::Decoder;
+/// # use tinyvg::encode::Encoder;
+/// let original = std::fs::read("data/shield.tvg").unwrap();
+/// let image = Decoder::new(original.as_slice()).decode().unwrap();
+///
+/// let mut bytes = Vec::new();
+/// Encoder::new(&mut bytes).encode(&image).unwrap();
+///
+/// assert_eq!(bytes, original);
+/// ```
+///
+/// Large canvases, such as generated plots, are written with 32 bit units of
+/// `CoordinateRange::Enhanced`, also when only some of their coordinates need
+/// it:
+///
+/// ```
+/// # use tinyvg::decode::{DecodeOptions, Decoder};
+/// # use tinyvg::encode::Encoder;
+/// # use tinyvg::format::{Color, Command, CoordinateRange, Image, Line, Point, Rect, Style};
+/// let mut image = Image::empty(100_000, 100_000);
+/// image.color_table.push(Color::BLACK);
+/// image.commands.push(Command::FillRectangles {
+///     fill_style: Style::FlatColor { color_index: 0 },
+///     rectangles: vec![Rect::new(0.0, 0.0, 10.0, 10.0)],
+///     outline: None,
+/// });
+/// image.commands.push(Command::DrawLines {
+///     line_style: Style::FlatColor { color_index: 0 },
+///     line_width: 2.0,
+///     lines: vec![Line::new((1.0, 99_999.0), (99_999.0, 1.0))],
+/// });
+/// image.commands.push(Command::FillPolygon {
+///     fill_style: Style::FlatColor { color_index: 0 },
+///     polygon: (0..1000)
+///         .map(|i| Point::new(i as f64 * 100.0, (i * i % 100_000) as f64))
+///         .collect(),
+///     outline: None,
+/// });
+/// assert_eq!(image.header.coordinate_range, CoordinateRange::Enhanced);
+///
+/// let mut bytes = Vec::new();
+/// Encoder::new(&mut bytes).encode(&image).unwrap();
+///
+/// // Range in the top two bits of the fourth byte, then 32 bit dimensions
+/// assert_eq!(bytes[3] >> 6, 2);
+/// assert_eq!(bytes[4..8], 100_000u32.to_le_bytes());
+///
+/// // The canvas is wider than the default decode limits allow
+/// let decoded = Decoder::new(bytes.as_slice())
+///     .with_options(DecodeOptions::unlimited())
+///     .decode()
+///     .unwrap();
+/// assert_eq!(decoded, image);
+///
+/// // A small canvas with a header range that is too narrow for its geometry,
+/// // here because of the fractional precision of the scale
+/// let mut mixed = Image::empty(200, 200);
+/// mixed.header.scale = 8;
+/// mixed.color_table.push(Color::BLACK);
+/// mixed.commands.push(Command::DrawLines {
+///     line_style: Style::FlatColor { color_index: 0 },
+///     line_width: 0.5,
+///     lines: vec![Line::new((0.0, 0.0), (199.5, 150.25))],
+/// });
+/// assert_eq!(mixed.header.coordinate_range, CoordinateRange::Reduced);
+///
+/// let mut bytes = Vec::new();
+/// Encoder::new(&mut bytes).encode(&mixed).unwrap();
+///
+/// let decoded = Decoder::new(bytes.as_slice()).decode().unwrap();
+/// assert_eq!(decoded.header.coordinate_range, CoordinateRange::Enhanced);
+/// assert_eq!(decoded.commands, mixed.commands);
+/// ```
+pub struct Encoder<W> {
+    writer: W,
+    coordinate_range: CoordinateRange,
+    scale: u8,
+    color_count: usize,
+}
+
+/// Style variants of the binary format, in the order of their tag values
+#[derive(Debug, Clone, Copy)]
+enum StyleVariant {
+    FlatColor = 0,
+    LinearGradient = 1,
+    RadialGradient = 2,
+}
+
+impl StyleVariant {
+    fn of(style: &Style) -> Self {
+        match style {
+            Style::FlatColor { .. } => StyleVariant::FlatColor,
+            Style::LinearGradient { .. } => StyleVariant::LinearGradient,
+            Style::RadialGradient { .. } => StyleVariant::RadialGradient,
+        }
+    }
+}
+
+/// Largest number of items in an outlined fill command, whose count is stored
+/// in 6 bits
+const MAX_OUTLINE_ITEMS: usize = 64;
+
+impl<W> Encoder<W>
+where
+    W: Write,
+{
+    /// Create a new encoder wrapping a `std::io::Write`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            coordinate_range: CoordinateRange::Default,
+            scale: 0,
+            color_count: 0,
+        }
+    }
+
+    /// Encode an image, including its trailer. Fails without writing
+    /// anything if a value can't be represented in the binary format, e.g. a
+    /// unit that does not fit in any `CoordinateRange` or a command without
+    /// any items.
+    pub fn encode(mut self, image: &Image) -> Result<()> {
+        // Encode to memory first, so that nothing is written if the image
+        // can't be encoded
+        let mut buffer = Encoder::new(Vec::new());
+        buffer.image(image)?;
+
+        self.writer
+            .write_all(&buffer.writer)
+            .wrap_err("failed to write encoded image")?;
+
+        Ok(())
+    }
+
+    /// Unwrap this encoder, returning the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn image(&mut self, image: &Image) -> Result<()> {
+        self.header(image).wrap_err("error encoding header")?;
+        self.color_table(image)
+            .wrap_err("error encoding color table")?;
+
+        for (index, command) in image.commands.iter().enumerate() {
+            self.command(command)
+                .wrap_err_with(|| format!("error encoding command {}", index))?;
+        }
+
+        // End of document
+        self.writer.write_u8(0)?;
+        self.writer.write_all(&image.trailer)?;
+
+        Ok(())
+    }
+
+    fn header(&mut self, image: &Image) -> Result<()> {
+        let header = &image.header;
+
+        ensure!(
+            header.version == 1,
+            "only version 1 files can be encoded, found version {}",
+            header.version
+        );
+        ensure!(
+            header.scale < 16,
+            "scale must fit in 4 bits, found {}",
+            header.scale
+        );
+
+        // Units that don't fit in any range are reported with the offending
+        // value when they are written
+        self.coordinate_range = match image.required_coordinate_range() {
+            Some(required) if required.bits() > header.coordinate_range.bits() => required,
+            _ => header.coordinate_range,
+        };
+        self.scale = header.scale;
+        self.color_count = image.color_table.len();
+
+        let coordinate_range = match self.coordinate_range {
+            CoordinateRange::Default => 0,
+            CoordinateRange::Reduced => 1,
+            CoordinateRange::Enhanced => 2,
+        };
+
+        let color_encoding = match header.color_encoding {
+            ColorEncoding::Rgba8888 => 0,
+            ColorEncoding::Rgb565 => 1,
+            ColorEncoding::RgbaF32 => 2,
+        };
+
+        self.writer.write_all(&[0x72, 0x56, header.version])?;
+        self.writer
+            .write_u8((coordinate_range << 6) | (color_encoding << 4) | header.scale)?;
+
+        self.size(header.width)?;
+        self.size(header.height)?;
+
+        self.var_uint(image.color_table.len())?;
+
+        Ok(())
+    }
+
+    fn color_table(&mut self, image: &Image) -> Result<()> {
+        for color in &image.color_table {
+            self.color(color, image.header.color_encoding)?;
+        }
+
+        Ok(())
+    }
+
+    fn color(&mut self, color: &Color, encoding: ColorEncoding) -> Result<()> {
+        match encoding {
+            ColorEncoding::Rgba8888 => self.writer.write_all(&color::to_rgba8888(color))?,
+            ColorEncoding::Rgb565 => self
+                .writer
+                .write_u16::<LittleEndian>(color::to_rgb565(color))?,
+            ColorEncoding::RgbaF32 => {
+                let raw = color::to_rgba_f32(color);
+                ensure!(
+                    color::is_valid_rgba_f32(raw),
+                    "color channels {:?} are outside of the range 0 to 1",
+                    raw
+                );
+
+                for channel in raw {
+                    self.writer.write_f32::<LittleEndian>(channel)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn size(&mut self, size: u32) -> Result<()> {
+        ensure!(
+            self.coordinate_range.fits_size(size),
+            "image size {} does not fit in the coordinate range {:?}",
+            size,
+            self.coordinate_range
+        );
+
+        match self.coordinate_range {
+            CoordinateRange::Reduced => self.writer.write_u8(size as u8)?,
+            CoordinateRange::Default => self.writer.write_u16::<LittleEndian>(size as u16)?,
+            CoordinateRange::Enhanced => self.writer.write_u32::<LittleEndian>(size)?,
+        }
+
+        Ok(())
+    }
+
+    fn var_uint(&mut self, value: usize) -> Result<()> {
+        let mut value: u32 = value
+            .try_into()
+            .wrap_err("value does not fit in a var uint")?;
+
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                self.writer.write_u8(byte)?;
+                break;
+            }
+
+            self.writer.write_u8(byte | 0x80)?;
+        }
+
+        Ok(())
+    }
+
+    /// Counts of items are stored minus one, so every command has at least
+    /// one item
+    fn count(&mut self, count: usize) -> Result<()> {
+        ensure!(count > 0, "commands must have at least one item");
+
+        self.var_uint(count - 1)
+    }
+
+    fn unit(&mut self, value: f64) -> Result<()> {
+        let raw = (value * (1u64 << self.scale) as f64).round();
+
+        if !(raw.is_finite() && CoordinateRange::Enhanced.fits_unit(raw as i64)) {
+            bail!(CoordinateOverflow {
+                value,
+                scale: self.scale,
+            });
+        }
+
+        ensure!(
+            self.coordinate_range.fits_unit(raw as i64),
+            "unit value {} does not fit in the coordinate range {:?}",
+            value,
+            self.coordinate_range
+        );
+
+        let raw = raw as i32;
+        match self.coordinate_range {
+            CoordinateRange::Reduced => self.writer.write_i8(raw as i8)?,
+            CoordinateRange::Default => self.writer.write_i16::<LittleEndian>(raw as i16)?,
+            CoordinateRange::Enhanced => self.writer.write_i32::<LittleEndian>(raw)?,
+        }
+
+        Ok(())
+    }
+
+    fn point(&mut self, point: &Point) -> Result<()> {
+        self.unit(point.x)?;
+        self.unit(point.y)
+    }
+
+    fn rectangle(&mut self, rect: &Rect) -> Result<()> {
+        ensure!(
+            has_area(rect),
+            "rectangle at ({}, {}) with size {} x {} has no area",
+            rect.x0,
+            rect.y0,
+            rect.width(),
+            rect.height()
+        );
+
+        self.unit(rect.x0)?;
+        self.unit(rect.y0)?;
+        self.unit(rect.width())?;
+        self.unit(rect.height())
+    }
+
+    fn color_index(&mut self, index: usize) -> Result<()> {
+        ensure!(
+            index < self.color_count,
+            "file has {} colors but tried to use index {}",
+            self.color_count,
+            index
+        );
+
+        self.var_uint(index)
+    }
+
+    fn style(&mut self, style: &Style) -> Result<()> {
+        match style {
+            Style::FlatColor { color_index } => self.color_index(*color_index),
+            Style::LinearGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            }
+            | Style::RadialGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => {
+                self.point(point_0)?;
+                self.point(point_1)?;
+                self.color_index(*color_index_0)?;
+                self.color_index(*color_index_1)
+            }
+        }
+    }
+
+    fn command_tag(&mut self, index: u8, style: &Style) -> Result<()> {
+        self.writer
+            .write_u8(index | ((StyleVariant::of(style) as u8) << 6))?;
+
+        Ok(())
+    }
+
+    fn command(&mut self, command: &Command) -> Result<()> {
+        match command {
+            Command::FillPolygon {
+                fill_style,
+                polygon,
+                outline,
+            } => self.fill_command(1, 8, fill_style, outline, polygon, Self::point),
+            Command::FillRectangles {
+                fill_style,
+                rectangles,
+                outline,
+            } => self.fill_command(2, 9, fill_style, outline, rectangles, Self::rectangle),
+            Command::FillPath {
+                fill_style,
+                path,
+                outline,
+            } => {
+                match outline {
+                    None => {
+                        self.command_tag(3, fill_style)?;
+                        self.count(path.len())?;
+                        self.style(fill_style)?;
+                    }
+                    Some(outline) => self.outline_header(10, fill_style, outline, path.len())?,
+                }
+
+                self.path(path)
+            }
+            Command::DrawLines {
+                line_style,
+                line_width,
+                lines,
+            } => {
+                self.line_header(4, line_style, *line_width, lines.len())?;
+
+                for line in lines {
+                    self.point(&line.p0)?;
+                    self.point(&line.p1)?;
+                }
+
+                Ok(())
+            }
+            Command::DrawLineLoop {
+                line_style,
+                line_width,
+                close_path,
+                points,
+            } => {
+                let index = if *close_path { 5 } else { 6 };
+                self.line_header(index, line_style, *line_width, points.len())?;
+
+                for point in points {
+                    self.point(point)?;
+                }
+
+                Ok(())
+            }
+            Command::DrawLinePath {
+                line_style,
+                line_width,
+                path,
+            } => {
+                self.line_header(7, line_style, *line_width, path.len())?;
+                self.path(path)
+            }
+        }
+    }
+
+    /// Fill polygon and fill rectangles, which are written with command
+    /// `index` or `outline_index` depending on whether they have an outline
+    fn fill_command<T>(
+        &mut self,
+        index: u8,
+        outline_index: u8,
+        fill_style: &Style,
+        outline: &Option<OutlineStyle>,
+        items: &[T],
+        item: impl Fn(&mut Self, &T) -> Result<()>,
+    ) -> Result<()> {
+        match outline {
+            None => {
+                self.command_tag(index, fill_style)?;
+                self.count(items.len())?;
+                self.style(fill_style)?;
+            }
+            Some(outline) => {
+                self.outline_header(outline_index, fill_style, outline, items.len())?
+            }
+        }
+
+        for i in items {
+            item(self, i)?;
+        }
+
+        Ok(())
+    }
+
+    fn outline_header(
+        &mut self,
+        index: u8,
+        fill_style: &Style,
+        outline: &OutlineStyle,
+        count: usize,
+    ) -> Result<()> {
+        ensure!(count > 0, "commands must have at least one item");
+        ensure!(
+            count <= MAX_OUTLINE_ITEMS,
+            "outlined fill commands can have at most {} items, found {}",
+            MAX_OUTLINE_ITEMS,
+            count
+        );
+
+        self.command_tag(index, fill_style)?;
+        self.writer
+            .write_u8((count - 1) as u8 | ((StyleVariant::of(&outline.line_style) as u8) << 6))?;
+
+        self.style(fill_style)?;
+        self.style(&outline.line_style)?;
+        self.unit(outline.line_width)
+    }
+
+    fn line_header(
+        &mut self,
+        index: u8,
+        line_style: &Style,
+        line_width: f64,
+        count: usize,
+    ) -> Result<()> {
+        self.command_tag(index, line_style)?;
+        self.count(count)?;
+        self.style(line_style)?;
+        self.unit(line_width)
+    }
+
+    /// Segment lengths followed by the segments, the number of segments is
+    /// part of the command header
+    fn path(&mut self, path: &[Segment]) -> Result<()> {
+        for segment in path {
+            self.count(segment.commands.len())
+                .wrap_err("path segments must have at least one command")?;
+        }
+
+        for segment in path {
+            self.point(&segment.start)?;
+
+            for command in &segment.commands {
+                self.segment_command(&command.kind, command.line_width)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn segment_command(
+        &mut self,
+        kind: &SegmentCommandKind,
+        line_width: Option<f64>,
+    ) -> Result<()> {
+        let instruction = match kind {
+            SegmentCommandKind::Line { .. } => 0,
+            SegmentCommandKind::HorizontalLine { .. } => 1,
+            SegmentCommandKind::VerticalLine { .. } => 2,
+            SegmentCommandKind::CubicBezier { .. } => 3,
+            SegmentCommandKind::ArcCircle { .. } => 4,
+            SegmentCommandKind::ArcEllipse { .. } => 5,
+            SegmentCommandKind::ClosePath => 6,
+            SegmentCommandKind::QuadraticBezier { .. } => 7,
+        };
+
+        match line_width {
+            Some(line_width) => {
+                self.writer.write_u8(instruction | 0b0001_0000)?;
+                self.unit(line_width)?;
+            }
+            None => self.writer.write_u8(instruction)?,
+        }
+
+        match kind {
+            SegmentCommandKind::Line { end } => self.point(end)?,
+            SegmentCommandKind::HorizontalLine { x } => self.unit(*x)?,
+            SegmentCommandKind::VerticalLine { y } => self.unit(*y)?,
+            SegmentCommandKind::CubicBezier {
+                control_0,
+                control_1,
+                point_1,
+            } => {
+                self.point(control_0)?;
+                self.point(control_1)?;
+                self.point(point_1)?;
+            }
+            SegmentCommandKind::ArcCircle {
+                large,
+                sweep,
+                radius,
+                target,
+            } => {
+                self.arc_flags(*large, *sweep)?;
+                self.unit(*radius)?;
+                self.point(target)?;
+            }
+            SegmentCommandKind::ArcEllipse {
+                large,
+                sweep,
+                radius_x,
+                radius_y,
+                rotation,
+                target,
+            } => {
+                self.arc_flags(*large, *sweep)?;
+                self.unit(*radius_x)?;
+                self.unit(*radius_y)?;
+                self.unit(*rotation)?;
+                self.point(target)?;
+            }
+            SegmentCommandKind::ClosePath => {}
+            SegmentCommandKind::QuadraticBezier { control, point_1 } => {
+                self.point(control)?;
+                self.point(point_1)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn arc_flags(&mut self, large: bool, sweep: bool) -> Result<()> {
+        // The decoder flips the sweep flag of the binary format
+        let mut flags = large as u8;
+        if !sweep {
+            flags |= 0b0000_0010;
+        }
+
+        self.writer.write_u8(flags)?;
+
+        Ok(())
+    }
+}
+
+impl Image {
+    /// Encode this image to the binary TinyVG format, writing it to the given
+    /// `std::io::Write`. See `Encoder` for details.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::format::{Color, Command, Image, Rect, Style};
+    /// let mut image = Image::empty(16, 16);
+    /// image.color_table.push(Color::BLACK);
+    /// image.commands.push(Command::FillRectangles {
+    ///     fill_style: Style::FlatColor { color_index: 0 },
+    ///     rectangles: vec![Rect::new(0.0, 0.0, 16.0, 16.0)],
+    ///     outline: None,
+    /// });
+    ///
+    /// let mut bytes = Vec::new();
+    /// image.encode(&mut bytes).unwrap();
+    ///
+    /// assert_eq!(
+    ///     bytes,
+    ///     [
+    ///         0x72, 0x56, 1, 0x40, 16, 16, 1, 0, 0, 0, 255,
+    ///         0x02, 0, 0, 0, 0, 16, 16, 0,
+    ///     ]
+    /// );
+    /// assert_eq!(Decoder::new(bytes.as_slice()).decode().unwrap(), image);
+    ///
+    /// // Rectangles without area have no defined appearance
+    /// image.commands.push(Command::FillRectangles {
+    ///     fill_style: Style::FlatColor { color_index: 0 },
+    ///     rectangles: vec![Rect::new(0.0, 0.0, 16.0, 0.0)],
+    ///     outline: None,
+    /// });
+    /// assert!(image.encode(&mut Vec::new()).is_err());
+    /// ```
+    pub fn encode(&self, writer: &mut impl Write) -> Result<()> {
+        Encoder::new(writer).encode(self)
+    }
+
+    /// Hash of the binary encoding of this image, e.g. to key caches of
+    /// renders. Images that encode to the same bytes have the same hash. The
+    /// hash is stable across platforms and releases, so it can be stored.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// let bytes = std::fs::read("data/shield.tvg").unwrap();
+    /// let mut image = Decoder::new(bytes.as_slice()).decode().unwrap();
+    /// let hash = image.content_hash().unwrap();
+    ///
+    /// assert_eq!(Decoder::new(bytes.as_slice()).decode().unwrap().content_hash().unwrap(), hash);
+    ///
+    /// image.color_table.swap(0, 1);
+    /// assert_ne!(image.content_hash().unwrap(), hash);
+    /// ```
+    pub fn content_hash(&self) -> Result<u64> {
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes)?;
+
+        Ok(fnv1a(&bytes))
+    }
+
+    /// Overwrite the bytes of the command at `index` in `bytes`, an encoding
+    /// of this image before the command was edited, without encoding the
+    /// rest of the image. `source_map` is the byte range of every command in
+    /// `bytes`, see `Decoder::source_map`. Saves a full encode of large files
+    /// in editors that save after every small edit.
+    ///
+    /// TinyVG has no padding, so the edited command must encode to exactly as
+    /// many bytes as before. Returns `Ok(false)` without changing `bytes` if
+    /// it doesn't, the image then has to be encoded in full. The header and
+    /// color table must be unchanged, and the command must fit in the
+    /// coordinate range of the header.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::format::{Command, Style};
+    /// let mut bytes = std::fs::read("data/shield.tvg").unwrap();
+    ///
+    /// let mut decoder = Decoder::new(bytes.as_slice());
+    /// let mut image = decoder.decode_header().unwrap();
+    /// decoder.decode_commands(&mut image).unwrap();
+    /// let source_map = decoder.source_map().to_vec();
+    ///
+    /// // Recoloring a command keeps its size
+    /// if let Command::FillPath { fill_style, .. } = &mut image.commands[0] {
+    ///     *fill_style = Style::FlatColor { color_index: 1 };
+    /// }
+    ///
+    /// assert!(image.patch_command(0, &mut bytes, &source_map).unwrap());
+    ///
+    /// let mut reencoded = Vec::new();
+    /// image.encode(&mut reencoded).unwrap();
+    /// assert_eq!(bytes, reencoded);
+    /// ```
+    pub fn patch_command(
+        &self,
+        index: usize,
+        bytes: &mut [u8],
+        source_map: &[Range<usize>],
+    ) -> Result<bool> {
+        let command = self.commands.get(index).ok_or_else(|| {
+            format_err!(
+                "image has {} commands but tried to patch command {}",
+                self.commands.len(),
+                index
+            )
+        })?;
+        let range = source_map
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format_err!("source map has no range for command {}", index))?;
+        ensure!(
+            range.end <= bytes.len(),
+            "command {} ends at byte {}, past the end of the {} bytes",
+            index,
+            range.end,
+            bytes.len()
+        );
+
+        let encoded = Decoder::new(&bytes[..])
+            .decode_header()
+            .wrap_err("error decoding header")?;
+        ensure!(
+            encoded.header == self.header && encoded.color_table == self.color_table,
+            "header or color table changed, the image has to be encoded in full"
+        );
+
+        let mut encoder = Encoder {
+            writer: Vec::new(),
+            coordinate_range: self.header.coordinate_range,
+            scale: self.header.scale,
+            color_count: self.color_table.len(),
+        };
+        encoder
+            .command(command)
+            .wrap_err_with(|| format!("error encoding command {}", index))?;
+
+        if encoder.writer.len() != range.len() {
+            return Ok(false);
+        }
+
+        bytes[range].copy_from_slice(&encoder.writer);
+
+        Ok(true)
+    }
+}
+
+/// 64 bit FNV-1a, which is stable across platforms and releases unlike the
+/// hashers of the standard library
+pub(crate) fn fnv1a(input: &[u8]) -> u64 {
+    input.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,279 @@
+//! The error type of this crate. Every fallible function returns an `Error`,
+//! whose variants name the failures that callers may want to handle, rather
+//! than only report. Failures further down are wrapped in `Error::Context`,
+//! which describes what was being done; `Error::root_cause` skips those
+//! layers.
+//!
+//! ```
+//! # use tinyvg::{Decoder, Error};
+//! let error = Decoder::new(&b"GIF89a"[..]).decode().unwrap_err();
+//!
+//! assert!(matches!(error.root_cause(), Error::BadMagic([0x47, 0x49])));
+//! ```
+//!
+//! Failures to read or write are reported as `Error::Io`.
+
+use crate::geometry::CoordinateOverflow;
+use crate::validate::ValidationError;
+
+/// Result of a fallible function of this crate
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A failure of this crate, see the module documentation
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// A failure without a variant of its own, described by the message
+    #[error("{0}")]
+    Message(String),
+
+    /// A failure of a library this crate uses, e.g. cairo
+    #[error(transparent)]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+
+    /// The source failed while doing what the message describes
+    #[error("{message}")]
+    Context {
+        /// What was being done
+        message: String,
+
+        /// Why it failed
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// The data does not start with the TinyVG magic number `0x72 0x56`
+    #[error("tinyvg file must begin with magic number 0x72 0x56, found {:x} {:x}", .0[0], .0[1])]
+    BadMagic([u8; 2]),
+
+    /// The file is of a TinyVG version other than 1
+    #[error("Tried to parse TinyVG file with version {0} - only version 1 files are supported")]
+    UnsupportedVersion(u8),
+
+    /// The header uses a color encoding this crate can't decode, e.g. a
+    /// custom encoding
+    #[error("{}", match .0 {
+        3 => "custom color encodings are not supported".to_string(),
+        encoding => format!("unrecognized color encoding {}", encoding),
+    })]
+    UnsupportedColorEncoding(u8),
+
+    /// The header uses a coordinate range that isn't defined by TinyVG
+    #[error("unrecognized coordinate type {0}")]
+    UnsupportedCoordinateRange(u8),
+
+    /// A command could not be decoded
+    #[error("error parsing command {index} at byte {offset}")]
+    InvalidCommand {
+        /// Index of the command, counting from the first command
+        index: usize,
+
+        /// Offset of the command's first byte from the start of the file
+        offset: usize,
+
+        /// Why the command could not be decoded
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// A style references a color past the end of the color table
+    #[error("file has {colors} colors but tried to get index {index}")]
+    ColorIndexOutOfRange {
+        /// The referenced index
+        index: usize,
+
+        /// Number of colors in the color table
+        colors: usize,
+    },
+
+    /// A size or count in the file is above a limit of the decoder, see
+    /// `DecodeOptions`
+    #[error(
+        "{} is {value}, above the limit of {max} (DecodeOptions::{limit})",
+        limit.trim_start_matches("max_").replace('_', " ")
+    )]
+    LimitExceeded {
+        /// Name of the `DecodeOptions` field, e.g. `"max_colors"`
+        limit: &'static str,
+
+        /// The size or count in the file
+        value: u64,
+
+        /// The limit
+        max: u64,
+    },
+
+    /// A render is larger than `RenderOptions::max_output_size`
+    #[error(
+        "render of {width}x{height} pixels is larger than the limit of {max_width}x{max_height} (RenderOptions::max_output_size)"
+    )]
+    OutputTooLarge {
+        /// Width of the render in pixels
+        width: u64,
+
+        /// Height of the render in pixels
+        height: u64,
+
+        /// Maximum width
+        max_width: u32,
+
+        /// Maximum height
+        max_height: u32,
+    },
+
+    /// The input does not conform to the TinyVG specification, see
+    /// `Decoder::with_strict`
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+
+    /// A unit value does not fit in any coordinate range
+    #[error(transparent)]
+    CoordinateOverflow(#[from] CoordinateOverflow),
+}
+
+impl Error {
+    /// Wrap a failure of another library, or a message
+    ///
+    /// ```
+    /// # use tinyvg::Error;
+    /// let error = Error::other(std::str::from_utf8(&[0xFF]).unwrap_err());
+    /// assert!(matches!(error, Error::Other(_)));
+    /// ```
+    pub fn other(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Error::Other(error.into())
+    }
+
+    /// The innermost failure, skipping `Error::Context` and
+    /// `Error::InvalidCommand`
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } | Error::InvalidCommand { source, .. } => {
+                source.root_cause()
+            }
+            error => error,
+        }
+    }
+
+    /// Wrap this error with a message describing what was being done
+    pub(crate) fn wrap_err(self, message: impl std::fmt::Display) -> Self {
+        Error::Context {
+            message: message.to_string(),
+            source: Box::new(self),
+        }
+    }
+}
+
+/// Implement `From` for failures of other libraries, which become
+/// `Error::Other`
+macro_rules! from_other {
+    ($($(#[$attr:meta])* $error:ty,)*) => {
+        $(
+            $(#[$attr])*
+            impl From<$error> for Error {
+                fn from(error: $error) -> Self {
+                    Error::Other(Box::new(error))
+                }
+            }
+        )*
+    };
+}
+
+from_other! {
+    std::fmt::Error,
+    std::num::TryFromIntError,
+    std::str::Utf8Error,
+    std::string::FromUtf8Error,
+    std::num::ParseIntError,
+    std::num::ParseFloatError,
+    packed_struct::PackingError,
+    #[cfg(feature = "render-png")]
+    cairo::Error,
+    #[cfg(feature = "render-png")]
+    cairo::IoError,
+    #[cfg(feature = "render-png")]
+    cairo::BorrowError,
+    #[cfg(feature = "animation")]
+    png::EncodingError,
+    #[cfg(feature = "animation")]
+    gif::EncodingError,
+    #[cfg(feature = "raster-formats")]
+    image::ImageError,
+    #[cfg(feature = "generate")]
+    qrcode::types::QrError,
+    #[cfg(feature = "import-svg")]
+    usvg::Error,
+}
+
+/// Add a message describing what was being done to the error of a result,
+/// see `Error::Context`
+pub(crate) trait Context<T> {
+    /// Wrap the error with `message`
+    fn wrap_err(self, message: impl std::fmt::Display) -> Result<T>;
+
+    /// Wrap the error with the message returned by `message`, which is only
+    /// called on failure
+    fn wrap_err_with<D: std::fmt::Display>(self, message: impl FnOnce() -> D) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn wrap_err(self, message: impl std::fmt::Display) -> Result<T> {
+        self.map_err(|error| error.into().wrap_err(message))
+    }
+
+    fn wrap_err_with<D: std::fmt::Display>(self, message: impl FnOnce() -> D) -> Result<T> {
+        self.map_err(|error| error.into().wrap_err(message()))
+    }
+}
+
+/// Create an `Error` from a format string, which becomes `Error::Message`,
+/// or from anything that converts into an `Error`
+macro_rules! format_err {
+    ($message:literal $(,)?) => {
+        $crate::error::Error::Message(format!($message))
+    };
+    ($error:expr $(,)?) => {
+        $crate::error::Error::from($error)
+    };
+    ($format:expr, $($arg:tt)*) => {
+        $crate::error::Error::Message(format!($format, $($arg)*))
+    };
+}
+
+/// Return early with an error, see `format_err!`
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err($crate::error::format_err!($($arg)*))
+    };
+}
+
+/// Return early with an error if a condition is false, see `format_err!`
+macro_rules! ensure {
+    ($condition:expr, $($arg:tt)*) => {
+        // Written without `!`, which clippy flags on float comparisons
+        if $condition {
+        } else {
+            $crate::error::bail!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {bail, ensure, format_err};
+
+/// Message of an error with all of its causes, outermost first, for warnings,
+/// reports and bindings to languages that only take a single string
+pub(crate) fn full_message(error: &Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+
+    message
+}
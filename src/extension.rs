@@ -0,0 +1,207 @@
+//! Application-specific data stored in the trailer as keyed records, so that
+//! tools can attach things like layer names or link annotations to an image
+//! without changing the format. Decoders that don't know about extensions see
+//! an ordinary trailer, and since the trailer is kept byte-for-byte by
+//! decoding and encoding, records survive tools that don't understand them.
+//!
+//! Records are identified by a key, which should be namespaced by the
+//! application (e.g. `"com.example.layers"`) so that records of different
+//! applications don't clash. Typed records implement `Extension`.
+//!
+//! ```
+//! # use tinyvg::{Decoder, Error, Image};
+//! # use tinyvg::extension::Extension;
+//! /// Names of the layers of an editor, one per line
+//! #[derive(Debug, PartialEq)]
+//! struct LayerNames(Vec<String>);
+//!
+//! impl Extension for LayerNames {
+//!     const KEY: &'static str = "com.example.layers";
+//!
+//!     fn to_bytes(&self) -> Vec<u8> {
+//!         self.0.join("\n").into_bytes()
+//!     }
+//!
+//!     fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+//!         let text = std::str::from_utf8(data)?;
+//!         Ok(LayerNames(text.lines().map(str::to_string).collect()))
+//!     }
+//! }
+//!
+//! let mut image = Image::empty(16, 16);
+//! let layers = LayerNames(vec!["background".to_string(), "icon".to_string()]);
+//! image.set_extension(&layers).unwrap();
+//! image
+//!     .set_extension_record("org.example.link", b"https://example.com")
+//!     .unwrap();
+//!
+//! let mut bytes = Vec::new();
+//! image.encode(&mut bytes).unwrap();
+//! let decoded = Decoder::new(&bytes[..]).decode().unwrap();
+//!
+//! assert_eq!(decoded.extension::<LayerNames>().unwrap(), Some(layers));
+//!
+//! let keys: Vec<_> = decoded
+//!     .extensions()
+//!     .unwrap()
+//!     .iter()
+//!     .map(|record| record.key)
+//!     .collect();
+//! assert_eq!(keys, ["com.example.layers", "org.example.link"]);
+//! ```
+
+use crate::error::{ensure, format_err, Context, Result};
+use crate::format::Image;
+
+/// Start of a trailer holding extension records
+const EXTENSIONS_MAGIC: &[u8; 4] = b"tvgx";
+
+/// A typed extension record, converted to and from the bytes stored in the
+/// trailer
+pub trait Extension: Sized {
+    /// Key of the record, namespaced by the application
+    const KEY: &'static str;
+
+    /// Data of the record
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Parse the data of the record
+    fn from_bytes(data: &[u8]) -> Result<Self>;
+}
+
+/// A record in the trailer, borrowed from the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtensionRecord<'a> {
+    /// Key of the record
+    pub key: &'a str,
+
+    /// Data of the record, interpreted by the application that wrote it
+    pub data: &'a [u8],
+}
+
+impl Image {
+    /// Every extension record in the trailer, in the order they are stored.
+    /// Empty if the trailer is empty or holds something other than
+    /// extensions.
+    /// Fails if the trailer holds extensions that are cut off.
+    pub fn extensions(&self) -> Result<Vec<ExtensionRecord<'_>>> {
+        if !self.trailer.starts_with(EXTENSIONS_MAGIC) {
+            return Ok(Vec::new());
+        }
+
+        let mut records = Vec::new();
+        let mut rest = &self.trailer[EXTENSIONS_MAGIC.len()..];
+
+        while !rest.is_empty() {
+            let key = take_field(&mut rest).wrap_err("extension key is cut off")?;
+            let key = std::str::from_utf8(key)
+                .map_err(|e| format_err!("extension key is not UTF-8: {}", e))?;
+            let data = take_field(&mut rest)
+                .wrap_err_with(|| format!("data of extension {:?} is cut off", key))?;
+
+            records.push(ExtensionRecord { key, data });
+        }
+
+        Ok(records)
+    }
+
+    /// Data of the extension record with the given key, if there is one
+    pub fn extension_record(&self, key: &str) -> Result<Option<&[u8]>> {
+        Ok(self
+            .extensions()?
+            .into_iter()
+            .find(|record| record.key == key)
+            .map(|record| record.data))
+    }
+
+    /// The typed extension record of type `E`, if there is one
+    pub fn extension<E: Extension>(&self) -> Result<Option<E>> {
+        self.extension_record(E::KEY)?
+            .map(|data| {
+                E::from_bytes(data)
+                    .wrap_err_with(|| format!("failed to parse extension {:?}", E::KEY))
+            })
+            .transpose()
+    }
+
+    /// Store an extension record in the trailer, replacing the record with
+    /// the same key if there is one. Fails without changing the trailer if it
+    /// holds something other than extensions, so that it isn't lost.
+    pub fn set_extension_record(&mut self, key: &str, data: &[u8]) -> Result<()> {
+        ensure!(
+            self.trailer.is_empty() || self.trailer.starts_with(EXTENSIONS_MAGIC),
+            "trailer holds data other than extensions"
+        );
+
+        let mut records = self.extensions()?;
+        match records.iter_mut().find(|record| record.key == key) {
+            Some(record) => record.data = data,
+            None => records.push(ExtensionRecord { key, data }),
+        }
+
+        let trailer = write_records(&records);
+        self.trailer = trailer;
+
+        Ok(())
+    }
+
+    /// Store a typed extension record in the trailer, see
+    /// `Image::set_extension_record`
+    pub fn set_extension<E: Extension>(&mut self, extension: &E) -> Result<()> {
+        self.set_extension_record(E::KEY, &extension.to_bytes())
+    }
+
+    /// Remove the extension record with the given key from the trailer.
+    /// Returns whether there was one. The trailer is emptied when the last
+    /// record is removed.
+    pub fn remove_extension_record(&mut self, key: &str) -> Result<bool> {
+        let mut records = self.extensions()?;
+        let len = records.len();
+        records.retain(|record| record.key != key);
+
+        if records.len() == len {
+            return Ok(false);
+        }
+
+        let trailer = if records.is_empty() {
+            Vec::new()
+        } else {
+            write_records(&records)
+        };
+        self.trailer = trailer;
+
+        Ok(true)
+    }
+}
+
+/// Trailer holding the records, each as the length and bytes of its key
+/// followed by the length and bytes of its data
+fn write_records(records: &[ExtensionRecord]) -> Vec<u8> {
+    let mut trailer = EXTENSIONS_MAGIC.to_vec();
+
+    for record in records {
+        for field in [record.key.as_bytes(), record.data] {
+            trailer.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            trailer.extend_from_slice(field);
+        }
+    }
+
+    trailer
+}
+
+/// Split a length-prefixed field off the start of `rest`
+fn take_field<'a>(rest: &mut &'a [u8]) -> Result<&'a [u8]> {
+    ensure!(rest.len() >= 4, "missing length");
+
+    let len = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+    ensure!(
+        rest.len() - 4 >= len,
+        "{} bytes missing",
+        len - (rest.len() - 4)
+    );
+
+    let (field, remaining) = rest[4..].split_at(len);
+    *rest = remaining;
+
+    Ok(field)
+}
@@ -0,0 +1,214 @@
+//! C API for linking to this crate from C, C++ or any language with a C FFI,
+//! e.g. game engines. Enabled by the `ffi` feature. Build a shared or static
+//! library to link to with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib` (or
+//! `--crate-type staticlib`). The declarations are in
+//! `include/tinyvg.h`, generated from this module with
+//! `cbindgen --config cbindgen.toml --output include/tinyvg.h`.
+//!
+//! ```c
+//! TinyvgImage *image = tinyvg_decode(bytes, length);
+//! if (!image) {
+//!     fprintf(stderr, "%s\n", tinyvg_last_error());
+//!     return;
+//! }
+//!
+//! TinyvgPixmap pixmap;
+//! if (tinyvg_render_rgba(image, 2.0, &pixmap)) {
+//!     upload_texture(pixmap.data, pixmap.width, pixmap.height);
+//!     tinyvg_pixmap_free(&pixmap);
+//! }
+//!
+//! tinyvg_free(image);
+//! ```
+//!
+//! Functions never unwind into the caller: errors and panics are reported by
+//! returning null or false, and the message is kept for
+//! `tinyvg_last_error`.
+
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::error::{ensure, full_message, Result};
+use crate::{Decoder, Image};
+
+/// A decoded image, only handled through pointers
+pub struct TinyvgImage {
+    image: Image,
+}
+
+/// Straight (not premultiplied) RGBA pixels, row by row without padding
+#[repr(C)]
+pub struct TinyvgPixmap {
+    /// Width in pixels
+    pub width: u32,
+
+    /// Height in pixels
+    pub height: u32,
+
+    /// `width * height * 4` bytes, owned by this crate
+    pub data: *mut u8,
+
+    /// Number of bytes in `data`
+    pub len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, keeping the message of its error or panic for
+/// `tinyvg_last_error`
+fn guard<T>(f: impl FnOnce() -> Result<T>) -> Option<T> {
+    let message = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return Some(value),
+        Ok(Err(error)) => full_message(&error),
+        Err(panic) => match panic.downcast_ref::<&str>() {
+            Some(message) => format!("panic: {}", message),
+            None => match panic.downcast_ref::<String>() {
+                Some(message) => format!("panic: {}", message),
+                None => "panic".to_string(),
+            },
+        },
+    };
+
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+
+    None
+}
+
+/// Decode a TinyVG file, with the limits of `DecodeOptions::default`. Returns
+/// null on failure. Free the image with `tinyvg_free`.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tinyvg_decode(data: *const u8, len: usize) -> *mut TinyvgImage {
+    guard(|| {
+        ensure!(!data.is_null(), "data is null");
+        let bytes = std::slice::from_raw_parts(data, len);
+        let image = Decoder::new(bytes).decode()?;
+
+        Ok(Box::into_raw(Box::new(TinyvgImage { image })))
+    })
+    .unwrap_or(std::ptr::null_mut())
+}
+
+/// Width of an image from its header, in image units
+///
+/// # Safety
+///
+/// `image` must be a pointer returned by `tinyvg_decode`.
+#[no_mangle]
+pub unsafe extern "C" fn tinyvg_width(image: *const TinyvgImage) -> u32 {
+    (*image).image.header.width
+}
+
+/// Height of an image from its header, in image units
+///
+/// # Safety
+///
+/// `image` must be a pointer returned by `tinyvg_decode`.
+#[no_mangle]
+pub unsafe extern "C" fn tinyvg_height(image: *const TinyvgImage) -> u32 {
+    (*image).image.header.height
+}
+
+/// Render an image at `scale` times the size in its header into `pixmap`.
+/// Returns false on failure, in which case `pixmap` is left untouched. Free
+/// the pixels with `tinyvg_pixmap_free`. Fails if the crate was built
+/// without a renderer (`render-png` or `render-skia`).
+///
+/// # Safety
+///
+/// `image` must be a pointer returned by `tinyvg_decode`, and `pixmap` must
+/// point to writable memory for a `TinyvgPixmap`.
+#[no_mangle]
+pub unsafe extern "C" fn tinyvg_render_rgba(
+    image: *const TinyvgImage,
+    scale: f64,
+    pixmap: *mut TinyvgPixmap,
+) -> bool {
+    guard(|| {
+        ensure!(!image.is_null() && !pixmap.is_null(), "argument is null");
+        let rendered = render(&(*image).image, scale)?;
+
+        let data = rendered.as_rgba8().into_owned().into_boxed_slice();
+        *pixmap = TinyvgPixmap {
+            width: rendered.width,
+            height: rendered.height,
+            len: data.len(),
+            data: Box::into_raw(data) as *mut u8,
+        };
+
+        Ok(())
+    })
+    .is_some()
+}
+
+#[cfg(any(feature = "render-png", feature = "render-skia"))]
+fn render(image: &Image, scale: f64) -> Result<crate::RasterImage> {
+    image.render_pixmap_scaled(
+        crate::RenderSize::Scale(scale),
+        &crate::RenderOptions::default(),
+    )
+}
+
+#[cfg(not(any(feature = "render-png", feature = "render-skia")))]
+fn render(_image: &Image, _scale: f64) -> Result<crate::RasterImage> {
+    crate::error::bail!("rendering needs the render-png or render-skia feature")
+}
+
+/// Free the pixels of a pixmap filled by `tinyvg_render_rgba`, and reset it
+/// to an empty pixmap
+///
+/// # Safety
+///
+/// `pixmap` must be null or point to a pixmap filled by
+/// `tinyvg_render_rgba` that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn tinyvg_pixmap_free(pixmap: *mut TinyvgPixmap) {
+    if let Some(pixmap) = pixmap.as_mut() {
+        if !pixmap.data.is_null() {
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                pixmap.data,
+                pixmap.len,
+            )));
+        }
+
+        *pixmap = TinyvgPixmap {
+            width: 0,
+            height: 0,
+            data: std::ptr::null_mut(),
+            len: 0,
+        };
+    }
+}
+
+/// Free an image returned by `tinyvg_decode`
+///
+/// # Safety
+///
+/// `image` must be null or a pointer returned by `tinyvg_decode` that hasn't
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn tinyvg_free(image: *mut TinyvgImage) {
+    if !image.is_null() {
+        drop(Box::from_raw(image));
+    }
+}
+
+/// Message of the last failure on this thread, or null if nothing failed
+/// yet. The string is valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn tinyvg_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
@@ -2,11 +2,20 @@
 
 //! In-memory representation of a TinyVG file
 
+use std::collections::BTreeMap;
+
+pub use crate::color::Color;
 pub use kurbo::{Line, Point, Rect};
-pub use piet::Color;
 
-/// A single TinyVG file
-#[derive(Debug, PartialEq, Clone)]
+use crate::error::{ensure, format_err, Result};
+
+/// Key of the extension record holding command labels, see
+/// `Image::store_labels_in_trailer`
+const LABELS_KEY: &str = "tinyvg.labels";
+
+/// A single TinyVG file. Images compare equal if they encode alike, so
+/// `Image::labels` and `Image::bounds` are not compared.
+#[derive(Debug, Clone)]
 pub struct Image {
     /// Image header
     pub header: Header,
@@ -20,6 +29,322 @@ pub struct Image {
     /// Remaining data after the TinyVG image ended. Can be used for arbitrary
     /// metadata, it is not defined by the spec
     pub trailer: Vec<u8>,
+
+    /// Labels of commands by command index, e.g. the source element each
+    /// command was generated from. Labels are not part of TinyVG and are not
+    /// encoded, unless stored in the trailer with
+    /// `Image::store_labels_in_trailer`.
+    pub labels: BTreeMap<usize, String>,
+
+    /// Bounding box of every command, indexed like `Image::commands`, if they
+    /// were computed while decoding with `DecodeOptions::compute_bounds`, see
+    /// `Command::bounds`. Bounds are not encoded. Methods of `Image` that
+    /// change the geometry clear them, but changes made directly to
+    /// `Image::commands` leave them stale.
+    pub bounds: Option<Vec<Rect>>,
+}
+
+impl PartialEq for Image {
+    fn eq(&self, other: &Image) -> bool {
+        self.header == other.header
+            && self.color_table == other.color_table
+            && self.commands == other.commands
+            && self.trailer == other.trailer
+    }
+}
+
+impl Image {
+    /// An image of the given size without any colors or commands, e.g. for use
+    /// as a placeholder. It renders to a transparent surface and encodes to
+    /// just a header and the end-of-document marker.
+    ///
+    /// ```
+    /// # use tinyvg::{Decoder, Image};
+    /// # use tinyvg::text_format::{parse_text, write_text};
+    /// let image = Image::empty(64, 32);
+    ///
+    /// // Magic number, version, flags, size, color count, end of document
+    /// let bytes = [0x72, 0x56, 1, 0x40, 64, 32, 0, 0];
+    /// assert_eq!(Decoder::new(&bytes[..]).decode().unwrap(), image);
+    ///
+    /// let text = write_text(&image);
+    /// assert_eq!(parse_text(&text).unwrap(), image);
+    ///
+    /// let mut png = Vec::new();
+    /// image.render_png(&mut png).unwrap();
+    /// ```
+    pub fn empty(width: u32, height: u32) -> Image {
+        let coordinate_range = [
+            CoordinateRange::Reduced,
+            CoordinateRange::Default,
+            CoordinateRange::Enhanced,
+        ]
+        .into_iter()
+        .find(|range| range.fits_size(width) && range.fits_size(height))
+        .unwrap_or(CoordinateRange::Enhanced);
+
+        Image {
+            header: Header {
+                version: 1,
+                scale: 0,
+                color_encoding: ColorEncoding::Rgba8888,
+                coordinate_range,
+                width,
+                height,
+            },
+            color_table: Vec::new(),
+            commands: Vec::new(),
+            trailer: Vec::new(),
+            labels: BTreeMap::new(),
+            bounds: None,
+        }
+    }
+
+    /// Every style referenced by the commands of this image, in command order.
+    /// Fill commands with an outline yield the fill style followed by the
+    /// outline's line style.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// let image = Decoder::new(File::open("data/everything.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let gradients = image
+    ///     .styles()
+    ///     .filter(|style| style.color_indices().count() == 2)
+    ///     .count();
+    ///
+    /// assert!(gradients > 0);
+    /// ```
+    pub fn styles(&self) -> impl Iterator<Item = &Style> {
+        self.commands.iter().flat_map(|c| c.styles())
+    }
+
+    /// Call `f` with every style of this image, in the same order as
+    /// `Image::styles`
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::format::Style;
+    /// let mut image = Decoder::new(File::open("data/everything.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// // Replace gradients with their first color
+    /// image.visit_styles_mut(|style| {
+    ///     if let Some(color_index) = style.color_indices().next() {
+    ///         *style = Style::FlatColor { color_index };
+    ///     }
+    /// });
+    ///
+    /// assert!(image.styles().all(|s| matches!(s, Style::FlatColor { .. })));
+    /// ```
+    pub fn visit_styles_mut(&mut self, mut f: impl FnMut(&mut Style)) {
+        for style in self.commands.iter_mut().flat_map(|c| c.styles_mut()) {
+            f(style);
+        }
+    }
+
+    /// Number of references to each entry of the color table, indexed like
+    /// `color_table`. A gradient references both of its colors. References
+    /// to indices outside of the color table are not counted.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(image.palette_usage(), vec![1, 0]);
+    /// ```
+    pub fn palette_usage(&self) -> Vec<usize> {
+        let mut usage = vec![0; self.color_table.len()];
+
+        for index in self.styles().flat_map(|s| s.color_indices()) {
+            if let Some(count) = usage.get_mut(index) {
+                *count += 1;
+            }
+        }
+
+        usage
+    }
+
+    /// Number of colors in this image, as written to the header when encoding.
+    /// This is always computed from `color_table`, so it can't disagree with
+    /// the colors after the table has been edited.
+    pub fn color_count(&self) -> u32 {
+        self.color_table.len() as u32
+    }
+
+    /// Compare two images, treating them as equal if their color tables are
+    /// permutations of each other and every style reference is remapped
+    /// consistently. Useful for deduplicating images that were produced from
+    /// the same source art by different exporters.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::format::{Command, Style};
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mut swapped = image.clone();
+    /// swapped.color_table.reverse();
+    /// for command in &mut swapped.commands {
+    ///     if let Command::FillPath {
+    ///         fill_style: Style::FlatColor { color_index },
+    ///         ..
+    ///     } = command
+    ///     {
+    ///         *color_index = 1 - *color_index;
+    ///     }
+    /// }
+    ///
+    /// assert_ne!(image, swapped);
+    /// assert!(image.equivalent(&swapped));
+    /// ```
+    pub fn equivalent(&self, other: &Image) -> bool {
+        if self.header != other.header
+            || self.trailer != other.trailer
+            || self.color_table.len() != other.color_table.len()
+            || self.commands.len() != other.commands.len()
+        {
+            return false;
+        }
+
+        let len = self.color_table.len();
+        let mut mapping = vec![None; len];
+        let mut reverse = vec![None; len];
+
+        for (a, b) in self.styles().zip(other.styles()) {
+            for (i, j) in a.color_indices().zip(b.color_indices()) {
+                if i >= len || j >= len {
+                    return false;
+                }
+
+                match (mapping[i], reverse[j]) {
+                    (None, None) => {
+                        if self.color_table[i] != other.color_table[j] {
+                            return false;
+                        }
+
+                        mapping[i] = Some(j);
+                        reverse[j] = Some(i);
+                    }
+                    (Some(m), Some(r)) if m == j && r == i => {}
+                    _ => return false,
+                }
+            }
+        }
+
+        // Colors that are never referenced still have to be a permutation of
+        // each other, otherwise the palettes differ.
+        let unused = |table: &[Color], used: &[Option<usize>]| {
+            let mut colors = table
+                .iter()
+                .zip(used)
+                .filter(|(_, used)| used.is_none())
+                .map(|(color, _)| color.as_rgba_u32())
+                .collect::<Vec<_>>();
+            colors.sort_unstable();
+            colors
+        };
+
+        if unused(&self.color_table, &mapping) != unused(&other.color_table, &reverse) {
+            return false;
+        }
+
+        let mut commands = self.commands.clone();
+        for style in commands.iter_mut().flat_map(|c| c.styles_mut()) {
+            for index in style.color_indices_mut() {
+                *index = mapping[*index].unwrap_or(usize::MAX);
+            }
+        }
+
+        commands == other.commands
+    }
+
+    /// Store the labels of this image in the trailer as an extension record,
+    /// so that they survive encoding, replacing the labels stored before.
+    /// Other extension records, e.g. hotspots, are kept. Fails without
+    /// changing the trailer if it holds something other than extensions. Read
+    /// them back with `Image::load_labels_from_trailer`.
+    ///
+    /// ```
+    /// # use tinyvg::{Decoder, Image};
+    /// # use tinyvg::format::{Color, Command, Rect, Style};
+    /// let mut image = Image::empty(16, 16);
+    /// image.color_table.push(Color::rgb8(0x33, 0x66, 0x99));
+    /// image.commands.push(Command::FillRectangles {
+    ///     fill_style: Style::FlatColor { color_index: 0 },
+    ///     rectangles: vec![Rect::new(0.0, 0.0, 16.0, 16.0)],
+    ///     outline: None,
+    /// });
+    /// image
+    ///     .set_extension_record("org.example.link", b"https://example.com")
+    ///     .unwrap();
+    /// image.labels.insert(0, "svg#background".to_string());
+    /// image.store_labels_in_trailer().unwrap();
+    ///
+    /// let mut bytes = Vec::new();
+    /// image.encode(&mut bytes).unwrap();
+    ///
+    /// let mut decoded = Decoder::new(&bytes[..]).decode().unwrap();
+    /// assert!(decoded.labels.is_empty());
+    ///
+    /// decoded.load_labels_from_trailer().unwrap();
+    /// assert_eq!(decoded.labels, image.labels);
+    /// assert_eq!(
+    ///     decoded.extension_record("org.example.link").unwrap(),
+    ///     Some(&b"https://example.com"[..])
+    /// );
+    /// ```
+    pub fn store_labels_in_trailer(&mut self) -> Result<()> {
+        let mut data = Vec::new();
+
+        for (index, label) in &self.labels {
+            data.extend_from_slice(&(*index as u32).to_le_bytes());
+            data.extend_from_slice(&(label.len() as u32).to_le_bytes());
+            data.extend_from_slice(label.as_bytes());
+        }
+
+        self.set_extension_record(LABELS_KEY, &data)
+    }
+
+    /// Replace the labels of this image with the ones stored in its trailer
+    /// by `Image::store_labels_in_trailer`. Fails if the trailer holds no
+    /// labels, without changing the labels.
+    pub fn load_labels_from_trailer(&mut self) -> Result<()> {
+        let mut rest = self
+            .extension_record(LABELS_KEY)?
+            .ok_or_else(|| format_err!("trailer does not hold command labels"))?;
+
+        let mut labels = BTreeMap::new();
+
+        while !rest.is_empty() {
+            ensure!(rest.len() >= 8, "command label is cut off");
+
+            let index = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+            rest = &rest[8..];
+
+            ensure!(rest.len() >= len, "command label is cut off");
+            let label = std::str::from_utf8(&rest[..len])
+                .map_err(|e| format_err!("command label is not UTF-8: {}", e))?;
+            rest = &rest[len..];
+
+            labels.insert(index, label.to_string());
+        }
+
+        self.labels = labels;
+
+        Ok(())
+    }
 }
 
 /// Types of color values. Only useful for encoding/decoding TinyVG binary
@@ -56,6 +381,126 @@ pub enum Style {
     },
 }
 
+impl Style {
+    /// Indices into the color table referenced by this style, one for flat
+    /// colors and two for gradients
+    pub fn color_indices(&self) -> impl Iterator<Item = usize> {
+        let (first, second) = match self {
+            Style::FlatColor { color_index } => (*color_index, None),
+            Style::LinearGradient {
+                color_index_0,
+                color_index_1,
+                ..
+            }
+            | Style::RadialGradient {
+                color_index_0,
+                color_index_1,
+                ..
+            } => (*color_index_0, Some(*color_index_1)),
+        };
+
+        std::iter::once(first).chain(second)
+    }
+
+    /// Whether this is a gradient whose two points are the same. Such
+    /// gradients have no direction or radius, and render backends either fail
+    /// on them or draw them black.
+    pub fn is_degenerate_gradient(&self) -> bool {
+        match self {
+            Style::FlatColor { .. } => false,
+            Style::LinearGradient {
+                point_0, point_1, ..
+            }
+            | Style::RadialGradient {
+                point_0, point_1, ..
+            } => point_0 == point_1,
+        }
+    }
+
+    /// Vector from the first to the second point of a gradient, `None` for
+    /// flat colors. For radial gradients its length is the radius.
+    pub fn gradient_vector(&self) -> Option<kurbo::Vec2> {
+        match self {
+            Style::FlatColor { .. } => None,
+            Style::LinearGradient {
+                point_0, point_1, ..
+            }
+            | Style::RadialGradient {
+                point_0, point_1, ..
+            } => Some(*point_1 - *point_0),
+        }
+    }
+
+    /// Direction of a gradient in radians, measured from the x axis towards
+    /// the y axis (clockwise, since y points down). `None` for flat colors.
+    pub fn gradient_angle(&self) -> Option<f64> {
+        self.gradient_vector().map(|v| v.atan2())
+    }
+
+    /// Point a gradient in the direction `angle` (see `gradient_angle`),
+    /// keeping its first point and its length. Does nothing for flat colors.
+    ///
+    /// ```
+    /// # use tinyvg::format::{Point, Style};
+    /// let mut style = Style::LinearGradient {
+    ///     point_0: Point::new(2.0, 2.0),
+    ///     point_1: Point::new(6.0, 2.0),
+    ///     color_index_0: 0,
+    ///     color_index_1: 1,
+    /// };
+    ///
+    /// style.set_gradient_angle(std::f64::consts::FRAC_PI_2);
+    ///
+    /// let vector = style.gradient_vector().unwrap();
+    /// assert!((vector.x - 0.0).abs() < 1e-9 && (vector.y - 4.0).abs() < 1e-9);
+    /// ```
+    pub fn set_gradient_angle(&mut self, angle: f64) {
+        if let Style::LinearGradient {
+            point_0, point_1, ..
+        }
+        | Style::RadialGradient {
+            point_0, point_1, ..
+        } = self
+        {
+            let length = (*point_1 - *point_0).hypot();
+            *point_1 = *point_0 + kurbo::Vec2::from_angle(angle) * length;
+        }
+    }
+
+    /// Apply `transform` to both points of a gradient. Does nothing for flat
+    /// colors.
+    pub fn transform_gradient(&mut self, transform: kurbo::Affine) {
+        if let Style::LinearGradient {
+            point_0, point_1, ..
+        }
+        | Style::RadialGradient {
+            point_0, point_1, ..
+        } = self
+        {
+            *point_0 = transform * *point_0;
+            *point_1 = transform * *point_1;
+        }
+    }
+
+    pub(crate) fn color_indices_mut(&mut self) -> impl Iterator<Item = &mut usize> {
+        let (first, second) = match self {
+            Style::FlatColor { color_index } => (color_index, None),
+            Style::LinearGradient {
+                color_index_0,
+                color_index_1,
+                ..
+            }
+            | Style::RadialGradient {
+                color_index_0,
+                color_index_1,
+                ..
+            } => (color_index_0, Some(color_index_1)),
+        };
+
+        std::iter::once(first).chain(second)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct OutlineStyle {
     pub line_width: f64,
@@ -97,6 +542,79 @@ pub enum Command {
     },
 }
 
+impl Command {
+    /// Name of the command in the TinyVG specification, e.g. `fill_path` or
+    /// `outline_fill_rectangles`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::FillPolygon { outline: None, .. } => "fill_polygon",
+            Command::FillPolygon { .. } => "outline_fill_polygon",
+            Command::FillRectangles { outline: None, .. } => "fill_rectangles",
+            Command::FillRectangles { .. } => "outline_fill_rectangles",
+            Command::FillPath { outline: None, .. } => "fill_path",
+            Command::FillPath { .. } => "outline_fill_path",
+            Command::DrawLines { .. } => "draw_lines",
+            Command::DrawLineLoop {
+                close_path: true, ..
+            } => "draw_line_loop",
+            Command::DrawLineLoop { .. } => "draw_line_strip",
+            Command::DrawLinePath { .. } => "draw_line_path",
+        }
+    }
+
+    /// The styles of this command: the fill or line style, followed by the
+    /// line style of the outline if there is one
+    pub fn styles(&self) -> impl Iterator<Item = &Style> {
+        let (primary, outline) = match self {
+            Command::FillPolygon {
+                fill_style,
+                outline,
+                ..
+            }
+            | Command::FillRectangles {
+                fill_style,
+                outline,
+                ..
+            }
+            | Command::FillPath {
+                fill_style,
+                outline,
+                ..
+            } => (fill_style, outline.as_ref()),
+            Command::DrawLines { line_style, .. }
+            | Command::DrawLineLoop { line_style, .. }
+            | Command::DrawLinePath { line_style, .. } => (line_style, None),
+        };
+
+        std::iter::once(primary).chain(outline.map(|o| &o.line_style))
+    }
+
+    pub(crate) fn styles_mut(&mut self) -> impl Iterator<Item = &mut Style> {
+        let (primary, outline) = match self {
+            Command::FillPolygon {
+                fill_style,
+                outline,
+                ..
+            }
+            | Command::FillRectangles {
+                fill_style,
+                outline,
+                ..
+            }
+            | Command::FillPath {
+                fill_style,
+                outline,
+                ..
+            } => (fill_style, outline.as_mut()),
+            Command::DrawLines { line_style, .. }
+            | Command::DrawLineLoop { line_style, .. }
+            | Command::DrawLinePath { line_style, .. } => (line_style, None),
+        };
+
+        std::iter::once(primary).chain(outline.map(|o| &mut o.line_style))
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Segment {
     pub start: Point,
@@ -125,6 +643,15 @@ pub enum SegmentCommandKind {
         control_1: Point,
         point_1: Point,
     },
+    /// Kept apart from `ArcEllipse` so that files round trip to the same
+    /// instruction, even though it is an ellipse arc with equal radii and no
+    /// rotation
+    ArcCircle {
+        large: bool,
+        sweep: bool,
+        radius: f64,
+        target: Point,
+    },
     ArcEllipse {
         large: bool,
         sweep: bool,
@@ -140,6 +667,35 @@ pub enum SegmentCommandKind {
     },
 }
 
+impl SegmentCommandKind {
+    /// Point the pen ends on after this command, when it starts at `pen` in a
+    /// segment that started at `start`
+    ///
+    /// ```
+    /// # use tinyvg::format::{Point, SegmentCommandKind};
+    /// let pen = Point::new(4.0, 8.0);
+    /// let start = Point::new(0.0, 0.0);
+    ///
+    /// assert_eq!(
+    ///     SegmentCommandKind::HorizontalLine { x: 2.0 }.end_point(pen, start),
+    ///     Point::new(2.0, 8.0)
+    /// );
+    /// assert_eq!(SegmentCommandKind::ClosePath.end_point(pen, start), start);
+    /// ```
+    pub fn end_point(&self, pen: Point, start: Point) -> Point {
+        match self {
+            SegmentCommandKind::Line { end } => *end,
+            SegmentCommandKind::HorizontalLine { x } => Point { x: *x, y: pen.y },
+            SegmentCommandKind::VerticalLine { y } => Point { x: pen.x, y: *y },
+            SegmentCommandKind::CubicBezier { point_1, .. }
+            | SegmentCommandKind::QuadraticBezier { point_1, .. } => *point_1,
+            SegmentCommandKind::ArcCircle { target, .. }
+            | SegmentCommandKind::ArcEllipse { target, .. } => *target,
+            SegmentCommandKind::ClosePath => start,
+        }
+    }
+}
+
 /// Width of certain coordinate values. Only useful for encoding/decoding TinyVG
 /// binary format.
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -154,6 +710,30 @@ pub enum CoordinateRange {
     Enhanced,
 }
 
+impl CoordinateRange {
+    /// Number of bits used for each coordinate value
+    pub fn bits(self) -> u32 {
+        match self {
+            CoordinateRange::Reduced => 8,
+            CoordinateRange::Default => 16,
+            CoordinateRange::Enhanced => 32,
+        }
+    }
+
+    /// Whether a raw (already multiplied by the scale factor) signed unit value
+    /// can be represented with this range
+    pub fn fits_unit(self, raw: i64) -> bool {
+        let half = 1i64 << (self.bits() - 1);
+
+        raw >= -half && raw < half
+    }
+
+    /// Whether an image width or height can be represented with this range
+    pub fn fits_size(self, size: u32) -> bool {
+        (size as u64) < (1u64 << self.bits())
+    }
+}
+
 /// Image header for TinyVG image. Mostly useful for binary encoding/decoding.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Header {
@@ -176,7 +756,4 @@ pub struct Header {
 
     /// Height in pixels
     pub height: u32,
-
-    /// Number of colors in this image. Equivalent to `file.color_table.len()`
-    pub color_count: u32,
 }
@@ -0,0 +1,155 @@
+//! Generators for graphics which are usually produced on a server, such as QR
+//! codes and identicons. The results are regular images, and encode to a
+//! fraction of the size of the equivalent PNG.
+
+use qrcode::QrCode;
+
+use crate::encode::fnv1a;
+use crate::error::{ensure, Context, Result};
+use crate::format::{Color, Command, Image, Rect, Style};
+
+/// Cells per side of an identicon, excluding its margin
+const IDENTICON_CELLS: usize = 5;
+
+/// Encode `data` as a QR code, with every module `module_size` units wide.
+/// The code is surrounded by a light quiet zone of `quiet_zone` modules,
+/// scanners need 4 modules to reliably find the code. Dark modules are
+/// merged into one rectangle per horizontal run.
+///
+/// ```
+/// # use tinyvg::generate::qr_code;
+/// let image = qr_code(b"https://tinyvg.tech", 4, 4).unwrap();
+///
+/// // Version 2 codes are 25 modules wide
+/// assert_eq!(image.header.width, (25 + 2 * 4) * 4);
+///
+/// let mut bytes = Vec::new();
+/// image.encode(&mut bytes).unwrap();
+///
+/// let mut png = Vec::new();
+/// image.render_png(&mut png).unwrap();
+/// ```
+pub fn qr_code(data: &[u8], module_size: u32, quiet_zone: u32) -> Result<Image> {
+    ensure!(
+        module_size > 0,
+        "QR code modules must be at least 1 unit wide"
+    );
+
+    let code = QrCode::new(data).wrap_err("failed to create QR code")?;
+    let modules = code.width();
+    let colors = code.to_colors();
+
+    let side = (modules as u32 + 2 * quiet_zone) * module_size;
+    let mut image = Image::empty(side, side);
+    image.color_table = vec![Color::WHITE, Color::BLACK];
+
+    image.commands.push(Command::FillRectangles {
+        fill_style: Style::FlatColor { color_index: 0 },
+        rectangles: vec![Rect::new(0.0, 0.0, side as f64, side as f64)],
+        outline: None,
+    });
+
+    let module = module_size as f64;
+    let offset = (quiet_zone * module_size) as f64;
+    let mut rectangles = Vec::new();
+
+    for (y, row) in colors.chunks(modules).enumerate() {
+        let mut x = 0;
+        while x < modules {
+            if row[x] != qrcode::Color::Dark {
+                x += 1;
+                continue;
+            }
+
+            let start = x;
+            while x < modules && row[x] == qrcode::Color::Dark {
+                x += 1;
+            }
+
+            rectangles.push(Rect::new(
+                offset + start as f64 * module,
+                offset + y as f64 * module,
+                offset + x as f64 * module,
+                offset + (y + 1) as f64 * module,
+            ));
+        }
+    }
+
+    image.commands.push(Command::FillRectangles {
+        fill_style: Style::FlatColor { color_index: 1 },
+        rectangles,
+        outline: None,
+    });
+
+    image.fit_coordinate_range()?;
+
+    Ok(image)
+}
+
+/// Generate a `size` by `size` unit identicon for `input`, e.g. a user name or
+/// an email hash. A 5 by 5 grid that is mirrored horizontally is filled from
+/// a hash of the input, in a color picked by the same hash, so that equal
+/// inputs always give the same image.
+///
+/// ```
+/// # use tinyvg::generate::identicon;
+/// let a = identicon(b"lily", 64).unwrap();
+/// let b = identicon(b"mara", 64).unwrap();
+///
+/// assert_eq!(a, identicon(b"lily", 64).unwrap());
+/// assert_ne!(a, b);
+///
+/// let mut png = Vec::new();
+/// a.render_png(&mut png).unwrap();
+/// ```
+pub fn identicon(input: &[u8], size: u32) -> Result<Image> {
+    ensure!(size > 0, "identicons must be at least 1 unit wide");
+
+    let hash = fnv1a(input);
+
+    // Half a cell of margin on each side
+    let cell = size as f64 / (IDENTICON_CELLS + 1) as f64;
+    let margin = cell / 2.0;
+
+    let mut image = Image::empty(size, size);
+    image.header.scale = 4;
+
+    let hue = (hash >> 32) as f64 / u32::MAX as f64 * 360.0;
+    image.color_table = vec![Color::rgb8(0xf0, 0xf0, 0xf0), Color::hlc(hue, 55.0, 50.0)];
+
+    image.commands.push(Command::FillRectangles {
+        fill_style: Style::FlatColor { color_index: 0 },
+        rectangles: vec![Rect::new(0.0, 0.0, size as f64, size as f64)],
+        outline: None,
+    });
+
+    let half = (IDENTICON_CELLS + 1) / 2;
+    let mut rectangles = Vec::new();
+
+    for y in 0..IDENTICON_CELLS {
+        for x in 0..half {
+            if (hash >> (y * half + x)) & 1 == 0 {
+                continue;
+            }
+
+            let mirrored = IDENTICON_CELLS - 1 - x;
+            for column in std::iter::once(x).chain((mirrored != x).then_some(mirrored)) {
+                let x0 = margin + column as f64 * cell;
+                let y0 = margin + y as f64 * cell;
+                rectangles.push(Rect::new(x0, y0, x0 + cell, y0 + cell));
+            }
+        }
+    }
+
+    if !rectangles.is_empty() {
+        image.commands.push(Command::FillRectangles {
+            fill_style: Style::FlatColor { color_index: 1 },
+            rectangles,
+            outline: None,
+        });
+    }
+
+    image.fit_coordinate_range()?;
+
+    Ok(image)
+}
@@ -0,0 +1,1127 @@
+//! Operations which rewrite the geometry of an image in place, and the checks
+//! that keep the result representable in the TinyVG binary format.
+
+use std::fmt;
+
+use kurbo::{Affine, PathEl, Shape};
+
+use crate::error::{ensure, Result};
+use crate::format::{
+    Command, CoordinateRange, Image, Point, Rect, Segment, SegmentCommand, SegmentCommandKind,
+    Style,
+};
+use crate::render::{svg_arc, ARC_TOLERANCE};
+
+/// Error returned when a unit value of an image does not fit in any
+/// `CoordinateRange`, even `CoordinateRange::Enhanced`. Returned as
+/// `Error::CoordinateOverflow`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoordinateOverflow {
+    /// The unit value with the largest magnitude in the image
+    pub value: f64,
+
+    /// Scale of the image, units are multiplied by `2^scale` when encoded
+    pub scale: u8,
+}
+
+impl fmt::Display for CoordinateOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unit value {} does not fit in 32 bits with a scale of 1/{}",
+            self.value,
+            1u32 << self.scale
+        )
+    }
+}
+
+impl std::error::Error for CoordinateOverflow {}
+
+/// How stroke widths change when the geometry of an image is scaled, by
+/// `Image::scale_with` or by the transform of a render context (see
+/// `RenderOptions::stroke_scaling`). Applies to line widths of draw commands,
+/// outline widths of fill commands and per-segment widths of paths alike.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StrokeScaling {
+    /// Strokes scale with the geometry
+    Proportional,
+
+    /// Strokes keep their width, e.g. hairlines of a technical drawing
+    Constant,
+
+    /// Strokes scale with the geometry, but don't get thinner than the given
+    /// width after scaling, so that they stay visible when scaled down
+    ClampMin(f64),
+}
+
+impl StrokeScaling {
+    /// Width of a stroke of `width` after scaling by `factor`
+    pub fn apply(self, width: f64, factor: f64) -> f64 {
+        match self {
+            StrokeScaling::Proportional => width * factor,
+            StrokeScaling::Constant => width,
+            StrokeScaling::ClampMin(minimum) => (width * factor).max(minimum),
+        }
+    }
+}
+
+impl Command {
+    /// A rectangle containing everything this command draws, including half
+    /// of its widest stroke. Curves are bounded by their control points, so
+    /// the rectangle may be larger than the drawn shape, but never smaller.
+    /// Arcs that can't be drawn are bounded by their end points.
+    ///
+    /// ```
+    /// # use tinyvg::format::{Command, Line, Point, Rect, Style};
+    /// let command = Command::DrawLines {
+    ///     line_style: Style::FlatColor { color_index: 0 },
+    ///     line_width: 2.0,
+    ///     lines: vec![Line::new((1.0, 1.0), (5.0, 3.0))],
+    /// };
+    ///
+    /// assert_eq!(command.bounds(), Rect::new(0.0, 0.0, 6.0, 4.0));
+    /// ```
+    pub fn bounds(&self) -> Rect {
+        let mut bounds: Option<Rect> = None;
+        let mut add = |p: Point| {
+            bounds = Some(match bounds {
+                Some(bounds) => bounds.union_pt(p),
+                None => Rect::from_points(p, p),
+            });
+        };
+
+        let mut stroke: f64 = 0.0;
+
+        match self {
+            Command::FillPolygon { outline, .. }
+            | Command::FillRectangles { outline, .. }
+            | Command::FillPath { outline, .. } => {
+                if let Some(outline) = outline {
+                    stroke = outline.line_width;
+                }
+            }
+            Command::DrawLines { line_width, .. }
+            | Command::DrawLineLoop { line_width, .. }
+            | Command::DrawLinePath { line_width, .. } => stroke = *line_width,
+        }
+
+        match self {
+            Command::FillPolygon {
+                polygon: points, ..
+            }
+            | Command::DrawLineLoop { points, .. } => points.iter().copied().for_each(&mut add),
+            Command::FillRectangles { rectangles, .. } => {
+                for r in rectangles {
+                    add(Point::new(r.x0, r.y0));
+                    add(Point::new(r.x1, r.y1));
+                }
+            }
+            Command::DrawLines { lines, .. } => {
+                for l in lines {
+                    add(l.p0);
+                    add(l.p1);
+                }
+            }
+            Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } => {
+                for Segment { start, commands } in path {
+                    let mut pen = *start;
+                    add(pen);
+
+                    for command in commands {
+                        if let Some(line_width) = command.line_width {
+                            stroke = stroke.max(line_width);
+                        }
+
+                        match &command.kind {
+                            SegmentCommandKind::CubicBezier {
+                                control_0,
+                                control_1,
+                                ..
+                            } => {
+                                add(*control_0);
+                                add(*control_1);
+                            }
+                            SegmentCommandKind::ArcCircle {
+                                large,
+                                sweep,
+                                radius,
+                                target,
+                            } => {
+                                if let Ok(arc) =
+                                    svg_arc(pen, *large, *sweep, *radius, *radius, 0.0, *target)
+                                {
+                                    let arc_bounds = arc.bounding_box();
+                                    add(arc_bounds.origin());
+                                    add(Point::new(arc_bounds.x1, arc_bounds.y1));
+                                }
+                            }
+                            SegmentCommandKind::ArcEllipse {
+                                large,
+                                sweep,
+                                radius_x,
+                                radius_y,
+                                rotation,
+                                target,
+                            } => {
+                                if let Ok(arc) = svg_arc(
+                                    pen, *large, *sweep, *radius_x, *radius_y, *rotation, *target,
+                                ) {
+                                    let arc_bounds = arc.bounding_box();
+                                    add(arc_bounds.origin());
+                                    add(Point::new(arc_bounds.x1, arc_bounds.y1));
+                                }
+                            }
+                            SegmentCommandKind::QuadraticBezier { control, .. } => add(*control),
+                            _ => {}
+                        }
+
+                        pen = command.kind.end_point(pen, *start);
+                        add(pen);
+                    }
+                }
+            }
+        }
+
+        let half = stroke.max(0.0) / 2.0;
+        bounds.unwrap_or_default().inflate(half, half)
+    }
+
+    /// Rotate the gradients of this command by `angle` radians about the
+    /// center of the command's bounds, e.g. to turn a top to bottom gradient
+    /// into a left to right one without recomputing its points
+    ///
+    /// ```
+    /// # use tinyvg::format::{Command, Point, Rect, Style};
+    /// let mut command = Command::FillRectangles {
+    ///     fill_style: Style::LinearGradient {
+    ///         point_0: Point::new(0.0, 0.0),
+    ///         point_1: Point::new(0.0, 10.0),
+    ///         color_index_0: 0,
+    ///         color_index_1: 1,
+    ///     },
+    ///     rectangles: vec![Rect::new(0.0, 0.0, 10.0, 10.0)],
+    ///     outline: None,
+    /// };
+    ///
+    /// command.rotate_gradients(-std::f64::consts::FRAC_PI_2);
+    ///
+    /// let angle = command.styles().next().unwrap().gradient_angle().unwrap();
+    /// assert!(angle.abs() < 1e-9);
+    /// ```
+    pub fn rotate_gradients(&mut self, angle: f64) {
+        let center = self.bounds().center().to_vec2();
+
+        self.transform_gradients(
+            Affine::translate(center) * Affine::rotate(angle) * Affine::translate(-center),
+        );
+    }
+
+    /// Scale the gradients of this command by `factor` about the center of
+    /// the command's bounds, spreading them over a larger or smaller part of
+    /// the shape
+    pub fn scale_gradients(&mut self, factor: f64) {
+        let center = self.bounds().center().to_vec2();
+
+        self.transform_gradients(
+            Affine::translate(center) * Affine::scale(factor) * Affine::translate(-center),
+        );
+    }
+
+    fn transform_gradients(&mut self, transform: Affine) {
+        for style in self.styles_mut() {
+            style.transform_gradient(transform);
+        }
+    }
+}
+
+/// Whether a rectangle has a positive width and height. Rectangles with zero
+/// or negative extents have no defined appearance: they are reported when
+/// decoding and linting, and skipped when rendering and exporting.
+pub(crate) fn has_area(rect: &Rect) -> bool {
+    rect.width() > 0.0 && rect.height() > 0.0
+}
+
+impl Image {
+    /// Scale all geometry and line widths of this image by `factor`, along
+    /// with the width and height in the header. Same as `Image::scale_with`
+    /// with `StrokeScaling::Proportional`. If the scaled coordinates no
+    /// longer fit in the header's `CoordinateRange`, the range is upgraded. If
+    /// they don't even fit in `CoordinateRange::Enhanced`, a
+    /// `CoordinateOverflow` error is returned and the image is left unchanged.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::format::CoordinateRange;
+    /// let mut image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    /// assert_eq!(image.header.coordinate_range, CoordinateRange::Reduced);
+    ///
+    /// image.scale(4.0).unwrap();
+    ///
+    /// assert_eq!(image.header.width, 96);
+    /// assert_eq!(image.header.coordinate_range, CoordinateRange::Default);
+    /// ```
+    pub fn scale(&mut self, factor: f64) -> Result<()> {
+        self.scale_with(factor, StrokeScaling::Proportional)
+    }
+
+    /// Scale all geometry of this image by `factor` like `Image::scale`, with
+    /// stroke widths changed according to `strokes`
+    ///
+    /// ```
+    /// # use tinyvg::format::{Color, Command, Image, Line, Style};
+    /// # use tinyvg::geometry::StrokeScaling;
+    /// let mut image = Image::empty(100, 100);
+    /// image.color_table.push(Color::BLACK);
+    /// image.commands.push(Command::DrawLines {
+    ///     line_style: Style::FlatColor { color_index: 0 },
+    ///     line_width: 4.0,
+    ///     lines: vec![Line::new((0.0, 0.0), (100.0, 100.0))],
+    /// });
+    ///
+    /// let line_width = |image: &Image| match &image.commands[0] {
+    ///     Command::DrawLines { line_width, .. } => *line_width,
+    ///     _ => unreachable!(),
+    /// };
+    ///
+    /// let mut constant = image.clone();
+    /// constant.scale_with(0.1, StrokeScaling::Constant).unwrap();
+    /// assert_eq!(constant.header.width, 10);
+    /// assert_eq!(line_width(&constant), 4.0);
+    ///
+    /// let mut clamped = image.clone();
+    /// clamped.scale_with(0.1, StrokeScaling::ClampMin(1.0)).unwrap();
+    /// assert_eq!(line_width(&clamped), 1.0);
+    /// ```
+    pub fn scale_with(&mut self, factor: f64, strokes: StrokeScaling) -> Result<()> {
+        ensure!(
+            factor.is_finite() && factor > 0.0,
+            "scale factor must be positive and finite, got {}",
+            factor
+        );
+
+        let mut scaled = self.clone();
+
+        let mut widths = Vec::new();
+        scaled.visit_line_widths_mut(|width| widths.push(*width));
+
+        scaled.visit_geometry_mut(
+            |p| {
+                p.x *= factor;
+                p.y *= factor;
+            },
+            |length| *length *= factor,
+        );
+
+        // Widths are computed from the originals rather than the scaled
+        // values, so that constant widths stay exactly the same
+        let mut widths = widths.into_iter();
+        scaled.visit_line_widths_mut(|width| {
+            if let Some(original) = widths.next() {
+                *width = strokes.apply(original, factor);
+            }
+        });
+
+        scaled.header.width = (self.header.width as f64 * factor).round() as u32;
+        scaled.header.height = (self.header.height as f64 * factor).round() as u32;
+
+        scaled.fit_coordinate_range()?;
+        *self = scaled;
+
+        Ok(())
+    }
+
+    /// Apply `transform` to all geometry of this image: points, rectangles,
+    /// control points, arcs and gradient anchors. Line widths and radii are
+    /// scaled by the square root of the transform's determinant, its average
+    /// scale. The header's size becomes the size of the bounding box of the
+    /// transformed image area, so include a translation to move that box back
+    /// to the origin, e.g. after rotating. Like `Image::scale`, the
+    /// `CoordinateRange` is upgraded if needed, and the image is left
+    /// unchanged if the result can't be represented.
+    ///
+    /// Geometry that a transform can't keep in its original form is rewritten:
+    /// under rotations and skews, rectangles become paths and horizontal and
+    /// vertical path lines become lines. Under transforms that scale
+    /// unevenly or skew, arcs become cubic Béziers.
+    ///
+    /// ```
+    /// # use std::f64::consts::FRAC_PI_2;
+    /// # use tinyvg::format::{Color, Command, Image, Rect, Style};
+    /// # use tinyvg::kurbo::Affine;
+    /// let mut image = Image::empty(40, 20);
+    /// image.color_table.push(Color::BLACK);
+    /// image.commands.push(Command::FillRectangles {
+    ///     fill_style: Style::FlatColor { color_index: 0 },
+    ///     rectangles: vec![Rect::new(0.0, 0.0, 10.0, 5.0)],
+    ///     outline: None,
+    /// });
+    ///
+    /// // Turn the image a quarter clockwise, into portrait
+    /// image
+    ///     .transform(Affine::translate((20.0, 0.0)) * Affine::rotate(FRAC_PI_2))
+    ///     .unwrap();
+    ///
+    /// assert_eq!((image.header.width, image.header.height), (20, 40));
+    /// assert!(matches!(image.commands[0], Command::FillPath { .. }));
+    /// ```
+    pub fn transform(&mut self, transform: Affine) -> Result<()> {
+        let [a, b, c, d, _, _] = transform.as_coeffs();
+        let determinant = transform.determinant();
+
+        ensure!(
+            transform.as_coeffs().iter().all(|c| c.is_finite()) && determinant != 0.0,
+            "transform must be finite and invertible, got {:?}",
+            transform
+        );
+
+        let axis_aligned = b == 0.0 && c == 0.0;
+        let similarity = is_similarity(transform);
+        let factor = determinant.abs().sqrt();
+
+        let mut transformed = self.clone();
+
+        for command in &mut transformed.commands {
+            if !axis_aligned {
+                if let Command::FillRectangles {
+                    fill_style,
+                    rectangles,
+                    outline,
+                } = command
+                {
+                    *command = Command::FillPath {
+                        fill_style: fill_style.clone(),
+                        path: rectangles.iter().map(rect_segment).collect(),
+                        outline: outline.clone(),
+                    };
+                }
+            }
+
+            if let Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } = command {
+                for segment in path {
+                    if !axis_aligned {
+                        straighten_segment(segment);
+                    }
+
+                    if !similarity && segment.commands.iter().any(|c| is_arc(&c.kind)) {
+                        normalize_segment(segment, true);
+                    }
+                }
+            }
+        }
+
+        transformed.visit_geometry_mut(|p| *p = transform * *p, |length| *length *= factor);
+
+        // Mirroring turns arcs the other way around, and rotations turn the
+        // axes of ellipses
+        for command in &mut transformed.commands {
+            match command {
+                Command::FillRectangles { rectangles, .. } => {
+                    for r in rectangles {
+                        *r = r.abs();
+                    }
+                }
+                Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } => {
+                    for command in path.iter_mut().flat_map(|s| &mut s.commands) {
+                        match &mut command.kind {
+                            SegmentCommandKind::ArcCircle { sweep, .. } => {
+                                *sweep ^= determinant < 0.0;
+                            }
+                            SegmentCommandKind::ArcEllipse {
+                                sweep, rotation, ..
+                            } => {
+                                *sweep ^= determinant < 0.0;
+
+                                let (sin, cos) = rotation.sin_cos();
+                                *rotation = (b * cos + d * sin).atan2(a * cos + c * sin);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let area = Rect::new(
+            0.0,
+            0.0,
+            self.header.width as f64,
+            self.header.height as f64,
+        );
+        let bounds = transform.transform_rect_bbox(area);
+        transformed.header.width = bounds.width().round() as u32;
+        transformed.header.height = bounds.height().round() as u32;
+
+        transformed.fit_coordinate_range()?;
+        *self = transformed;
+
+        Ok(())
+    }
+
+    /// The smallest `CoordinateRange` that can represent every unit value and
+    /// the dimensions of this image at the header's scale. Returns `None` if
+    /// the image does not fit in any range.
+    pub fn required_coordinate_range(&self) -> Option<CoordinateRange> {
+        let (min, max) = self.raw_unit_bounds();
+
+        [
+            CoordinateRange::Reduced,
+            CoordinateRange::Default,
+            CoordinateRange::Enhanced,
+        ]
+        .into_iter()
+        .find(|range| {
+            range.fits_unit(min)
+                && range.fits_unit(max)
+                && range.fits_size(self.header.width)
+                && range.fits_size(self.header.height)
+        })
+    }
+
+    /// Upgrade the header's `CoordinateRange` if the image's geometry does not
+    /// fit in it. Never downgrades the range.
+    pub fn fit_coordinate_range(&mut self) -> Result<()> {
+        let required = self.required_coordinate_range().ok_or_else(|| {
+            let (min, max) = self.raw_unit_bounds();
+            let raw = if min.abs() > max.abs() { min } else { max };
+
+            CoordinateOverflow {
+                value: raw as f64 / (1u64 << self.header.scale) as f64,
+                scale: self.header.scale,
+            }
+        })?;
+
+        if required.bits() > self.header.coordinate_range.bits() {
+            self.header.coordinate_range = required;
+        }
+
+        Ok(())
+    }
+
+    /// Rewrite every quadratic Bézier in the paths of this image as the cubic
+    /// Bézier describing the same curve. With `arcs`, arcs are replaced by
+    /// cubic Béziers approximating them as closely as the renderer does, and
+    /// degenerate arcs (zero radius or no distance to travel) by lines.
+    /// Exporters to formats without quadratic or arc support can then handle
+    /// a single kind of curve. The line width of a replaced command is kept
+    /// on the first command replacing it.
+    ///
+    /// ```
+    /// # use tinyvg::format::{
+    /// #     Color, Command, Image, Point, Segment, SegmentCommand, SegmentCommandKind, Style,
+    /// # };
+    /// let mut image = Image::empty(10, 10);
+    /// image.color_table.push(Color::BLACK);
+    /// image.commands.push(Command::DrawLinePath {
+    ///     line_style: Style::FlatColor { color_index: 0 },
+    ///     line_width: 1.0,
+    ///     path: vec![Segment {
+    ///         start: Point::new(0.0, 0.0),
+    ///         commands: vec![SegmentCommand {
+    ///             kind: SegmentCommandKind::QuadraticBezier {
+    ///                 control: Point::new(3.0, 6.0),
+    ///                 point_1: Point::new(9.0, 0.0),
+    ///             },
+    ///             line_width: Some(2.0),
+    ///         }],
+    ///     }],
+    /// });
+    ///
+    /// image.normalize_curves(false);
+    ///
+    /// let path = match &image.commands[0] {
+    ///     Command::DrawLinePath { path, .. } => path,
+    ///     _ => unreachable!(),
+    /// };
+    /// assert_eq!(
+    ///     path[0].commands,
+    ///     vec![SegmentCommand {
+    ///         kind: SegmentCommandKind::CubicBezier {
+    ///             control_0: Point::new(2.0, 4.0),
+    ///             control_1: Point::new(5.0, 4.0),
+    ///             point_1: Point::new(9.0, 0.0),
+    ///         },
+    ///         line_width: Some(2.0),
+    ///     }]
+    /// );
+    /// ```
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::format::{Command, SegmentCommandKind};
+    /// let mut image = Decoder::new(File::open("data/everything.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// image.normalize_curves(true);
+    ///
+    /// for command in &image.commands {
+    ///     if let Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } = command {
+    ///         for command in path.iter().flat_map(|s| &s.commands) {
+    ///             assert!(!matches!(
+    ///                 command.kind,
+    ///                 SegmentCommandKind::QuadraticBezier { .. }
+    ///                     | SegmentCommandKind::ArcCircle { .. }
+    ///                     | SegmentCommandKind::ArcEllipse { .. }
+    ///             ));
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn normalize_curves(&mut self, arcs: bool) {
+        self.bounds = None;
+
+        for command in &mut self.commands {
+            if let Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } = command {
+                for segment in path {
+                    normalize_segment(segment, arcs);
+                }
+            }
+        }
+    }
+
+    /// Replace all curves and arcs with straight lines, deviating at most
+    /// `tolerance` units from the original curves. Flattened images draw
+    /// faster, at the cost of visible corners when zoomed in. As with
+    /// `normalize_curves`, the line width of a curve is kept on its first
+    /// line.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::format::{Command, SegmentCommandKind};
+    /// let mut image = Decoder::new(File::open("data/everything.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// image.flatten_curves(0.5);
+    ///
+    /// for command in &image.commands {
+    ///     if let Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } = command {
+    ///         for command in path.iter().flat_map(|s| &s.commands) {
+    ///             assert!(matches!(
+    ///                 command.kind,
+    ///                 SegmentCommandKind::Line { .. }
+    ///                     | SegmentCommandKind::HorizontalLine { .. }
+    ///                     | SegmentCommandKind::VerticalLine { .. }
+    ///                     | SegmentCommandKind::ClosePath
+    ///             ));
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn flatten_curves(&mut self, tolerance: f64) {
+        self.normalize_curves(true);
+
+        for command in &mut self.commands {
+            if let Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } = command {
+                for segment in path {
+                    flatten_segment(segment, tolerance);
+                }
+            }
+        }
+    }
+
+    /// Smallest and largest raw unit values in the image, after multiplying
+    /// with the scale factor
+    fn raw_unit_bounds(&self) -> (i64, i64) {
+        let factor = (1u64 << self.header.scale) as f64;
+        let mut min = 0;
+        let mut max = 0;
+
+        self.for_each_unit(|value| {
+            let raw = (value * factor).round() as i64;
+            min = raw.min(min);
+            max = raw.max(max);
+        });
+
+        (min, max)
+    }
+
+    /// Call `f` with every value that is encoded as a unit in the binary
+    /// format, including line widths, radii and arc rotations
+    pub(crate) fn for_each_unit(&self, mut f: impl FnMut(f64)) {
+        let point = |p: &Point, f: &mut dyn FnMut(f64)| {
+            f(p.x);
+            f(p.y);
+        };
+
+        for style in self.styles() {
+            if let Style::LinearGradient {
+                point_0, point_1, ..
+            }
+            | Style::RadialGradient {
+                point_0, point_1, ..
+            } = style
+            {
+                point(point_0, &mut f);
+                point(point_1, &mut f);
+            }
+        }
+
+        for command in &self.commands {
+            match command {
+                Command::FillPolygon { outline, .. }
+                | Command::FillRectangles { outline, .. }
+                | Command::FillPath { outline, .. } => {
+                    if let Some(outline) = outline {
+                        f(outline.line_width);
+                    }
+                }
+                Command::DrawLines { line_width, .. }
+                | Command::DrawLineLoop { line_width, .. }
+                | Command::DrawLinePath { line_width, .. } => f(*line_width),
+            }
+
+            match command {
+                Command::FillPolygon {
+                    polygon: points, ..
+                }
+                | Command::DrawLineLoop { points, .. } => {
+                    for p in points {
+                        point(p, &mut f);
+                    }
+                }
+                Command::FillRectangles { rectangles, .. } => {
+                    for r in rectangles {
+                        f(r.x0);
+                        f(r.y0);
+                        f(r.width());
+                        f(r.height());
+                    }
+                }
+                Command::DrawLines { lines, .. } => {
+                    for l in lines {
+                        point(&l.p0, &mut f);
+                        point(&l.p1, &mut f);
+                    }
+                }
+                Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } => {
+                    for Segment { start, commands } in path {
+                        point(start, &mut f);
+
+                        for command in commands {
+                            if let Some(line_width) = command.line_width {
+                                f(line_width);
+                            }
+
+                            match &command.kind {
+                                SegmentCommandKind::Line { end } => point(end, &mut f),
+                                SegmentCommandKind::HorizontalLine { x } => f(*x),
+                                SegmentCommandKind::VerticalLine { y } => f(*y),
+                                SegmentCommandKind::CubicBezier {
+                                    control_0,
+                                    control_1,
+                                    point_1,
+                                } => {
+                                    point(control_0, &mut f);
+                                    point(control_1, &mut f);
+                                    point(point_1, &mut f);
+                                }
+                                SegmentCommandKind::ArcCircle { radius, target, .. } => {
+                                    f(*radius);
+                                    point(target, &mut f);
+                                }
+                                SegmentCommandKind::ArcEllipse {
+                                    radius_x,
+                                    radius_y,
+                                    rotation,
+                                    target,
+                                    ..
+                                } => {
+                                    f(*radius_x);
+                                    f(*radius_y);
+                                    f(*rotation);
+                                    point(target, &mut f);
+                                }
+                                SegmentCommandKind::ClosePath => {}
+                                SegmentCommandKind::QuadraticBezier { control, point_1 } => {
+                                    point(control, &mut f);
+                                    point(point_1, &mut f);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Call `f` with every point of the image: path and polygon points, line
+    /// ends, rectangle corners, Bézier control points, arc targets and
+    /// gradient anchors. Horizontal and vertical path lines are passed as the
+    /// point they end on, only the coordinate they store is written back.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// let mut image = Decoder::new(File::open("data/everything.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// // Mirror the image horizontally
+    /// let width = image.header.width as f64;
+    /// image.visit_points_mut(|p| p.x = width - p.x);
+    /// ```
+    pub fn visit_points_mut(&mut self, f: impl FnMut(&mut Point)) {
+        self.visit_geometry_mut(f, |_| {});
+    }
+
+    /// Call `point` with every point of the image (including gradient anchors
+    /// and rectangle corners) and `length` with every line width and radius.
+    /// Horizontal and vertical path lines are passed as points on the current
+    /// pen position, only the coordinate they store is written back.
+    pub(crate) fn visit_geometry_mut(
+        &mut self,
+        mut point: impl FnMut(&mut Point),
+        mut length: impl FnMut(&mut f64),
+    ) {
+        self.bounds = None;
+
+        for command in &mut self.commands {
+            for style in command.styles_mut() {
+                if let Style::LinearGradient {
+                    point_0, point_1, ..
+                }
+                | Style::RadialGradient {
+                    point_0, point_1, ..
+                } = style
+                {
+                    point(point_0);
+                    point(point_1);
+                }
+            }
+
+            match command {
+                Command::FillPolygon { outline, .. }
+                | Command::FillRectangles { outline, .. }
+                | Command::FillPath { outline, .. } => {
+                    if let Some(outline) = outline {
+                        length(&mut outline.line_width);
+                    }
+                }
+                Command::DrawLines { line_width, .. }
+                | Command::DrawLineLoop { line_width, .. }
+                | Command::DrawLinePath { line_width, .. } => length(line_width),
+            }
+
+            match command {
+                Command::FillPolygon {
+                    polygon: points, ..
+                }
+                | Command::DrawLineLoop { points, .. } => points.iter_mut().for_each(&mut point),
+                Command::FillRectangles { rectangles, .. } => {
+                    for r in rectangles {
+                        let mut p0 = Point { x: r.x0, y: r.y0 };
+                        let mut p1 = Point { x: r.x1, y: r.y1 };
+                        point(&mut p0);
+                        point(&mut p1);
+
+                        r.x0 = p0.x;
+                        r.y0 = p0.y;
+                        r.x1 = p1.x;
+                        r.y1 = p1.y;
+                    }
+                }
+                Command::DrawLines { lines, .. } => {
+                    for l in lines {
+                        point(&mut l.p0);
+                        point(&mut l.p1);
+                    }
+                }
+                Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } => {
+                    for segment in path {
+                        visit_segment_mut(segment, &mut point, &mut length);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Call `f` with every stroke width of the image: line widths of draw
+    /// commands, outline widths of fill commands and per-segment widths of
+    /// paths
+    pub(crate) fn visit_line_widths_mut(&mut self, mut f: impl FnMut(&mut f64)) {
+        self.bounds = None;
+
+        for command in &mut self.commands {
+            match command {
+                Command::FillPolygon { outline, .. }
+                | Command::FillRectangles { outline, .. }
+                | Command::FillPath { outline, .. } => {
+                    if let Some(outline) = outline {
+                        f(&mut outline.line_width);
+                    }
+                }
+                Command::DrawLines { line_width, .. }
+                | Command::DrawLineLoop { line_width, .. }
+                | Command::DrawLinePath { line_width, .. } => f(line_width),
+            }
+
+            if let Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } = command {
+                for segment in path {
+                    for command in &mut segment.commands {
+                        if let Some(line_width) = &mut command.line_width {
+                            f(line_width);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn visit_segment_mut(
+    segment: &mut Segment,
+    point: &mut impl FnMut(&mut Point),
+    length: &mut impl FnMut(&mut f64),
+) {
+    // The pen is tracked in original coordinates, so that horizontal and
+    // vertical lines get passed the point they actually end on
+    let start = segment.start;
+    let mut pen = start;
+    point(&mut segment.start);
+
+    for command in &mut segment.commands {
+        if let Some(line_width) = &mut command.line_width {
+            length(line_width);
+        }
+
+        let end = command.kind.end_point(pen, start);
+
+        match &mut command.kind {
+            SegmentCommandKind::Line { end } => point(end),
+            SegmentCommandKind::HorizontalLine { x } => {
+                let mut p = end;
+                point(&mut p);
+                *x = p.x;
+            }
+            SegmentCommandKind::VerticalLine { y } => {
+                let mut p = end;
+                point(&mut p);
+                *y = p.y;
+            }
+            SegmentCommandKind::CubicBezier {
+                control_0,
+                control_1,
+                point_1,
+            } => {
+                point(control_0);
+                point(control_1);
+                point(point_1);
+            }
+            SegmentCommandKind::ArcCircle { radius, target, .. } => {
+                length(radius);
+                point(target);
+            }
+            SegmentCommandKind::ArcEllipse {
+                radius_x,
+                radius_y,
+                target,
+                ..
+            } => {
+                length(radius_x);
+                length(radius_y);
+                point(target);
+            }
+            SegmentCommandKind::ClosePath => {}
+            SegmentCommandKind::QuadraticBezier { control, point_1 } => {
+                point(control);
+                point(point_1);
+            }
+        }
+
+        pen = end;
+    }
+}
+
+/// Whether a transform keeps circles circles: a rotation and uniform scale,
+/// possibly mirrored
+fn is_similarity(transform: Affine) -> bool {
+    let [a, b, c, d, _, _] = transform.as_coeffs();
+    let tolerance = 1e-9 * (a.abs() + b.abs() + c.abs() + d.abs());
+
+    ((a - d).abs() <= tolerance && (b + c).abs() <= tolerance)
+        || ((a + d).abs() <= tolerance && (b - c).abs() <= tolerance)
+}
+
+fn is_arc(kind: &SegmentCommandKind) -> bool {
+    matches!(
+        kind,
+        SegmentCommandKind::ArcCircle { .. } | SegmentCommandKind::ArcEllipse { .. }
+    )
+}
+
+/// A closed segment around a rectangle
+pub(crate) fn rect_segment(rect: &Rect) -> Segment {
+    let line = |x, y| SegmentCommand {
+        kind: SegmentCommandKind::Line {
+            end: Point::new(x, y),
+        },
+        line_width: None,
+    };
+
+    Segment {
+        start: Point::new(rect.x0, rect.y0),
+        commands: vec![
+            line(rect.x1, rect.y0),
+            line(rect.x1, rect.y1),
+            line(rect.x0, rect.y1),
+            SegmentCommand {
+                kind: SegmentCommandKind::ClosePath,
+                line_width: None,
+            },
+        ],
+    }
+}
+
+/// Replace the horizontal and vertical lines of a segment with lines to the
+/// points they end on, which stay lines under any transform
+fn straighten_segment(segment: &mut Segment) {
+    let start = segment.start;
+    let mut pen = start;
+
+    for command in &mut segment.commands {
+        pen = command.kind.end_point(pen, start);
+
+        if let SegmentCommandKind::HorizontalLine { .. } | SegmentCommandKind::VerticalLine { .. } =
+            command.kind
+        {
+            command.kind = SegmentCommandKind::Line { end: pen };
+        }
+    }
+}
+
+fn normalize_segment(segment: &mut Segment, arcs: bool) {
+    let start = segment.start;
+    let mut pen = start;
+    let mut normalized = Vec::with_capacity(segment.commands.len());
+
+    for SegmentCommand { kind, line_width } in segment.commands.drain(..) {
+        let previous = pen;
+        pen = kind.end_point(pen, start);
+
+        // Circle arcs are flattened the same way as ellipse arcs
+        let kind = match kind {
+            SegmentCommandKind::ArcCircle {
+                large,
+                sweep,
+                radius,
+                target,
+            } if arcs => SegmentCommandKind::ArcEllipse {
+                large,
+                sweep,
+                radius_x: radius,
+                radius_y: radius,
+                rotation: 0.0,
+                target,
+            },
+            kind => kind,
+        };
+
+        match kind {
+            SegmentCommandKind::QuadraticBezier { control, point_1 } => {
+                // Degree elevation: the cubic control points lie two thirds
+                // of the way from each end point to the quadratic control
+                // point
+                normalized.push(SegmentCommand {
+                    kind: SegmentCommandKind::CubicBezier {
+                        control_0: previous + (control - previous) * 2.0 / 3.0,
+                        control_1: point_1 + (control - point_1) * 2.0 / 3.0,
+                        point_1,
+                    },
+                    line_width,
+                });
+            }
+            SegmentCommandKind::ArcEllipse {
+                large,
+                sweep,
+                radius_x,
+                radius_y,
+                rotation,
+                target,
+            } if arcs => {
+                let mut curves = Vec::new();
+
+                if let Ok(arc) =
+                    svg_arc(previous, large, sweep, radius_x, radius_y, rotation, target)
+                {
+                    for element in arc.append_iter(ARC_TOLERANCE) {
+                        if let PathEl::CurveTo(control_0, control_1, point_1) = element {
+                            curves.push(SegmentCommandKind::CubicBezier {
+                                control_0,
+                                control_1,
+                                point_1,
+                            });
+                        }
+                    }
+                }
+
+                if curves.is_empty() {
+                    curves.push(SegmentCommandKind::Line { end: target });
+                }
+
+                let mut line_width = line_width;
+                normalized.extend(curves.into_iter().map(|kind| SegmentCommand {
+                    kind,
+                    line_width: line_width.take(),
+                }));
+            }
+            kind => normalized.push(SegmentCommand { kind, line_width }),
+        }
+    }
+
+    segment.commands = normalized;
+}
+
+/// Replace the cubic curves of a segment with lines, expects quadratic curves
+/// and arcs to already be normalized to cubics
+fn flatten_segment(segment: &mut Segment, tolerance: f64) {
+    let start = segment.start;
+    let mut pen = start;
+    let mut flattened = Vec::with_capacity(segment.commands.len());
+
+    for SegmentCommand { kind, line_width } in segment.commands.drain(..) {
+        let previous = pen;
+        pen = kind.end_point(pen, start);
+
+        match kind {
+            SegmentCommandKind::CubicBezier {
+                control_0,
+                control_1,
+                point_1,
+            } => {
+                let mut lines = Vec::new();
+                kurbo::flatten(
+                    [
+                        PathEl::MoveTo(previous),
+                        PathEl::CurveTo(control_0, control_1, point_1),
+                    ],
+                    tolerance,
+                    |element| {
+                        if let PathEl::LineTo(end) = element {
+                            lines.push(SegmentCommandKind::Line { end });
+                        }
+                    },
+                );
+
+                if lines.is_empty() {
+                    lines.push(SegmentCommandKind::Line { end: point_1 });
+                }
+
+                let mut line_width = line_width;
+                flattened.extend(lines.into_iter().map(|kind| SegmentCommand {
+                    kind,
+                    line_width: line_width.take(),
+                }));
+            }
+            kind => flattened.push(SegmentCommand { kind, line_width }),
+        }
+    }
+
+    segment.commands = flattened;
+}
@@ -0,0 +1,409 @@
+//! Tessellation of images into triangle meshes with `lyon`, so that they can
+//! be drawn directly on the GPU, e.g. by applications drawing many icons per
+//! frame. Enabled by the `render-gpu` feature.
+//!
+//! Meshes are independent of any graphics API: upload `Mesh::vertices` and
+//! `Mesh::indices` to a vertex and index buffer of `wgpu`, OpenGL or similar,
+//! and draw them as a triangle list with alpha blending. Positions are in
+//! image units, so the vertex shader maps them to clip space with the
+//! transform of the icon. Triangles are in drawing order, so drawing them in
+//! order without depth testing paints later commands over earlier ones.
+//!
+//! Gradients are not baked into the vertices, since interpolating colors
+//! linearly across triangles is wrong for radial gradients and smears linear
+//! ones. Instead every vertex carries the paint of its triangle, and the
+//! fragment shader evaluates it at the interpolated image position `p`:
+//!
+//! ```text
+//! axis = point_1 - point_0
+//! t = 0                                              if axis is zero
+//! t = dot(p - point_0, axis) / dot(axis, axis)       if kind is Vertex::LINEAR
+//! t = length(p - point_0) / length(axis)             if kind is Vertex::RADIAL
+//! color = mix(color_0, color_1, clamp(t, 0, 1))
+//! ```
+//!
+//! Flat colors have zero gradient points and equal colors, so the same
+//! formula works for every kind. `Vertex::color_at` evaluates it on the CPU.
+
+use kurbo::{BezPath, PathEl, Point, Shape};
+use lyon::math::point;
+use lyon::path::Path;
+use lyon::tessellation::{
+    BuffersBuilder, FillOptions, FillRule, FillTessellator, FillVertex, LineJoin, StrokeOptions,
+    StrokeTessellator, StrokeVertex, VertexBuffers,
+};
+
+use crate::error::{ensure, format_err, Result};
+use crate::format::{Command, Image, OutlineStyle, Style};
+use crate::geometry::has_area;
+use crate::render::{bez_path, path_strokes, polygon_path, ARC_TOLERANCE};
+
+/// Miter limit of strokes, the default of piet so that every backend joins
+/// sharp corners alike
+const MITER_LIMIT: f32 = 10.0;
+
+/// Triangles of a tessellated image, see `Image::tessellate`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    /// Corners of the triangles
+    pub vertices: Vec<Vertex>,
+
+    /// Indices into `vertices`, three per triangle
+    pub indices: Vec<u32>,
+}
+
+/// A corner of a triangle. The layout is fixed, so that vertices can be
+/// copied into a vertex buffer as they are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Vertex {
+    /// Position in image units
+    pub position: [f32; 2],
+
+    /// Color of flat styles, or the color at `point_0` of gradients. Red,
+    /// green, blue and alpha between 0 and 1, not premultiplied.
+    pub color_0: [f32; 4],
+
+    /// Color at `point_1` of gradients, equal to `color_0` for flat styles
+    pub color_1: [f32; 4],
+
+    /// First point of the gradient in image units, zero for flat styles
+    pub point_0: [f32; 2],
+
+    /// Second point of the gradient in image units, zero for flat styles
+    pub point_1: [f32; 2],
+
+    /// `Vertex::FLAT`, `Vertex::LINEAR` or `Vertex::RADIAL`
+    pub kind: u32,
+}
+
+impl Vertex {
+    /// Kind of vertices painted with a flat color
+    pub const FLAT: u32 = 0;
+
+    /// Kind of vertices painted with a linear gradient
+    pub const LINEAR: u32 = 1;
+
+    /// Kind of vertices painted with a radial gradient
+    pub const RADIAL: u32 = 2;
+
+    /// Color of the paint of this vertex at `position` in image units, as the
+    /// fragment shader computes it, see the module documentation
+    pub fn color_at(&self, position: [f32; 2]) -> [f32; 4] {
+        let axis = [
+            self.point_1[0] - self.point_0[0],
+            self.point_1[1] - self.point_0[1],
+        ];
+        let offset = [position[0] - self.point_0[0], position[1] - self.point_0[1]];
+        let axis_length2 = axis[0] * axis[0] + axis[1] * axis[1];
+
+        // Degenerate gradients are drawn with their first color
+        let t = if axis_length2 == 0.0 {
+            0.0
+        } else if self.kind == Vertex::RADIAL {
+            offset[0].hypot(offset[1]) / axis_length2.sqrt()
+        } else {
+            (offset[0] * axis[0] + offset[1] * axis[1]) / axis_length2
+        };
+        let t = t.clamp(0.0, 1.0);
+
+        let mut color = [0.0; 4];
+        for (channel, (c0, c1)) in color.iter_mut().zip(self.color_0.iter().zip(&self.color_1)) {
+            *channel = c0 + (c1 - c0) * t;
+        }
+
+        color
+    }
+}
+
+impl Image {
+    /// Tessellate this image into triangles. Curves are flattened into lines
+    /// no further than `tolerance` image units from the curve, so the
+    /// tolerance should be about a quarter of a pixel at the size the image
+    /// is drawn at.
+    ///
+    /// Vertices carry the style of their triangle rather than a color, so
+    /// the fragment shader has to evaluate gradients, see the module
+    /// documentation.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// use tinyvg::gpu::Vertex;
+    ///
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mesh = image.tessellate(0.05).unwrap();
+    ///
+    /// assert_eq!(mesh.indices.len() % 3, 0);
+    /// assert!(mesh
+    ///     .vertices
+    ///     .iter()
+    ///     .filter(|vertex| vertex.kind == Vertex::FLAT)
+    ///     .all(|vertex| vertex.color_at(vertex.position) == vertex.color_0));
+    /// assert!(mesh
+    ///     .indices
+    ///     .iter()
+    ///     .all(|index| (*index as usize) < mesh.vertices.len()));
+    /// ```
+    pub fn tessellate(&self, tolerance: f64) -> Result<Mesh> {
+        ensure!(
+            tolerance > 0.0,
+            "tessellation tolerance must be positive, got {}",
+            tolerance
+        );
+
+        let mut mesh = MeshBuilder {
+            image: self,
+            tolerance: tolerance as f32,
+            buffers: VertexBuffers::new(),
+            fill: FillTessellator::new(),
+            stroke: StrokeTessellator::new(),
+        };
+
+        for cmd in &self.commands {
+            mesh.command(cmd)?;
+        }
+
+        Ok(Mesh {
+            vertices: mesh.buffers.vertices,
+            indices: mesh.buffers.indices,
+        })
+    }
+}
+
+/// Tessellators and the buffers they append triangles to
+struct MeshBuilder<'a> {
+    image: &'a Image,
+    tolerance: f32,
+    buffers: VertexBuffers<Vertex, u32>,
+    fill: FillTessellator,
+    stroke: StrokeTessellator,
+}
+
+impl MeshBuilder<'_> {
+    fn command(&mut self, cmd: &Command) -> Result<()> {
+        match cmd {
+            Command::FillPath {
+                fill_style,
+                path,
+                outline,
+            } => {
+                self.fill(&bez_path(path)?, fill_style)?;
+
+                if let Some(OutlineStyle {
+                    line_width,
+                    line_style,
+                }) = outline
+                {
+                    for (stroke, line_width) in path_strokes(*line_width, path)? {
+                        self.stroke(&stroke, line_style, line_width)?;
+                    }
+                }
+            }
+            Command::FillRectangles {
+                fill_style,
+                rectangles,
+                outline,
+            } => {
+                for rect in rectangles.iter().filter(|r| has_area(r)) {
+                    let path = rect.to_path(ARC_TOLERANCE);
+
+                    self.fill(&path, fill_style)?;
+                    if let Some(outline) = outline {
+                        self.stroke(&path, &outline.line_style, outline.line_width)?;
+                    }
+                }
+            }
+            Command::FillPolygon {
+                fill_style,
+                polygon,
+                outline,
+            } => {
+                let path = polygon_path(polygon);
+
+                self.fill(&path, fill_style)?;
+                if let Some(outline) = outline {
+                    self.stroke(&path, &outline.line_style, outline.line_width)?;
+                }
+            }
+            Command::DrawLines {
+                line_style,
+                line_width,
+                lines,
+            } => {
+                for line in lines {
+                    self.stroke(&line.to_path(ARC_TOLERANCE), line_style, *line_width)?;
+                }
+            }
+            Command::DrawLineLoop {
+                line_style,
+                line_width,
+                close_path,
+                points,
+            } => {
+                let mut path = polygon_path(points);
+                if *close_path {
+                    path.line_to(points[0]);
+                }
+
+                self.stroke(&path, line_style, *line_width)?;
+            }
+            Command::DrawLinePath {
+                line_style,
+                line_width,
+                path,
+            } => {
+                for (stroke, line_width) in path_strokes(*line_width, path)? {
+                    self.stroke(&stroke, line_style, line_width)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fill(&mut self, path: &BezPath, style: &Style) -> Result<()> {
+        let paint = Paint::new(self.image, style)?;
+        let options = FillOptions::tolerance(self.tolerance).with_fill_rule(FillRule::NonZero);
+
+        self.fill
+            .tessellate_path(
+                &lyon_path(path),
+                &options,
+                &mut BuffersBuilder::new(&mut self.buffers, |vertex: FillVertex| {
+                    paint.vertex(vertex.position())
+                }),
+            )
+            .map_err(|e| format_err!("failed to tessellate fill: {:?}", e))
+    }
+
+    fn stroke(&mut self, path: &BezPath, style: &Style, line_width: f64) -> Result<()> {
+        let paint = Paint::new(self.image, style)?;
+        let options = StrokeOptions::tolerance(self.tolerance)
+            .with_line_width(line_width as f32)
+            .with_line_join(LineJoin::Miter)
+            .with_miter_limit(MITER_LIMIT);
+
+        self.stroke
+            .tessellate_path(
+                &lyon_path(path),
+                &options,
+                &mut BuffersBuilder::new(&mut self.buffers, |vertex: StrokeVertex| {
+                    paint.vertex(vertex.position())
+                }),
+            )
+            .map_err(|e| format_err!("failed to tessellate stroke: {:?}", e))
+    }
+}
+
+/// Paint of a style, copied into every vertex of its triangles
+struct Paint {
+    kind: u32,
+    point_0: [f32; 2],
+    point_1: [f32; 2],
+    color_0: [f32; 4],
+    color_1: [f32; 4],
+}
+
+impl Paint {
+    fn new(image: &Image, style: &Style) -> Result<Self> {
+        let color = |index: usize| -> Result<[f32; 4]> {
+            let (r, g, b, a) = image.color(index)?.as_rgba();
+            Ok([r as f32, g as f32, b as f32, a as f32])
+        };
+        let point = |p: Point| [p.x as f32, p.y as f32];
+
+        Ok(match *style {
+            Style::FlatColor { color_index } => {
+                let color = color(color_index)?;
+                Paint {
+                    kind: Vertex::FLAT,
+                    point_0: [0.0; 2],
+                    point_1: [0.0; 2],
+                    color_0: color,
+                    color_1: color,
+                }
+            }
+            Style::LinearGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => Paint {
+                kind: Vertex::LINEAR,
+                point_0: point(point_0),
+                point_1: point(point_1),
+                color_0: color(color_index_0)?,
+                color_1: color(color_index_1)?,
+            },
+            Style::RadialGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => Paint {
+                kind: Vertex::RADIAL,
+                point_0: point(point_0),
+                point_1: point(point_1),
+                color_0: color(color_index_0)?,
+                color_1: color(color_index_1)?,
+            },
+        })
+    }
+
+    fn vertex(&self, position: lyon::math::Point) -> Vertex {
+        Vertex {
+            position: [position.x, position.y],
+            color_0: self.color_0,
+            color_1: self.color_1,
+            point_0: self.point_0,
+            point_1: self.point_1,
+            kind: self.kind,
+        }
+    }
+}
+
+/// Convert a kurbo path into a lyon path, ending every sub-path explicitly as
+/// lyon requires
+fn lyon_path(path: &BezPath) -> Path {
+    let lyon = |p: Point| point(p.x as f32, p.y as f32);
+
+    let mut builder = Path::builder();
+    let mut open = false;
+
+    for element in path.elements() {
+        match *element {
+            PathEl::MoveTo(p) => {
+                if open {
+                    builder.end(false);
+                }
+                builder.begin(lyon(p));
+                open = true;
+            }
+            PathEl::LineTo(p) => {
+                builder.line_to(lyon(p));
+            }
+            PathEl::QuadTo(control, p) => {
+                builder.quadratic_bezier_to(lyon(control), lyon(p));
+            }
+            PathEl::CurveTo(control_0, control_1, p) => {
+                builder.cubic_bezier_to(lyon(control_0), lyon(control_1), lyon(p));
+            }
+            PathEl::ClosePath => {
+                if open {
+                    builder.end(true);
+                    open = false;
+                }
+            }
+        }
+    }
+
+    if open {
+        builder.end(false);
+    }
+
+    builder.build()
+}
@@ -0,0 +1,377 @@
+//! Clickable areas of an image that link to URLs, stored in the trailer as an
+//! extension record (see the `extension` module), and exported as an HTML
+//! image map or an SVG wrapper around a raster render of the image. This makes
+//! rendered diagrams clickable without shipping the vector image.
+//!
+//! ```
+//! # use tinyvg::{Decoder, Image};
+//! # use tinyvg::format::Rect;
+//! # use tinyvg::hotspot::{html_image_map, svg_link_wrapper, Hotspot, HotspotArea};
+//! let bytes = std::fs::read("data/shield.tvg").unwrap();
+//! let mut image = Decoder::new(bytes.as_slice()).decode().unwrap();
+//!
+//! image
+//!     .set_hotspots(vec![
+//!         Hotspot {
+//!             area: HotspotArea::Command(0),
+//!             url: "https://example.com/shield".to_string(),
+//!             title: Some("Shield".to_string()),
+//!         },
+//!         Hotspot {
+//!             area: HotspotArea::Rect(Rect::new(0.0, 0.0, 12.0, 12.0)),
+//!             url: "https://example.com/corner".to_string(),
+//!             title: None,
+//!         },
+//!     ])
+//!     .unwrap();
+//!
+//! // The hotspots survive encoding
+//! let mut encoded = Vec::new();
+//! image.encode(&mut encoded).unwrap();
+//! let image = Decoder::new(encoded.as_slice()).decode().unwrap();
+//! let hotspots = image.hotspots().unwrap();
+//! assert_eq!(hotspots.len(), 2);
+//!
+//! // Coordinates are scaled to the size of the render
+//! let map = html_image_map(&image, &hotspots, "shield", (48, 48)).unwrap();
+//! assert!(map.contains(r#"<area shape="rect" coords="0,0,24,24" href="https://example.com/corner">"#));
+//! assert!(map.contains(r#"shape="poly""#));
+//!
+//! let svg = svg_link_wrapper(&image, &hotspots, "shield.png", (48, 48)).unwrap();
+//! assert!(svg.contains(r#"<image href="shield.png""#));
+//! assert!(svg.contains(r#"<a href="https://example.com/shield">"#));
+//! ```
+
+use std::fmt::Write;
+
+use kurbo::{PathEl, Point, Rect};
+
+use crate::error::{ensure, format_err, Result};
+use crate::extension::Extension;
+use crate::format::{Command, Image};
+use crate::number::NumberFormat;
+use crate::render::{bez_path, polygon_path};
+use crate::report::escape;
+
+/// Maximum distance between a curve and the polygon that approximates it in
+/// exported maps, in image units
+const TOLERANCE: f64 = 0.1;
+
+/// A clickable area of an image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hotspot {
+    /// Where the hotspot is
+    pub area: HotspotArea,
+
+    /// Target of the link
+    pub url: String,
+
+    /// Text shown for the link, e.g. as a tooltip or alternative text
+    pub title: Option<String>,
+}
+
+/// Area covered by a hotspot, in the coordinates of the image
+#[derive(Debug, Clone, PartialEq)]
+pub enum HotspotArea {
+    /// A rectangle
+    Rect(Rect),
+
+    /// A polygon
+    Polygon(Vec<Point>),
+
+    /// The shape of a command, by index. Fills cover their polygons,
+    /// rectangles or path (with curves approximated by polygons), lines
+    /// cover their bounding box.
+    Command(usize),
+}
+
+/// Every hotspot of an image, stored as an extension record
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hotspots(pub Vec<Hotspot>);
+
+impl Image {
+    /// The hotspots stored in the trailer with `Image::set_hotspots`, or none
+    /// if there aren't any
+    pub fn hotspots(&self) -> Result<Vec<Hotspot>> {
+        Ok(self.extension::<Hotspots>()?.unwrap_or_default().0)
+    }
+
+    /// Store hotspots in the trailer, replacing the ones stored before. Fails
+    /// if a hotspot references a command that doesn't exist, or if the
+    /// trailer holds something other than extensions.
+    pub fn set_hotspots(&mut self, hotspots: Vec<Hotspot>) -> Result<()> {
+        for hotspot in &hotspots {
+            if let HotspotArea::Command(index) = hotspot.area {
+                ensure!(
+                    index < self.commands.len(),
+                    "hotspot for {} references command {}, but the image has {} commands",
+                    hotspot.url,
+                    index,
+                    self.commands.len()
+                );
+            }
+        }
+
+        self.set_extension(&Hotspots(hotspots))
+    }
+}
+
+/// An HTML `<map>` element named `name` with an `<area>` for every hotspot,
+/// for an `<img usemap="#name">` of the image rendered at `width` by `height`
+/// pixels
+pub fn html_image_map(
+    image: &Image,
+    hotspots: &[Hotspot],
+    name: &str,
+    (width, height): (u32, u32),
+) -> Result<String> {
+    let scale_x = width as f64 / image.header.width as f64;
+    let scale_y = height as f64 / image.header.height as f64;
+    let scaled = |points: &mut dyn Iterator<Item = Point>| {
+        points
+            .map(|p| {
+                format!(
+                    "{},{}",
+                    (p.x * scale_x).round() as i64,
+                    (p.y * scale_y).round() as i64
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    let mut html = format!("<map name=\"{}\">\n", escape(name));
+
+    for hotspot in hotspots {
+        for shape in shapes(image, &hotspot.area)? {
+            let (kind, coords) = match shape {
+                Shape::Rect(rect) => (
+                    "rect",
+                    scaled(&mut [rect.origin(), Point::new(rect.x1, rect.y1)].into_iter()),
+                ),
+                Shape::Polygon(points) => ("poly", scaled(&mut points.into_iter())),
+            };
+
+            write!(
+                html,
+                "  <area shape=\"{}\" coords=\"{}\" href=\"{}\"",
+                kind,
+                coords,
+                escape(&hotspot.url)
+            )?;
+            if let Some(title) = &hotspot.title {
+                write!(html, " alt=\"{0}\" title=\"{0}\"", escape(title))?;
+            }
+            html.push_str(">\n");
+        }
+    }
+
+    html.push_str("</map>\n");
+
+    Ok(html)
+}
+
+/// An SVG document that shows the raster image at `href`, rendered at
+/// `width` by `height` pixels, with a transparent link over every hotspot
+pub fn svg_link_wrapper(
+    image: &Image,
+    hotspots: &[Hotspot],
+    href: &str,
+    (width, height): (u32, u32),
+) -> Result<String> {
+    let number = |value: f64| NumberFormat { precision: Some(2) }.format(value);
+    let (image_width, image_height) = (image.header.width, image.header.height);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         viewBox=\"0 0 {} {}\">\n  \
+         <image href=\"{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\"/>\n",
+        width,
+        height,
+        image_width,
+        image_height,
+        escape(href),
+        image_width,
+        image_height,
+    );
+
+    for hotspot in hotspots {
+        writeln!(svg, "  <a href=\"{}\">", escape(&hotspot.url))?;
+        if let Some(title) = &hotspot.title {
+            writeln!(svg, "    <title>{}</title>", escape(title))?;
+        }
+
+        for shape in shapes(image, &hotspot.area)? {
+            match shape {
+                Shape::Rect(rect) => writeln!(
+                    svg,
+                    "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill-opacity=\"0\"/>",
+                    number(rect.x0),
+                    number(rect.y0),
+                    number(rect.width()),
+                    number(rect.height())
+                )?,
+                Shape::Polygon(points) => writeln!(
+                    svg,
+                    "    <polygon points=\"{}\" fill-opacity=\"0\"/>",
+                    points
+                        .iter()
+                        .map(|p| format!("{},{}", number(p.x), number(p.y)))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                )?,
+            }
+        }
+
+        svg.push_str("  </a>\n");
+    }
+
+    svg.push_str("</svg>\n");
+
+    Ok(svg)
+}
+
+/// Shape that an exported hotspot is made of
+enum Shape {
+    Rect(Rect),
+    Polygon(Vec<Point>),
+}
+
+/// The shapes that cover a hotspot area
+fn shapes(image: &Image, area: &HotspotArea) -> Result<Vec<Shape>> {
+    let command = match area {
+        HotspotArea::Rect(rect) => return Ok(vec![Shape::Rect(*rect)]),
+        HotspotArea::Polygon(points) => return Ok(vec![Shape::Polygon(points.clone())]),
+        HotspotArea::Command(index) => image.commands.get(*index).ok_or_else(|| {
+            format_err!("hotspot references command {}, which doesn't exist", index)
+        })?,
+    };
+
+    let path = match command {
+        Command::FillRectangles { rectangles, .. } => {
+            return Ok(rectangles.iter().map(|rect| Shape::Rect(*rect)).collect())
+        }
+        Command::FillPolygon { polygon, .. } => polygon_path(polygon),
+        Command::FillPath { path, .. } => bez_path(path)?,
+        command => return Ok(vec![Shape::Rect(command.bounds())]),
+    };
+
+    // One polygon per subpath, since an area can only have one
+    let mut polygons: Vec<Vec<Point>> = Vec::new();
+    path.flatten(TOLERANCE, |element| match element {
+        PathEl::MoveTo(point) => polygons.push(vec![point]),
+        PathEl::LineTo(point) => {
+            if let Some(polygon) = polygons.last_mut() {
+                polygon.push(point);
+            }
+        }
+        _ => {}
+    });
+
+    Ok(polygons
+        .into_iter()
+        .filter(|polygon| polygon.len() >= 3)
+        .map(Shape::Polygon)
+        .collect())
+}
+
+impl Extension for Hotspots {
+    const KEY: &'static str = "tinyvg.hotspots";
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        let put_u32 = |data: &mut Vec<u8>, value: u32| data.extend(value.to_le_bytes());
+        let put_f32 = |data: &mut Vec<u8>, value: f64| data.extend((value as f32).to_le_bytes());
+
+        for hotspot in &self.0 {
+            match &hotspot.area {
+                HotspotArea::Rect(rect) => {
+                    data.push(0);
+                    for value in [rect.x0, rect.y0, rect.x1, rect.y1] {
+                        put_f32(&mut data, value);
+                    }
+                }
+                HotspotArea::Polygon(points) => {
+                    data.push(1);
+                    put_u32(&mut data, points.len() as u32);
+                    for point in points {
+                        put_f32(&mut data, point.x);
+                        put_f32(&mut data, point.y);
+                    }
+                }
+                HotspotArea::Command(index) => {
+                    data.push(2);
+                    put_u32(&mut data, *index as u32);
+                }
+            }
+
+            for text in [&hotspot.url[..], hotspot.title.as_deref().unwrap_or("")] {
+                put_u32(&mut data, text.len() as u32);
+                data.extend_from_slice(text.as_bytes());
+            }
+        }
+
+        data
+    }
+
+    fn from_bytes(mut data: &[u8]) -> Result<Self> {
+        let mut hotspots = Vec::new();
+        while !data.is_empty() {
+            let area = match take(&mut data, 1)?[0] {
+                0 => HotspotArea::Rect(Rect::new(
+                    read_f32(&mut data)?,
+                    read_f32(&mut data)?,
+                    read_f32(&mut data)?,
+                    read_f32(&mut data)?,
+                )),
+                1 => {
+                    let count = read_u32(&mut data)? as usize;
+                    // Each point takes 8 bytes, so a cut off list fails
+                    // before anything is allocated for it
+                    ensure!(data.len() / 8 >= count, "hotspot is cut off");
+
+                    let points = (0..count)
+                        .map(|_| Ok(Point::new(read_f32(&mut data)?, read_f32(&mut data)?)))
+                        .collect::<Result<_>>()?;
+                    HotspotArea::Polygon(points)
+                }
+                2 => HotspotArea::Command(read_u32(&mut data)? as usize),
+                kind => return Err(format_err!("unknown hotspot area {}", kind)),
+            };
+
+            let url = read_text(&mut data)?;
+            let title = Some(read_text(&mut data)?).filter(|title| !title.is_empty());
+
+            hotspots.push(Hotspot { area, url, title });
+        }
+
+        Ok(Hotspots(hotspots))
+    }
+}
+
+/// Split `len` bytes off the start of `data`
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    ensure!(data.len() >= len, "hotspot is cut off");
+
+    let (taken, rest) = data.split_at(len);
+    *data = rest;
+
+    Ok(taken)
+}
+
+fn read_u32(data: &mut &[u8]) -> Result<u32> {
+    let bytes = take(data, 4)?;
+
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_f32(data: &mut &[u8]) -> Result<f64> {
+    Ok(f32::from_bits(read_u32(data)?) as f64)
+}
+
+fn read_text(data: &mut &[u8]) -> Result<String> {
+    let len = read_u32(data)? as usize;
+    let bytes = take(data, len)?;
+
+    String::from_utf8(bytes.to_vec()).map_err(|e| format_err!("hotspot text is not UTF-8: {}", e))
+}
@@ -0,0 +1,418 @@
+//! Lookup of icons by name in freedesktop.org icon themes, for Linux desktop
+//! apps that want TinyVG files as their icon source. Icons are `.tvg` files in
+//! the directories of a theme, next to the PNG and SVG files of other
+//! toolkits. Enabled by the `icon-theme` feature.
+//!
+//! Lookup follows the [Icon Theme Specification]: the theme, the themes it
+//! inherits from and `hicolor` are searched in that order, each for a
+//! directory of the requested size or else the closest size. Names that
+//! aren't found are retried without their last dash-separated part (so
+//! `edit-copy-symbolic` falls back to `edit-copy`, then `edit`), and finally
+//! looked up directly in the search paths, e.g. `/usr/share/pixmaps`.
+//!
+//! [Icon Theme Specification]: https://specifications.freedesktop.org/icon-theme-spec/latest/
+//!
+//! ```
+//! # use std::path::PathBuf;
+//! # use tinyvg::icon_theme::IconTheme;
+//! let icons = std::env::temp_dir().join("tinyvg-icon-theme-doc");
+//! let scalable = icons.join("example").join("scalable").join("status");
+//! std::fs::create_dir_all(&scalable).unwrap();
+//! std::fs::write(
+//!     icons.join("example").join("index.theme"),
+//!     "[Icon Theme]\n\
+//!      Name=Example\n\
+//!      Directories=scalable/status\n\
+//!      \n\
+//!      [scalable/status]\n\
+//!      Size=16\n\
+//!      MinSize=8\n\
+//!      MaxSize=512\n\
+//!      Type=Scalable\n",
+//! )
+//! .unwrap();
+//! std::fs::copy("data/shield.tvg", scalable.join("security-high.tvg")).unwrap();
+//!
+//! let mut theme = IconTheme::with_search_paths("example", vec![icons.clone()]).unwrap();
+//!
+//! assert_eq!(
+//!     theme.lookup("security-high-symbolic", 32, 1),
+//!     Some(scalable.join("security-high.tvg"))
+//! );
+//! assert_eq!(theme.lookup("no-such-icon", 32, 1), None);
+//!
+//! // Rendered at 32 pixels, or 64 on a display with a scale factor of 2
+//! let pixmap = theme.render("security-high", 32, 2).unwrap();
+//! assert_eq!((pixmap.width, pixmap.height), (64, 64));
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::cache::RenderCache;
+use crate::error::{format_err, Context, Result};
+use crate::render::{RasterImage, RenderOptions, RenderSize};
+
+/// Theme searched after a theme and the themes it inherits from
+const FALLBACK_THEME: &str = "hicolor";
+
+/// Bytes of rendered icons kept by `IconTheme::render`
+const CACHE_BUDGET: usize = 16 * 1024 * 1024;
+
+/// An icon theme and the themes it inherits from. See the module
+/// documentation.
+pub struct IconTheme {
+    search_paths: Vec<PathBuf>,
+    themes: Vec<ThemeIndex>,
+    cache: RenderCache,
+}
+
+/// The parts of a theme's `index.theme` that lookup needs
+struct ThemeIndex {
+    name: String,
+    directories: Vec<ThemeDirectory>,
+}
+
+/// A directory of a theme with icons of one size
+struct ThemeDirectory {
+    path: String,
+    size: u32,
+    scale: u32,
+    kind: DirectoryKind,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+/// How the icons of a directory match requested sizes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DirectoryKind {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+impl IconTheme {
+    /// Load the theme `name` from the standard search paths: `~/.icons`,
+    /// `$XDG_DATA_HOME/icons`, `icons` in every directory of
+    /// `$XDG_DATA_DIRS`, and `/usr/share/pixmaps`
+    pub fn new(name: &str) -> Result<Self> {
+        Self::with_search_paths(name, default_search_paths())
+    }
+
+    /// Load the theme `name` from the given search paths, in order of
+    /// priority. Each path holds theme directories, and icons that don't
+    /// belong to a theme.
+    pub fn with_search_paths(name: &str, search_paths: Vec<PathBuf>) -> Result<Self> {
+        let mut theme = IconTheme {
+            search_paths,
+            themes: Vec::new(),
+            cache: RenderCache::new(CACHE_BUDGET),
+        };
+
+        let index = theme
+            .load_index(name)?
+            .ok_or_else(|| format_err!("icon theme {} not found", name))?;
+
+        // Breadth first, so that a theme's own parents come before theirs
+        let mut pending = vec![index];
+        while !pending.is_empty() {
+            let mut parents = Vec::new();
+
+            for (index, inherits) in pending {
+                theme.themes.push(index);
+
+                for parent in inherits {
+                    let mut seen = theme.themes.iter().chain(parents.iter().map(|(t, _)| t));
+                    if seen.any(|t| t.name == parent) {
+                        continue;
+                    }
+                    if let Some(index) = theme.load_index(&parent)? {
+                        parents.push(index);
+                    }
+                }
+            }
+
+            pending = parents;
+        }
+
+        if !theme.themes.iter().any(|t| t.name == FALLBACK_THEME) {
+            if let Some((index, _)) = theme.load_index(FALLBACK_THEME)? {
+                theme.themes.push(index);
+            }
+        }
+
+        Ok(theme)
+    }
+
+    /// Render icons with `options` instead of the defaults
+    pub fn with_options(mut self, options: RenderOptions) -> Self {
+        self.cache = RenderCache::new(CACHE_BUDGET).with_options(options);
+        self
+    }
+
+    /// Names of the theme and the themes searched after it, in order
+    pub fn themes(&self) -> impl Iterator<Item = &str> {
+        self.themes.iter().map(|theme| theme.name.as_str())
+    }
+
+    /// Path of the `.tvg` file that best matches the icon `name` at `size`
+    /// pixels on a display with a scale factor of `scale`, or `None` if
+    /// there is none, even with the fallbacks
+    pub fn lookup(&self, name: &str, size: u32, scale: u32) -> Option<PathBuf> {
+        let mut name = name;
+
+        loop {
+            let found = self
+                .themes
+                .iter()
+                .find_map(|theme| self.lookup_in_theme(theme, name, size, scale))
+                .or_else(|| self.lookup_unthemed(name));
+
+            if found.is_some() {
+                return found;
+            }
+
+            name = &name[..name.rfind('-')?];
+        }
+    }
+
+    /// Render the icon `name` at `size` pixels on a display with a scale
+    /// factor of `scale`, i.e. at `size * scale` pixels. Renders are cached.
+    pub fn render(&mut self, name: &str, size: u32, scale: u32) -> Result<Arc<RasterImage>> {
+        let path = self.lookup(name, size, scale).ok_or_else(|| {
+            format_err!("icon {} not found in theme {}", name, self.themes[0].name)
+        })?;
+
+        let pixels = size * scale.max(1);
+        self.cache.load(
+            path,
+            RenderSize::Fit {
+                width: pixels,
+                height: pixels,
+            },
+        )
+    }
+
+    /// Forget the rendered icons, e.g. after the theme changed on disk
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    /// The icon in a directory matching the size, or else in the directory
+    /// closest to the size
+    fn lookup_in_theme(
+        &self,
+        theme: &ThemeIndex,
+        name: &str,
+        size: u32,
+        scale: u32,
+    ) -> Option<PathBuf> {
+        let file_name = format!("{}.tvg", name);
+        let file = |directory: &ThemeDirectory| {
+            self.search_paths
+                .iter()
+                .map(|base| {
+                    base.join(&theme.name)
+                        .join(&directory.path)
+                        .join(&file_name)
+                })
+                .find(|path| path.is_file())
+        };
+
+        for directory in &theme.directories {
+            if directory.matches_size(size, scale) {
+                if let Some(path) = file(directory) {
+                    return Some(path);
+                }
+            }
+        }
+
+        theme
+            .directories
+            .iter()
+            .filter_map(|directory| {
+                let path = file(directory)?;
+                Some((directory.size_distance(size, scale), path))
+            })
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, path)| path)
+    }
+
+    /// An icon directly in one of the search paths
+    fn lookup_unthemed(&self, name: &str) -> Option<PathBuf> {
+        self.search_paths
+            .iter()
+            .map(|base| base.join(format!("{}.tvg", name)))
+            .find(|path| path.is_file())
+    }
+
+    /// The index of the theme `name` and the names of the themes it
+    /// inherits from, from the first search path that has the theme
+    fn load_index(&self, name: &str) -> Result<Option<(ThemeIndex, Vec<String>)>> {
+        let path = match self
+            .search_paths
+            .iter()
+            .map(|base| base.join(name).join("index.theme"))
+            .find(|path| path.is_file())
+        {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let text = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+
+        parse_index(name, &text)
+            .wrap_err_with(|| format!("failed to parse {}", path.display()))
+            .map(Some)
+    }
+}
+
+impl ThemeDirectory {
+    /// Whether the directory has icons for exactly this size, from
+    /// `DirectoryMatchesSize` of the specification
+    fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+
+        match self.kind {
+            DirectoryKind::Fixed => self.size == size,
+            DirectoryKind::Scalable => (self.min_size..=self.max_size).contains(&size),
+            DirectoryKind::Threshold => (self.size.saturating_sub(self.threshold)
+                ..=self.size + self.threshold)
+                .contains(&size),
+        }
+    }
+
+    /// How far the size of the directory's icons is from this size in
+    /// pixels, from `DirectorySizeDistance` of the specification
+    fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let pixels = size * scale;
+        let (min, max) = match self.kind {
+            DirectoryKind::Fixed => (self.size, self.size),
+            DirectoryKind::Scalable => (self.min_size, self.max_size),
+            DirectoryKind::Threshold => (
+                self.size.saturating_sub(self.threshold),
+                self.size + self.threshold,
+            ),
+        };
+
+        if pixels < min * self.scale {
+            min * self.scale - pixels
+        } else {
+            pixels.saturating_sub(max * self.scale)
+        }
+    }
+}
+
+/// Parse an `index.theme` file, which is an INI file with an `Icon Theme`
+/// group and a group for every directory
+fn parse_index(name: &str, text: &str) -> Result<(ThemeIndex, Vec<String>)> {
+    let mut groups: HashMap<&str, HashMap<&str, &str>> = HashMap::new();
+    let mut group = None;
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            group = Some(header);
+            groups.entry(header).or_default();
+        } else if let (Some(group), Some((key, value))) = (group, line.split_once('=')) {
+            groups
+                .entry(group)
+                .or_default()
+                .insert(key.trim(), value.trim());
+        }
+    }
+
+    let theme = groups
+        .get("Icon Theme")
+        .ok_or_else(|| format_err!("missing [Icon Theme] group"))?;
+    let list = |key: &str| {
+        theme
+            .get(key)
+            .into_iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    let mut directories = Vec::new();
+    for path in list("Directories")
+        .into_iter()
+        .chain(list("ScaledDirectories"))
+    {
+        // Directories without a group of their own are ignored, like other
+        // implementations do
+        let keys = match groups.get(path.as_str()) {
+            Some(keys) => keys,
+            None => continue,
+        };
+        let number = |key: &str| -> Result<Option<u32>> {
+            keys.get(key)
+                .map(|value| {
+                    value.parse().map_err(|_| {
+                        format_err!("{} of directory {} is not a number: {}", key, path, value)
+                    })
+                })
+                .transpose()
+        };
+
+        let size = number("Size")?.ok_or_else(|| format_err!("directory {} has no Size", path))?;
+        let kind = match keys.get("Type").copied() {
+            Some("Fixed") => DirectoryKind::Fixed,
+            Some("Scalable") => DirectoryKind::Scalable,
+            Some("Threshold") | None => DirectoryKind::Threshold,
+            Some(kind) => return Err(format_err!("directory {} has unknown Type {}", path, kind)),
+        };
+
+        directories.push(ThemeDirectory {
+            size,
+            scale: number("Scale")?.unwrap_or(1),
+            kind,
+            min_size: number("MinSize")?.unwrap_or(size),
+            max_size: number("MaxSize")?.unwrap_or(size),
+            threshold: number("Threshold")?.unwrap_or(2),
+            path,
+        });
+    }
+
+    let index = ThemeIndex {
+        name: name.to_string(),
+        directories,
+    };
+
+    Ok((index, list("Inherits")))
+}
+
+/// Search paths of the specification, in order of priority
+fn default_search_paths() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".local").join("share")));
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    let mut paths = Vec::new();
+    paths.extend(home.map(|home| home.join(".icons")));
+    paths.extend(data_home.map(|dir| dir.join("icons")));
+    paths.extend(
+        data_dirs
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| Path::new(dir).join("icons")),
+    );
+    paths.push(PathBuf::from("/usr/share/pixmaps"));
+
+    paths
+}
@@ -0,0 +1,288 @@
+//! Conversion of other vector formats into TinyVG images, so that assets can
+//! be authored in common tools and shipped as TinyVG.
+
+use usvg::{NodeExt, NodeKind, Paint, PathSegment, Transform, Units};
+
+use crate::error::{Context, Result};
+use crate::format::{
+    Color, Command, Image, OutlineStyle, Point, Segment, SegmentCommand, SegmentCommandKind, Style,
+};
+
+/// Fractional bits of imported coordinates, SVG coordinates are rarely whole
+/// numbers
+const IMPORT_SCALE: u8 = 4;
+
+/// Convert an SVG document into a TinyVG image. The document is parsed and
+/// simplified with `usvg`, which resolves styles, `use` elements, shapes and
+/// transforms into plain paths. Every path becomes a `FillPath`, a
+/// `DrawLinePath` or, if it is both filled and stroked, an outlined
+/// `FillPath`.
+///
+/// TinyVG gradients have exactly two colors, so gradients are imported with
+/// the colors of their first and last stops. Patterns, images, text that
+/// wasn't converted to paths, filters, masks and clip paths are skipped. The
+/// image is sized to the SVG's width and height, rounded up.
+///
+/// ```
+/// # use tinyvg::import::from_svg;
+/// # use tinyvg::format::Command;
+/// let svg = r##"
+///     <svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">
+///         <defs>
+///             <linearGradient id="sky" x1="0" y1="0" x2="0" y2="1">
+///                 <stop offset="0" stop-color="#1e90ff"/>
+///                 <stop offset="1" stop-color="#87cefa"/>
+///             </linearGradient>
+///         </defs>
+///         <rect width="32" height="32" fill="url(#sky)"/>
+///         <circle cx="16" cy="16" r="8" fill="yellow" stroke="orange" stroke-width="2"/>
+///         <path d="M4 28 L28 28" fill="none" stroke="black"/>
+///     </svg>
+/// "##;
+///
+/// let image = from_svg(svg.as_bytes()).unwrap();
+///
+/// assert_eq!((image.header.width, image.header.height), (32, 32));
+/// assert!(matches!(
+///     image.commands.as_slice(),
+///     [
+///         Command::FillPath { outline: None, .. },
+///         Command::FillPath { outline: Some(_), .. },
+///         Command::DrawLinePath { .. },
+///     ]
+/// ));
+///
+/// let mut bytes = Vec::new();
+/// image.encode(&mut bytes).unwrap();
+/// ```
+pub fn from_svg(data: &[u8]) -> Result<Image> {
+    let options = usvg::Options::default();
+    let tree = usvg::Tree::from_data(data, &options.to_ref()).wrap_err("failed to parse SVG")?;
+
+    let svg = tree.svg_node();
+    let mut image = Image::empty(
+        svg.size.width().ceil() as u32,
+        svg.size.height().ceil() as u32,
+    );
+    image.header.scale = IMPORT_SCALE;
+
+    let view_box =
+        usvg::utils::view_box_to_transform(svg.view_box.rect, svg.view_box.aspect, svg.size);
+
+    for node in tree.root().descendants() {
+        if let NodeKind::Path(ref path) = *node.borrow() {
+            if path.visibility != usvg::Visibility::Visible {
+                continue;
+            }
+
+            let mut transform = view_box;
+            transform.append(&node.abs_transform());
+
+            if let Some(command) = path_command(&mut image, path, &transform) {
+                image.commands.push(command);
+            }
+        }
+    }
+
+    image.fit_coordinate_range()?;
+
+    Ok(image)
+}
+
+fn path_command(image: &mut Image, path: &usvg::Path, transform: &Transform) -> Option<Command> {
+    let segments = segments(&path.data, transform);
+    if segments.is_empty() {
+        return None;
+    }
+
+    let fill = path
+        .fill
+        .as_ref()
+        .and_then(|fill| paint_style(image, &fill.paint, fill.opacity.value(), path, transform));
+
+    let stroke = path.stroke.as_ref().and_then(|stroke| {
+        let line_style = paint_style(
+            image,
+            &stroke.paint,
+            stroke.opacity.value(),
+            path,
+            transform,
+        )?;
+
+        Some(OutlineStyle {
+            line_width: stroke.width.value() * transform_scale(transform),
+            line_style,
+        })
+    });
+
+    match (fill, stroke) {
+        (Some(fill_style), outline) => Some(Command::FillPath {
+            fill_style,
+            path: segments,
+            outline,
+        }),
+        (
+            None,
+            Some(OutlineStyle {
+                line_width,
+                line_style,
+            }),
+        ) => Some(Command::DrawLinePath {
+            line_style,
+            line_width,
+            path: segments,
+        }),
+        (None, None) => None,
+    }
+}
+
+/// Split path data into segments at every move, in image coordinates
+fn segments(data: &usvg::PathData, transform: &Transform) -> Vec<Segment> {
+    let point = |x: f64, y: f64| {
+        let (x, y) = transform.apply(x, y);
+        Point::new(x, y)
+    };
+
+    let mut segments: Vec<Segment> = Vec::new();
+    for segment in data.iter() {
+        let kind = match *segment {
+            PathSegment::MoveTo { x, y } => {
+                segments.push(Segment {
+                    start: point(x, y),
+                    commands: Vec::new(),
+                });
+                continue;
+            }
+            PathSegment::LineTo { x, y } => SegmentCommandKind::Line { end: point(x, y) },
+            PathSegment::CurveTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => SegmentCommandKind::CubicBezier {
+                control_0: point(x1, y1),
+                control_1: point(x2, y2),
+                point_1: point(x, y),
+            },
+            PathSegment::ClosePath => SegmentCommandKind::ClosePath,
+        };
+
+        if let Some(segment) = segments.last_mut() {
+            segment.commands.push(SegmentCommand {
+                kind,
+                line_width: None,
+            });
+        }
+    }
+
+    // Segments are stored with at least one command
+    segments.retain(|s| !s.commands.is_empty());
+
+    segments
+}
+
+fn paint_style(
+    image: &mut Image,
+    paint: &Paint,
+    opacity: f64,
+    path: &usvg::Path,
+    transform: &Transform,
+) -> Option<Style> {
+    match paint {
+        Paint::Color(color) => Some(Style::FlatColor {
+            color_index: color_index(image, *color, opacity),
+        }),
+        Paint::LinearGradient(gradient) => {
+            let (color_index_0, color_index_1) = stop_colors(image, &gradient.stops, opacity)?;
+            let gradient_transform = gradient_transform(&gradient.base, path, transform)?;
+            let point = |x, y| {
+                let (x, y) = gradient_transform.apply(x, y);
+                Point::new(x, y)
+            };
+
+            Some(Style::LinearGradient {
+                point_0: point(gradient.x1, gradient.y1),
+                point_1: point(gradient.x2, gradient.y2),
+                color_index_0,
+                color_index_1,
+            })
+        }
+        Paint::RadialGradient(gradient) => {
+            let (color_index_0, color_index_1) = stop_colors(image, &gradient.stops, opacity)?;
+            let gradient_transform = gradient_transform(&gradient.base, path, transform)?;
+            let point = |x, y| {
+                let (x, y) = gradient_transform.apply(x, y);
+                Point::new(x, y)
+            };
+
+            // TinyVG radial gradients are circles through the second point
+            Some(Style::RadialGradient {
+                point_0: point(gradient.cx, gradient.cy),
+                point_1: point(gradient.cx + gradient.r.value(), gradient.cy),
+                color_index_0,
+                color_index_1,
+            })
+        }
+        Paint::Pattern(_) => None,
+    }
+}
+
+/// Transform from gradient coordinates to image coordinates
+fn gradient_transform(
+    gradient: &usvg::BaseGradient,
+    path: &usvg::Path,
+    transform: &Transform,
+) -> Option<Transform> {
+    let mut result = *transform;
+
+    if gradient.units == Units::ObjectBoundingBox {
+        let bbox = path.data.bbox()?.to_rect()?;
+        result.append(&Transform::from_bbox(bbox));
+    }
+
+    result.append(&gradient.transform);
+
+    Some(result)
+}
+
+/// Color indices of the first and last gradient stops
+fn stop_colors(image: &mut Image, stops: &[usvg::Stop], opacity: f64) -> Option<(usize, usize)> {
+    let first = stops.first()?;
+    let last = stops.last()?;
+
+    Some((
+        color_index(image, first.color, opacity * first.opacity.value()),
+        color_index(image, last.color, opacity * last.opacity.value()),
+    ))
+}
+
+/// Index of a color in the color table, adding it if it isn't there yet
+fn color_index(image: &mut Image, color: usvg::Color, opacity: f64) -> usize {
+    let color = Color::rgba8(
+        color.red,
+        color.green,
+        color.blue,
+        (opacity.clamp(0.0, 1.0) * 255.0).round() as u8,
+    );
+
+    match image
+        .color_table
+        .iter()
+        .position(|c| c.as_rgba_u32() == color.as_rgba_u32())
+    {
+        Some(index) => index,
+        None => {
+            image.color_table.push(color);
+            image.color_table.len() - 1
+        }
+    }
+}
+
+/// Factor by which a transform scales lengths, averaged over both axes
+fn transform_scale(transform: &Transform) -> f64 {
+    (transform.a * transform.d - transform.b * transform.c)
+        .abs()
+        .sqrt()
+}
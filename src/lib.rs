@@ -1,11 +1,87 @@
 #![warn(missing_docs)]
 //! Decoder and renderer for the TinyVG vector graphics format
 
+#[cfg(feature = "animation")]
+pub mod animation;
+pub mod banding;
+#[cfg(any(feature = "render-png", feature = "render-skia"))]
+pub mod cache;
+#[cfg(feature = "chart")]
+pub mod chart;
+pub mod color;
+pub mod contact_sheet;
 pub mod decode;
+#[cfg(feature = "async")]
+pub mod decode_async;
+pub mod encode;
+pub mod error;
+pub mod extension;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod format;
+#[cfg(feature = "generate")]
+pub mod generate;
+pub mod geometry;
+#[cfg(feature = "render-gpu")]
+pub mod gpu;
+pub mod hotspot;
+#[cfg(feature = "icon-theme")]
+pub mod icon_theme;
+#[cfg(feature = "import-svg")]
+pub mod import;
+pub mod lint;
+mod morph;
+pub mod number;
+mod overlay;
+pub mod palette;
+pub mod png_source;
+pub mod prelude;
+pub mod preset;
+pub mod progress;
+#[cfg(feature = "render-png")]
+pub mod progressive;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "render-png")]
+mod raster;
 mod render;
+#[cfg(feature = "render-skia")]
+mod render_skia;
+pub mod report;
+#[cfg(feature = "render-png")]
+pub mod scrub;
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+mod snap;
+#[cfg(feature = "decode-stats")]
+pub mod stats;
+pub mod svg;
+#[cfg(feature = "text")]
+pub mod text_format;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+#[cfg(feature = "render-png")]
 pub mod render_helper;
 
+/// The `kurbo` version whose `Point`, `Line` and `Rect` appear in this crate's
+/// API. Use this re-export instead of depending on `kurbo` directly, so that
+/// the versions can't diverge.
+pub use kurbo;
+/// The `piet` version whose `Color` and `RenderContext` appear in this
+/// crate's API. Use this re-export instead of depending on `piet` directly,
+/// so that the versions can't diverge.
+#[cfg(feature = "piet")]
+pub use piet;
+
 pub use decode::Decoder;
+pub use encode::Encoder;
+pub use error::Error;
 pub use format::Image;
+pub use geometry::StrokeScaling;
+pub use overlay::DebugOverlay;
+pub use render::{
+    BackgroundPlate, GradientFallback, LineWidthOverrides, PassOrder, PixelFormat, Pixmap,
+    PrimitiveLimit, RasterImage, RenderOptions, RenderPasses, RenderSelection, RenderSize,
+    RenderStrategy, SnapMode,
+};
@@ -0,0 +1,344 @@
+//! Configurable design lints for TinyVG images. These do not check whether a
+//! file is a valid TinyVG file, but whether it follows the style rules of an
+//! icon set (pixel grid, palette size, stroke widths, safe area).
+
+use std::fmt;
+
+use crate::format::{Command, Image, Point, Rect, Segment, SegmentCommandKind};
+use crate::geometry::has_area;
+
+/// Design rules to check an image against. Every rule is optional, rules set
+/// to `None` are not checked.
+///
+/// ```
+/// # use std::fs::File;
+/// # use tinyvg::Decoder;
+/// # use tinyvg::lint::LintConfig;
+/// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let config = LintConfig {
+///     grid: Some(0.25),
+///     max_colors: Some(2),
+///     ..Default::default()
+/// };
+///
+/// assert!(image.lint(&config).is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LintConfig {
+    /// All coordinates must be multiples of this value, e.g. `0.5` for a half
+    /// pixel grid
+    pub grid: Option<f64>,
+
+    /// Maximum number of colors in the color table
+    pub max_colors: Option<usize>,
+
+    /// Every line width must be one of these values
+    pub line_widths: Option<Vec<f64>>,
+
+    /// All points must be at least this far away from the image edges. Stroke
+    /// widths are not taken into account.
+    pub safe_area_inset: Option<f64>,
+
+    /// Report rectangles with a width or height of zero or less. They are
+    /// valid in the binary format, but are not drawn.
+    pub empty_rectangles: bool,
+}
+
+/// A single violation of a rule in `LintConfig`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lint {
+    /// A coordinate of a command is not on the grid
+    OffGrid {
+        /// Index of the offending command
+        command: usize,
+
+        /// First coordinate of the command that is off the grid
+        value: f64,
+    },
+
+    /// The color table has more colors than allowed
+    TooManyColors {
+        /// Number of colors in the color table
+        count: usize,
+
+        /// Maximum number of colors allowed by the config
+        max: usize,
+    },
+
+    /// A command uses a line width that is not allowed
+    LineWidth {
+        /// Index of the offending command
+        command: usize,
+
+        /// First line width of the command that is not allowed
+        width: f64,
+    },
+
+    /// A point of a command is outside of the safe area
+    OutsideSafeArea {
+        /// Index of the offending command
+        command: usize,
+
+        /// First point of the command that is outside of the safe area
+        point: Point,
+    },
+
+    /// A rectangle of a command has a width or height of zero or less
+    EmptyRectangle {
+        /// Index of the offending command
+        command: usize,
+
+        /// First rectangle of the command without area
+        rectangle: Rect,
+    },
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::OffGrid { command, value } => {
+                write!(f, "command {}: coordinate {} is off the grid", command, value)
+            }
+            Lint::TooManyColors { count, max } => {
+                write!(f, "image uses {} colors, at most {} are allowed", count, max)
+            }
+            Lint::LineWidth { command, width } => {
+                write!(f, "command {}: line width {} is not allowed", command, width)
+            }
+            Lint::OutsideSafeArea { command, point } => write!(
+                f,
+                "command {}: point ({}, {}) is outside of the safe area",
+                command, point.x, point.y
+            ),
+            Lint::EmptyRectangle { command, rectangle } => write!(
+                f,
+                "command {}: rectangle at ({}, {}) with size {} x {} has no area",
+                command,
+                rectangle.x0,
+                rectangle.y0,
+                rectangle.width(),
+                rectangle.height()
+            ),
+        }
+    }
+}
+
+const EPSILON: f64 = 1e-6;
+
+impl Image {
+    /// Check this image against the given design rules. Returns every
+    /// violation that was found, in command order. At most one violation of
+    /// each kind is reported per command.
+    pub fn lint(&self, config: &LintConfig) -> Vec<Lint> {
+        let mut lints = Vec::new();
+
+        if let Some(max) = config.max_colors {
+            if self.color_table.len() > max {
+                lints.push(Lint::TooManyColors {
+                    count: self.color_table.len(),
+                    max,
+                });
+            }
+        }
+
+        let width = self.header.width as f64;
+        let height = self.header.height as f64;
+
+        for (index, command) in self.commands.iter().enumerate() {
+            if let Some(grid) = config.grid {
+                let mut off_grid = None;
+                for_each_coordinate(command, |coordinate| {
+                    for value in coordinate.values() {
+                        let steps = value / grid;
+                        if off_grid.is_none() && (steps - steps.round()).abs() > EPSILON {
+                            off_grid = Some(value);
+                        }
+                    }
+                });
+
+                if let Some(value) = off_grid {
+                    lints.push(Lint::OffGrid {
+                        command: index,
+                        value,
+                    });
+                }
+            }
+
+            if let Some(allowed) = &config.line_widths {
+                let width = line_widths(command)
+                    .into_iter()
+                    .find(|w| !allowed.iter().any(|a| (a - w).abs() < EPSILON));
+
+                if let Some(width) = width {
+                    lints.push(Lint::LineWidth {
+                        command: index,
+                        width,
+                    });
+                }
+            }
+
+            if let Some(inset) = config.safe_area_inset {
+                let safe_x = (inset - EPSILON)..=(width - inset + EPSILON);
+                let safe_y = (inset - EPSILON)..=(height - inset + EPSILON);
+                let inside_x = |x: f64| safe_x.contains(&x);
+                let inside_y = |y: f64| safe_y.contains(&y);
+
+                let mut outside = None;
+                for_each_coordinate(command, |coordinate| {
+                    let point = match coordinate {
+                        Coordinate::Point(p) if !(inside_x(p.x) && inside_y(p.y)) => p,
+                        Coordinate::X(x, y) if !inside_x(x) => Point { x, y },
+                        Coordinate::Y(x, y) if !inside_y(y) => Point { x, y },
+                        _ => return,
+                    };
+
+                    if outside.is_none() {
+                        outside = Some(point);
+                    }
+                });
+
+                if let Some(point) = outside {
+                    lints.push(Lint::OutsideSafeArea {
+                        command: index,
+                        point,
+                    });
+                }
+            }
+
+            if config.empty_rectangles {
+                if let Command::FillRectangles { rectangles, .. } = command {
+                    if let Some(rectangle) = rectangles.iter().find(|r| !has_area(r)) {
+                        lints.push(Lint::EmptyRectangle {
+                            command: index,
+                            rectangle: *rectangle,
+                        });
+                    }
+                }
+            }
+        }
+
+        lints
+    }
+}
+
+/// A coordinate value found in a command. Horizontal and vertical lines only
+/// set one of the axes, the other one is carried along from the pen position
+/// so that it can be reported.
+enum Coordinate {
+    Point(Point),
+    X(f64, f64),
+    Y(f64, f64),
+    Length(f64),
+}
+
+impl Coordinate {
+    fn values(&self) -> Vec<f64> {
+        match *self {
+            Coordinate::Point(p) => vec![p.x, p.y],
+            Coordinate::X(x, _) => vec![x],
+            Coordinate::Y(_, y) => vec![y],
+            Coordinate::Length(l) => vec![l],
+        }
+    }
+}
+
+fn for_each_coordinate(command: &Command, mut f: impl FnMut(Coordinate)) {
+    match command {
+        Command::FillPolygon { polygon, .. } => {
+            for p in polygon {
+                f(Coordinate::Point(*p));
+            }
+        }
+        Command::FillRectangles { rectangles, .. } => {
+            for r in rectangles {
+                f(Coordinate::Point(r.origin()));
+                f(Coordinate::Point(Point { x: r.x1, y: r.y1 }));
+            }
+        }
+        Command::DrawLines { lines, .. } => {
+            for l in lines {
+                f(Coordinate::Point(l.p0));
+                f(Coordinate::Point(l.p1));
+            }
+        }
+        Command::DrawLineLoop { points, .. } => {
+            for p in points {
+                f(Coordinate::Point(*p));
+            }
+        }
+        Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } => {
+            for segment in path {
+                path_coordinates(segment, &mut f);
+            }
+        }
+    }
+}
+
+fn path_coordinates(segment: &Segment, f: &mut impl FnMut(Coordinate)) {
+    let mut pen = segment.start;
+    f(Coordinate::Point(pen));
+
+    for command in &segment.commands {
+        match &command.kind {
+            SegmentCommandKind::Line { end } => f(Coordinate::Point(*end)),
+            SegmentCommandKind::HorizontalLine { x } => f(Coordinate::X(*x, pen.y)),
+            SegmentCommandKind::VerticalLine { y } => f(Coordinate::Y(pen.x, *y)),
+            SegmentCommandKind::CubicBezier {
+                control_0,
+                control_1,
+                point_1,
+            } => {
+                f(Coordinate::Point(*control_0));
+                f(Coordinate::Point(*control_1));
+                f(Coordinate::Point(*point_1));
+            }
+            SegmentCommandKind::ArcCircle { radius, target, .. } => {
+                f(Coordinate::Length(*radius));
+                f(Coordinate::Point(*target));
+            }
+            SegmentCommandKind::ArcEllipse {
+                radius_x,
+                radius_y,
+                target,
+                ..
+            } => {
+                f(Coordinate::Length(*radius_x));
+                f(Coordinate::Length(*radius_y));
+                f(Coordinate::Point(*target));
+            }
+            SegmentCommandKind::ClosePath => {}
+            SegmentCommandKind::QuadraticBezier { control, point_1 } => {
+                f(Coordinate::Point(*control));
+                f(Coordinate::Point(*point_1));
+            }
+        }
+
+        pen = command.kind.end_point(pen, segment.start);
+    }
+}
+
+fn line_widths(command: &Command) -> Vec<f64> {
+    let mut widths = Vec::new();
+
+    match command {
+        Command::FillPolygon { outline, .. }
+        | Command::FillRectangles { outline, .. }
+        | Command::FillPath { outline, .. } => {
+            widths.extend(outline.as_ref().map(|o| o.line_width));
+        }
+        Command::DrawLines { line_width, .. }
+        | Command::DrawLineLoop { line_width, .. }
+        | Command::DrawLinePath { line_width, .. } => widths.push(*line_width),
+    }
+
+    if let Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } = command {
+        for segment in path {
+            widths.extend(segment.commands.iter().filter_map(|c| c.line_width));
+        }
+    }
+
+    widths
+}
@@ -1,24 +1,1006 @@
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
 
-use eyre::Result;
+use eyre::{eyre, Context, Result};
+use structopt::clap::{ErrorKind, Shell};
 use structopt::StructOpt;
+use tinyvg::palette::{parse_palette, write_palette};
+use tinyvg::preset::{Length, OutputFormat, Preset};
+use tinyvg::progress::{run_batch, BatchOptions, ProgressEvent};
+use tinyvg::Decoder;
 
-/// TinyVG to PNG renderer
+/// TinyVG to PNG renderer and file tools
 #[derive(StructOpt)]
-struct Options {
+enum Options {
+    /// Render a TinyVG file to PNG
+    Render(RenderArgs),
+
+    /// Recover the TinyVG source file embedded in a PNG rendered with
+    /// `tinyvg render --embed-source`
+    Extract {
+        /// Optional output path. If not specified, uses the input path with a
+        /// `.tvg` suffix.
+        #[structopt(short)]
+        output: Option<PathBuf>,
+
+        /// Input path to PNG file
+        input: PathBuf,
+    },
+
+    /// Write an HTML report with header, statistics, lints, palette and a
+    /// preview of a TinyVG file
+    Report {
+        /// Optional output path. If not specified, uses the input path with a
+        /// `.html` suffix.
+        #[structopt(short)]
+        output: Option<PathBuf>,
+
+        /// Input path to TinyVG binary file
+        input: PathBuf,
+    },
+
+    /// Read or replace the trailer data stored after the end of a TinyVG image
+    Trailer(TrailerCommand),
+
+    /// Share one color table between TinyVG files, e.g. to theme an icon pack
+    Palette(PaletteCommand),
+
+    /// Render TinyVG files as the frames of an animated PNG or GIF
+    #[cfg(feature = "animation")]
+    Animate {
+        /// Time each frame is shown, in milliseconds. With `--fps`, the time
+        /// between keyframes.
+        #[structopt(long, default_value = "100")]
+        delay: u64,
+
+        /// Treat the inputs as keyframes and interpolate frames between them
+        /// at this many frames per second. Consecutive inputs must have the
+        /// same commands.
+        #[structopt(long)]
+        fps: Option<u32>,
+
+        /// Output format, png for animated PNG or gif. If not specified, it is
+        /// picked from the extension of the output path.
+        #[structopt(long, possible_values = &["png", "apng", "gif"])]
+        format: Option<tinyvg::animation::AnimationFormat>,
+
+        /// Output path
+        #[structopt(short)]
+        output: PathBuf,
+
+        /// Input paths to TinyVG binary files, one per frame in order
+        #[structopt(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+
+    /// Decode, encode and decode again every `.tvg` file in a directory and
+    /// its subdirectories, and report files that don't come back the same
+    VerifyRoundtrip {
+        /// Also require the encoded file to be byte-for-byte identical to the
+        /// input
+        #[structopt(long)]
+        bytes: bool,
+
+        /// Input path to a directory, or to a single TinyVG binary file
+        input: PathBuf,
+    },
+
+    /// Shrink an input that crashes a fuzz target (see `fuzz/`) to a smaller
+    /// input that still crashes it
+    CorpusMinimize {
+        /// Fuzz target that the input crashes
+        #[structopt(long, default_value = "render", possible_values = FuzzTarget::NAMES)]
+        target: FuzzTarget,
+
+        /// Optional output path. If not specified, uses the input path with a
+        /// `.min` suffix.
+        #[structopt(short)]
+        output: Option<PathBuf>,
+
+        /// Input path to the crashing input
+        input: PathBuf,
+    },
+
+    /// Write a completion script for a shell to stdout, e.g.
+    /// `tinyvg completions zsh > _tinyvg`
+    Completions {
+        /// Shell to generate the completions for
+        #[structopt(possible_values = &Shell::variants(), case_insensitive = true)]
+        shell: Shell,
+    },
+
+    /// Write a man page for tinyvg and all of its subcommands, e.g.
+    /// `tinyvg man > tinyvg.1`
+    Man {
+        /// Optional output path. If not specified, the man page is written to
+        /// stdout.
+        #[structopt(short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(StructOpt)]
+struct RenderArgs {
     /// Optional output path. If not specified, uses the input path with a
-    /// `.png` suffix.
-    #[structopt(short)]
+    /// `.png` suffix (or the suffix of the output format).
+    #[structopt(short, conflicts_with = "recursive")]
     output: Option<PathBuf>,
 
-    /// Input path to TinyVG binary file
+    /// Named export preset, one of web-thumbnail, favicon or print-pdf
+    #[structopt(long, possible_values = Preset::NAMES)]
+    preset: Option<String>,
+
+    /// Output format, overrides the format of the preset
+    #[structopt(long, possible_values = &["png", "pdf", "eps", "jpg", "jpeg", "webp", "bmp", "tga"])]
+    format: Option<OutputFormat>,
+
+    /// Width of the output in pixels (512 or 512px), relative to the image
+    /// (2x or 150%), or as a physical size (30mm@300dpi). If no height is
+    /// given, the aspect ratio is kept. Overrides the size of the preset.
+    #[structopt(long)]
+    width: Option<Length>,
+
+    /// Height of the output, like `--width`
+    #[structopt(long)]
+    height: Option<Length>,
+
+    /// Scale of the output relative to the image, e.g. 2, 2x or 150%
+    #[structopt(long, parse(try_from_str = parse_scale), conflicts_with_all = &["width", "height"])]
+    scale: Option<f64>,
+
+    /// Embed the TinyVG source file in the PNG, so it can be recovered
+    /// with `tinyvg extract`
+    #[structopt(long)]
+    embed_source: bool,
+
+    /// Render every `.tvg` file in the input directory and its
+    /// subdirectories
+    #[structopt(short, long)]
+    recursive: bool,
+
+    /// Output directory for `--recursive`, the directory structure of the
+    /// input is mirrored in it. If not specified, outputs are written next to
+    /// the inputs.
+    #[structopt(short = "d", long, requires = "recursive")]
+    output_dir: Option<PathBuf>,
+
+    /// Number of files rendered in parallel with `--recursive`
+    #[structopt(short, long, default_value = "1")]
+    jobs: usize,
+
+    /// Keep rendering the remaining files with `--recursive` after a file
+    /// fails, instead of stopping at the first failure. Failed files are
+    /// listed at the end either way.
+    #[structopt(short, long, requires = "recursive")]
+    keep_going: bool,
+
+    /// File name of each output with `--recursive`, e.g.
+    /// `{stem}_{size}px.{ext}`. Placeholders are `{stem}` (input file name
+    /// without extension), `{ext}` (extension of the output format),
+    /// `{preset}` (preset name, or `default`), `{width}` and `{height}` (of
+    /// the output) and `{size}` (longer side of the output).
+    #[structopt(long, requires = "recursive")]
+    name_template: Option<String>,
+
+    /// Input path to TinyVG binary file, or a directory with `--recursive`
     input: PathBuf,
 }
 
+#[derive(StructOpt)]
+enum TrailerCommand {
+    /// Write the trailer bytes of a TinyVG file to stdout
+    Get {
+        /// Optional output path. If not specified, the trailer is written to
+        /// stdout.
+        #[structopt(short)]
+        output: Option<PathBuf>,
+
+        /// Input path to TinyVG binary file
+        input: PathBuf,
+    },
+
+    /// Replace the trailer bytes of a TinyVG file. The image content is kept
+    /// byte-for-byte.
+    Set {
+        /// New trailer data. Prefix with `@` to read the data from a file,
+        /// e.g. `--data @meta.bin`.
+        #[structopt(long)]
+        data: String,
+
+        /// Optional output path. If not specified, the input file is
+        /// overwritten.
+        #[structopt(short)]
+        output: Option<PathBuf>,
+
+        /// Input path to TinyVG binary file
+        input: PathBuf,
+    },
+}
+
+#[derive(StructOpt)]
+enum PaletteCommand {
+    /// Merge the colors of every input into one palette file, and rewrite
+    /// the inputs to use that palette as their color table
+    Extract {
+        /// Path of the palette file to write
+        #[structopt(short)]
+        output: PathBuf,
+
+        /// Input paths to TinyVG binary files, overwritten in place
+        #[structopt(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+
+    /// Replace the color table of every input with a palette file, e.g. a
+    /// themed copy of an extracted palette
+    Apply {
+        /// Path of the palette file to read
+        #[structopt(long)]
+        palette: PathBuf,
+
+        /// Input paths to TinyVG binary files, overwritten in place
+        #[structopt(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+}
+
 fn main() -> Result<()> {
-    let opts = Options::from_args();
+    match parse_options() {
+        Options::Render(args) => render(args)?,
+        Options::Extract { output, input } => extract(input, output)?,
+        Options::Report { output, input } => report(input, output)?,
+        Options::Trailer(TrailerCommand::Get { output, input }) => trailer_get(input, output)?,
+        Options::Trailer(TrailerCommand::Set {
+            data,
+            output,
+            input,
+        }) => trailer_set(input, data, output)?,
+        Options::Palette(PaletteCommand::Extract { output, inputs }) => {
+            palette_extract(inputs, output)?
+        }
+        Options::Palette(PaletteCommand::Apply { palette, inputs }) => {
+            palette_apply(inputs, palette)?
+        }
+        #[cfg(feature = "animation")]
+        Options::Animate {
+            delay,
+            fps,
+            format,
+            output,
+            inputs,
+        } => animate(inputs, output, format, delay, fps)?,
+        Options::VerifyRoundtrip { bytes, input } => verify_roundtrip(input, bytes)?,
+        Options::CorpusMinimize {
+            target,
+            output,
+            input,
+        } => corpus_minimize(input, output, target)?,
+        Options::Completions { shell } => {
+            Options::clap().gen_completions_to("tinyvg", shell, &mut std::io::stdout())
+        }
+        Options::Man { output } => man(output)?,
+    }
 
-    tinyvg::render_helper::render(opts.input, opts.output)?;
+    Ok(())
+}
+
+/// Parse the command line, accepting `tinyvg [-o <output>] <input>` from
+/// before the subcommands as a deprecated form of `tinyvg render`
+fn parse_options() -> Options {
+    let args: Vec<_> = std::env::args_os().collect();
+
+    match Options::from_iter_safe(&args) {
+        Ok(options) => options,
+        Err(e)
+            if matches!(
+                e.kind,
+                ErrorKind::UnrecognizedSubcommand | ErrorKind::UnknownArgument
+            ) =>
+        {
+            let mut render_args = args.clone();
+            render_args.insert(1, "render".into());
+
+            match Options::from_iter_safe(&render_args) {
+                Ok(options) => {
+                    eprintln!(
+                        "warning: `tinyvg <input>` is deprecated, use `tinyvg render <input>`"
+                    );
+                    options
+                }
+                Err(_) => e.exit(),
+            }
+        }
+        Err(e) => e.exit(),
+    }
+}
+
+fn render(args: RenderArgs) -> Result<()> {
+    let mut preset = match &args.preset {
+        Some(name) => Preset::from_name(name).ok_or_else(|| eyre!("unknown preset {}", name))?,
+        None => Preset::default(),
+    };
+
+    if let Some(format) = args.format {
+        preset.format = format;
+    }
+
+    if let Some(scale) = args.scale {
+        preset.width = Some(Length::Scale(scale));
+        preset.height = Some(Length::Scale(scale));
+    }
+
+    if args.width.is_some() || args.height.is_some() {
+        preset.width = args.width;
+        preset.height = args.height;
+    }
+
+    if !args.recursive {
+        return Ok(tinyvg::render_helper::render_with_preset(
+            args.input,
+            args.output,
+            &preset,
+            args.embed_source,
+        )?);
+    }
+
+    if let Some(template) = &args.name_template {
+        // Fail before rendering anything if the template is invalid
+        expand_name_template(template, "stem", "preset", "ext", (1, 1))?;
+    }
+
+    let mut inputs = Vec::new();
+    find_tvg_files(&args.input, &mut inputs)?;
+    inputs.sort();
+
+    let options = BatchOptions {
+        jobs: args.jobs,
+        keep_going: args.keep_going,
+    };
+    let (events, receiver) = mpsc::channel();
+    let summary = run_batch(&inputs, &options, &events, |input| {
+        render_into_tree(&args, &preset, input).map_err(tinyvg::Error::other)
+    });
+    drop(events);
+
+    let mut failures: Vec<_> = receiver
+        .into_iter()
+        .filter_map(|event| match event {
+            ProgressEvent::Failed { file, error } => Some((file, eyre::Report::new(error))),
+            _ => None,
+        })
+        .collect();
+    failures.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (input, e) in &failures {
+        eprintln!("{}: {:#}", input.display(), e);
+    }
+
+    if summary.skipped > 0 {
+        println!(
+            "rendered {} files, {} failed, {} skipped after the first failure (use --keep-going to render them)",
+            summary.succeeded,
+            summary.failed,
+            summary.skipped
+        );
+    } else {
+        println!(
+            "rendered {} files, {} failed",
+            summary.succeeded, summary.failed
+        );
+    }
+
+    if !failures.is_empty() {
+        return Err(eyre!("failed to render {} files", failures.len()));
+    }
+
+    Ok(())
+}
+
+/// Parse the argument of `--scale`, where plain numbers are factors
+fn parse_scale(s: &str) -> Result<f64> {
+    let s = s.trim();
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return parse_scale(&format!("{}x", s));
+    }
+
+    match s.parse::<Length>()? {
+        Length::Scale(scale) => Ok(scale),
+        Length::Pixels(_) => Err(eyre!(
+            "expected a factor like 2x or a percentage like 150%, found {}",
+            s
+        )),
+    }
+}
+
+/// Render one file found by `render --recursive`, mirroring its location
+/// relative to the input directory in the output directory
+fn render_into_tree(args: &RenderArgs, preset: &Preset, input: &Path) -> Result<()> {
+    let mut output = match &args.output_dir {
+        Some(output_dir) => {
+            let relative = input.strip_prefix(&args.input)?;
+            let output = output_dir
+                .join(relative)
+                .with_extension(preset.format.extension());
+
+            if let Some(parent) = output.parent() {
+                std::fs::create_dir_all(parent).wrap_err("failed to create output directory")?;
+            }
+
+            Some(output)
+        }
+        None => None,
+    };
+
+    if let Some(template) = &args.name_template {
+        let image = Decoder::new(BufReader::new(File::open(input)?)).decode_header()?;
+        let size = preset.output_size(image.header.width, image.header.height);
+
+        let stem = input
+            .file_stem()
+            .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let name = expand_name_template(
+            template,
+            &stem,
+            args.preset.as_deref().unwrap_or("default"),
+            preset.format.extension(),
+            size,
+        )?;
+
+        let default_output = output.unwrap_or_else(|| input.to_owned());
+        output = Some(default_output.with_file_name(name));
+    }
+
+    Ok(tinyvg::render_helper::render_with_preset(
+        input,
+        output,
+        preset,
+        args.embed_source,
+    )?)
+}
+
+/// Output file name for `--name-template`
+fn expand_name_template(
+    template: &str,
+    stem: &str,
+    preset: &str,
+    ext: &str,
+    (width, height): (u32, u32),
+) -> Result<String> {
+    let mut name = String::new();
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        name.push_str(&rest[..open]);
+
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| eyre!("unclosed placeholder in name template {:?}", template))?
+            + open;
+
+        match &rest[open + 1..close] {
+            "stem" => name.push_str(stem),
+            "preset" => name.push_str(preset),
+            "ext" => name.push_str(ext),
+            "width" => name.push_str(&width.to_string()),
+            "height" => name.push_str(&height.to_string()),
+            "size" => name.push_str(&width.max(height).to_string()),
+            placeholder => {
+                return Err(eyre!(
+                    "unknown placeholder {{{}}} in name template, expected one of {{stem}}, \
+                     {{ext}}, {{preset}}, {{width}}, {{height}} or {{size}}",
+                    placeholder
+                ))
+            }
+        }
+
+        rest = &rest[close + 1..];
+    }
+    name.push_str(rest);
+
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(eyre!(
+            "name template {:?} must produce a file name without directories",
+            template
+        ));
+    }
+
+    Ok(name)
+}
+
+fn find_tvg_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        std::fs::read_dir(dir).wrap_err_with(|| format!("failed to read {}", dir.display()))?;
+
+    for entry in entries {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            find_tvg_files(&path, files)?;
+        } else if path.extension().is_some_and(|e| e == "tvg") {
+            files.push(path);
+        }
+    }
 
     Ok(())
 }
+
+fn extract(input: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let png = std::fs::read(&input).wrap_err("failed to read input file")?;
+
+    let source = tinyvg::png_source::extract_source(&png)?
+        .ok_or_else(|| eyre!("{} does not contain a TinyVG source", input.display()))?;
+
+    let output = output.unwrap_or_else(|| input.with_extension("tvg"));
+    std::fs::write(output, source).wrap_err("failed to write output file")?;
+
+    Ok(())
+}
+
+fn report(input: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let source = std::fs::read(&input).wrap_err("failed to read input file")?;
+
+    let html = tinyvg::report::html_report(&source, &Default::default())?;
+
+    let output = output.unwrap_or_else(|| input.with_extension("html"));
+    std::fs::write(output, html).wrap_err("failed to write output file")?;
+
+    Ok(())
+}
+
+fn trailer_get(input: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let image = Decoder::new(BufReader::new(File::open(&input)?)).decode()?;
+
+    match output {
+        Some(path) => std::fs::write(path, &image.trailer).wrap_err("failed to write trailer")?,
+        None => std::io::stdout()
+            .write_all(&image.trailer)
+            .wrap_err("failed to write trailer")?,
+    }
+
+    Ok(())
+}
+
+fn trailer_set(input: PathBuf, data: String, output: Option<PathBuf>) -> Result<()> {
+    let bytes = std::fs::read(&input).wrap_err("failed to read input file")?;
+
+    // Everything that is not part of the trailer is copied over untouched, so
+    // the image content does not go through a decode/encode cycle
+    let image = Decoder::new(bytes.as_slice()).decode()?;
+    let content_len = bytes.len() - image.trailer.len();
+
+    let data = match data.strip_prefix('@') {
+        Some(path) => std::fs::read(path)
+            .wrap_err_with(|| format!("failed to read trailer data from {}", path))?,
+        None => data.into_bytes(),
+    };
+
+    let mut out = bytes[..content_len].to_vec();
+    out.extend(data);
+
+    std::fs::write(output.unwrap_or(input), out).wrap_err("failed to write output file")?;
+
+    Ok(())
+}
+
+fn palette_extract(inputs: Vec<PathBuf>, output: PathBuf) -> Result<()> {
+    let mut images = inputs
+        .iter()
+        .map(|input| decode_file(input))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut palette = Vec::new();
+    for image in &mut images {
+        image.externalize_palette(&mut palette);
+    }
+
+    // Nothing is written until every input is decoded, so that a broken
+    // input doesn't leave the pack half converted
+    std::fs::write(&output, write_palette(&palette)).wrap_err("failed to write palette")?;
+
+    for (image, input) in images.iter_mut().zip(&inputs) {
+        image.apply_external_palette(&palette)?;
+        encode_file(image, input)?;
+    }
+
+    Ok(())
+}
+
+fn palette_apply(inputs: Vec<PathBuf>, palette: PathBuf) -> Result<()> {
+    let text = std::fs::read_to_string(&palette).wrap_err("failed to read palette")?;
+    let palette = parse_palette(&text).wrap_err("failed to parse palette")?;
+
+    let mut images = inputs
+        .iter()
+        .map(|input| decode_file(input))
+        .collect::<Result<Vec<_>>>()?;
+
+    for (image, input) in images.iter_mut().zip(&inputs) {
+        image
+            .apply_external_palette(&palette)
+            .wrap_err_with(|| format!("failed to apply palette to {}", input.display()))?;
+    }
+
+    for (image, input) in images.iter().zip(&inputs) {
+        encode_file(image, input)?;
+    }
+
+    Ok(())
+}
+
+fn decode_file(path: &Path) -> Result<tinyvg::Image> {
+    Decoder::new(BufReader::new(File::open(path)?))
+        .decode()
+        .wrap_err_with(|| format!("failed to decode {}", path.display()))
+}
+
+fn encode_file(image: &tinyvg::Image, path: &Path) -> Result<()> {
+    let mut bytes = Vec::new();
+    image
+        .encode(&mut bytes)
+        .wrap_err_with(|| format!("failed to encode {}", path.display()))?;
+
+    std::fs::write(path, bytes).wrap_err_with(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(feature = "animation")]
+fn animate(
+    inputs: Vec<PathBuf>,
+    output: PathBuf,
+    format: Option<tinyvg::animation::AnimationFormat>,
+    delay: u64,
+    fps: Option<u32>,
+) -> Result<()> {
+    use std::time::Duration;
+    use tinyvg::animation::{write_animation, Easing, Timeline};
+
+    let format = match format {
+        Some(format) => format,
+        None => output
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .ok_or_else(|| eyre!("output path has no extension, use --format"))?
+            .parse()?,
+    };
+
+    let frames = inputs
+        .iter()
+        .map(|input| decode_file(input))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut file =
+        std::io::BufWriter::new(File::create(&output).wrap_err("failed to create output file")?);
+    match fps {
+        Some(fps) => {
+            let mut frames = frames.into_iter();
+            let first = frames
+                .next()
+                .ok_or_else(|| eyre!("an animation needs at least one frame"))?;
+
+            let timeline = frames.fold(Timeline::new(first), |timeline, keyframe| {
+                timeline.then(Duration::from_millis(delay), keyframe, Easing::EaseInOut)
+            });
+            timeline.write_animation(fps, format, &mut file)?;
+        }
+        None => write_animation(&frames, Duration::from_millis(delay), format, &mut file)?,
+    }
+    file.flush().wrap_err("failed to write output file")?;
+
+    Ok(())
+}
+
+fn verify_roundtrip(input: PathBuf, bytes: bool) -> Result<()> {
+    let mut files = Vec::new();
+    if input.is_dir() {
+        find_tvg_files(&input, &mut files)?;
+        files.sort();
+    } else {
+        files.push(input);
+    }
+
+    let mut failures = 0;
+    for path in &files {
+        if let Err(e) = verify_file_roundtrip(path, bytes) {
+            failures += 1;
+            println!("{}: {:#}", path.display(), e);
+        }
+    }
+
+    println!(
+        "{} of {} files round trip",
+        files.len() - failures,
+        files.len()
+    );
+
+    if failures > 0 {
+        return Err(eyre!("{} files failed to round trip", failures));
+    }
+
+    Ok(())
+}
+
+fn verify_file_roundtrip(path: &Path, bytes: bool) -> Result<()> {
+    let original = std::fs::read(path).wrap_err("failed to read file")?;
+    let image = Decoder::new(original.as_slice())
+        .decode()
+        .wrap_err("failed to decode")?;
+
+    let mut encoded = Vec::new();
+    image.encode(&mut encoded).wrap_err("failed to encode")?;
+    let decoded = Decoder::new(encoded.as_slice())
+        .decode()
+        .wrap_err("failed to decode the encoded file")?;
+
+    if decoded.header != image.header {
+        return Err(eyre!(
+            "header differs: {:?} became {:?}",
+            image.header,
+            decoded.header
+        ));
+    }
+
+    if let Some(index) = (0..image.color_table.len().max(decoded.color_table.len()))
+        .find(|&i| image.color_table.get(i) != decoded.color_table.get(i))
+    {
+        return Err(eyre!("color {} differs", index));
+    }
+
+    let mismatches: Vec<String> = (0..image.commands.len().max(decoded.commands.len()))
+        .filter(|&i| image.commands.get(i) != decoded.commands.get(i))
+        .map(|i| match image.commands.get(i) {
+            Some(command) => format!("{} ({})", i, command.name()),
+            None => format!("{} (added)", i),
+        })
+        .collect();
+    if !mismatches.is_empty() {
+        return Err(eyre!("commands differ: {}", mismatches.join(", ")));
+    }
+
+    if decoded.trailer != image.trailer {
+        return Err(eyre!("trailer differs"));
+    }
+
+    if bytes && encoded != original {
+        let offset = encoded
+            .iter()
+            .zip(&original)
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| encoded.len().min(original.len()));
+
+        return Err(eyre!(
+            "encoded file differs from byte {} ({} bytes, input has {})",
+            offset,
+            encoded.len(),
+            original.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Code under test of a fuzz target, run on plain bytes so that crashes can
+/// be reproduced without libFuzzer. Must match `fuzz/fuzz_targets/`.
+#[derive(Debug, Clone, Copy)]
+enum FuzzTarget {
+    Decode,
+    Render,
+    TextFormat,
+}
+
+impl FuzzTarget {
+    const NAMES: &'static [&'static str] = &["decode", "render", "text-format"];
+
+    /// Images with more pixels aren't rendered, like in the fuzz target
+    const MAX_PIXELS: u64 = 1024 * 1024;
+
+    fn run(self, data: &[u8]) {
+        match self {
+            FuzzTarget::Decode => {
+                if let Ok(image) = Decoder::new(data).decode() {
+                    let _ = image.encode(&mut Vec::new());
+                }
+            }
+            FuzzTarget::Render => {
+                let image = match Decoder::new(data).decode() {
+                    Ok(image) => image,
+                    Err(_) => return,
+                };
+
+                if image.header.width as u64 * image.header.height as u64 > Self::MAX_PIXELS {
+                    return;
+                }
+
+                let options = tinyvg::RenderOptions {
+                    max_primitives: Some(tinyvg::PrimitiveLimit {
+                        count: 10_000,
+                        truncate: true,
+                    }),
+                    ..Default::default()
+                };
+
+                let _ = image.render_pixmap_with_options(&options);
+            }
+            FuzzTarget::TextFormat => {
+                if let Ok(text) = std::str::from_utf8(data) {
+                    if let Ok(image) = tinyvg::text_format::parse_text(text) {
+                        let _ = tinyvg::text_format::write_text(&image);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether running the target on the data panics
+    fn crashes(self, data: &[u8]) -> bool {
+        std::panic::catch_unwind(|| self.run(data)).is_err()
+    }
+}
+
+impl std::str::FromStr for FuzzTarget {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "decode" => Ok(FuzzTarget::Decode),
+            "render" => Ok(FuzzTarget::Render),
+            "text-format" | "text_format" => Ok(FuzzTarget::TextFormat),
+            _ => Err(eyre!("unknown fuzz target {}", s)),
+        }
+    }
+}
+
+fn corpus_minimize(input: PathBuf, output: Option<PathBuf>, target: FuzzTarget) -> Result<()> {
+    let output = output.unwrap_or_else(|| input.with_extension("min"));
+
+    let mut data =
+        std::fs::read(&input).wrap_err_with(|| format!("failed to read {}", input.display()))?;
+    let original_len = data.len();
+
+    // Report the panic of the original input, then keep quiet while shrinking
+    eyre::ensure!(
+        target.crashes(&data),
+        "{} does not crash the {:?} target",
+        input.display(),
+        target
+    );
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+
+    // Remove ever smaller chunks for as long as the input keeps crashing
+    let mut chunk = data.len() / 2;
+    while chunk > 0 {
+        let mut start = 0;
+        while start < data.len() {
+            let end = (start + chunk).min(data.len());
+            let mut candidate = data[..start].to_vec();
+            candidate.extend_from_slice(&data[end..]);
+
+            if target.crashes(&candidate) {
+                data = candidate;
+            } else {
+                start += chunk;
+            }
+        }
+
+        chunk /= 2;
+    }
+
+    // Then zero the bytes that don't matter, so the ones that do stand out
+    for index in 0..data.len() {
+        if data[index] != 0 {
+            let byte = std::mem::replace(&mut data[index], 0);
+            if !target.crashes(&data) {
+                data[index] = byte;
+            }
+        }
+    }
+
+    std::panic::set_hook(hook);
+
+    std::fs::write(&output, &data)
+        .wrap_err_with(|| format!("failed to write {}", output.display()))?;
+    println!(
+        "{}: {} bytes -> {} bytes",
+        output.display(),
+        original_len,
+        data.len()
+    );
+
+    Ok(())
+}
+
+/// Help texts are wrapped to this width in the man page, independent of the
+/// terminal that generates it
+const MAN_WIDTH: usize = 80;
+
+fn man(output: Option<PathBuf>) -> Result<()> {
+    let mut page = format!(
+        ".TH TINYVG 1 \"\" \"tinyvg {}\"\n\
+         .SH NAME\n\
+         tinyvg \\- {}\n\
+         .SH DESCRIPTION\n",
+        env!("CARGO_PKG_VERSION"),
+        env!("CARGO_PKG_DESCRIPTION"),
+    );
+
+    let mut commands = vec![vec![]];
+    while let Some(command) = commands.pop() {
+        let help = help_text(&command)?;
+
+        if !command.is_empty() {
+            page.push_str(&format!(".SS \"tinyvg {}\"\n", command.join(" ")));
+        }
+        page.push_str(".nf\n");
+        for line in help.lines() {
+            page.push_str(&roff_line(line));
+            page.push('\n');
+        }
+        page.push_str(".fi\n");
+
+        if command.is_empty() {
+            page.push_str(".SH COMMANDS\n");
+        }
+
+        // Depth-first, in the order of the help text
+        for name in subcommand_names(&help).into_iter().rev() {
+            let mut subcommand = command.clone();
+            subcommand.push(name);
+            commands.push(subcommand);
+        }
+    }
+
+    match output {
+        Some(path) => std::fs::write(path, page).wrap_err("failed to write man page")?,
+        None => std::io::stdout()
+            .write_all(page.as_bytes())
+            .wrap_err("failed to write man page")?,
+    }
+
+    Ok(())
+}
+
+/// Output of `tinyvg <command> --help`
+fn help_text(command: &[String]) -> Result<String> {
+    let args = std::iter::once("tinyvg")
+        .chain(command.iter().map(String::as_str))
+        .chain(std::iter::once("--help"));
+
+    match Options::clap()
+        .set_term_width(MAN_WIDTH)
+        .get_matches_from_safe(args)
+    {
+        Err(error) if error.kind == ErrorKind::HelpDisplayed => Ok(error.message),
+        Err(error) => Err(eyre!(
+            "failed to get help of {:?}: {}",
+            command,
+            error.message
+        )),
+        Ok(_) => Err(eyre!("no help for {:?}", command)),
+    }
+}
+
+/// Names in the SUBCOMMANDS section of a help text, except `help`
+fn subcommand_names(help: &str) -> Vec<String> {
+    help.lines()
+        .skip_while(|line| *line != "SUBCOMMANDS:")
+        .skip(1)
+        .take_while(|line| line.starts_with(' '))
+        // Wrapped descriptions are indented further than the names
+        .filter(|line| line.starts_with("    ") && !line[4..].starts_with(' '))
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|name| *name != "help")
+        .map(str::to_string)
+        .collect()
+}
+
+/// A line of text that roff prints as-is in a `.nf` block
+fn roff_line(line: &str) -> String {
+    let line = line.replace('\\', "\\e").replace('-', "\\-");
+
+    if line.starts_with(['.', '\'']) {
+        format!("\\&{}", line)
+    } else {
+        line
+    }
+}
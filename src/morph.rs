@@ -0,0 +1,435 @@
+//! Interpolation between two images with the same structure, e.g. the
+//! states of an icon, so that transitions between them can be rendered frame
+//! by frame.
+
+use kurbo::{Line, Point, Rect};
+
+use crate::error::{ensure, format_err, Result};
+use crate::format::{
+    Color, Command, Image, OutlineStyle, Segment, SegmentCommand, SegmentCommandKind, Style,
+};
+
+impl Image {
+    /// Image between `a` (at `t = 0`) and `b` (at `t = 1`), with every point,
+    /// line width, gradient and color interpolated linearly. Values of `t`
+    /// outside of 0 to 1 extrapolate.
+    ///
+    /// The images must have the same structure: the same commands in the same
+    /// order, with the same number of points, segments and path instructions,
+    /// the same kinds of styles referencing the same color indices, and color
+    /// tables of the same length. Arc flags are taken from `a`, everything
+    /// else that isn't a number must match. The header is taken from `a`,
+    /// with its size interpolated.
+    ///
+    /// ```
+    /// # use tinyvg::format::{Color, Command, Image, Point, Style};
+    /// let mut a = Image::empty(16, 16);
+    /// a.color_table = vec![Color::rgb8(0x00, 0x00, 0x00)];
+    /// a.commands.push(Command::FillPolygon {
+    ///     fill_style: Style::FlatColor { color_index: 0 },
+    ///     polygon: vec![Point::new(0.0, 0.0), Point::new(8.0, 0.0), Point::new(0.0, 8.0)],
+    ///     outline: None,
+    /// });
+    ///
+    /// let mut b = a.clone();
+    /// b.color_table = vec![Color::rgb8(0xff, 0xff, 0xff)];
+    /// if let Command::FillPolygon { polygon, .. } = &mut b.commands[0] {
+    ///     polygon[1] = Point::new(16.0, 0.0);
+    /// }
+    ///
+    /// let half = Image::lerp(&a, &b, 0.5).unwrap();
+    ///
+    /// match &half.commands[0] {
+    ///     Command::FillPolygon { polygon, .. } => assert_eq!(polygon[1], Point::new(12.0, 0.0)),
+    ///     command => panic!("unexpected command {:?}", command),
+    /// }
+    /// assert_eq!(half.color_table[0].as_rgba8(), (0x80, 0x80, 0x80, 0xff));
+    ///
+    /// // Images with different commands can't be interpolated
+    /// b.commands.clear();
+    /// assert!(Image::lerp(&a, &b, 0.5).is_err());
+    /// ```
+    pub fn lerp(a: &Image, b: &Image, t: f64) -> Result<Image> {
+        ensure!(
+            a.color_table.len() == b.color_table.len(),
+            "images have {} and {} colors",
+            a.color_table.len(),
+            b.color_table.len()
+        );
+        ensure!(
+            a.commands.len() == b.commands.len(),
+            "images have {} and {} commands",
+            a.commands.len(),
+            b.commands.len()
+        );
+
+        let mut header = a.header.clone();
+        header.width = lerp(a.header.width as f64, b.header.width as f64, t)
+            .round()
+            .max(0.0) as u32;
+        header.height = lerp(a.header.height as f64, b.header.height as f64, t)
+            .round()
+            .max(0.0) as u32;
+
+        let color_table = a
+            .color_table
+            .iter()
+            .zip(&b.color_table)
+            .map(|(a, b)| lerp_color(a, b, t))
+            .collect();
+
+        let commands = a
+            .commands
+            .iter()
+            .zip(&b.commands)
+            .enumerate()
+            .map(|(index, (a, b))| {
+                lerp_command(a, b, t).ok_or_else(|| {
+                    format_err!(
+                        "command {} differs in structure: {} and {}",
+                        index,
+                        a.name(),
+                        b.name()
+                    )
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Image {
+            header,
+            color_table,
+            commands,
+            trailer: a.trailer.clone(),
+            labels: a.labels.clone(),
+            bounds: None,
+        })
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn lerp_point(a: Point, b: Point, t: f64) -> Point {
+    a.lerp(b, t)
+}
+
+fn lerp_color(a: &Color, b: &Color, t: f64) -> Color {
+    let (r0, g0, b0, a0) = a.as_rgba();
+    let (r1, g1, b1, a1) = b.as_rgba();
+
+    Color::rgba(
+        lerp(r0, r1, t),
+        lerp(g0, g1, t),
+        lerp(b0, b1, t),
+        lerp(a0, a1, t),
+    )
+}
+
+/// Interpolate each pair of items, or `None` if the lists differ in length or
+/// any pair differs in structure
+fn lerp_all<T>(a: &[T], b: &[T], lerp: impl Fn(&T, &T) -> Option<T>) -> Option<Vec<T>> {
+    if a.len() != b.len() {
+        return None;
+    }
+
+    a.iter().zip(b).map(|(a, b)| lerp(a, b)).collect()
+}
+
+fn lerp_points(a: &[Point], b: &[Point], t: f64) -> Option<Vec<Point>> {
+    lerp_all(a, b, |a, b| Some(lerp_point(*a, *b, t)))
+}
+
+fn lerp_style(a: &Style, b: &Style, t: f64) -> Option<Style> {
+    match (a, b) {
+        (Style::FlatColor { color_index: a }, Style::FlatColor { color_index: b }) => {
+            (a == b).then_some(Style::FlatColor { color_index: *a })
+        }
+        (
+            Style::LinearGradient {
+                point_0: a0,
+                point_1: a1,
+                color_index_0,
+                color_index_1,
+            },
+            Style::LinearGradient {
+                point_0: b0,
+                point_1: b1,
+                color_index_0: b_index_0,
+                color_index_1: b_index_1,
+            },
+        ) => ((color_index_0, color_index_1) == (b_index_0, b_index_1)).then(|| {
+            Style::LinearGradient {
+                point_0: lerp_point(*a0, *b0, t),
+                point_1: lerp_point(*a1, *b1, t),
+                color_index_0: *color_index_0,
+                color_index_1: *color_index_1,
+            }
+        }),
+        (
+            Style::RadialGradient {
+                point_0: a0,
+                point_1: a1,
+                color_index_0,
+                color_index_1,
+            },
+            Style::RadialGradient {
+                point_0: b0,
+                point_1: b1,
+                color_index_0: b_index_0,
+                color_index_1: b_index_1,
+            },
+        ) => ((color_index_0, color_index_1) == (b_index_0, b_index_1)).then(|| {
+            Style::RadialGradient {
+                point_0: lerp_point(*a0, *b0, t),
+                point_1: lerp_point(*a1, *b1, t),
+                color_index_0: *color_index_0,
+                color_index_1: *color_index_1,
+            }
+        }),
+        _ => None,
+    }
+}
+
+fn lerp_outline(
+    a: &Option<OutlineStyle>,
+    b: &Option<OutlineStyle>,
+    t: f64,
+) -> Option<Option<OutlineStyle>> {
+    match (a, b) {
+        (None, None) => Some(None),
+        (Some(a), Some(b)) => Some(Some(OutlineStyle {
+            line_width: lerp(a.line_width, b.line_width, t),
+            line_style: lerp_style(&a.line_style, &b.line_style, t)?,
+        })),
+        _ => None,
+    }
+}
+
+fn lerp_path(a: &[Segment], b: &[Segment], t: f64) -> Option<Vec<Segment>> {
+    lerp_all(a, b, |a, b| {
+        Some(Segment {
+            start: lerp_point(a.start, b.start, t),
+            commands: lerp_all(&a.commands, &b.commands, |a, b| {
+                lerp_segment_command(a, b, t)
+            })?,
+        })
+    })
+}
+
+fn lerp_segment_command(a: &SegmentCommand, b: &SegmentCommand, t: f64) -> Option<SegmentCommand> {
+    let line_width = match (a.line_width, b.line_width) {
+        (None, None) => None,
+        (Some(a), Some(b)) => Some(lerp(a, b, t)),
+        _ => return None,
+    };
+
+    let kind = match (&a.kind, &b.kind) {
+        (SegmentCommandKind::Line { end: a }, SegmentCommandKind::Line { end: b }) => {
+            SegmentCommandKind::Line {
+                end: lerp_point(*a, *b, t),
+            }
+        }
+        (
+            SegmentCommandKind::HorizontalLine { x: a },
+            SegmentCommandKind::HorizontalLine { x: b },
+        ) => SegmentCommandKind::HorizontalLine { x: lerp(*a, *b, t) },
+        (SegmentCommandKind::VerticalLine { y: a }, SegmentCommandKind::VerticalLine { y: b }) => {
+            SegmentCommandKind::VerticalLine { y: lerp(*a, *b, t) }
+        }
+        (
+            SegmentCommandKind::CubicBezier {
+                control_0: a0,
+                control_1: a1,
+                point_1: a2,
+            },
+            SegmentCommandKind::CubicBezier {
+                control_0: b0,
+                control_1: b1,
+                point_1: b2,
+            },
+        ) => SegmentCommandKind::CubicBezier {
+            control_0: lerp_point(*a0, *b0, t),
+            control_1: lerp_point(*a1, *b1, t),
+            point_1: lerp_point(*a2, *b2, t),
+        },
+        (
+            SegmentCommandKind::QuadraticBezier {
+                control: a0,
+                point_1: a1,
+            },
+            SegmentCommandKind::QuadraticBezier {
+                control: b0,
+                point_1: b1,
+            },
+        ) => SegmentCommandKind::QuadraticBezier {
+            control: lerp_point(*a0, *b0, t),
+            point_1: lerp_point(*a1, *b1, t),
+        },
+        (
+            SegmentCommandKind::ArcCircle {
+                large,
+                sweep,
+                radius: a_radius,
+                target: a_target,
+            },
+            SegmentCommandKind::ArcCircle {
+                radius: b_radius,
+                target: b_target,
+                ..
+            },
+        ) => SegmentCommandKind::ArcCircle {
+            large: *large,
+            sweep: *sweep,
+            radius: lerp(*a_radius, *b_radius, t),
+            target: lerp_point(*a_target, *b_target, t),
+        },
+        (
+            SegmentCommandKind::ArcEllipse {
+                large,
+                sweep,
+                radius_x: a_radius_x,
+                radius_y: a_radius_y,
+                rotation: a_rotation,
+                target: a_target,
+            },
+            SegmentCommandKind::ArcEllipse {
+                radius_x: b_radius_x,
+                radius_y: b_radius_y,
+                rotation: b_rotation,
+                target: b_target,
+                ..
+            },
+        ) => SegmentCommandKind::ArcEllipse {
+            large: *large,
+            sweep: *sweep,
+            radius_x: lerp(*a_radius_x, *b_radius_x, t),
+            radius_y: lerp(*a_radius_y, *b_radius_y, t),
+            rotation: lerp(*a_rotation, *b_rotation, t),
+            target: lerp_point(*a_target, *b_target, t),
+        },
+        (SegmentCommandKind::ClosePath, SegmentCommandKind::ClosePath) => {
+            SegmentCommandKind::ClosePath
+        }
+        _ => return None,
+    };
+
+    Some(SegmentCommand { kind, line_width })
+}
+
+fn lerp_command(a: &Command, b: &Command, t: f64) -> Option<Command> {
+    Some(match (a, b) {
+        (
+            Command::FillPolygon {
+                fill_style: a_style,
+                polygon: a_polygon,
+                outline: a_outline,
+            },
+            Command::FillPolygon {
+                fill_style: b_style,
+                polygon: b_polygon,
+                outline: b_outline,
+            },
+        ) => Command::FillPolygon {
+            fill_style: lerp_style(a_style, b_style, t)?,
+            polygon: lerp_points(a_polygon, b_polygon, t)?,
+            outline: lerp_outline(a_outline, b_outline, t)?,
+        },
+        (
+            Command::FillRectangles {
+                fill_style: a_style,
+                rectangles: a_rectangles,
+                outline: a_outline,
+            },
+            Command::FillRectangles {
+                fill_style: b_style,
+                rectangles: b_rectangles,
+                outline: b_outline,
+            },
+        ) => Command::FillRectangles {
+            fill_style: lerp_style(a_style, b_style, t)?,
+            rectangles: lerp_all(a_rectangles, b_rectangles, |a, b| {
+                Some(Rect::new(
+                    lerp(a.x0, b.x0, t),
+                    lerp(a.y0, b.y0, t),
+                    lerp(a.x1, b.x1, t),
+                    lerp(a.y1, b.y1, t),
+                ))
+            })?,
+            outline: lerp_outline(a_outline, b_outline, t)?,
+        },
+        (
+            Command::FillPath {
+                fill_style: a_style,
+                path: a_path,
+                outline: a_outline,
+            },
+            Command::FillPath {
+                fill_style: b_style,
+                path: b_path,
+                outline: b_outline,
+            },
+        ) => Command::FillPath {
+            fill_style: lerp_style(a_style, b_style, t)?,
+            path: lerp_path(a_path, b_path, t)?,
+            outline: lerp_outline(a_outline, b_outline, t)?,
+        },
+        (
+            Command::DrawLines {
+                line_style: a_style,
+                line_width: a_width,
+                lines: a_lines,
+            },
+            Command::DrawLines {
+                line_style: b_style,
+                line_width: b_width,
+                lines: b_lines,
+            },
+        ) => Command::DrawLines {
+            line_style: lerp_style(a_style, b_style, t)?,
+            line_width: lerp(*a_width, *b_width, t),
+            lines: lerp_all(a_lines, b_lines, |a, b| {
+                Some(Line::new(
+                    lerp_point(a.p0, b.p0, t),
+                    lerp_point(a.p1, b.p1, t),
+                ))
+            })?,
+        },
+        (
+            Command::DrawLineLoop {
+                line_style: a_style,
+                line_width: a_width,
+                close_path,
+                points: a_points,
+            },
+            Command::DrawLineLoop {
+                line_style: b_style,
+                line_width: b_width,
+                close_path: b_close_path,
+                points: b_points,
+            },
+        ) if close_path == b_close_path => Command::DrawLineLoop {
+            line_style: lerp_style(a_style, b_style, t)?,
+            line_width: lerp(*a_width, *b_width, t),
+            close_path: *close_path,
+            points: lerp_points(a_points, b_points, t)?,
+        },
+        (
+            Command::DrawLinePath {
+                line_style: a_style,
+                line_width: a_width,
+                path: a_path,
+            },
+            Command::DrawLinePath {
+                line_style: b_style,
+                line_width: b_width,
+                path: b_path,
+            },
+        ) => Command::DrawLinePath {
+            line_style: lerp_style(a_style, b_style, t)?,
+            line_width: lerp(*a_width, *b_width, t),
+            path: lerp_path(a_path, b_path, t)?,
+        },
+        _ => return None,
+    })
+}
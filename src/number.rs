@@ -0,0 +1,71 @@
+//! Number formatting shared by the text, SVG and HTML writers, so that every
+//! exporter writes numbers the same way. Rust's float formatting does not
+//! depend on the locale, and the rules here (no exponents, no negative zero,
+//! trailing zeros trimmed) make the output identical across platforms.
+//!
+//! ```
+//! # use tinyvg::number::{fixed, shortest, NumberFormat};
+//! assert_eq!(shortest(16.5), "16.5");
+//! assert_eq!(shortest(-0.0), "0");
+//! assert_eq!(shortest(1e-7), "0.0000001");
+//! assert_eq!(fixed(0.5, 3), "0.500");
+//!
+//! let format = NumberFormat {
+//!     precision: Some(2),
+//! };
+//! assert_eq!(format.format(1.0 / 3.0), "0.33");
+//! assert_eq!(format.format(2.0), "2");
+//! ```
+
+/// How numbers are written by exporters that allow trading exactness for
+/// smaller output
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NumberFormat {
+    /// Maximum number of digits after the decimal point, trailing zeros are
+    /// trimmed. `None` writes the shortest representation that reads back as
+    /// the exact same value.
+    pub precision: Option<usize>,
+}
+
+impl NumberFormat {
+    /// Format a number
+    pub fn format(self, value: f64) -> String {
+        match self.precision {
+            Some(precision) => {
+                let text = fixed(value, precision);
+
+                let text = if text.contains('.') {
+                    text.trim_end_matches('0').trim_end_matches('.')
+                } else {
+                    &text
+                };
+
+                if text == "-0" {
+                    "0".to_string()
+                } else {
+                    text.to_string()
+                }
+            }
+            None => shortest(value),
+        }
+    }
+}
+
+/// The shortest representation of a number that reads back as the exact same
+/// value
+pub fn shortest(value: f64) -> String {
+    // Adding zero turns negative zero into positive zero
+    (value + 0.0).to_string()
+}
+
+/// A number with exactly `digits` digits after the decimal point
+pub fn fixed(value: f64, digits: usize) -> String {
+    let text = format!("{:.*}", digits, value);
+
+    // Values that round to zero keep their sign, e.g. "-0.000"
+    if text.starts_with('-') && text[1..].chars().all(|c| c == '0' || c == '.') {
+        text[1..].to_string()
+    } else {
+        text
+    }
+}
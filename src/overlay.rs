@@ -0,0 +1,244 @@
+//! Debug overlay drawn over the artwork by `RenderOptions::debug`, showing
+//! the geometry of the commands as it is stored in the file. The overlay is
+//! built as a separate image, so every render backend draws it the same way.
+
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+use kurbo::{Line, Point, Rect};
+
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+use crate::format::{Color, Command, Image, Segment, SegmentCommandKind, Style};
+
+/// Half the width of point markers, in device pixels
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+const MARKER_SIZE: f64 = 2.5;
+
+/// Width of overlay lines, in device pixels
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+const LINE_WIDTH: f64 = 1.0;
+
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+const BOUNDING_BOX_COLOR: usize = 0;
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+const SEGMENT_START_COLOR: usize = 1;
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+const CONTROL_POINT_COLOR: usize = 2;
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+const GRADIENT_ANCHOR_COLOR: usize = 3;
+
+/// Classes of geometry drawn over the artwork, in colors that stand out
+/// against most artwork. Useful to find out why a file from another exporter
+/// looks wrong.
+///
+/// - Bounding boxes of commands are outlined in magenta
+/// - Starts of path segments are marked in green
+/// - Control points of curves are marked in orange, with handles to the end
+///   points of their curve
+/// - Both points of gradients are marked in cyan, joined by a line
+///
+/// ```
+/// # use tinyvg::{DebugOverlay, Decoder, RenderOptions};
+/// # use std::fs::File;
+/// let image = Decoder::new(File::open("data/everything.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let options = RenderOptions {
+///     debug: DebugOverlay {
+///         bounding_boxes: false,
+///         ..DebugOverlay::all()
+///     },
+///     ..Default::default()
+/// };
+///
+/// let mut png = Vec::new();
+/// image.render_png_with_options(&mut png, &options).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DebugOverlay {
+    /// Whether bounding boxes of commands are drawn
+    pub bounding_boxes: bool,
+
+    /// Whether starts of path segments are marked
+    pub segment_starts: bool,
+
+    /// Whether control points of curves are marked
+    pub control_points: bool,
+
+    /// Whether the points of gradients are marked
+    pub gradient_anchors: bool,
+}
+
+impl DebugOverlay {
+    /// An overlay with every class of geometry
+    pub fn all() -> Self {
+        Self {
+            bounding_boxes: true,
+            segment_starts: true,
+            control_points: true,
+            gradient_anchors: true,
+        }
+    }
+
+    /// Whether nothing is drawn
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+impl Image {
+    /// Image of the same size as this one, showing the geometry of the
+    /// commands for which `selected` returns true. `scale` is the number of
+    /// device pixels per image unit, so that markers have the same size at
+    /// any zoom.
+    pub(crate) fn debug_overlay(
+        &self,
+        overlay: DebugOverlay,
+        scale: f64,
+        selected: impl Fn(usize) -> bool,
+    ) -> Image {
+        let scale = if scale > 0.0 { scale } else { 1.0 };
+        let mut builder = OverlayBuilder {
+            marker_size: MARKER_SIZE / scale,
+            line_width: LINE_WIDTH / scale,
+            commands: Vec::new(),
+        };
+
+        for (index, cmd) in self.commands.iter().enumerate() {
+            if !selected(index) {
+                continue;
+            }
+
+            if overlay.bounding_boxes {
+                builder.outline(cmd.bounds(), BOUNDING_BOX_COLOR);
+            }
+
+            if overlay.gradient_anchors {
+                for style in cmd.styles() {
+                    if let Style::LinearGradient {
+                        point_0, point_1, ..
+                    }
+                    | Style::RadialGradient {
+                        point_0, point_1, ..
+                    } = *style
+                    {
+                        builder.lines(vec![Line::new(point_0, point_1)], GRADIENT_ANCHOR_COLOR);
+                        builder.markers(vec![point_0, point_1], GRADIENT_ANCHOR_COLOR);
+                    }
+                }
+            }
+
+            if let Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } = cmd {
+                if overlay.control_points {
+                    let (handles, controls) = control_points(path);
+                    builder.lines(handles, CONTROL_POINT_COLOR);
+                    builder.markers(controls, CONTROL_POINT_COLOR);
+                }
+
+                if overlay.segment_starts {
+                    builder.markers(path.iter().map(|s| s.start).collect(), SEGMENT_START_COLOR);
+                }
+            }
+        }
+
+        Image {
+            header: self.header.clone(),
+            color_table: vec![
+                Color::rgb8(0xff, 0x00, 0xff),
+                Color::rgb8(0x00, 0xe0, 0x00),
+                Color::rgb8(0xff, 0x80, 0x00),
+                Color::rgb8(0x00, 0xc0, 0xff),
+            ],
+            commands: builder.commands,
+            trailer: Vec::new(),
+            labels: Default::default(),
+            bounds: None,
+        }
+    }
+}
+
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+struct OverlayBuilder {
+    marker_size: f64,
+    line_width: f64,
+    commands: Vec<Command>,
+}
+
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+impl OverlayBuilder {
+    fn outline(&mut self, rect: Rect, color_index: usize) {
+        self.commands.push(Command::DrawLineLoop {
+            line_style: Style::FlatColor { color_index },
+            line_width: self.line_width,
+            close_path: true,
+            points: vec![
+                Point::new(rect.x0, rect.y0),
+                Point::new(rect.x1, rect.y0),
+                Point::new(rect.x1, rect.y1),
+                Point::new(rect.x0, rect.y1),
+            ],
+        });
+    }
+
+    fn lines(&mut self, lines: Vec<Line>, color_index: usize) {
+        if !lines.is_empty() {
+            self.commands.push(Command::DrawLines {
+                line_style: Style::FlatColor { color_index },
+                line_width: self.line_width,
+                lines,
+            });
+        }
+    }
+
+    /// Square markers centered on the points
+    fn markers(&mut self, points: Vec<Point>, color_index: usize) {
+        if !points.is_empty() {
+            let size = self.marker_size;
+
+            self.commands.push(Command::FillRectangles {
+                fill_style: Style::FlatColor { color_index },
+                rectangles: points
+                    .into_iter()
+                    .map(|p| Rect::new(p.x - size, p.y - size, p.x + size, p.y + size))
+                    .collect(),
+                outline: None,
+            });
+        }
+    }
+}
+
+/// Handles from the end points of every curve to its control points, and
+/// the control points themselves
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+fn control_points(path: &[Segment]) -> (Vec<Line>, Vec<Point>) {
+    let mut handles = Vec::new();
+    let mut controls = Vec::new();
+
+    for segment in path {
+        let mut pen = segment.start;
+
+        for command in &segment.commands {
+            match command.kind {
+                SegmentCommandKind::CubicBezier {
+                    control_0,
+                    control_1,
+                    point_1,
+                } => {
+                    handles.push(Line::new(pen, control_0));
+                    handles.push(Line::new(control_1, point_1));
+                    controls.extend([control_0, control_1]);
+                }
+                SegmentCommandKind::QuadraticBezier { control, point_1 } => {
+                    handles.push(Line::new(pen, control));
+                    handles.push(Line::new(control, point_1));
+                    controls.push(control);
+                }
+                _ => {}
+            }
+
+            pen = command.kind.end_point(pen, segment.start);
+        }
+    }
+
+    (handles, controls)
+}
@@ -0,0 +1,426 @@
+//! Palette reduction, for targets with few colors such as `Rgb565` files or
+//! constrained displays. Colors are clustered in CIELAB space, so that colors
+//! which look alike are merged first.
+//!
+//! Also palettes shared by several images, e.g. an icon pack that is themed
+//! centrally. Every image of the pack uses the same color table, so that
+//! replacing the table of every image with a themed palette recolors the
+//! whole pack. Palettes are stored as text files with one `#rrggbbaa` color
+//! per line, see `write_palette`.
+
+use crate::error::{bail, ensure, format_err, Result};
+use crate::format::{Color, Image};
+
+/// How far the colors of an image moved when its palette was reduced, in
+/// CIE76 ΔE units. A ΔE of about 2.3 is a just noticeable difference.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PaletteReduction {
+    /// Number of colors before the reduction
+    pub original_colors: usize,
+
+    /// Number of colors after the reduction
+    pub colors: usize,
+
+    /// Mean error over all style references to a color
+    pub mean_error: f64,
+
+    /// Largest error of any color in the original palette
+    pub max_error: f64,
+}
+
+/// Maximum number of k-means iterations
+const MAX_ITERATIONS: usize = 32;
+
+/// Weight of alpha (0 to 1) relative to Lab lightness (0 to 100), so that
+/// transparent and opaque versions of a color aren't merged before similar
+/// opaque colors are
+const ALPHA_WEIGHT: f64 = 100.0;
+
+impl Image {
+    /// Reduce the color table to at most `n` colors. Colors are clustered
+    /// with k-means in CIELAB space (with alpha as a fourth dimension),
+    /// weighted by how many styles reference them, so that frequently used
+    /// colors move the least. Styles are remapped to the merged colors.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// let mut image = Decoder::new(File::open("data/tiger.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let reduction = image.reduce_palette(16).unwrap();
+    ///
+    /// assert!(image.color_table.len() <= 16);
+    /// assert_eq!(reduction.colors, image.color_table.len());
+    /// assert!(reduction.mean_error <= reduction.max_error);
+    ///
+    /// let mut png = Vec::new();
+    /// image.render_png(&mut png).unwrap();
+    /// ```
+    pub fn reduce_palette(&mut self, n: usize) -> Result<PaletteReduction> {
+        let original_colors = self.color_table.len();
+
+        if original_colors <= n {
+            return Ok(PaletteReduction {
+                original_colors,
+                colors: original_colors,
+                ..Default::default()
+            });
+        }
+
+        ensure!(n > 0, "cannot reduce a palette to zero colors");
+
+        let points = self.color_table.iter().map(to_lab).collect::<Vec<_>>();
+        let usage = self.palette_usage();
+        let weights = usage.iter().map(|u| (*u).max(1) as f64).collect::<Vec<_>>();
+
+        let centers = initial_centers(&points, &weights, n);
+        let (centers, assignment) = k_means(&points, &weights, centers);
+
+        // Drop clusters without colors, keeping the order of first use so
+        // that the most used colors come first
+        let mut remap = vec![None; centers.len()];
+        let mut color_table = Vec::new();
+        for cluster in &assignment {
+            if remap[*cluster].is_none() {
+                remap[*cluster] = Some(color_table.len());
+                color_table.push(from_lab(centers[*cluster]));
+            }
+        }
+
+        let mut total_error = 0.0;
+        let mut references = 0;
+        let mut max_error: f64 = 0.0;
+        for (index, cluster) in assignment.iter().enumerate() {
+            let error = delta_e(
+                points[index],
+                to_lab(&color_table[remap[*cluster].unwrap()]),
+            );
+
+            total_error += error * usage[index] as f64;
+            references += usage[index];
+            max_error = max_error.max(error);
+        }
+
+        self.visit_styles_mut(|style| {
+            for index in style.color_indices_mut() {
+                if let Some(cluster) = assignment.get(*index) {
+                    *index = remap[*cluster].unwrap();
+                }
+            }
+        });
+
+        self.color_table = color_table;
+
+        Ok(PaletteReduction {
+            original_colors,
+            colors: self.color_table.len(),
+            mean_error: if references > 0 {
+                total_error / references as f64
+            } else {
+                0.0
+            },
+            max_error,
+        })
+    }
+
+    /// Switch this image to the shared `palette`, adding the colors it uses
+    /// that aren't in the palette yet to its end. Styles are remapped to the
+    /// indices of their colors in the palette, so the image looks the same.
+    /// After externalizing every image of a pack, apply the final palette to
+    /// each of them with `Image::apply_external_palette`, so that their
+    /// color tables are identical.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::format::Color;
+    /// let mut icons = ["data/shield.tvg", "data/app_icon.tvg"].map(|path| {
+    ///     Decoder::new(File::open(path).unwrap()).decode().unwrap()
+    /// });
+    /// let originals = icons.clone();
+    ///
+    /// let mut palette = Vec::new();
+    /// for icon in &mut icons {
+    ///     icon.externalize_palette(&mut palette);
+    /// }
+    /// for (icon, original) in icons.iter_mut().zip(&originals) {
+    ///     icon.apply_external_palette(&palette).unwrap();
+    ///     assert_eq!(icon.color_table, palette);
+    ///     assert_eq!(icon.render_pixmap().unwrap(), original.render_pixmap().unwrap());
+    /// }
+    ///
+    /// // Theme the whole pack by editing the shared palette
+    /// let themed: Vec<Color> = palette.iter().map(|c| c.clone().with_alpha(0.5)).collect();
+    /// for icon in &mut icons {
+    ///     icon.apply_external_palette(&themed).unwrap();
+    /// }
+    /// ```
+    pub fn externalize_palette(&mut self, palette: &mut Vec<Color>) {
+        let remap = self
+            .color_table
+            .iter()
+            .map(|color| {
+                match palette
+                    .iter()
+                    .position(|c| c.as_rgba_u32() == color.as_rgba_u32())
+                {
+                    Some(index) => index,
+                    None => {
+                        palette.push(color.clone());
+                        palette.len() - 1
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.visit_styles_mut(|style| {
+            for index in style.color_indices_mut() {
+                if let Some(mapped) = remap.get(*index) {
+                    *index = *mapped;
+                }
+            }
+        });
+
+        self.color_table = palette.clone();
+    }
+
+    /// Replace the color table of this image with an external palette, e.g.
+    /// a themed version of the palette it was externalized to. Styles keep
+    /// their indices. Fails if a style references a color past the end of
+    /// the palette.
+    pub fn apply_external_palette(&mut self, palette: &[Color]) -> Result<()> {
+        if let Some(index) = self
+            .styles()
+            .flat_map(|style| style.color_indices())
+            .find(|index| *index >= palette.len())
+        {
+            bail!(
+                "palette has {} colors but the image uses index {}",
+                palette.len(),
+                index
+            );
+        }
+
+        self.color_table = palette.to_vec();
+
+        Ok(())
+    }
+}
+
+/// Write a palette as text, one `#rrggbbaa` color per line
+///
+/// ```
+/// # use tinyvg::format::Color;
+/// # use tinyvg::palette::{parse_palette, write_palette};
+/// let palette = vec![Color::rgb8(0x29, 0xad, 0xff), Color::rgba8(0, 0, 0, 0x80)];
+///
+/// let text = write_palette(&palette);
+/// assert_eq!(text, "#29adffff\n#00000080\n");
+/// assert_eq!(parse_palette(&text).unwrap(), palette);
+/// ```
+pub fn write_palette(palette: &[Color]) -> String {
+    palette
+        .iter()
+        .map(|color| format!("#{:08x}\n", color.as_rgba_u32()))
+        .collect()
+}
+
+/// Parse a palette written by `write_palette`. Colors may also be written as
+/// `#rrggbb` for opaque colors, and blank lines are skipped.
+pub fn parse_palette(text: &str) -> Result<Vec<Color>> {
+    text.lines()
+        .enumerate()
+        .map(|(number, line)| (number, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(number, line)| {
+            let hex = line
+                .strip_prefix('#')
+                .filter(|hex| {
+                    (hex.len() == 6 || hex.len() == 8) && hex.bytes().all(|b| b.is_ascii_hexdigit())
+                })
+                .ok_or_else(|| {
+                    format_err!(
+                        "line {}: expected a color like #rrggbbaa, found {:?}",
+                        number + 1,
+                        line
+                    )
+                })?;
+
+            let value = u32::from_str_radix(hex, 16)?;
+            Ok(if hex.len() == 6 {
+                Color::from_rgba32_u32((value << 8) | 0xff)
+            } else {
+                Color::from_rgba32_u32(value)
+            })
+        })
+        .collect()
+}
+
+/// L, a, b and weighted alpha
+type Lab = [f64; 4];
+
+/// Deterministic farthest-point initialization: the most used color, then
+/// repeatedly the color farthest from all chosen centers, scaled by its weight
+fn initial_centers(points: &[Lab], weights: &[f64], n: usize) -> Vec<Lab> {
+    let first = (0..points.len())
+        .max_by(|a, b| weights[*a].total_cmp(&weights[*b]).then(b.cmp(a)))
+        .unwrap();
+
+    let mut centers = vec![points[first]];
+    let mut distances = points
+        .iter()
+        .map(|p| distance_squared(*p, points[first]))
+        .collect::<Vec<_>>();
+
+    while centers.len() < n {
+        let next = (0..points.len())
+            .max_by(|a, b| {
+                (distances[*a] * weights[*a])
+                    .total_cmp(&(distances[*b] * weights[*b]))
+                    .then(b.cmp(a))
+            })
+            .unwrap();
+
+        if distances[next] == 0.0 {
+            break;
+        }
+
+        centers.push(points[next]);
+        for (distance, point) in distances.iter_mut().zip(points) {
+            *distance = distance.min(distance_squared(*point, points[next]));
+        }
+    }
+
+    centers
+}
+
+/// Weighted k-means, returning the final centers and the cluster of every
+/// point
+fn k_means(points: &[Lab], weights: &[f64], mut centers: Vec<Lab>) -> (Vec<Lab>, Vec<usize>) {
+    let mut assignment = vec![0; points.len()];
+
+    for iteration in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (point, cluster) in points.iter().zip(&mut assignment) {
+            let nearest = (0..centers.len())
+                .min_by(|a, b| {
+                    distance_squared(*point, centers[*a])
+                        .total_cmp(&distance_squared(*point, centers[*b]))
+                })
+                .unwrap();
+
+            if nearest != *cluster {
+                *cluster = nearest;
+                changed = true;
+            }
+        }
+
+        if !changed && iteration > 0 {
+            break;
+        }
+
+        let mut sums = vec![([0.0; 4], 0.0); centers.len()];
+        for ((point, weight), cluster) in points.iter().zip(weights).zip(&assignment) {
+            let (sum, total) = &mut sums[*cluster];
+            for (s, p) in sum.iter_mut().zip(point) {
+                *s += p * weight;
+            }
+            *total += weight;
+        }
+
+        for (center, (sum, total)) in centers.iter_mut().zip(sums) {
+            if total > 0.0 {
+                *center = sum.map(|s| s / total);
+            }
+        }
+    }
+
+    (centers, assignment)
+}
+
+fn distance_squared(a: Lab, b: Lab) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// CIE76 color difference, ignoring alpha
+fn delta_e(a: Lab, b: Lab) -> f64 {
+    distance_squared([a[0], a[1], a[2], 0.0], [b[0], b[1], b[2], 0.0]).sqrt()
+}
+
+/// D65 reference white
+const WHITE: [f64; 3] = [0.95047, 1.0, 1.08883];
+
+fn to_lab(color: &Color) -> Lab {
+    let (r, g, b, a) = color.as_rgba();
+    let [r, g, b] = [r, g, b].map(to_linear);
+
+    let xyz = [
+        0.4124564 * r + 0.3575761 * g + 0.1804375 * b,
+        0.2126729 * r + 0.7151522 * g + 0.0721750 * b,
+        0.0193339 * r + 0.1191920 * g + 0.9503041 * b,
+    ];
+
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let [fx, fy, fz] = [0, 1, 2].map(|i| f(xyz[i] / WHITE[i]));
+
+    [
+        116.0 * fy - 16.0,
+        500.0 * (fx - fy),
+        200.0 * (fy - fz),
+        a * ALPHA_WEIGHT,
+    ]
+}
+
+fn from_lab([l, a, b, alpha]: Lab) -> Color {
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+
+    let f_inverse = |t: f64| {
+        if t.powi(3) > 216.0 / 24389.0 {
+            t.powi(3)
+        } else {
+            (116.0 * t - 16.0) * 27.0 / 24389.0
+        }
+    };
+    let t = [fx, fy, fz];
+    let [x, y, z] = [0, 1, 2].map(|i| f_inverse(t[i]) * WHITE[i]);
+
+    let rgb = [
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    ]
+    .map(|c| from_linear(c).clamp(0.0, 1.0));
+
+    Color::rgba(
+        rgb[0],
+        rgb[1],
+        rgb[2],
+        (alpha / ALPHA_WEIGHT).clamp(0.0, 1.0),
+    )
+}
+
+fn to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -0,0 +1,136 @@
+//! Embedding of TinyVG source files in rendered PNG images. The original
+//! `.tvg` bytes are stored in a private ancillary `prVW` chunk, so the vector
+//! source travels with the raster and can be recovered later. PNG decoders
+//! which don't know about the chunk skip it.
+//!
+//! ```
+//! # use tinyvg::png_source::{embed_source, extract_source};
+//! # use tinyvg::Decoder;
+//! let source = std::fs::read("data/shield.tvg").unwrap();
+//! let image = Decoder::new(source.as_slice()).decode().unwrap();
+//!
+//! let mut png = Vec::new();
+//! image.render_png(&mut png).unwrap();
+//!
+//! let png = embed_source(&png, &source).unwrap();
+//!
+//! assert_eq!(extract_source(&png).unwrap(), Some(source));
+//! ```
+
+use crate::error::{bail, ensure, format_err, Result};
+
+/// Type of the PNG chunk that holds the TinyVG source
+pub const CHUNK_TYPE: [u8; 4] = *b"prVW";
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+struct Chunk<'a> {
+    offset: usize,
+    kind: [u8; 4],
+    data: &'a [u8],
+    crc: u32,
+}
+
+/// Insert `source` into the PNG data `png` as a `prVW` chunk, right before the
+/// end of the image. Any existing `prVW` chunk is replaced.
+pub fn embed_source(png: &[u8], source: &[u8]) -> Result<Vec<u8>> {
+    ensure!(
+        source.len() <= i32::MAX as usize,
+        "source is too large to fit in a PNG chunk"
+    );
+
+    let mut out = Vec::with_capacity(png.len() + source.len() + 12);
+    out.extend_from_slice(&SIGNATURE);
+
+    for chunk in chunks(png)? {
+        if chunk.kind == CHUNK_TYPE {
+            continue;
+        }
+
+        if &chunk.kind == b"IEND" {
+            write_chunk(&mut out, CHUNK_TYPE, source);
+        }
+
+        out.extend_from_slice(&png[chunk.offset..chunk.offset + chunk.data.len() + 12]);
+    }
+
+    Ok(out)
+}
+
+/// Get the TinyVG source stored in the PNG data `png`, if there is any
+pub fn extract_source(png: &[u8]) -> Result<Option<Vec<u8>>> {
+    for chunk in chunks(png)? {
+        if chunk.kind == CHUNK_TYPE {
+            ensure!(
+                chunk.crc == crc32(&chunk.kind, chunk.data),
+                "embedded TinyVG source is corrupt (checksum mismatch)"
+            );
+
+            return Ok(Some(chunk.data.to_vec()));
+        }
+    }
+
+    Ok(None)
+}
+
+fn chunks(png: &[u8]) -> Result<Vec<Chunk<'_>>> {
+    ensure!(png.starts_with(&SIGNATURE), "data is not a PNG file");
+
+    let mut chunks = Vec::new();
+    let mut offset = SIGNATURE.len();
+
+    loop {
+        let header = png
+            .get(offset..offset + 8)
+            .ok_or_else(|| format_err!("PNG file ended before the IEND chunk"))?;
+
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = [header[4], header[5], header[6], header[7]];
+
+        let data = match png.get(offset + 8..offset + 8 + len) {
+            Some(data) => data,
+            None => bail!("PNG chunk at offset {} is truncated", offset),
+        };
+
+        let crc = png
+            .get(offset + 8 + len..offset + 12 + len)
+            .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+            .ok_or_else(|| format_err!("PNG chunk at offset {} is truncated", offset))?;
+
+        chunks.push(Chunk {
+            offset,
+            kind,
+            data,
+            crc,
+        });
+
+        if &kind == b"IEND" {
+            return Ok(chunks);
+        }
+
+        offset += len + 12;
+    }
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc32(&kind, data).to_be_bytes());
+}
+
+/// CRC-32 as used by PNG, computed over the chunk type and data
+fn crc32(kind: &[u8], data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for byte in kind.iter().chain(data) {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
@@ -0,0 +1,25 @@
+//! The types most programs using this crate need, for glob importing.
+//!
+//! ```
+//! use tinyvg::prelude::*;
+//! # use std::fs::File;
+//!
+//! let image: Image = Decoder::new(File::open("data/shield.tvg").unwrap())
+//!     .decode()
+//!     .unwrap();
+//!
+//! let options = RenderOptions {
+//!     padding: 1.0,
+//!     ..Default::default()
+//! };
+//!
+//! let mut png = Vec::new();
+//! image.render_png_with_options(&mut png, &options).unwrap();
+//! ```
+
+pub use crate::decode::{DecodeOptions, DecodeWarning, Decoder, PartialDecode, TrailerCheck};
+pub use crate::encode::Encoder;
+pub use crate::error::Error;
+pub use crate::format::{Color, Command, Image, Point, Rect, Style};
+pub use crate::geometry::CoordinateOverflow;
+pub use crate::render::{RenderOptions, RenderStrategy};
@@ -0,0 +1,430 @@
+//! Named bundles of export settings, so that common outputs (thumbnails,
+//! favicons, print) don't need every consumer to pick their own combination
+//! of size, background and sampling.
+
+use crate::error::{bail, ensure, format_err, Error, Result};
+use crate::format::Color;
+
+/// File format produced by a `Preset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Raster PNG image
+    Png,
+
+    /// Vector PDF document, one page sized to the image
+    Pdf,
+
+    /// Vector Encapsulated PostScript, with a bounding box sized to the image
+    Eps,
+
+    /// Raster JPEG image, composited onto white as JPEG has no transparency.
+    /// Needs the `raster-formats` feature.
+    Jpeg,
+
+    /// Raster lossless WebP image. Needs the `raster-formats` feature.
+    Webp,
+
+    /// Raster BMP image. Needs the `raster-formats` feature.
+    Bmp,
+
+    /// Raster TGA image. Needs the `raster-formats` feature.
+    Tga,
+}
+
+impl OutputFormat {
+    /// File extension for this format, without the leading dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Eps => "eps",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Bmp => "bmp",
+            OutputFormat::Tga => "tga",
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = Error;
+
+    /// Parse a format from its file extension
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "png" => Ok(OutputFormat::Png),
+            "pdf" => Ok(OutputFormat::Pdf),
+            "eps" => Ok(OutputFormat::Eps),
+            "jpg" | "jpeg" => Ok(OutputFormat::Jpeg),
+            "webp" => Ok(OutputFormat::Webp),
+            "bmp" => Ok(OutputFormat::Bmp),
+            "tga" => Ok(OutputFormat::Tga),
+            _ => Err(format_err!("unknown output format {}", s)),
+        }
+    }
+}
+
+/// Length of one side of the output, either in pixels or relative to the
+/// image. Parsed from strings like `512`, `512px`, `2x`, `150%` or, for
+/// physical sizes, `30mm@300dpi`. Units are `px`, `mm`, `cm`, `in` and `pt`.
+/// Numbers always use `.` as the decimal separator, whatever the locale.
+///
+/// ```
+/// # use tinyvg::preset::Length;
+/// assert_eq!("512px".parse::<Length>().unwrap(), Length::Pixels(512));
+/// assert_eq!("150%".parse::<Length>().unwrap(), Length::Scale(1.5));
+/// assert_eq!("2x".parse::<Length>().unwrap(), Length::Scale(2.0));
+///
+/// // 1 inch at 300 dots per inch
+/// assert_eq!("25.4mm@300dpi".parse::<Length>().unwrap(), Length::Pixels(300));
+/// assert_eq!("1in@96dpi".parse::<Length>().unwrap(), Length::Pixels(96));
+///
+/// assert!("30mm".parse::<Length>().is_err());
+/// assert!("1,5x".parse::<Length>().is_err());
+/// assert!("0px".parse::<Length>().is_err());
+///
+/// assert_eq!(Length::Scale(1.5).resolve(24), 36);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    /// A number of pixels (or points for PDF)
+    Pixels(u32),
+
+    /// A multiple of the length of the image
+    Scale(f64),
+}
+
+impl Length {
+    /// Number of pixels for an image side of `image` units, at least 1
+    pub fn resolve(self, image: u32) -> u32 {
+        match self {
+            Length::Pixels(pixels) => pixels,
+            Length::Scale(scale) => ((image as f64 * scale).round() as u32).max(1),
+        }
+    }
+}
+
+impl std::str::FromStr for Length {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        if let Some(percent) = s.strip_suffix('%') {
+            return Ok(Length::Scale(parse_positive(percent)? / 100.0));
+        }
+
+        let (length, dpi) = match s.split_once('@') {
+            Some((length, resolution)) => {
+                let dpi = resolution.strip_suffix("dpi").ok_or_else(|| {
+                    format_err!("expected a resolution like 300dpi, found {}", resolution)
+                })?;
+
+                (length, Some(parse_positive(dpi)?))
+            }
+            None => (s, None),
+        };
+
+        let number_end = length
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(length.len());
+        let (value, unit) = length.split_at(number_end);
+        let value = parse_positive(value)?;
+
+        // Units are matched as a whole, so that the `x` of `px` is not taken
+        // for a scale factor
+        let inches_per_unit = match unit {
+            "x" => {
+                ensure!(dpi.is_none(), "scale factors don't take a resolution");
+                return Ok(Length::Scale(value));
+            }
+            "" | "px" => {
+                ensure!(dpi.is_none(), "pixel sizes don't take a resolution");
+                return pixels(value);
+            }
+            "mm" => 1.0 / 25.4,
+            "cm" => 1.0 / 2.54,
+            "in" => 1.0,
+            "pt" => 1.0 / 72.0,
+            _ => bail!("unknown unit {:?}, expected x, px, mm, cm, in or pt", unit),
+        };
+
+        let dpi =
+            dpi.ok_or_else(|| format_err!("{} needs a resolution, e.g. {}@300dpi", unit, length))?;
+
+        pixels(value * inches_per_unit * dpi)
+    }
+}
+
+/// Parse a finite number greater than zero, with `.` as the decimal separator
+fn parse_positive(s: &str) -> Result<f64> {
+    let value: f64 = s
+        .parse()
+        .map_err(|_| format_err!("expected a number like 1.5, found {:?}", s))?;
+
+    ensure!(
+        value.is_finite() && value > 0.0,
+        "expected a number greater than zero, found {}",
+        s
+    );
+
+    Ok(value)
+}
+
+/// Round a length to whole pixels, which must be at least one
+fn pixels(value: f64) -> Result<Length> {
+    let pixels = value.round();
+
+    ensure!(
+        (1.0..=u32::MAX as f64).contains(&pixels),
+        "{} pixels is not a valid size",
+        pixels
+    );
+
+    Ok(Length::Pixels(pixels as u32))
+}
+
+/// A bundle of export settings
+///
+/// ```
+/// # use tinyvg::Decoder;
+/// # use tinyvg::preset::Preset;
+/// # use std::fs::File;
+/// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let mut png = Vec::new();
+/// image.render_preset(&mut png, &Preset::web_thumbnail()).unwrap();
+///
+/// assert_eq!(Preset::from_name("favicon"), Some(Preset::favicon()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    /// Length of the longer side of the output in pixels (or points for PDF),
+    /// the other side is scaled to keep the aspect ratio. `None` keeps the
+    /// size from the image header.
+    pub size: Option<u32>,
+
+    /// Width of the output, overrides `size`. If only one of `width` and
+    /// `height` is set, the other side is scaled to keep the aspect ratio.
+    pub width: Option<Length>,
+
+    /// Height of the output, overrides `size`, see `width`
+    pub height: Option<Length>,
+
+    /// Color the output is filled with before drawing, `None` leaves it
+    /// transparent
+    pub background: Option<Color>,
+
+    /// Raster images are rendered at this many times the output size and
+    /// then scaled down, which smooths edges of small outputs. 1 disables
+    /// supersampling. Ignored for vector formats.
+    pub supersampling: u32,
+
+    /// Format of the output
+    pub format: OutputFormat,
+}
+
+impl Default for Preset {
+    /// PNG at the size of the image, without background or supersampling
+    fn default() -> Self {
+        Self {
+            size: None,
+            width: None,
+            height: None,
+            background: None,
+            supersampling: 1,
+            format: OutputFormat::Png,
+        }
+    }
+}
+
+impl Preset {
+    /// Names accepted by `Preset::from_name`
+    pub const NAMES: &[&str] = &["web-thumbnail", "favicon", "print-pdf"];
+
+    /// 256 pixel PNG on a white background, for gallery and link previews
+    pub fn web_thumbnail() -> Self {
+        Self {
+            size: Some(256),
+            width: None,
+            height: None,
+            background: Some(Color::WHITE),
+            supersampling: 2,
+            format: OutputFormat::Png,
+        }
+    }
+
+    /// 32 pixel transparent PNG, heavily supersampled since small icons show
+    /// every jagged edge
+    pub fn favicon() -> Self {
+        Self {
+            size: Some(32),
+            width: None,
+            height: None,
+            background: None,
+            supersampling: 4,
+            format: OutputFormat::Png,
+        }
+    }
+
+    /// PDF at the size of the image on a white page
+    pub fn print_pdf() -> Self {
+        Self {
+            size: None,
+            width: None,
+            height: None,
+            background: Some(Color::WHITE),
+            supersampling: 1,
+            format: OutputFormat::Pdf,
+        }
+    }
+
+    /// Look up a preset by name, as listed in `Preset::NAMES`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "web-thumbnail" => Some(Self::web_thumbnail()),
+            "favicon" => Some(Self::favicon()),
+            "print-pdf" => Some(Self::print_pdf()),
+            _ => None,
+        }
+    }
+
+    /// Output size in pixels for an image of the given size
+    ///
+    /// ```
+    /// # use tinyvg::preset::{Length, Preset};
+    /// let preset = Preset {
+    ///     width: Some(Length::Pixels(48)),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(preset.output_size(24, 12), (48, 24));
+    ///
+    /// let preset = Preset {
+    ///     width: Some(Length::Scale(1.5)),
+    ///     height: Some(Length::Pixels(10)),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(preset.output_size(24, 12), (36, 10));
+    /// ```
+    pub fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let keep_aspect = |length: u32, from: u32, to: u32| {
+            if from == 0 {
+                to
+            } else {
+                ((to as f64 * length as f64 / from as f64).round() as u32).max(1)
+            }
+        };
+
+        match (self.width, self.height) {
+            (Some(w), Some(h)) => return (w.resolve(width), h.resolve(height)),
+            (Some(w), None) => {
+                let w = w.resolve(width);
+                return (w, keep_aspect(w, width, height));
+            }
+            (None, Some(h)) => {
+                let h = h.resolve(height);
+                return (keep_aspect(h, height, width), h);
+            }
+            (None, None) => {}
+        }
+
+        let size = match self.size {
+            Some(size) if width > 0 && height > 0 => size as f64,
+            _ => return (width, height),
+        };
+
+        let scale = size / width.max(height) as f64;
+
+        (
+            ((width as f64 * scale).round() as u32).max(1),
+            ((height as f64 * scale).round() as u32).max(1),
+        )
+    }
+}
+
+#[cfg(feature = "render-png")]
+impl crate::format::Image {
+    /// Render this image with the settings of a preset, writing data in the
+    /// preset's format to the given `std::io::Write`
+    pub fn render_preset(&self, writer: &mut impl std::io::Write, preset: &Preset) -> Result<()> {
+        use crate::raster::encode_pixmap;
+        use crate::render::{surface_pixmap, BackgroundPlate, RenderOptions};
+
+        ensure!(
+            preset.supersampling >= 1,
+            "supersampling factor must be at least 1"
+        );
+
+        let options = RenderOptions {
+            plate: preset.background.clone().map(|color| BackgroundPlate {
+                color,
+                corner_radius: 0.0,
+            }),
+            ..Default::default()
+        };
+
+        let (width, height) = preset.output_size(self.header.width, self.header.height);
+
+        match preset.format {
+            OutputFormat::Pdf => {
+                self.render_pdf_sized(writer, width as f64, height as f64, &options)
+            }
+            OutputFormat::Eps => {
+                self.render_eps_sized(writer, width as f64, height as f64, &options)
+            }
+            format => {
+                let factor = preset.supersampling as i32;
+                options.check_output_size(
+                    width as u64 * factor as u64,
+                    height as u64 * factor as u64,
+                )?;
+
+                let (surface, render_result) =
+                    self.render_surface(width as i32 * factor, height as i32 * factor, &options)?;
+
+                let surface = if factor == 1 {
+                    surface
+                } else {
+                    downsample(&surface, width as i32, height as i32, factor)?
+                };
+
+                if format == OutputFormat::Png {
+                    surface.write_to_png(writer)?;
+                } else {
+                    encode_pixmap(&surface_pixmap(&surface, false)?, format, writer)?;
+                }
+
+                render_result
+            }
+        }
+    }
+}
+
+/// Scale a surface rendered at `factor` times the target size down to the
+/// target size
+#[cfg(feature = "render-png")]
+fn downsample(
+    surface: &cairo::ImageSurface,
+    width: i32,
+    height: i32,
+    factor: i32,
+) -> Result<cairo::ImageSurface> {
+    use crate::error::Context;
+
+    let target = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)
+        .wrap_err("failed to create cairo surface")?;
+    let cr = cairo::Context::new(&target).wrap_err("failed to create cairo context")?;
+
+    cr.scale(1.0 / factor as f64, 1.0 / factor as f64);
+    cr.set_source_surface(surface, 0.0, 0.0)
+        .wrap_err("failed to set source surface")?;
+    cr.source().set_filter(cairo::Filter::Good);
+    cr.paint().wrap_err("failed to downsample surface")?;
+
+    drop(cr);
+    target.flush();
+
+    Ok(target)
+}
@@ -0,0 +1,174 @@
+//! Progress of operations on many files, reported as typed events over a
+//! channel, so that GUIs and other front ends can show progress without
+//! parsing the output of the command line tool.
+//!
+//! ```
+//! # use std::path::{Path, PathBuf};
+//! # use std::sync::mpsc;
+//! # use tinyvg::progress::{run_batch, BatchOptions, ProgressEvent};
+//! # use tinyvg::Decoder;
+//! let files = vec![PathBuf::from("data/shield.tvg"), PathBuf::from("data/missing.tvg")];
+//! let (events, receiver) = mpsc::channel();
+//!
+//! let options = BatchOptions {
+//!     jobs: 2,
+//!     keep_going: true,
+//! };
+//! let summary = run_batch(&files, &options, &events, |path: &Path| {
+//!     let bytes = std::fs::read(path)?;
+//!     Decoder::from_slice(&bytes).decode()?;
+//!     Ok(())
+//! });
+//! drop(events);
+//!
+//! assert_eq!((summary.succeeded, summary.failed, summary.skipped), (1, 1, 0));
+//!
+//! let failed: Vec<_> = receiver
+//!     .iter()
+//!     .filter_map(|event| match event {
+//!         ProgressEvent::Failed { file, .. } => Some(file),
+//!         _ => None,
+//!     })
+//!     .collect();
+//! assert_eq!(failed, [PathBuf::from("data/missing.tvg")]);
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+
+/// What happened to a file of a batch operation. Every file that is started
+/// is followed by either `Finished` or `Failed`.
+#[derive(Debug)]
+pub enum ProgressEvent {
+    /// Work on the file started
+    Started {
+        /// The file
+        file: PathBuf,
+    },
+
+    /// Work on the file succeeded
+    Finished {
+        /// The file
+        file: PathBuf,
+
+        /// How long the work on the file took
+        duration: Duration,
+    },
+
+    /// Work on the file failed
+    Failed {
+        /// The file
+        file: PathBuf,
+
+        /// Why it failed
+        error: Error,
+    },
+}
+
+/// How a batch operation runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchOptions {
+    /// Number of files worked on in parallel, at least 1
+    pub jobs: usize,
+
+    /// Keep working on the remaining files after a file fails, instead of
+    /// stopping at the first failure
+    pub keep_going: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            jobs: 1,
+            keep_going: false,
+        }
+    }
+}
+
+/// Number of files of a batch operation by outcome
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    /// Files whose work succeeded
+    pub succeeded: usize,
+
+    /// Files whose work failed
+    pub failed: usize,
+
+    /// Files that were not started, because an earlier file failed and
+    /// `BatchOptions::keep_going` was not set
+    pub skipped: usize,
+}
+
+/// Run `work` on every file, in order but with `BatchOptions::jobs` files in
+/// parallel, and send a `ProgressEvent` to `events` as each file is started
+/// and done. Events are not sent once the receiver is dropped, the work
+/// continues.
+pub fn run_batch(
+    files: &[PathBuf],
+    options: &BatchOptions,
+    events: &Sender<ProgressEvent>,
+    work: impl Fn(&Path) -> Result<()> + Sync,
+) -> BatchSummary {
+    let next = AtomicUsize::new(0);
+    let succeeded = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.jobs.max(1) {
+            let events = events.clone();
+            let (next, succeeded, failed, stop, work) = (&next, &succeeded, &failed, &stop, &work);
+
+            scope.spawn(move || loop {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let file = match files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    Some(file) => file,
+                    None => break,
+                };
+
+                let _ = events.send(ProgressEvent::Started { file: file.clone() });
+                let start = Instant::now();
+
+                let event = match work(file.as_path()) {
+                    Ok(()) => {
+                        succeeded.fetch_add(1, Ordering::Relaxed);
+
+                        ProgressEvent::Finished {
+                            file: file.clone(),
+                            duration: start.elapsed(),
+                        }
+                    }
+                    Err(error) => {
+                        failed.fetch_add(1, Ordering::Relaxed);
+
+                        if !options.keep_going {
+                            stop.store(true, Ordering::Relaxed);
+                        }
+
+                        ProgressEvent::Failed {
+                            file: file.clone(),
+                            error,
+                        }
+                    }
+                };
+                let _ = events.send(event);
+            });
+        }
+    });
+
+    let succeeded = succeeded.into_inner();
+    let failed = failed.into_inner();
+
+    BatchSummary {
+        succeeded,
+        failed,
+        skipped: files.len() - succeeded - failed,
+    }
+}
@@ -0,0 +1,102 @@
+//! Progressive rendering for interactive viewers. A quick preview is drawn
+//! first, without antialiasing and with curves flattened to coarse lines, so
+//! that something can be shown right away while panning or zooming. A refined
+//! pass at full quality follows, unless the viewer no longer needs it.
+
+use std::ops::ControlFlow;
+
+use cairo::{Antialias, Format, ImageSurface};
+
+use crate::error::{Context, Result};
+use crate::format::Image;
+use crate::render::RenderOptions;
+
+/// Maximum distance in pixels between a curve and its flattened lines in the
+/// preview pass
+const PREVIEW_TOLERANCE: f64 = 1.0;
+
+/// The pass that a surface passed to the `render_progressive` callback was
+/// rendered by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderPass {
+    /// Drawn without antialiasing and with flattened curves
+    Preview,
+
+    /// Drawn at full quality
+    Refined,
+}
+
+impl Image {
+    /// Render this image to `width` by `height` pixel surfaces in two passes,
+    /// calling `on_pass` with the surface after each pass. The image is scaled
+    /// to fit. Returning `ControlFlow::Break` from the callback after the
+    /// preview pass skips the refined pass, e.g. when the viewport has moved
+    /// again in the meantime.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::ops::ControlFlow;
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::progressive::RenderPass;
+    /// let image = Decoder::new(File::open("data/tiger.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mut passes = Vec::new();
+    /// image
+    ///     .render_progressive(200, 200, &Default::default(), |pass, surface| {
+    ///         assert_eq!(surface.width(), 200);
+    ///         passes.push(pass);
+    ///         ControlFlow::Continue(())
+    ///     })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(passes, [RenderPass::Preview, RenderPass::Refined]);
+    ///
+    /// // Stopping after the preview
+    /// passes.clear();
+    /// image
+    ///     .render_progressive(200, 200, &Default::default(), |pass, _| {
+    ///         passes.push(pass);
+    ///         ControlFlow::Break(())
+    ///     })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(passes, [RenderPass::Preview]);
+    /// ```
+    pub fn render_progressive(
+        &self,
+        width: i32,
+        height: i32,
+        options: &RenderOptions,
+        mut on_pass: impl FnMut(RenderPass, &ImageSurface) -> ControlFlow<()>,
+    ) -> Result<()> {
+        // Flatten in image units so that the lines stay within a pixel of the
+        // curves at the target size
+        let scale = (width as f64 / self.header.width.max(1) as f64)
+            .min(height as f64 / self.header.height.max(1) as f64);
+        let mut preview = self.clone();
+        preview.flatten_curves(PREVIEW_TOLERANCE / scale.max(f64::EPSILON));
+
+        options.check_output_size(width as u32 as u64, height as u32 as u64)?;
+
+        let surface = ImageSurface::create(Format::ARgb32, width, height)
+            .wrap_err("failed to create cairo surface")?;
+        let cr = cairo::Context::new(&surface).wrap_err("failed to create cairo context")?;
+        cr.set_antialias(Antialias::None);
+
+        preview.render_to_context(&cr, width as f64, height as f64, options)??;
+        surface.flush();
+
+        if on_pass(RenderPass::Preview, &surface).is_break() {
+            return Ok(());
+        }
+
+        let (surface, render_result) = self.render_surface(width, height, options)?;
+        render_result?;
+
+        let _ = on_pass(RenderPass::Refined, &surface);
+
+        Ok(())
+    }
+}
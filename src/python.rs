@@ -0,0 +1,160 @@
+//! Python bindings with `pyo3`, so that asset pipelines written in Python
+//! can decode and render thousands of files in one process instead of
+//! running the CLI per file. Enabled by the `python` feature, and built into
+//! a Python package with `maturin build --features python`.
+//!
+//! ```python
+//! import tinyvg
+//!
+//! with open("icon.tvg", "rb") as f:
+//!     data = f.read()
+//!
+//! image = tinyvg.decode(data)
+//! print(image.width, image.height, [command.name for command in image.commands])
+//!
+//! with open("icon@2x.png", "wb") as f:
+//!     f.write(image.render_png(scale=2.0))
+//! ```
+//!
+//! Failures raise `ValueError` with the message of the error and its causes.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::error::{full_message, Error, Result};
+use crate::{Decoder, Image};
+
+/// A decoded TinyVG image, `tinyvg.Image` in Python
+#[pyclass(name = "Image", module = "tinyvg")]
+struct PyImage {
+    image: Image,
+}
+
+#[pymethods]
+impl PyImage {
+    /// Width from the header, in image units
+    #[getter]
+    fn width(&self) -> u32 {
+        self.image.header.width
+    }
+
+    /// Height from the header, in image units
+    #[getter]
+    fn height(&self) -> u32 {
+        self.image.header.height
+    }
+
+    /// Color table as `(r, g, b, a)` tuples of integers between 0 and 255
+    #[getter]
+    fn colors(&self) -> Vec<(u8, u8, u8, u8)> {
+        self.image
+            .color_table
+            .iter()
+            .map(|color| color.as_rgba8())
+            .collect()
+    }
+
+    /// Draw commands in drawing order
+    #[getter]
+    fn commands(&self) -> Vec<PyCommand> {
+        self.image
+            .commands
+            .iter()
+            .map(|cmd| {
+                let bounds = cmd.bounds();
+
+                PyCommand {
+                    name: cmd.name(),
+                    bounds: (bounds.x0, bounds.y0, bounds.x1, bounds.y1),
+                }
+            })
+            .collect()
+    }
+
+    /// Render to PNG at `scale` times the size in the header
+    #[args(scale = "1.0")]
+    fn render_png<'py>(&self, py: Python<'py>, scale: f64) -> PyResult<&'py PyBytes> {
+        let png = py.allow_threads(|| render_png(&self.image, scale));
+
+        Ok(PyBytes::new(py, &png.map_err(value_error)?))
+    }
+
+    /// Encode as TinyVG binary data
+    fn encode<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        let mut bytes = Vec::new();
+        self.image.encode(&mut bytes).map_err(value_error)?;
+
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<tinyvg.Image {}x{}, {} commands>",
+            self.image.header.width,
+            self.image.header.height,
+            self.image.commands.len()
+        )
+    }
+}
+
+/// A draw command, `tinyvg.Command` in Python
+#[pyclass(name = "Command", module = "tinyvg")]
+#[derive(Clone)]
+struct PyCommand {
+    /// Name of the command in the TinyVG specification, e.g. `fill_path`
+    #[pyo3(get)]
+    name: &'static str,
+
+    /// Bounding box as `(x0, y0, x1, y1)` in image units
+    #[pyo3(get)]
+    bounds: (f64, f64, f64, f64),
+}
+
+/// Decode TinyVG binary data, with the limits of `DecodeOptions::default`
+#[pyfunction]
+fn decode(data: &[u8]) -> PyResult<PyImage> {
+    let image = Decoder::new(data).decode().map_err(value_error)?;
+
+    Ok(PyImage { image })
+}
+
+/// Decode TinyVG binary data and render it to PNG at `scale` times the size
+/// in its header
+#[pyfunction(scale = "1.0")]
+#[pyo3(name = "render_png")]
+fn decode_and_render_png<'py>(py: Python<'py>, data: &[u8], scale: f64) -> PyResult<&'py PyBytes> {
+    decode(data)?.render_png(py, scale)
+}
+
+#[cfg(any(feature = "render-png", feature = "render-skia"))]
+fn render_png(image: &Image, scale: f64) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image.render_png_scaled(
+        &mut png,
+        crate::RenderSize::Scale(scale),
+        &crate::RenderOptions::default(),
+    )?;
+
+    Ok(png)
+}
+
+#[cfg(not(any(feature = "render-png", feature = "render-skia")))]
+fn render_png(_image: &Image, _scale: f64) -> Result<Vec<u8>> {
+    crate::error::bail!("rendering needs the render-png or render-skia feature")
+}
+
+fn value_error(error: Error) -> PyErr {
+    PyValueError::new_err(full_message(&error))
+}
+
+/// The `tinyvg` Python module
+#[pymodule]
+fn tinyvg(_py: Python<'_>, module: &PyModule) -> PyResult<()> {
+    module.add_class::<PyImage>()?;
+    module.add_class::<PyCommand>()?;
+    module.add_function(wrap_pyfunction!(decode, module)?)?;
+    module.add_function(wrap_pyfunction!(decode_and_render_png, module)?)?;
+
+    Ok(())
+}
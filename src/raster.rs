@@ -0,0 +1,81 @@
+//! Encoding of rendered pixmaps into raster formats other than PNG, with the
+//! `image` crate. The encoders are only built with the `raster-formats`
+//! feature, without it only PNG is supported.
+
+use crate::error::{bail, Result};
+use crate::preset::OutputFormat;
+use crate::render::RasterImage;
+
+/// Quality of JPEG output, between 1 and 100
+#[cfg(feature = "raster-formats")]
+const JPEG_QUALITY: u8 = 90;
+
+/// Encode a pixmap in a raster format other than PNG. JPEG has no alpha
+/// channel, so pixels are composited onto white.
+#[cfg(feature = "raster-formats")]
+pub(crate) fn encode_pixmap(
+    pixmap: &RasterImage,
+    format: OutputFormat,
+    writer: &mut impl std::io::Write,
+) -> Result<()> {
+    use crate::error::Context;
+    use image::codecs::{bmp::BmpEncoder, jpeg::JpegEncoder, tga::TgaEncoder, webp::WebPEncoder};
+    use image::{ColorType, ImageEncoder};
+
+    let (width, height) = (pixmap.width, pixmap.height);
+    let rgba = pixmap.as_rgba8();
+    let rgba = rgba.as_ref();
+
+    match format {
+        OutputFormat::Jpeg => {
+            let rgb: Vec<u8> = rgba
+                .chunks_exact(4)
+                .flat_map(|pixel| {
+                    let alpha = pixel[3] as u32;
+                    let over_white = move |channel: u8| {
+                        ((channel as u32 * alpha + 255 * (255 - alpha) + 127) / 255) as u8
+                    };
+
+                    [
+                        over_white(pixel[0]),
+                        over_white(pixel[1]),
+                        over_white(pixel[2]),
+                    ]
+                })
+                .collect();
+
+            JpegEncoder::new_with_quality(writer, JPEG_QUALITY).write_image(
+                &rgb,
+                width,
+                height,
+                ColorType::Rgb8,
+            )
+        }
+        OutputFormat::Webp => {
+            WebPEncoder::new_lossless(writer).write_image(rgba, width, height, ColorType::Rgba8)
+        }
+        OutputFormat::Bmp => {
+            BmpEncoder::new(writer).write_image(rgba, width, height, ColorType::Rgba8)
+        }
+        OutputFormat::Tga => {
+            TgaEncoder::new(writer).write_image(rgba, width, height, ColorType::Rgba8)
+        }
+        OutputFormat::Png | OutputFormat::Pdf | OutputFormat::Eps => {
+            bail!("{} is not encoded from pixmaps", format.extension())
+        }
+    }
+    .wrap_err_with(|| format!("failed to encode {}", format.extension()))
+}
+
+/// Without the `raster-formats` feature there are no encoders
+#[cfg(not(feature = "raster-formats"))]
+pub(crate) fn encode_pixmap(
+    _pixmap: &RasterImage,
+    format: OutputFormat,
+    _writer: &mut impl std::io::Write,
+) -> Result<()> {
+    bail!(
+        "{} output needs the raster-formats feature",
+        format.extension()
+    )
+}
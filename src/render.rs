@@ -1,9 +1,822 @@
-use eyre::{Context, Result};
-use kurbo::{Arc, BezPath, CubicBez, Line, QuadBez, SvgArc, Vec2};
-use piet::kurbo::{Point, Size};
-use piet::{Color, FixedLinearGradient, FixedRadialGradient, GradientStop, RenderContext};
+use std::borrow::Cow;
+#[cfg(feature = "piet")]
+use std::collections::HashMap;
 
-use crate::format::{Command, OutlineStyle, Segment, SegmentCommand, SegmentCommandKind, Style};
+#[cfg(feature = "piet")]
+use kurbo::Shape;
+use kurbo::{Affine, Arc, BezPath, Point, Rect, SvgArc, Vec2};
+#[cfg(feature = "piet")]
+use piet::{FixedLinearGradient, FixedRadialGradient, GradientStop, RenderContext};
+
+#[cfg(feature = "render-png")]
+use crate::error::Context;
+use crate::error::{ensure, format_err, Error, Result};
+#[cfg(feature = "piet")]
+use crate::format::OutlineStyle;
+use crate::format::{Color, Segment, SegmentCommand, SegmentCommandKind};
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+use crate::format::{Command, Style};
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+use crate::geometry::has_area;
+use crate::geometry::StrokeScaling;
+use crate::overlay::DebugOverlay;
+
+/// Options that control how an image is rendered
+///
+/// ```
+/// # use tinyvg::{Decoder, RenderOptions, RenderStrategy};
+/// # use std::fs::File;
+/// let image = Decoder::new(File::open("data/tiger.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let options = RenderOptions {
+///     strategy: RenderStrategy::Batched,
+///     ..Default::default()
+/// };
+///
+/// let mut png = Vec::new();
+/// image.render_png_with_options(&mut png, &options).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    /// How commands are turned into draw calls on the render context
+    pub strategy: RenderStrategy,
+
+    /// Whether geometry is aligned to the device pixel grid before drawing
+    pub snap: SnapMode,
+
+    /// Adjustments applied to stroke widths before drawing
+    pub line_widths: LineWidthOverrides,
+
+    /// How stroke widths follow the transform of the render context, applied
+    /// after `line_widths`. Widths are in image units for
+    /// `StrokeScaling::Constant`, and the minimum of
+    /// `StrokeScaling::ClampMin` is in device pixels.
+    pub stroke_scaling: StrokeScaling,
+
+    /// Transform applied to everything that is drawn, in image units, before
+    /// the image is scaled to the output, e.g. to rotate or mirror an icon
+    /// without changing the image. Line widths scale with the transform,
+    /// subject to `stroke_scaling`. Geometry moved outside of the image is
+    /// clipped by the output like any other, and `viewport` still selects
+    /// commands by their untransformed bounds.
+    pub transform: Affine,
+
+    /// Space between the edges of the image and the artwork, in image units.
+    /// The artwork is scaled down uniformly to fit inside the padding and
+    /// centered.
+    pub padding: f64,
+
+    /// Background drawn behind the artwork, covering the whole image
+    /// including the padding
+    pub plate: Option<BackgroundPlate>,
+
+    /// Commands to draw, `None` draws every command
+    pub selection: Option<RenderSelection>,
+
+    /// How gradients whose two points are the same are drawn
+    pub degenerate_gradients: GradientFallback,
+
+    /// Region of the image that is visible, in image units. Commands whose
+    /// bounding box is outside of the viewport are skipped, see
+    /// `Image::draw_with_bounds`. `None` draws every command.
+    pub viewport: Option<Rect>,
+
+    /// Limit on the number of path elements drawn, `None` draws any number.
+    /// Protects servers rendering untrusted files from files that are valid
+    /// but absurdly complex.
+    pub max_primitives: Option<PrimitiveLimit>,
+
+    /// Largest surface that rendering creates, as width and height in
+    /// pixels. Larger renders fail with `Error::OutputTooLarge` before any
+    /// pixels are allocated, so that a file with a huge size in its header
+    /// can't exhaust memory. `None` allows any size. Defaults to 16384x16384.
+    pub max_output_size: Option<(u32, u32)>,
+
+    /// Color filled behind the whole image before anything else is drawn,
+    /// e.g. to match the backdrop an icon is composited onto. `None` leaves
+    /// the background transparent.
+    pub background: Option<Color>,
+
+    /// Whether the render target is cleared to `background` (or to
+    /// transparent) before drawing, replacing its pixels instead of blending
+    /// over them. Only matters when drawing onto an existing render context,
+    /// new surfaces start out transparent.
+    pub clear: bool,
+
+    /// Whether pixmaps hold premultiplied alpha, as most GPU and compositing
+    /// APIs expect, see `RasterImage::format`. PNG data is never
+    /// premultiplied.
+    pub premultiplied: bool,
+
+    /// Pixels the image is drawn onto instead of a transparent surface, e.g.
+    /// application chrome or the previous frame, so that embedders don't need
+    /// a separate compositing pass. Must be the size of the rendered output,
+    /// and is drawn below `background`. Only used when rendering creates the
+    /// surface, as `Image::render_png` and `Image::render_pixmap` do.
+    pub base_surface: Option<std::sync::Arc<RasterImage>>,
+
+    /// Which parts of the commands are drawn and in which order, e.g. to
+    /// preview the strokes only
+    pub passes: RenderPasses,
+
+    /// Geometry drawn over the artwork for debugging, see `DebugOverlay`
+    pub debug: DebugOverlay,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            strategy: RenderStrategy::Sequential,
+            snap: SnapMode::None,
+            line_widths: LineWidthOverrides::default(),
+            stroke_scaling: StrokeScaling::Proportional,
+            transform: Affine::IDENTITY,
+            padding: 0.0,
+            plate: None,
+            selection: None,
+            degenerate_gradients: GradientFallback::FirstColor,
+            viewport: None,
+            max_primitives: None,
+            max_output_size: Some((16384, 16384)),
+            background: None,
+            clear: false,
+            premultiplied: false,
+            base_surface: None,
+            passes: RenderPasses::default(),
+            debug: DebugOverlay::default(),
+        }
+    }
+}
+
+/// Pixels of a rendered image, see `Image::render_pixmap`. Rows are
+/// `stride` bytes apart, so index with the stride (or `pixel`) instead of
+/// assuming that rows are packed, and check `format` before handing the
+/// bytes to an API that expects a specific alpha mode.
+///
+/// ```
+/// # use std::fs::File;
+/// # use std::sync::Arc;
+/// # use tinyvg::{Decoder, PixelFormat, RenderOptions};
+/// # use tinyvg::format::Color;
+/// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let options = RenderOptions {
+///     background: Some(Color::rgb8(0x20, 0x20, 0x20)),
+///     premultiplied: true,
+///     ..Default::default()
+/// };
+/// let pixmap = image.render_pixmap_with_options(&options).unwrap();
+///
+/// // The corners are outside of the shield
+/// assert_eq!(pixmap.format, PixelFormat::Rgba8Premultiplied);
+/// assert_eq!(pixmap.stride, 24 * 4);
+/// assert_eq!(pixmap.pixel(0, 0), [0x20, 0x20, 0x20, 0xff]);
+///
+/// // Pixmaps can be the backdrop of the next render
+/// let options = RenderOptions {
+///     base_surface: Some(Arc::new(pixmap)),
+///     ..Default::default()
+/// };
+/// let pixmap = image.render_pixmap_with_options(&options).unwrap();
+/// assert_eq!(pixmap.pixel(0, 0), [0x20, 0x20, 0x20, 0xff]);
+///
+/// // Straight or premultiplied bytes, whatever the pixmap holds
+/// assert_eq!(pixmap.as_rgba8().len(), 24 * 24 * 4);
+/// assert_eq!(
+///     pixmap.to_premultiplied().format,
+///     PixelFormat::Rgba8Premultiplied
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RasterImage {
+    /// Width in pixels
+    pub width: u32,
+
+    /// Height in pixels
+    pub height: u32,
+
+    /// Number of bytes from the start of one row to the start of the next,
+    /// at least `width * 4`
+    pub stride: usize,
+
+    /// Layout of each pixel
+    pub format: PixelFormat,
+
+    /// Pixels in rows from top to bottom, `stride` bytes per row
+    pub data: Vec<u8>,
+}
+
+/// Former name of `RasterImage`
+pub type Pixmap = RasterImage;
+
+/// Layout of the pixels of a `RasterImage`. Every format is four bytes per
+/// pixel, in the order red, green, blue, alpha.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Straight alpha, as in PNG files and `ImageData` of a canvas
+    Rgba8,
+
+    /// Color channels premultiplied by alpha, as most GPU and compositing
+    /// APIs expect, see `RenderOptions::premultiplied`
+    Rgba8Premultiplied,
+}
+
+impl RasterImage {
+    /// Red, green, blue and alpha of the pixel at `x`, `y`, premultiplied if
+    /// the format is. Panics if the pixel is outside of the image.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+
+        let offset = y as usize * self.stride + x as usize * 4;
+        [
+            self.data[offset],
+            self.data[offset + 1],
+            self.data[offset + 2],
+            self.data[offset + 3],
+        ]
+    }
+
+    /// Whether the color channels are premultiplied by alpha
+    pub fn is_premultiplied(&self) -> bool {
+        self.format == PixelFormat::Rgba8Premultiplied
+    }
+
+    /// Straight RGBA pixels in packed rows of `width * 4` bytes, e.g. for
+    /// `ImageData` or an image encoder. Borrows the data if it already has
+    /// that layout.
+    pub fn as_rgba8(&self) -> Cow<'_, [u8]> {
+        let row_len = self.width as usize * 4;
+
+        if self.stride == row_len && !self.is_premultiplied() {
+            return Cow::Borrowed(&self.data[..row_len * self.height as usize]);
+        }
+
+        let premultiplied = self.is_premultiplied();
+        Cow::Owned(
+            self.pixels()
+                .flat_map(|pixel| {
+                    if premultiplied {
+                        unpremultiply(pixel)
+                    } else {
+                        pixel
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Copy of this image with premultiplied alpha and packed rows, e.g. for
+    /// uploading to a GPU texture
+    pub fn to_premultiplied(&self) -> RasterImage {
+        let premultiplied = self.is_premultiplied();
+
+        RasterImage {
+            width: self.width,
+            height: self.height,
+            stride: self.width as usize * 4,
+            format: PixelFormat::Rgba8Premultiplied,
+            data: self
+                .pixels()
+                .flat_map(|pixel| {
+                    if premultiplied {
+                        pixel
+                    } else {
+                        premultiply(pixel)
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Every pixel in rows from top to bottom, skipping the padding at the
+    /// end of rows
+    fn pixels(&self) -> impl Iterator<Item = [u8; 4]> + '_ {
+        let row_len = self.width as usize * 4;
+
+        self.data
+            .chunks(self.stride.max(1))
+            .take(self.height as usize)
+            .flat_map(move |row| row[..row_len].chunks_exact(4))
+            .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+    }
+
+    /// Pixels in rows from top to bottom as premultiplied red, green, blue
+    /// and alpha, after checking that the image is `width` by `height`
+    #[cfg(any(feature = "piet", feature = "render-skia"))]
+    pub(crate) fn premultiplied_pixels(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<impl Iterator<Item = [u8; 4]> + '_> {
+        ensure!(
+            (self.width, self.height) == (width, height),
+            "base surface is {}x{}, but the output is {}x{}",
+            self.width,
+            self.height,
+            width,
+            height
+        );
+        ensure!(
+            self.stride >= width as usize * 4 && self.data.len() >= self.stride * height as usize,
+            "base surface has {} bytes of pixels, expected {}",
+            self.data.len(),
+            self.stride.max(width as usize * 4) * height as usize
+        );
+
+        let premultiplied = self.is_premultiplied();
+        Ok(self.pixels().map(move |pixel| {
+            if premultiplied {
+                pixel
+            } else {
+                premultiply(pixel)
+            }
+        }))
+    }
+}
+
+/// Multiply the color channels of a straight RGBA pixel by its alpha
+fn premultiply(pixel: [u8; 4]) -> [u8; 4] {
+    let alpha = pixel[3] as u32;
+    let premultiply = |channel: u8| ((channel as u32 * alpha + 127) / 255) as u8;
+
+    [
+        premultiply(pixel[0]),
+        premultiply(pixel[1]),
+        premultiply(pixel[2]),
+        pixel[3],
+    ]
+}
+
+/// Divide the color channels of a premultiplied RGBA pixel by its alpha
+fn unpremultiply(pixel: [u8; 4]) -> [u8; 4] {
+    let alpha = pixel[3] as u32;
+    let unpremultiply = |channel: u8| {
+        (channel as u32 * 255 + alpha / 2)
+            .checked_div(alpha)
+            .map_or(0, |channel| channel.min(255) as u8)
+    };
+
+    [
+        unpremultiply(pixel[0]),
+        unpremultiply(pixel[1]),
+        unpremultiply(pixel[2]),
+        pixel[3],
+    ]
+}
+
+/// Limit on the number of path elements (lines, curves and rectangle edges)
+/// drawn for an image. Arcs count as the number of curves they are drawn
+/// with. The limit is checked before anything is drawn.
+///
+/// ```
+/// # use tinyvg::{Decoder, PrimitiveLimit, RenderOptions};
+/// # use std::fs::File;
+/// let image = Decoder::new(File::open("data/tiger.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let mut options = RenderOptions {
+///     max_primitives: Some(PrimitiveLimit {
+///         count: 1000,
+///         truncate: false,
+///     }),
+///     ..Default::default()
+/// };
+///
+/// let mut png = Vec::new();
+/// assert!(image.render_png_with_options(&mut png, &options).is_err());
+///
+/// // Draw the commands that fit in the limit instead
+/// options.max_primitives = Some(PrimitiveLimit {
+///     count: 1000,
+///     truncate: true,
+/// });
+///
+/// png.clear();
+/// image.render_png_with_options(&mut png, &options).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrimitiveLimit {
+    /// Maximum number of path elements
+    pub count: usize,
+
+    /// Images over the limit are drawn up to the last command that fits in
+    /// the limit if true. If false, drawing fails without drawing anything.
+    pub truncate: bool,
+}
+
+/// How gradients whose two points are the same are drawn. Such gradients
+/// come from buggy exporters, and render backends either fail on them or draw
+/// them black.
+///
+/// ```
+/// # use tinyvg::{GradientFallback, RenderOptions};
+/// # use tinyvg::format::{Color, Command, Image, Point, Rect, Style};
+/// let mut image = Image::empty(16, 16);
+/// image.color_table = vec![Color::WHITE, Color::BLACK];
+/// image.commands.push(Command::FillRectangles {
+///     fill_style: Style::LinearGradient {
+///         point_0: Point::new(8.0, 8.0),
+///         point_1: Point::new(8.0, 8.0),
+///         color_index_0: 0,
+///         color_index_1: 1,
+///     },
+///     rectangles: vec![Rect::new(0.0, 0.0, 16.0, 16.0)],
+///     outline: None,
+/// });
+///
+/// let options = RenderOptions {
+///     degenerate_gradients: GradientFallback::SecondColor,
+///     ..Default::default()
+/// };
+///
+/// let mut png = Vec::new();
+/// image.render_png_with_options(&mut png, &options).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientFallback {
+    /// Fill with the first color of the gradient
+    FirstColor,
+
+    /// Fill with the second color of the gradient
+    SecondColor,
+
+    /// Pass the gradient to the render backend unchanged
+    Unchanged,
+}
+
+/// A set of command indices, used to draw only some commands of an image.
+/// Viewers can toggle commands on and off without cloning and filtering the
+/// image for every frame.
+///
+/// ```
+/// # use tinyvg::{Decoder, RenderOptions, RenderSelection};
+/// # use std::fs::File;
+/// let image = Decoder::new(File::open("data/everything.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let mut selection = RenderSelection::all(image.commands.len());
+/// selection.set(0, false);
+/// assert!(!selection.contains(0));
+/// assert_eq!(selection.len(), image.commands.len() - 1);
+///
+/// let options = RenderOptions {
+///     selection: Some(selection),
+///     ..Default::default()
+/// };
+///
+/// let mut png = Vec::new();
+/// image.render_png_with_options(&mut png, &options).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderSelection {
+    words: Vec<u64>,
+}
+
+impl RenderSelection {
+    /// A selection without any commands
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A selection of the first `count` commands, usually all commands of an
+    /// image
+    pub fn all(count: usize) -> Self {
+        let mut words = vec![u64::MAX; count / 64];
+        if !count.is_multiple_of(64) {
+            words.push((1 << (count % 64)) - 1);
+        }
+
+        Self { words }
+    }
+
+    /// Select or deselect the command at `index`
+    pub fn set(&mut self, index: usize, selected: bool) {
+        let (word, bit) = (index / 64, index % 64);
+
+        if selected {
+            if word >= self.words.len() {
+                self.words.resize(word + 1, 0);
+            }
+
+            self.words[word] |= 1 << bit;
+        } else if let Some(word) = self.words.get_mut(word) {
+            *word &= !(1 << bit);
+        }
+    }
+
+    /// Whether the command at `index` is selected
+    pub fn contains(&self, index: usize) -> bool {
+        self.words
+            .get(index / 64)
+            .is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    /// Number of selected commands
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Whether no command is selected
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+}
+
+/// A rounded rectangle drawn behind the artwork, e.g. to produce consistent
+/// gallery tiles for thumbnails
+///
+/// ```
+/// # use tinyvg::{BackgroundPlate, Decoder, RenderOptions};
+/// # use tinyvg::format::Color;
+/// # use std::fs::File;
+/// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let options = RenderOptions {
+///     padding: 2.0,
+///     plate: Some(BackgroundPlate {
+///         color: Color::WHITE,
+///         corner_radius: 4.0,
+///     }),
+///     ..Default::default()
+/// };
+///
+/// let mut png = Vec::new();
+/// image.render_png_with_options(&mut png, &options).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundPlate {
+    /// Fill color of the plate
+    pub color: Color,
+
+    /// Radius of the corners in image units, zero for square corners
+    pub corner_radius: f64,
+}
+
+/// Adjustments applied uniformly to every stroke width of an image: line
+/// widths of draw commands, per-segment widths of line paths, and outlines of
+/// fill commands. Useful for rendering bolder strokes in accessibility modes
+/// without authoring new images.
+///
+/// ```
+/// # use tinyvg::{Decoder, LineWidthOverrides, RenderOptions};
+/// # use std::fs::File;
+/// let image = Decoder::new(File::open("data/everything.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// // Strokes are at least 2 pixels wide, and 2.5 unit strokes become 4 units
+/// let options = RenderOptions {
+///     line_widths: LineWidthOverrides {
+///         table: vec![(2.5, 4.0)],
+///         minimum: 2.0,
+///     },
+///     ..Default::default()
+/// };
+///
+/// let mut png = Vec::new();
+/// image.render_png_with_options(&mut png, &options).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineWidthOverrides {
+    /// Pairs of `(original, adjusted)` widths in image units. Strokes whose
+    /// width is exactly an original width are drawn with the adjusted width.
+    pub table: Vec<(f64, f64)>,
+
+    /// Minimum stroke width in device pixels, applied after `table`. Zero
+    /// leaves widths unchanged.
+    pub minimum: f64,
+}
+
+/// Which parts of the commands are drawn, and in which order. Outlines of
+/// fill commands count as strokes. Drawing strokes only gives a wireframe
+/// preview, and drawing all fills before all strokes helps telling apart
+/// problems with either in complex files.
+///
+/// ```
+/// # use tinyvg::{Decoder, PassOrder, RenderOptions, RenderPasses};
+/// # use std::fs::File;
+/// let image = Decoder::new(File::open("data/tiger.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let wireframe = RenderOptions {
+///     passes: RenderPasses {
+///         fills: false,
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+///
+/// let mut png = Vec::new();
+/// image.render_png_with_options(&mut png, &wireframe).unwrap();
+///
+/// let fills_first = RenderOptions {
+///     passes: RenderPasses {
+///         order: PassOrder::FillsFirst,
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+///
+/// png.clear();
+/// image.render_png_with_options(&mut png, &fills_first).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderPasses {
+    /// Order in which fills and strokes are drawn
+    pub order: PassOrder,
+
+    /// Whether fills are drawn
+    pub fills: bool,
+
+    /// Whether strokes, including outlines of fill commands, are drawn
+    pub strokes: bool,
+}
+
+impl Default for RenderPasses {
+    fn default() -> Self {
+        Self {
+            order: PassOrder::Interleaved,
+            fills: true,
+            strokes: true,
+        }
+    }
+}
+
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+impl RenderPasses {
+    /// Whether drawing with these passes differs from drawing the commands
+    /// as they are
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Order of fills and strokes, see `RenderPasses`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassOrder {
+    /// Every command is drawn in turn, with outlines drawn over their fills.
+    /// This is how TinyVG files are meant to be drawn.
+    Interleaved,
+
+    /// The fills of every command, followed by the strokes of every command
+    FillsFirst,
+
+    /// The strokes of every command, followed by the fills of every command
+    StrokesFirst,
+}
+
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+impl RenderOptions {
+    /// Whether `line_widths` or `stroke_scaling` change any stroke width
+    pub(crate) fn adjusts_line_widths(&self) -> bool {
+        !self.line_widths.is_empty() || self.stroke_scaling != StrokeScaling::Proportional
+    }
+
+    /// Fail with `Error::OutputTooLarge` if a surface of `width` by `height`
+    /// pixels is larger than `max_output_size`
+    pub(crate) fn check_output_size(&self, width: u64, height: u64) -> Result<()> {
+        match self.max_output_size {
+            Some((max_width, max_height))
+                if width > max_width as u64 || height > max_height as u64 =>
+            {
+                Err(Error::OutputTooLarge {
+                    width,
+                    height,
+                    max_width,
+                    max_height,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+impl LineWidthOverrides {
+    fn is_empty(&self) -> bool {
+        self.table.is_empty() && self.minimum <= 0.0
+    }
+
+    /// Adjust a width in image units, `scale` is the number of device pixels
+    /// per image unit
+    fn apply(&self, width: f64, scale: f64) -> f64 {
+        let width = self
+            .table
+            .iter()
+            .find(|(original, _)| *original == width)
+            .map_or(width, |(_, adjusted)| *adjusted);
+
+        if scale > 0.0 {
+            width.max(self.minimum / scale)
+        } else {
+            width
+        }
+    }
+}
+
+/// Alignment of geometry to the device pixel grid, based on the current
+/// transform of the render context
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnapMode {
+    /// Geometry is drawn exactly as specified
+    None,
+
+    /// Axis-aligned edges are moved onto pixel boundaries (or pixel centers
+    /// for strokes that are an odd number of pixels wide), and stroke widths
+    /// are rounded to whole pixels, at least one pixel wide. Makes small icons
+    /// crisp, at the cost of moving edges by up to half a pixel.
+    Pixel,
+}
+
+/// How commands are turned into draw calls on the render context
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderStrategy {
+    /// Every command is drawn on its own, in order
+    Sequential,
+
+    /// Consecutive fills (without outline) and lines that use the same brush
+    /// are merged into a single draw call, as long as their bounding boxes are
+    /// at least one unit apart so that the output is identical. This reduces
+    /// render context overhead for machine-generated files with thousands of
+    /// shapes of the same color.
+    Batched,
+}
+
+/// Output size of `Image::render_png_scaled`. The image is always scaled
+/// uniformly, so that icons authored at a small size can be rasterized
+/// crisply at any larger size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderSize {
+    /// Multiply the size from the header by a factor
+    Scale(f64),
+
+    /// The largest size that fits inside `width` by `height` pixels and keeps
+    /// the aspect ratio of the image
+    Fit {
+        /// Maximum width in pixels
+        width: u32,
+
+        /// Maximum height in pixels
+        height: u32,
+    },
+}
+
+impl RenderSize {
+    /// Size in pixels for an image of `width` by `height` units, at least one
+    /// pixel in each direction
+    ///
+    /// ```
+    /// # use tinyvg::RenderSize;
+    /// assert_eq!(RenderSize::Scale(2.0).output_size(24, 12).unwrap(), (48, 24));
+    /// assert_eq!(
+    ///     RenderSize::Fit { width: 256, height: 256 }.output_size(24, 12).unwrap(),
+    ///     (256, 128)
+    /// );
+    /// assert!(RenderSize::Scale(-1.0).output_size(24, 12).is_err());
+    /// ```
+    pub fn output_size(self, width: u32, height: u32) -> Result<(u32, u32)> {
+        let scale = match self {
+            RenderSize::Scale(scale) => scale,
+            RenderSize::Fit {
+                width: max_width,
+                height: max_height,
+            } => {
+                ensure!(
+                    max_width > 0 && max_height > 0,
+                    "output size must be at least 1x1 pixels"
+                );
+
+                if width == 0 || height == 0 {
+                    1.0
+                } else {
+                    (max_width as f64 / width as f64).min(max_height as f64 / height as f64)
+                }
+            }
+        };
+
+        ensure!(
+            scale.is_finite() && scale > 0.0,
+            "scale factor must be greater than zero, not {}",
+            scale
+        );
+
+        let scaled = |length: u32| ((length as f64 * scale).round() as u32).max(1);
+
+        Ok((scaled(width), scaled(height)))
+    }
+}
 
 impl crate::format::Image {
     /// Render PNG data to the given `std::io::Write`.
@@ -20,334 +833,1851 @@ impl crate::format::Image {
     /// ```
     #[cfg(feature = "render-png")]
     pub fn render_png(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        self.render_png_with_options(writer, &RenderOptions::default())
+    }
+
+    /// Render PNG data to the given `std::io::Write`, using the given render
+    /// options.
+    #[cfg(feature = "render-png")]
+    pub fn render_png_with_options(
+        &self,
+        writer: &mut impl std::io::Write,
+        options: &RenderOptions,
+    ) -> Result<()> {
+        let (surface, render_result) =
+            self.render_surface(self.header.width as i32, self.header.height as i32, options)?;
+
+        surface.write_to_png(writer)?;
+
+        render_result?;
+
+        Ok(())
+    }
+
+    /// Render PNG data to the given `std::io::Write` at a different size than
+    /// the one in the header. All geometry and line widths are scaled, so the
+    /// output is as sharp as rendering the image at its own size.
+    ///
+    /// ```
+    /// # use tinyvg::{Decoder, RenderOptions, RenderSize};
+    /// # use std::fs::File;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mut png = Vec::new();
+    /// image
+    ///     .render_png_scaled(
+    ///         &mut png,
+    ///         RenderSize::Fit {
+    ///             width: 256,
+    ///             height: 256,
+    ///         },
+    ///         &RenderOptions::default(),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "render-png")]
+    pub fn render_png_scaled(
+        &self,
+        writer: &mut impl std::io::Write,
+        size: RenderSize,
+        options: &RenderOptions,
+    ) -> Result<()> {
+        let (width, height) = size.output_size(self.header.width, self.header.height)?;
+        let (surface, render_result) = self.render_surface(width as i32, height as i32, options)?;
+
+        surface.write_to_png(writer)?;
+
+        render_result?;
+
+        Ok(())
+    }
+
+    /// Render this image at the size in its header to RGBA pixels in memory,
+    /// e.g. for uploading to a GPU texture or compositing without encoding
+    /// and decoding a PNG.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let pixmap = image.render_pixmap().unwrap();
+    ///
+    /// assert_eq!((pixmap.width, pixmap.height), (24, 24));
+    /// assert_eq!(pixmap.data.len(), 24 * 24 * 4);
+    ///
+    /// // The shield is blue next to its left edge
+    /// let [r, g, b, a] = pixmap.pixel(4, 8);
+    /// assert!(b > r && b > g && a == 255);
+    /// ```
+    #[cfg(feature = "render-png")]
+    pub fn render_pixmap(&self) -> Result<RasterImage> {
+        self.render_pixmap_with_options(&RenderOptions::default())
+    }
+
+    /// Render this image to RGBA pixels in memory, using the given render
+    /// options. See `Image::render_pixmap`.
+    #[cfg(feature = "render-png")]
+    pub fn render_pixmap_with_options(&self, options: &RenderOptions) -> Result<RasterImage> {
+        let (surface, render_result) =
+            self.render_surface(self.header.width as i32, self.header.height as i32, options)?;
+        render_result?;
+
+        surface_pixmap(&surface, options.premultiplied)
+    }
+
+    /// Render this image to RGBA pixels in memory at a different size than
+    /// the one in the header, see `RenderSize`.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::{Decoder, Error, RenderOptions, RenderSize};
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let pixmap = image
+    ///     .render_pixmap_scaled(RenderSize::Scale(2.0), &RenderOptions::default())
+    ///     .unwrap();
+    ///
+    /// assert_eq!((pixmap.width, pixmap.height), (48, 48));
+    ///
+    /// // Renders larger than `RenderOptions::max_output_size` fail before any
+    /// // pixels are allocated
+    /// let error = image
+    ///     .render_pixmap_scaled(RenderSize::Scale(1000.0), &RenderOptions::default())
+    ///     .unwrap_err();
+    /// assert!(matches!(error, Error::OutputTooLarge { .. }));
+    /// ```
+    #[cfg(feature = "render-png")]
+    pub fn render_pixmap_scaled(
+        &self,
+        size: RenderSize,
+        options: &RenderOptions,
+    ) -> Result<RasterImage> {
+        let (width, height) = size.output_size(self.header.width, self.header.height)?;
+        let (surface, render_result) = self.render_surface(width as i32, height as i32, options)?;
+        render_result?;
+
+        surface_pixmap(&surface, options.premultiplied)
+    }
+
+    /// Render a PDF document with a single page the size of this image to the
+    /// given `std::io::Write`. Unlike PNG output, shapes stay vectors, so the
+    /// artwork is sharp at any print resolution. Image units are points.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// # use std::fs::File;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mut pdf = Vec::new();
+    /// image.render_pdf(&mut pdf).unwrap();
+    ///
+    /// assert!(pdf.starts_with(b"%PDF"));
+    /// ```
+    #[cfg(feature = "render-png")]
+    pub fn render_pdf(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        self.render_pdf_with_options(writer, &RenderOptions::default())
+    }
+
+    /// Render a PDF document to the given `std::io::Write`, using the given
+    /// render options. See `Image::render_pdf`.
+    #[cfg(feature = "render-png")]
+    pub fn render_pdf_with_options(
+        &self,
+        writer: &mut impl std::io::Write,
+        options: &RenderOptions,
+    ) -> Result<()> {
+        self.render_pdf_sized(
+            writer,
+            self.header.width as f64,
+            self.header.height as f64,
+            options,
+        )
+    }
+
+    /// Render an Encapsulated PostScript file with a bounding box the size of
+    /// this image to the given `std::io::Write`, for print pipelines and
+    /// layout tools that embed EPS. Image units are points.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// # use std::fs::File;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mut eps = Vec::new();
+    /// image.render_eps(&mut eps).unwrap();
+    ///
+    /// assert!(eps.starts_with(b"%!PS-Adobe-3.0 EPSF-3.0"));
+    /// ```
+    #[cfg(feature = "render-png")]
+    pub fn render_eps(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        self.render_eps_with_options(writer, &RenderOptions::default())
+    }
+
+    /// Render an Encapsulated PostScript file to the given `std::io::Write`,
+    /// using the given render options. See `Image::render_eps`.
+    #[cfg(feature = "render-png")]
+    pub fn render_eps_with_options(
+        &self,
+        writer: &mut impl std::io::Write,
+        options: &RenderOptions,
+    ) -> Result<()> {
+        self.render_eps_sized(
+            writer,
+            self.header.width as f64,
+            self.header.height as f64,
+            options,
+        )
+    }
+
+    /// Render this image at the size in its header in any output format, to
+    /// the given `std::io::Write`. Formats other than PNG, PDF and EPS need
+    /// the `raster-formats` feature.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::preset::OutputFormat;
+    /// # use std::fs::File;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mut png = Vec::new();
+    /// image.render_image(OutputFormat::Png, &mut png).unwrap();
+    ///
+    /// assert!(png.starts_with(b"\x89PNG"));
+    /// ```
+    #[cfg(feature = "render-png")]
+    pub fn render_image(
+        &self,
+        format: crate::preset::OutputFormat,
+        writer: &mut impl std::io::Write,
+    ) -> Result<()> {
+        use crate::preset::OutputFormat;
+
+        match format {
+            OutputFormat::Png => self.render_png(writer),
+            OutputFormat::Pdf => self.render_pdf(writer),
+            OutputFormat::Eps => self.render_eps(writer),
+            format => crate::raster::encode_pixmap(&self.render_pixmap()?, format, writer),
+        }
+    }
+
+    /// Render a PDF page of `width` by `height` points, scaling the image to
+    /// fill it
+    #[cfg(feature = "render-png")]
+    pub(crate) fn render_pdf_sized(
+        &self,
+        writer: &mut impl std::io::Write,
+        width: f64,
+        height: f64,
+        options: &RenderOptions,
+    ) -> Result<()> {
+        let surface = cairo::PdfSurface::for_stream(width, height, Vec::<u8>::new())
+            .wrap_err("failed to create PDF surface")?;
+
+        let render_result = self.render_to(&surface, width, height, options)?;
+        write_stream(&surface, writer, "PDF")?;
+
+        render_result
+    }
+
+    /// Render an EPS file of `width` by `height` points, scaling the image to
+    /// fill it
+    #[cfg(feature = "render-png")]
+    pub(crate) fn render_eps_sized(
+        &self,
+        writer: &mut impl std::io::Write,
+        width: f64,
+        height: f64,
+        options: &RenderOptions,
+    ) -> Result<()> {
+        let surface = cairo::PsSurface::for_stream(width, height, Vec::<u8>::new())
+            .wrap_err("failed to create PostScript surface")?;
+        surface.set_eps(true);
+
+        let render_result = self.render_to(&surface, width, height, options)?;
+        write_stream(&surface, writer, "PostScript")?;
+
+        render_result
+    }
+
+    /// Compute a perceptual hash of this image. The image is rendered to a
+    /// `size` by `size` pixel surface (composited onto white), and the hash is
+    /// built from the low frequencies of its discrete cosine transform. Images
+    /// that look alike have hashes with a small hamming distance, even if they
+    /// are encoded very differently. `size` must be at least 8, 32 is a good
+    /// default.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// # use std::fs::File;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mut shifted = image.clone();
+    /// shifted.color_table[0] = tinyvg::format::Color::rgb8(0x2a, 0xae, 0xff);
+    ///
+    /// let a = image.phash(32).unwrap();
+    /// let b = shifted.phash(32).unwrap();
+    ///
+    /// assert!((a ^ b).count_ones() < 8);
+    /// ```
+    #[cfg(feature = "render-png")]
+    pub fn phash(&self, size: u32) -> Result<u64> {
+        use std::f64::consts::PI;
+
+        ensure!(
+            size >= 8,
+            "perceptual hash size must be at least 8, got {}",
+            size
+        );
+
+        let (surface, render_result) =
+            self.render_surface(size as i32, size as i32, &RenderOptions::default())?;
+        render_result?;
+
+        let size = size as usize;
+        let stride = surface.stride() as usize;
+        let mut luma = vec![0.0; size * size];
+
+        surface
+            .with_data(|data| {
+                for y in 0..size {
+                    for x in 0..size {
+                        let offset = y * stride + x * 4;
+                        let pixel = u32::from_ne_bytes([
+                            data[offset],
+                            data[offset + 1],
+                            data[offset + 2],
+                            data[offset + 3],
+                        ]);
+
+                        // Pixels are premultiplied, so compositing onto white
+                        // only needs the inverse alpha added to each channel
+                        let white = 255 - (pixel >> 24);
+                        let red = ((pixel >> 16) & 0xFF) + white;
+                        let green = ((pixel >> 8) & 0xFF) + white;
+                        let blue = (pixel & 0xFF) + white;
+
+                        luma[y * size + x] =
+                            0.299 * red as f64 + 0.587 * green as f64 + 0.114 * blue as f64;
+                    }
+                }
+            })
+            .map_err(|e| format_err!("{}", e))
+            .wrap_err("failed to read rendered surface")?;
+
+        let cosines = (0..8)
+            .map(|k| {
+                (0..size)
+                    .map(|n| ((2 * n + 1) as f64 * k as f64 * PI / (2 * size) as f64).cos())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut coefficients = [0.0; 64];
+        for v in 0..8 {
+            for u in 0..8 {
+                let mut sum = 0.0;
+                for y in 0..size {
+                    for x in 0..size {
+                        sum += luma[y * size + x] * cosines[u][x] * cosines[v][y];
+                    }
+                }
+
+                coefficients[v * 8 + u] = sum;
+            }
+        }
+
+        // The DC coefficient only reflects average brightness, leave it out of
+        // the median so that it does not skew the remaining bits
+        let mut sorted = coefficients[1..].to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let median = sorted[sorted.len() / 2];
+
+        let hash = coefficients
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c > median)
+            .fold(0u64, |hash, (i, _)| hash | (1 << i));
+
+        Ok(hash)
+    }
+
+    /// Render this image onto a new cairo surface of the given size, scaling
+    /// the image to fit. Errors from drawing are returned alongside the
+    /// surface, so that callers can still use a partially rendered image.
+    #[cfg(feature = "render-png")]
+    pub(crate) fn render_surface(
+        &self,
+        width: i32,
+        height: i32,
+        options: &RenderOptions,
+    ) -> Result<(cairo::ImageSurface, Result<()>)> {
         use cairo::{Format, ImageSurface};
+
+        // Sizes from the header are cast from u32, so negative sizes are
+        // huge ones
+        options.check_output_size(width as u32 as u64, height as u32 as u64)?;
+
+        let mut surface = ImageSurface::create(Format::ARgb32, width, height)
+            .wrap_err("failed to create cairo surface")?;
+
+        if let Some(base) = &options.base_surface {
+            let stride = surface.stride() as usize;
+            let mut pixels = base.premultiplied_pixels(width as u32, height as u32)?;
+            let mut data = surface
+                .data()
+                .map_err(|e| format_err!("{}", e))
+                .wrap_err("failed to write base surface")?;
+
+            for row in data.chunks_mut(stride).take(height as usize) {
+                for (pixel, [r, g, b, a]) in row[..width as usize * 4]
+                    .chunks_exact_mut(4)
+                    .zip(&mut pixels)
+                {
+                    // Cairo stores premultiplied ARGB in native endian 32 bit
+                    // words
+                    let argb = u32::from_be_bytes([a, r, g, b]);
+                    pixel.copy_from_slice(&argb.to_ne_bytes());
+                }
+            }
+        }
+
+        let render_result = self.render_to(&surface, width as f64, height as f64, options)?;
+
+        surface.flush();
+
+        Ok((surface, render_result))
+    }
+
+    /// Render this image onto an existing cairo surface, scaling the image to
+    /// fill `width` by `height` surface units. Errors from drawing are
+    /// returned separately from errors setting up the surface, like
+    /// `render_surface`.
+    #[cfg(feature = "render-png")]
+    pub(crate) fn render_to(
+        &self,
+        surface: &cairo::Surface,
+        width: f64,
+        height: f64,
+        options: &RenderOptions,
+    ) -> Result<Result<()>> {
+        let cr = cairo::Context::new(surface).wrap_err("failed to create cairo context")?;
+
+        self.render_to_context(&cr, width, height, options)
+    }
+
+    /// Render this image with an existing cairo context, scaling the image to
+    /// fill `width` by `height` units. Lets callers configure the context,
+    /// e.g. its antialiasing, before drawing.
+    #[cfg(feature = "render-png")]
+    pub(crate) fn render_to_context(
+        &self,
+        cr: &cairo::Context,
+        width: f64,
+        height: f64,
+        options: &RenderOptions,
+    ) -> Result<Result<()>> {
         use piet_cairo::CairoRenderContext;
 
-        let size = Size {
-            width: self.header.width as f64,
-            height: self.header.height as f64,
+        let scale = |target: f64, source: u32| {
+            if source == 0 {
+                1.0
+            } else {
+                target / source as f64
+            }
+        };
+
+        let mut piet_context = CairoRenderContext::new(cr);
+
+        piet_context.transform(Affine::scale_non_uniform(
+            scale(width, self.header.width),
+            scale(height, self.header.height),
+        ));
+
+        let result = self
+            .draw_with_options(&mut piet_context, options)
+            .wrap_err("failed to draw tinyvg file");
+
+        piet_context
+            .finish()
+            .map_err(|e| format_err!("{}", e))
+            .wrap_err("failed to finalize piet context")?;
+
+        Ok(result)
+    }
+
+    #[cfg(feature = "piet")]
+    fn outline_style<R>(
+        &self,
+        rc: &mut R,
+        cache: &mut BrushCache<R::Brush>,
+        o: &Option<OutlineStyle>,
+    ) -> Result<(f64, R::Brush)>
+    where
+        R: RenderContext,
+    {
+        match o {
+            Some(style) => Ok((style.line_width, self.brush(rc, cache, &style.line_style)?)),
+            None => Ok((0.0, nil_brush(rc))),
+        }
+    }
+
+    pub(crate) fn color(&self, index: usize) -> Result<Color> {
+        self.color_table
+            .get(index)
+            .cloned()
+            .ok_or_else(|| Error::ColorIndexOutOfRange {
+                index,
+                colors: self.color_table.len(),
+            })
+    }
+
+    #[cfg(feature = "piet")]
+    fn brush<R>(
+        &self,
+        rc: &mut R,
+        cache: &mut BrushCache<R::Brush>,
+        style: &Style,
+    ) -> Result<R::Brush>
+    where
+        R: RenderContext,
+    {
+        let (radial, point_0, point_1, color_index_0, color_index_1) = match style {
+            Style::FlatColor { color_index } => {
+                return Ok(rc.solid_brush(self.color(*color_index)?));
+            }
+            Style::LinearGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => (false, point_0, point_1, color_index_0, color_index_1),
+            Style::RadialGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => (true, point_0, point_1, color_index_0, color_index_1),
+        };
+
+        let color_0 = self.color(*color_index_0)?;
+        let color_1 = self.color(*color_index_1)?;
+
+        let key = GradientKey {
+            radial,
+            points: [
+                point_0.x.to_bits(),
+                point_0.y.to_bits(),
+                point_1.x.to_bits(),
+                point_1.y.to_bits(),
+            ],
+            colors: [color_0.as_rgba_u32(), color_1.as_rgba_u32()],
+        };
+
+        if let Some(brush) = cache.gradients.get(&key) {
+            return Ok(brush.clone());
+        }
+
+        let stops = vec![
+            GradientStop {
+                pos: 0.0,
+                color: color_0,
+            },
+            GradientStop {
+                pos: 1.0,
+                color: color_1,
+            },
+        ];
+
+        let brush = if radial {
+            rc.gradient(FixedRadialGradient {
+                center: *point_0,
+                origin_offset: Vec2 { x: 0.0, y: 0.0 },
+                radius: point_0.distance(*point_1),
+                stops,
+            })
+        } else {
+            rc.gradient(FixedLinearGradient {
+                start: *point_0,
+                end: *point_1,
+                stops,
+            })
+        }
+        .map_err(|e| format_err!("{}", e))?;
+
+        cache.gradients.insert(key, brush.clone());
+
+        Ok(brush)
+    }
+
+    /// Draw a TinyVG image onto the given `piet::RenderContext`, e.g. a
+    /// druid widget's context or a cairo context for a custom surface. PNGs
+    /// can more easily be generated by using `Image::render_png`.
+    ///
+    /// Coordinates are image units as in the file: the origin is the top left
+    /// corner of the image, x grows to the right, y grows downwards, and the
+    /// image covers `0..width` by `0..height` of its header. The image is
+    /// drawn with the context's current transform, so translate and scale the
+    /// context first to place the image; line widths scale with it. Nothing
+    /// is clipped to the image area, and the transform is the same after
+    /// drawing. `RenderContext::finish` is left to the caller.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use piet::RenderContext;
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::kurbo::{Affine, Vec2};
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 200, 100).unwrap();
+    /// let cr = cairo::Context::new(&surface).unwrap();
+    /// let mut rc = piet_cairo::CairoRenderContext::new(&cr);
+    ///
+    /// // Draw the 24 unit shield 48 pixels wide, at x = 120
+    /// rc.save().unwrap();
+    /// rc.transform(Affine::translate(Vec2::new(120.0, 10.0)) * Affine::scale(2.0));
+    /// image.draw(&mut rc).unwrap();
+    /// rc.restore().unwrap();
+    ///
+    /// rc.finish().unwrap();
+    /// ```
+    #[cfg(feature = "piet")]
+    pub fn draw(&self, rc: &mut impl RenderContext) -> Result<()> {
+        self.draw_with_options(rc, &RenderOptions::default())
+    }
+
+    /// Draw a TinyVG image onto the given `piet::RenderContext`, using the
+    /// given render options.
+    #[cfg(feature = "piet")]
+    pub fn draw_with_options(
+        &self,
+        rc: &mut impl RenderContext,
+        options: &RenderOptions,
+    ) -> Result<()> {
+        self.draw_culled(rc, options, None)
+    }
+
+    /// Draw a TinyVG image onto the given `piet::RenderContext` with
+    /// `transform` applied on top of the context's transform, e.g. to place
+    /// an icon in a larger scene without saving and restoring the context.
+    /// Same as setting `RenderOptions::transform`.
+    ///
+    /// ```
+    /// # use std::f64::consts::FRAC_PI_2;
+    /// # use std::fs::File;
+    /// # use piet::RenderContext;
+    /// # use tinyvg::Decoder;
+    /// # use tinyvg::kurbo::{Affine, Vec2};
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 200, 100).unwrap();
+    /// let cr = cairo::Context::new(&surface).unwrap();
+    /// let mut rc = piet_cairo::CairoRenderContext::new(&cr);
+    ///
+    /// // The shield at twice its size, turned a quarter clockwise about its
+    /// // top left corner at (100, 10)
+    /// let transform =
+    ///     Affine::translate(Vec2::new(100.0, 10.0)) * Affine::rotate(FRAC_PI_2) * Affine::scale(2.0);
+    /// image.draw_with_transform(&mut rc, transform).unwrap();
+    ///
+    /// // The context's transform is unchanged
+    /// assert_eq!(rc.current_transform(), Affine::IDENTITY);
+    /// rc.finish().unwrap();
+    /// ```
+    #[cfg(feature = "piet")]
+    pub fn draw_with_transform(
+        &self,
+        rc: &mut impl RenderContext,
+        transform: Affine,
+    ) -> Result<()> {
+        let options = RenderOptions {
+            transform,
+            ..Default::default()
+        };
+
+        self.draw_culled(rc, &options, None)
+    }
+
+    /// Draw a TinyVG image like `Image::draw_with_options`, using `bounds`
+    /// (indexed like `Image::commands`) to skip commands outside of
+    /// `RenderOptions::viewport`. The bounds are usually computed once while
+    /// decoding, see `DecodeOptions::compute_bounds`, so that rendering many
+    /// tiles of a large image doesn't compute them again for every tile.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use piet::RenderContext;
+    /// # use tinyvg::decode::DecodeOptions;
+    /// # use tinyvg::{Decoder, RenderOptions};
+    /// # use tinyvg::format::Rect;
+    /// let options = DecodeOptions {
+    ///     compute_bounds: true,
+    ///     ..Default::default()
+    /// };
+    /// let image = Decoder::new(File::open("data/tiger.tvg").unwrap())
+    ///     .with_options(options)
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// // Only draw the top left quarter
+    /// let options = RenderOptions {
+    ///     viewport: Some(Rect::new(
+    ///         0.0,
+    ///         0.0,
+    ///         image.header.width as f64 / 2.0,
+    ///         image.header.height as f64 / 2.0,
+    ///     )),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 100, 100).unwrap();
+    /// let cr = cairo::Context::new(&surface).unwrap();
+    /// let mut rc = piet_cairo::CairoRenderContext::new(&cr);
+    ///
+    /// image
+    ///     .draw_with_bounds(&mut rc, &options, image.bounds.as_ref().unwrap())
+    ///     .unwrap();
+    /// rc.finish().unwrap();
+    /// ```
+    #[cfg(feature = "piet")]
+    pub fn draw_with_bounds(
+        &self,
+        rc: &mut impl RenderContext,
+        options: &RenderOptions,
+        bounds: &[Rect],
+    ) -> Result<()> {
+        ensure!(
+            bounds.len() == self.commands.len(),
+            "image has {} commands but {} bounds were given",
+            self.commands.len(),
+            bounds.len()
+        );
+
+        self.draw_culled(rc, options, Some(bounds))
+    }
+
+    /// Draw with `bounds` of every command if they are known, otherwise they
+    /// are computed when needed for culling
+    #[cfg(feature = "piet")]
+    fn draw_culled(
+        &self,
+        rc: &mut impl RenderContext,
+        options: &RenderOptions,
+        bounds: Option<&[Rect]>,
+    ) -> Result<()> {
+        if options.transform != Affine::IDENTITY {
+            let inner = RenderOptions {
+                transform: Affine::IDENTITY,
+                ..options.clone()
+            };
+
+            rc.save().map_err(|e| format_err!("{}", e))?;
+            rc.transform(options.transform);
+
+            let result = self.draw_culled(rc, &inner, bounds);
+
+            rc.restore().map_err(|e| format_err!("{}", e))?;
+
+            return result;
+        }
+
+        if options.clear || options.background.is_some() {
+            let background = options
+                .background
+                .clone()
+                .unwrap_or(Color::rgba8(0, 0, 0, 0));
+
+            if options.clear {
+                rc.clear(None, background);
+            } else {
+                rc.fill(self.bounds_rect(), &background);
+            }
+
+            let options = RenderOptions {
+                background: None,
+                clear: false,
+                ..options.clone()
+            };
+
+            return self.draw_culled(rc, &options, bounds);
+        }
+
+        if options.padding > 0.0 || options.plate.is_some() {
+            return self.draw_framed(rc, options, bounds);
+        }
+
+        if !options.debug.is_empty() {
+            let scale = rc.current_transform().determinant().abs().sqrt();
+            let overlay = self.debug_overlay(
+                options.debug,
+                scale,
+                self.selected_commands(options, bounds)?,
+            );
+
+            let options = RenderOptions {
+                debug: DebugOverlay::default(),
+                ..options.clone()
+            };
+            self.draw_culled(rc, &options, bounds)?;
+
+            return overlay.draw_culled(rc, &RenderOptions::default(), None);
+        }
+
+        // Widths are adjusted before snapping, so that snapping rounds the
+        // adjusted widths to whole pixels
+        if options.adjusts_line_widths() {
+            let scale = rc.current_transform().determinant().abs().sqrt();
+            let adjusted = self.with_adjusted_line_widths(options, scale);
+
+            let options = RenderOptions {
+                line_widths: LineWidthOverrides::default(),
+                stroke_scaling: StrokeScaling::Proportional,
+                ..options.clone()
+            };
+
+            // Wider strokes reach further than the cached bounds
+            return adjusted.draw_culled(rc, &options, None);
+        }
+
+        if self.needs_gradient_fallback(options.degenerate_gradients) {
+            let flattened = self.with_gradient_fallback(options.degenerate_gradients);
+
+            return flattened.draw_culled(rc, options, bounds);
+        }
+
+        if options.snap == SnapMode::Pixel {
+            let snapped = self.snap_to_pixels(rc.current_transform());
+            let options = RenderOptions {
+                snap: SnapMode::None,
+                ..options.clone()
+            };
+
+            // Snapping moves geometry out of the cached bounds
+            return snapped.draw_culled(rc, &options, None);
+        }
+
+        // Splitting commands changes their indices, so the selection and
+        // culling are applied while splitting
+        if !options.passes.is_default() {
+            let split =
+                self.with_render_passes(options.passes, self.selected_commands(options, bounds)?);
+            let options = RenderOptions {
+                passes: RenderPasses::default(),
+                selection: None,
+                viewport: None,
+                max_primitives: None,
+                ..options.clone()
+            };
+
+            return split.draw_culled(rc, &options, None);
+        }
+
+        let mut cache = BrushCache::default();
+        let cache = &mut cache;
+
+        let selected = self.selected_commands(options, bounds)?;
+
+        match options.strategy {
+            RenderStrategy::Sequential => {
+                for (index, cmd) in self.commands.iter().enumerate() {
+                    if selected(index) {
+                        self.draw_command(rc, cache, cmd)?;
+                    }
+                }
+            }
+            RenderStrategy::Batched => self.draw_batched(rc, cache, selected)?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+impl crate::format::Image {
+    /// Copy of this image with `RenderOptions::line_widths` and
+    /// `RenderOptions::stroke_scaling` applied to every stroke width. `scale`
+    /// is the number of device pixels per image unit, the widths stay in image
+    /// units.
+    pub(crate) fn with_adjusted_line_widths(&self, options: &RenderOptions, scale: f64) -> Self {
+        let mut adjusted = self.clone();
+        adjusted.visit_line_widths_mut(|width| {
+            let adjusted_width = options.line_widths.apply(*width, scale);
+
+            // Render backends scale widths proportionally, so the policy's
+            // device width is converted back to image units
+            *width = if scale > 0.0 {
+                options.stroke_scaling.apply(adjusted_width, scale) / scale
+            } else {
+                adjusted_width
+            };
+        });
+
+        adjusted
+    }
+
+    /// Copy of this image with the commands for which `selected` returns
+    /// true split into fills and strokes, in the order given by `passes`.
+    /// Outlines of fill commands become separate line commands.
+    pub(crate) fn with_render_passes(
+        &self,
+        passes: RenderPasses,
+        selected: impl Fn(usize) -> bool,
+    ) -> Self {
+        let mut fills = Vec::new();
+        let mut strokes = Vec::new();
+        let mut interleaved = Vec::new();
+
+        for (index, cmd) in self.commands.iter().enumerate() {
+            if !selected(index) {
+                continue;
+            }
+
+            let (fill, stroke) = split_outline(cmd);
+            let fill = fill.filter(|_| passes.fills);
+            let stroke = stroke.filter(|_| passes.strokes);
+
+            match passes.order {
+                PassOrder::Interleaved => interleaved.extend(fill.into_iter().chain(stroke)),
+                PassOrder::FillsFirst | PassOrder::StrokesFirst => {
+                    fills.extend(fill);
+                    strokes.extend(stroke);
+                }
+            }
+        }
+
+        let commands = match passes.order {
+            PassOrder::Interleaved => interleaved,
+            PassOrder::FillsFirst => fills.into_iter().chain(strokes).collect(),
+            PassOrder::StrokesFirst => strokes.into_iter().chain(fills).collect(),
         };
 
-        let surface = ImageSurface::create(Format::ARgb32, size.width as i32, size.height as i32)
-            .wrap_err("failed to create cairo surface")?;
-        let cr = cairo::Context::new(&surface).unwrap();
+        Self {
+            header: self.header.clone(),
+            color_table: self.color_table.clone(),
+            commands,
+            trailer: self.trailer.clone(),
+            labels: Default::default(),
+            bounds: None,
+        }
+    }
+
+    /// Whether `Image::with_gradient_fallback` changes any style
+    pub(crate) fn needs_gradient_fallback(&self, fallback: GradientFallback) -> bool {
+        fallback != GradientFallback::Unchanged && self.styles().any(Style::is_degenerate_gradient)
+    }
+
+    /// Copy of this image with every degenerate gradient replaced by a flat
+    /// color, see `GradientFallback`
+    pub(crate) fn with_gradient_fallback(&self, fallback: GradientFallback) -> Self {
+        let mut flattened = self.clone();
+        flattened.visit_styles_mut(|style| {
+            if let Style::LinearGradient {
+                color_index_0,
+                color_index_1,
+                ..
+            }
+            | Style::RadialGradient {
+                color_index_0,
+                color_index_1,
+                ..
+            } = *style
+            {
+                if style.is_degenerate_gradient() {
+                    let color_index = match fallback {
+                        GradientFallback::SecondColor => color_index_1,
+                        _ => color_index_0,
+                    };
 
-        let render_result = {
-            let mut piet_context = CairoRenderContext::new(&cr);
+                    *style = Style::FlatColor { color_index };
+                }
+            }
+        });
 
-            let result = self
-                .draw(&mut piet_context)
-                .wrap_err("failed to draw tinyvg file");
+        flattened
+    }
 
-            piet_context
-                .finish()
-                .map_err(|e| eyre::eyre!("{}", e))
-                .wrap_err("failed to finalize piet context")?;
+    /// Predicate for the indices of commands that are drawn, following the
+    /// viewport, selection and primitive limit of the options. Fails if the
+    /// primitive limit is exceeded and may not be truncated.
+    pub(crate) fn selected_commands<'a>(
+        &'a self,
+        options: &'a RenderOptions,
+        bounds: Option<&'a [Rect]>,
+    ) -> Result<impl Fn(usize) -> bool + 'a> {
+        // Culled commands don't count towards the primitive limit
+        let visible = move |index: usize| match options.viewport {
+            Some(viewport) => {
+                let command_bounds = match bounds {
+                    Some(bounds) => bounds[index],
+                    None => self.commands[index].bounds(),
+                };
 
-            result
+                overlaps(command_bounds, viewport)
+            }
+            None => true,
         };
 
-        surface.flush();
-        surface.write_to_png(writer)?;
+        let in_selection = move |index: usize| {
+            visible(index)
+                && options
+                    .selection
+                    .as_ref()
+                    .is_none_or(|selection| selection.contains(index))
+        };
 
-        render_result?;
+        let end = match options.max_primitives {
+            Some(limit) => self.primitive_limit_end(limit, in_selection)?,
+            None => self.commands.len(),
+        };
 
-        Ok(())
+        Ok(move |index: usize| index < end && in_selection(index))
     }
 
-    fn outline_style<R>(&self, rc: &mut R, o: &Option<OutlineStyle>) -> Result<(f64, R::Brush)>
-    where
-        R: RenderContext,
-    {
-        match o {
-            Some(style) => Ok((style.line_width, self.brush(rc, &style.line_style)?)),
-            None => Ok((0.0, nil_brush(rc))),
+    /// The whole area of the image, in image units
+    pub(crate) fn bounds_rect(&self) -> Rect {
+        Rect::new(
+            0.0,
+            0.0,
+            self.header.width as f64,
+            self.header.height as f64,
+        )
+    }
+
+    /// Transform that scales the artwork down uniformly to fit inside
+    /// `padding` units on every side and centers it, `None` if the artwork
+    /// isn't inset
+    pub(crate) fn padding_transform(&self, padding: f64) -> Option<Affine> {
+        let width = self.header.width as f64;
+        let height = self.header.height as f64;
+
+        if padding <= 0.0 || width == 0.0 || height == 0.0 {
+            return None;
         }
+
+        let scale = ((width - 2.0 * padding) / width)
+            .min((height - 2.0 * padding) / height)
+            .max(0.0);
+        let offset = Vec2::new(width * (1.0 - scale), height * (1.0 - scale)) / 2.0;
+
+        Some(Affine::translate(offset) * Affine::scale(scale))
     }
 
-    fn color(&self, index: usize) -> Result<Color> {
-        self.color_table.get(index).cloned().ok_or_else(|| {
-            eyre::eyre!(
-                "file has {} colors but tried to get index {}",
-                self.color_table.len(),
-                index
-            )
-        })
+    /// Number of leading commands that can be drawn without drawing more path
+    /// elements than the limit allows, or an error if the limit is exceeded
+    /// and may not be truncated
+    fn primitive_limit_end(
+        &self,
+        limit: PrimitiveLimit,
+        selected: impl Fn(usize) -> bool,
+    ) -> Result<usize> {
+        let mut total = 0;
+
+        for (index, cmd) in self.commands.iter().enumerate() {
+            if !selected(index) {
+                continue;
+            }
+
+            total += primitive_count(cmd);
+
+            if total > limit.count {
+                ensure!(
+                    limit.truncate,
+                    "image has more than the maximum of {} path elements",
+                    limit.count
+                );
+
+                return Ok(index);
+            }
+        }
+
+        Ok(self.commands.len())
+    }
+}
+
+impl crate::format::Image {
+    /// Draw the background plate, then the artwork inset by the padding
+    #[cfg(feature = "piet")]
+    fn draw_framed(
+        &self,
+        rc: &mut impl RenderContext,
+        options: &RenderOptions,
+        bounds: Option<&[Rect]>,
+    ) -> Result<()> {
+        if let Some(plate) = &options.plate {
+            rc.fill(
+                self.bounds_rect().to_rounded_rect(plate.corner_radius),
+                &plate.color,
+            );
+        }
+
+        let inner = RenderOptions {
+            padding: 0.0,
+            plate: None,
+            ..options.clone()
+        };
+
+        let transform = match self.padding_transform(options.padding) {
+            Some(transform) => transform,
+            None => return self.draw_culled(rc, &inner, bounds),
+        };
+
+        rc.save().map_err(|e| format_err!("{}", e))?;
+        rc.transform(transform);
+
+        let result = self.draw_culled(rc, &inner, bounds);
+
+        rc.restore().map_err(|e| format_err!("{}", e))?;
+
+        result
     }
 
-    fn brush<R>(&self, rc: &mut R, style: &Style) -> Result<R::Brush>
+    #[cfg(feature = "piet")]
+    fn draw_command<R>(
+        &self,
+        rc: &mut R,
+        cache: &mut BrushCache<R::Brush>,
+        cmd: &Command,
+    ) -> Result<()>
     where
         R: RenderContext,
     {
-        let brush = match style {
-            Style::FlatColor { color_index } => rc.solid_brush(self.color(*color_index)?),
-            Style::LinearGradient {
-                point_0,
-                point_1,
-                color_index_0,
-                color_index_1,
-            } => rc
-                .gradient(FixedLinearGradient {
-                    start: *point_0,
-                    end: *point_1,
-                    stops: vec![
-                        GradientStop {
-                            pos: 0.0,
-                            color: self.color(*color_index_0)?,
-                        },
-                        GradientStop {
-                            pos: 1.0,
-                            color: self.color(*color_index_1)?,
-                        },
-                    ],
-                })
-                .map_err(|e| eyre::eyre!("{}", e))?,
-            Style::RadialGradient {
-                point_0,
-                point_1,
-                color_index_0,
-                color_index_1,
-            } => rc
-                .gradient(FixedRadialGradient {
-                    center: *point_0,
-                    origin_offset: Vec2 { x: 0.0, y: 0.0 },
-                    radius: point_0.distance(*point_1),
-                    stops: vec![
-                        GradientStop {
-                            pos: 0.0,
-                            color: self.color(*color_index_0)?,
-                        },
-                        GradientStop {
-                            pos: 1.0,
-                            color: self.color(*color_index_1)?,
-                        },
-                    ],
-                })
-                .map_err(|e| eyre::eyre!("{}", e))?,
-        };
+        match cmd {
+            Command::FillPath {
+                fill_style,
+                path,
+                outline,
+            } => {
+                let fill = self.brush(rc, cache, fill_style)?;
+                let (line_width, line_brush) = self.outline_style(rc, cache, outline)?;
 
-        Ok(brush)
-    }
+                draw_path(rc, fill, line_brush, line_width, path)?;
+            }
+            Command::FillRectangles {
+                fill_style,
+                rectangles,
+                outline,
+            } => {
+                let brush = self.brush(rc, cache, fill_style)?;
+                let (line_width, line_brush) = self.outline_style(rc, cache, outline)?;
 
-    /// Draw a TinyVG image onto the given `piet::RenderContext`. This is
-    /// exposed as an internal so that people who need formats other than PNGs
-    /// can utilize it. PNGs can more easily be generated by using
-    /// `Image::render_png`.
-    pub fn draw(&self, rc: &mut impl RenderContext) -> Result<()> {
-        for cmd in &self.commands {
-            match cmd {
-                Command::FillPath {
-                    fill_style,
-                    path,
-                    outline,
-                } => {
-                    let fill = self.brush(rc, fill_style)?;
-                    let (line_width, line_brush) = self.outline_style(rc, outline)?;
+                for rect in rectangles.iter().filter(|r| has_area(r)) {
+                    rc.fill(rect, &brush);
+                    rc.stroke(rect, &line_brush, line_width);
+                }
+            }
+            Command::FillPolygon {
+                fill_style,
+                polygon,
+                outline,
+            } => {
+                let brush = self.brush(rc, cache, fill_style)?;
+                let (line_width, line_brush) = self.outline_style(rc, cache, outline)?;
+
+                let bez = polygon_path(polygon);
+
+                rc.fill(&bez, &brush);
+                rc.stroke(&bez, &line_brush, line_width);
+            }
+            Command::DrawLines {
+                line_style,
+                line_width,
+                lines,
+            } => {
+                let brush = self.brush(rc, cache, line_style)?;
 
-                    draw_path(rc, fill, line_brush, line_width, path)?;
+                for line in lines {
+                    rc.stroke(line, &brush, *line_width);
                 }
-                Command::FillRectangles {
-                    fill_style,
-                    rectangles,
-                    outline,
-                } => {
-                    let brush = self.brush(rc, fill_style)?;
-                    let (line_width, line_brush) = self.outline_style(rc, outline)?;
-
-                    for rect in rectangles {
-                        rc.fill(rect, &brush);
-                        rc.stroke(rect, &line_brush, line_width);
-                    }
+            }
+            Command::DrawLineLoop {
+                line_style,
+                line_width,
+                close_path,
+                points,
+            } => {
+                let line = self.brush(rc, cache, line_style)?;
+
+                let mut bez = BezPath::new();
+                let start = points[0];
+                bez.move_to(start);
+
+                for p in points {
+                    bez.line_to(*p);
                 }
-                Command::FillPolygon {
-                    fill_style,
-                    polygon,
-                    outline,
-                } => {
-                    let brush = self.brush(rc, fill_style)?;
-                    let (line_width, line_brush) = self.outline_style(rc, outline)?;
-
-                    let mut bez = BezPath::new();
-                    bez.move_to(polygon[0]);
-
-                    for point in polygon {
-                        bez.line_to(*point);
-                    }
 
-                    rc.fill(&bez, &brush);
-                    rc.stroke(&bez, &line_brush, line_width);
+                if *close_path {
+                    bez.line_to(start);
                 }
-                Command::DrawLines {
-                    line_style,
-                    line_width,
-                    lines,
-                } => {
-                    let brush = self.brush(rc, line_style)?;
-
-                    for line in lines {
-                        rc.stroke(line, &brush, *line_width);
+
+                rc.stroke(bez, &line, *line_width);
+            }
+            Command::DrawLinePath {
+                line_style,
+                line_width,
+                path,
+            } => {
+                let line = self.brush(rc, cache, line_style)?;
+                let fill = nil_brush(rc);
+
+                draw_path(rc, fill, line, *line_width, path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draw the commands for which `selected` returns true, merging them into
+    /// batches. Commands that are not selected don't interrupt a batch.
+    #[cfg(feature = "piet")]
+    fn draw_batched<R>(
+        &self,
+        rc: &mut R,
+        cache: &mut BrushCache<R::Brush>,
+        selected: impl Fn(usize) -> bool,
+    ) -> Result<()>
+    where
+        R: RenderContext,
+    {
+        let mut batch: Option<Batch> = None;
+
+        for (index, cmd) in self.commands.iter().enumerate() {
+            if !selected(index) {
+                continue;
+            }
+
+            match batch_items(cmd)? {
+                Some(items) => {
+                    for item in items {
+                        if let Some(b) = &mut batch {
+                            if b.accepts(self, &item) {
+                                b.push(item);
+                                continue;
+                            }
+                        }
+
+                        if let Some(b) = batch.replace(Batch::new(item)) {
+                            self.draw_batch(rc, cache, b)?;
+                        }
                     }
                 }
-                Command::DrawLineLoop {
-                    line_style,
-                    line_width,
-                    close_path,
-                    points,
-                } => {
-                    let line = self.brush(rc, line_style)?;
-
-                    let mut bez = BezPath::new();
-                    let start = points[0];
-                    bez.move_to(start);
-
-                    for p in points {
-                        bez.line_to(*p);
+                None => {
+                    if let Some(b) = batch.take() {
+                        self.draw_batch(rc, cache, b)?;
                     }
 
-                    if *close_path {
-                        bez.line_to(start);
-                    }
+                    self.draw_command(rc, cache, cmd)?;
+                }
+            }
+        }
+
+        if let Some(b) = batch {
+            self.draw_batch(rc, cache, b)?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "piet")]
+    fn draw_batch<R>(
+        &self,
+        rc: &mut R,
+        cache: &mut BrushCache<R::Brush>,
+        batch: Batch,
+    ) -> Result<()>
+    where
+        R: RenderContext,
+    {
+        let brush = self.brush(rc, cache, batch.style)?;
+
+        match batch.kind {
+            BatchKind::Fill => rc.fill(&batch.path, &brush),
+            BatchKind::Stroke(line_width) => rc.stroke(&batch.path, &brush, line_width),
+        }
+
+        Ok(())
+    }
 
-                    rc.stroke(bez, &line, *line_width);
+    /// Whether two styles produce the same brush. Flat colors are compared by
+    /// their resolved color, so that duplicate palette entries still match.
+    #[cfg(feature = "piet")]
+    fn same_brush(&self, a: &Style, b: &Style) -> bool {
+        match (a, b) {
+            (Style::FlatColor { color_index: a }, Style::FlatColor { color_index: b }) => {
+                match (self.color(*a), self.color(*b)) {
+                    (Ok(a), Ok(b)) => a == b,
+                    _ => false,
                 }
-                Command::DrawLinePath {
-                    line_style,
-                    line_width,
+            }
+            _ => a == b,
+        }
+    }
+}
+
+/// Gradient brushes created during a single render pass, so that commands
+/// reusing a gradient share one brush. With cairo, creating a gradient brush
+/// is cheap and this makes no measurable difference.
+#[cfg(feature = "piet")]
+struct BrushCache<B> {
+    gradients: HashMap<GradientKey, B>,
+}
+
+#[cfg(feature = "piet")]
+impl<B> Default for BrushCache<B> {
+    fn default() -> Self {
+        Self {
+            gradients: HashMap::new(),
+        }
+    }
+}
+
+/// Gradient geometry and resolved colors, with floats compared bitwise
+#[cfg(feature = "piet")]
+#[derive(PartialEq, Eq, Hash)]
+struct GradientKey {
+    radial: bool,
+    points: [u64; 4],
+    colors: [u32; 2],
+}
+
+#[cfg(feature = "piet")]
+#[derive(Clone, Copy, PartialEq)]
+enum BatchKind {
+    Fill,
+    Stroke(f64),
+}
+
+/// A single shape that can be merged into a `Batch`
+#[cfg(feature = "piet")]
+struct BatchItem<'a> {
+    style: &'a Style,
+    kind: BatchKind,
+    path: BezPath,
+    bounds: Rect,
+}
+
+/// Shapes with the same brush which don't overlap, drawn with one call
+#[cfg(feature = "piet")]
+struct Batch<'a> {
+    style: &'a Style,
+    kind: BatchKind,
+    path: BezPath,
+    bounds: Vec<Rect>,
+}
+
+#[cfg(feature = "piet")]
+impl<'a> Batch<'a> {
+    fn new(item: BatchItem<'a>) -> Self {
+        Self {
+            style: item.style,
+            kind: item.kind,
+            path: item.path,
+            bounds: vec![item.bounds],
+        }
+    }
+
+    fn accepts(&self, image: &crate::format::Image, item: &BatchItem) -> bool {
+        self.kind == item.kind
+            && image.same_brush(self.style, item.style)
+            && self.bounds.iter().all(|b| !overlaps(*b, item.bounds))
+    }
+
+    fn push(&mut self, item: BatchItem<'a>) {
+        for el in item.path.elements() {
+            self.path.push(*el);
+        }
+
+        self.bounds.push(item.bounds);
+    }
+}
+
+/// Split a command into shapes that can be batched. Returns `None` for
+/// commands which have to be drawn on their own.
+#[cfg(feature = "piet")]
+fn batch_items(cmd: &Command) -> Result<Option<Vec<BatchItem<'_>>>> {
+    // Bounds are grown by one unit so that antialiased edges of neighboring
+    // shapes never share a pixel
+    let fill = |style, path: BezPath| {
+        let bounds = path.bounding_box().inflate(1.0, 1.0);
+
+        BatchItem {
+            style,
+            kind: BatchKind::Fill,
+            path,
+            bounds,
+        }
+    };
+
+    let items = match cmd {
+        Command::FillPolygon {
+            fill_style,
+            polygon,
+            outline: None,
+        } => vec![fill(fill_style, polygon_path(polygon))],
+        Command::FillRectangles {
+            fill_style,
+            rectangles,
+            outline: None,
+        } => rectangles
+            .iter()
+            .filter(|rect| has_area(rect))
+            .map(|rect| fill(fill_style, rect.to_path(0.1)))
+            .collect(),
+        Command::FillPath {
+            fill_style,
+            path,
+            outline: None,
+        } => vec![fill(fill_style, bez_path(path)?)],
+        Command::DrawLines {
+            line_style,
+            line_width,
+            lines,
+        } => lines
+            .iter()
+            .map(|line| {
+                let path = line.to_path(0.1);
+                let grow = line_width / 2.0 + 1.0;
+                let bounds = path.bounding_box().inflate(grow, grow);
+
+                BatchItem {
+                    style: line_style,
+                    kind: BatchKind::Stroke(*line_width),
                     path,
-                } => {
-                    let line = self.brush(rc, line_style)?;
-                    let fill = nil_brush(rc);
+                    bounds,
+                }
+            })
+            .collect(),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(items))
+}
+
+/// Convert the pixels of a cairo surface to RGBA, see `RasterImage`
+#[cfg(feature = "render-png")]
+pub(crate) fn surface_pixmap(
+    surface: &cairo::ImageSurface,
+    premultiplied: bool,
+) -> Result<RasterImage> {
+    let width = surface.width() as usize;
+    let height = surface.height() as usize;
+    let stride = surface.stride() as usize;
+    let mut pixels = Vec::with_capacity(width * height * 4);
+
+    surface
+        .with_data(|data| {
+            for row in data.chunks(stride).take(height) {
+                for pixel in row[..width * 4].chunks_exact(4) {
+                    // Cairo stores premultiplied ARGB in native endian
+                    // 32 bit words
+                    let pixel = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                    let alpha = pixel >> 24;
+
+                    let unpremultiply = |channel: u32| {
+                        if premultiplied {
+                            channel as u8
+                        } else {
+                            (channel * 255 + alpha / 2)
+                                .checked_div(alpha)
+                                .map_or(0, |channel| channel.min(255) as u8)
+                        }
+                    };
 
-                    draw_path(rc, fill, line, *line_width, path)?;
+                    pixels.extend([
+                        unpremultiply((pixel >> 16) & 0xFF),
+                        unpremultiply((pixel >> 8) & 0xFF),
+                        unpremultiply(pixel & 0xFF),
+                        alpha as u8,
+                    ]);
                 }
             }
+        })
+        .map_err(|e| format_err!("{}", e))
+        .wrap_err("failed to read rendered surface")?;
+
+    Ok(RasterImage {
+        width: width as u32,
+        height: height as u32,
+        stride: width * 4,
+        format: if premultiplied {
+            PixelFormat::Rgba8Premultiplied
+        } else {
+            PixelFormat::Rgba8
+        },
+        data: pixels,
+    })
+}
+
+/// Finish a surface writing to a `Vec<u8>` stream, and copy the stream to
+/// `writer`
+#[cfg(feature = "render-png")]
+fn write_stream(
+    surface: &cairo::Surface,
+    writer: &mut impl std::io::Write,
+    format: &str,
+) -> Result<()> {
+    let data = surface
+        .finish_output_stream()
+        .map_err(|e| format_err!("{}", e.error))
+        .wrap_err_with(|| format!("failed to finish {} surface", format))?;
+    let data = data
+        .downcast::<Vec<u8>>()
+        .map_err(|_| format_err!("{} surface returned an unexpected stream", format))?;
+
+    writer
+        .write_all(&data)
+        .wrap_err_with(|| format!("failed to write {}", format))
+}
+
+/// Split a command into the part that is filled and the part that is stroked,
+/// turning the outline of a fill command into a line command
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+fn split_outline(cmd: &Command) -> (Option<Command>, Option<Command>) {
+    match cmd {
+        Command::FillPath {
+            fill_style,
+            path,
+            outline,
+        } => (
+            Some(Command::FillPath {
+                fill_style: fill_style.clone(),
+                path: path.clone(),
+                outline: None,
+            }),
+            outline.as_ref().map(|outline| Command::DrawLinePath {
+                line_style: outline.line_style.clone(),
+                line_width: outline.line_width,
+                path: path.clone(),
+            }),
+        ),
+        Command::FillRectangles {
+            fill_style,
+            rectangles,
+            outline,
+        } => {
+            let path: Vec<_> = rectangles
+                .iter()
+                .filter(|r| has_area(r))
+                .map(|r| Segment {
+                    start: Point::new(r.x0, r.y0),
+                    commands: [
+                        SegmentCommandKind::Line {
+                            end: Point::new(r.x1, r.y0),
+                        },
+                        SegmentCommandKind::Line {
+                            end: Point::new(r.x1, r.y1),
+                        },
+                        SegmentCommandKind::Line {
+                            end: Point::new(r.x0, r.y1),
+                        },
+                        SegmentCommandKind::ClosePath,
+                    ]
+                    .into_iter()
+                    .map(|kind| SegmentCommand {
+                        kind,
+                        line_width: None,
+                    })
+                    .collect(),
+                })
+                .collect();
+
+            (
+                Some(Command::FillRectangles {
+                    fill_style: fill_style.clone(),
+                    rectangles: rectangles.clone(),
+                    outline: None,
+                }),
+                outline
+                    .as_ref()
+                    .filter(|_| !path.is_empty())
+                    .map(|outline| Command::DrawLinePath {
+                        line_style: outline.line_style.clone(),
+                        line_width: outline.line_width,
+                        path,
+                    }),
+            )
+        }
+        Command::FillPolygon {
+            fill_style,
+            polygon,
+            outline,
+        } => (
+            Some(Command::FillPolygon {
+                fill_style: fill_style.clone(),
+                polygon: polygon.clone(),
+                outline: None,
+            }),
+            // Polygon outlines are stroked along `polygon_path`, which isn't
+            // closed
+            outline.as_ref().map(|outline| Command::DrawLineLoop {
+                line_style: outline.line_style.clone(),
+                line_width: outline.line_width,
+                close_path: false,
+                points: polygon.clone(),
+            }),
+        ),
+        Command::DrawLines { .. } | Command::DrawLineLoop { .. } | Command::DrawLinePath { .. } => {
+            (None, Some(cmd.clone()))
         }
+    }
+}
 
-        Ok(())
+pub(crate) fn polygon_path(polygon: &[Point]) -> BezPath {
+    let mut bez = BezPath::new();
+    bez.move_to(polygon[0]);
+
+    for point in polygon {
+        bez.line_to(*point);
+    }
+
+    bez
+}
+
+/// Build the outline of a path, without stroking any of its segments
+pub(crate) fn bez_path(path: &[Segment]) -> Result<BezPath> {
+    let mut bezier = BezPath::new();
+
+    for Segment { start, commands } in path {
+        let mut pen = *start;
+
+        bezier.move_to(pen);
+
+        for SegmentCommand { kind, .. } in commands {
+            pen = push_segment_command(&mut bezier, pen, *start, kind)?;
+        }
+    }
+
+    Ok(bezier)
+}
+
+/// Append a segment command starting at `pen` to `bezier`, returning the new
+/// pen position. `start` is the start of the command's segment, which closing
+/// the path returns to.
+fn push_segment_command(
+    bezier: &mut BezPath,
+    pen: Point,
+    start: Point,
+    kind: &SegmentCommandKind,
+) -> Result<Point> {
+    let end = kind.end_point(pen, start);
+
+    match kind {
+        SegmentCommandKind::Line { .. }
+        | SegmentCommandKind::HorizontalLine { .. }
+        | SegmentCommandKind::VerticalLine { .. }
+        | SegmentCommandKind::ClosePath => bezier.line_to(end),
+        SegmentCommandKind::CubicBezier {
+            control_0,
+            control_1,
+            point_1,
+        } => bezier.curve_to(*control_0, *control_1, *point_1),
+        SegmentCommandKind::ArcCircle {
+            large,
+            sweep,
+            radius,
+            target,
+        } => {
+            let arc = svg_arc(pen, *large, *sweep, *radius, *radius, 0.0, *target)?;
+
+            for segment in arc.append_iter(ARC_TOLERANCE) {
+                bezier.push(segment);
+            }
+        }
+        SegmentCommandKind::ArcEllipse {
+            large,
+            sweep,
+            radius_x,
+            radius_y,
+            rotation,
+            target,
+        } => {
+            let arc = svg_arc(
+                pen, *large, *sweep, *radius_x, *radius_y, *rotation, *target,
+            )?;
+
+            for segment in arc.append_iter(ARC_TOLERANCE) {
+                bezier.push(segment);
+            }
+        }
+        SegmentCommandKind::QuadraticBezier { control, point_1 } => {
+            bezier.quad_to(*control, *point_1)
+        }
+    }
+
+    Ok(end)
+}
+
+/// Whether two rectangles overlap or touch
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.x0 <= b.x1 && b.x0 <= a.x1 && a.y0 <= b.y1 && b.y0 <= a.y1
+}
+
+/// Number of path elements drawn for a command, see `PrimitiveLimit`
+#[cfg(any(feature = "piet", feature = "render-skia"))]
+fn primitive_count(cmd: &Command) -> usize {
+    match cmd {
+        Command::FillPolygon { polygon, .. } => polygon.len() + 1,
+        Command::FillRectangles { rectangles, .. } => rectangles.len() * 5,
+        Command::DrawLines { lines, .. } => lines.len() * 2,
+        Command::DrawLineLoop { points, .. } => points.len() + 1,
+        Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } => path
+            .iter()
+            .map(|Segment { start, commands }| {
+                let mut pen = *start;
+                let mut count = 1;
+
+                for SegmentCommand { kind, .. } in commands {
+                    count += match kind {
+                        SegmentCommandKind::ArcEllipse {
+                            large,
+                            sweep,
+                            radius_x,
+                            radius_y,
+                            rotation,
+                            target,
+                        } => svg_arc(
+                            pen, *large, *sweep, *radius_x, *radius_y, *rotation, *target,
+                        )
+                        .map_or(1, |arc| arc.append_iter(ARC_TOLERANCE).count()),
+                        SegmentCommandKind::ArcCircle {
+                            large,
+                            sweep,
+                            radius,
+                            target,
+                        } => svg_arc(pen, *large, *sweep, *radius, *radius, 0.0, *target)
+                            .map_or(1, |arc| arc.append_iter(ARC_TOLERANCE).count()),
+                        _ => 1,
+                    };
+
+                    pen = kind.end_point(pen, *start);
+                }
+
+                count
+            })
+            .sum(),
     }
 }
 
+/// Maximum distance between an arc and the Bézier curves it is drawn with
+pub(crate) const ARC_TOLERANCE: f64 = 0.2;
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn svg_arc(
+    from: Point,
+    large: bool,
+    sweep: bool,
+    radius_x: f64,
+    radius_y: f64,
+    rotation: f64,
+    to: Point,
+) -> Result<Arc> {
+    let svg_arc = SvgArc {
+        from,
+        to,
+        radii: Vec2 {
+            x: radius_x,
+            y: radius_y,
+        },
+        x_rotation: rotation,
+        large_arc: large,
+        sweep,
+    };
+
+    Arc::from_svg_arc(&svg_arc)
+        .ok_or_else(|| format_err!("failed to create arc from svg arc {:?}", svg_arc))
+}
+
+/// Fill a path, then stroke it, see `path_strokes`
+#[cfg(feature = "piet")]
 fn draw_path<R>(
     rc: &mut R,
     fill: R::Brush,
     line: R::Brush,
-    mut line_width: f64,
+    line_width: f64,
     path: &[Segment],
 ) -> Result<()>
 where
     R: RenderContext,
 {
-    let mut bezier = BezPath::new();
+    rc.fill(bez_path(path)?, &fill);
+
+    for (stroke, line_width) in path_strokes(line_width, path)? {
+        rc.stroke(&stroke, &line, line_width);
+    }
+
+    Ok(())
+}
+
+/// Sub-paths of a path with the width they are stroked with. Segment commands
+/// can change the line width for themselves and the commands after them, so
+/// the stroke is split into sub-paths at every change. Commands between
+/// changes are stroked together, so that they are joined like a single
+/// stroke.
+pub(crate) fn path_strokes(mut line_width: f64, path: &[Segment]) -> Result<Vec<(BezPath, f64)>> {
+    let mut strokes = Vec::new();
 
     for Segment { start, commands } in path {
         let mut pen = *start;
+        let mut stroke = BezPath::new();
+        stroke.move_to(pen);
 
-        bezier.move_to(pen);
+        // Whether the current sub-path begins at the segment start
+        let mut from_start = true;
 
         for SegmentCommand {
             kind,
             line_width: next_line_width,
         } in commands
         {
-            if let Some(lw) = next_line_width {
-                line_width = *lw;
-            }
-
-            match kind {
-                SegmentCommandKind::Line { end } => {
-                    bezier.line_to(*end);
-                    rc.stroke(Line { p0: pen, p1: *end }, &line, line_width);
-
-                    pen = *end;
-                }
-                SegmentCommandKind::VerticalLine { y } => {
-                    let end = Point { x: pen.x, y: *y };
-
-                    bezier.line_to(end);
-                    rc.stroke(Line { p0: pen, p1: end }, &line, line_width);
-
-                    pen = end;
-                }
-                SegmentCommandKind::CubicBezier {
-                    control_0,
-                    control_1,
-                    point_1,
-                } => {
-                    bezier.curve_to(*control_0, *control_1, *point_1);
-                    rc.stroke(
-                        CubicBez::new(pen, *control_0, *control_1, *point_1),
-                        &line,
-                        line_width,
-                    );
-
-                    pen = *point_1;
-                }
-                SegmentCommandKind::HorizontalLine { x } => {
-                    let end = Point { x: *x, y: pen.y };
-
-                    bezier.line_to(end);
-                    rc.stroke(Line { p0: pen, p1: end }, &line, line_width);
-
-                    pen = end;
-                }
-                SegmentCommandKind::ArcEllipse {
-                    large,
-                    sweep,
-                    radius_x,
-                    radius_y,
-                    rotation,
-                    target,
-                } => {
-                    let svg_arc = SvgArc {
-                        from: pen,
-                        to: *target,
-                        radii: Vec2 {
-                            x: *radius_x,
-                            y: *radius_y,
-                        },
-                        x_rotation: *rotation,
-                        large_arc: *large,
-                        sweep: *sweep,
-                    };
-                    let arc = Arc::from_svg_arc(&svg_arc).ok_or_else(|| {
-                        eyre::eyre!("failed to create arc from svg arc {:?}", svg_arc)
-                    })?;
-
-                    for segment in arc.append_iter(0.2) {
-                        bezier.push(segment);
-                    }
-                    rc.stroke(&arc, &line, line_width);
+            if let Some(next_line_width) = next_line_width {
+                if *next_line_width != line_width && stroke.elements().len() > 1 {
+                    strokes.push((stroke, line_width));
 
-                    pen = *target;
+                    stroke = BezPath::new();
+                    stroke.move_to(pen);
+                    from_start = false;
                 }
-                SegmentCommandKind::ClosePath => {
-                    bezier.line_to(*start);
-                    rc.stroke(
-                        Line {
-                            p0: pen,
-                            p1: *start,
-                        },
-                        &line,
-                        line_width,
-                    );
 
-                    pen = *start;
-                }
-                SegmentCommandKind::QuadraticBezier { control, point_1 } => {
-                    bezier.quad_to(*control, *point_1);
-                    rc.stroke(QuadBez::new(pen, *control, *point_1), &line, line_width);
+                line_width = *next_line_width;
+            }
 
-                    pen = *point_1;
-                }
+            // Closing a sub-path that begins at the segment start joins its
+            // ends, instead of leaving two caps on top of each other
+            if from_start && matches!(kind, SegmentCommandKind::ClosePath) {
+                stroke.close_path();
+                pen = *start;
+            } else {
+                pen = push_segment_command(&mut stroke, pen, *start, kind)?;
             }
         }
-    }
 
-    rc.fill(&bezier, &fill);
+        if stroke.elements().len() > 1 {
+            strokes.push((stroke, line_width));
+        }
+    }
 
-    Ok(())
+    Ok(strokes)
 }
 
+#[cfg(feature = "piet")]
 fn nil_brush<R>(rc: &mut R) -> R::Brush
 where
     R: RenderContext,
@@ -1,11 +1,14 @@
 //! Helper function that can render a TinyVG image using only the path to the input file
 
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
 
 use crate::decode::Decoder;
-use eyre::{Context, Result};
+use crate::error::{ensure, Context, Result};
+use crate::preset::{OutputFormat, Preset};
+use crate::progress::{run_batch, BatchOptions, BatchSummary, ProgressEvent};
 
 /// Render a TinyVG file using input and output path. If the output path is not
 /// specified, it will be automatically determined by adding the `.png` suffix
@@ -18,24 +21,111 @@ use eyre::{Context, Result};
 ///   Some("data/shield-render.png".into())
 /// ).unwrap();
 /// ```
-#[cfg(feature = "render-png")]
 pub fn render(in_path: impl AsRef<Path>, out_path: Option<PathBuf>) -> Result<()> {
-    let mut decoder = Decoder::new(BufReader::new(File::open(&in_path)?));
+    render_inner(in_path.as_ref(), out_path, &Preset::default(), false)
+}
+
+/// Same as `render`, but also embeds the TinyVG source file in the output PNG
+/// so it can be recovered with `png_source::extract_source`.
+///
+/// ```
+/// # use tinyvg::render_helper::render_with_source;
+/// render_with_source(
+///   "data/shield.tvg",
+///   Some("data/shield-source.png".into())
+/// ).unwrap();
+/// ```
+pub fn render_with_source(in_path: impl AsRef<Path>, out_path: Option<PathBuf>) -> Result<()> {
+    render_inner(in_path.as_ref(), out_path, &Preset::default(), true)
+}
+
+/// Same as `render`, but uses the settings of a `Preset`. If the output path
+/// is not specified, the extension of the preset's output format is used.
+///
+/// ```
+/// # use tinyvg::render_helper::render_with_preset;
+/// # use tinyvg::preset::Preset;
+/// render_with_preset(
+///   "data/shield.tvg",
+///   Some("data/shield-favicon.png".into()),
+///   &Preset::favicon(),
+///   false,
+/// ).unwrap();
+/// ```
+pub fn render_with_preset(
+    in_path: impl AsRef<Path>,
+    out_path: Option<PathBuf>,
+    preset: &Preset,
+    embed_source: bool,
+) -> Result<()> {
+    render_inner(in_path.as_ref(), out_path, preset, embed_source)
+}
 
-    let mut image = decoder.decode_header()?;
+/// Render many TinyVG files with the settings of a `Preset`, each next to its
+/// input file, and send a `ProgressEvent` for each file to `events`.
+///
+/// ```
+/// # use std::sync::mpsc;
+/// # use tinyvg::progress::BatchOptions;
+/// # use tinyvg::render_helper::render_batch;
+/// # use tinyvg::preset::Preset;
+/// let (events, receiver) = mpsc::channel();
+/// let summary = render_batch(
+///   &["data/shield.tvg".into()],
+///   &Preset::default(),
+///   &BatchOptions::default(),
+///   &events,
+/// );
+/// assert_eq!(summary.succeeded, 1);
+/// assert_eq!(receiver.try_iter().count(), 2);
+/// ```
+pub fn render_batch(
+    inputs: &[PathBuf],
+    preset: &Preset,
+    options: &BatchOptions,
+    events: &Sender<ProgressEvent>,
+) -> BatchSummary {
+    run_batch(inputs, options, events, |input| {
+        render_inner(input, None, preset, false)
+    })
+}
+
+fn render_inner(
+    in_path: &Path,
+    out_path: Option<PathBuf>,
+    preset: &Preset,
+    embed_source: bool,
+) -> Result<()> {
+    ensure!(
+        !embed_source || preset.format == OutputFormat::Png,
+        "the source can only be embedded in PNG output"
+    );
 
-    let result = decoder.decode_commands(&mut image);
+    let source = std::fs::read(in_path).wrap_err("failed to read input file")?;
+    // A partially decoded image is still rendered, the error is reported
+    // after the output is written
+    let (image, result) = match Decoder::new(source.as_slice()).decode_partial()? {
+        Ok(image) => (image, Ok(())),
+        Err(partial) => (partial.image, Err(partial.error)),
+    };
 
     let out_path = out_path.unwrap_or_else(|| {
-        let mut out_path = in_path.as_ref().to_owned();
-        out_path.set_extension("png");
+        let mut out_path = in_path.to_owned();
+        out_path.set_extension(preset.format.extension());
 
         out_path
     });
 
+    let mut output = Vec::new();
+    image.render_preset(&mut output, preset)?;
+
+    if embed_source {
+        output = crate::png_source::embed_source(&output, &source)?;
+    }
+
     let mut file =
         BufWriter::new(File::create(&out_path).wrap_err("failed to create output file")?);
-    image.render_png(&mut file)?;
+    file.write_all(&output).wrap_err("failed to write output file")?;
 
     result?;
 
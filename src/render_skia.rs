@@ -0,0 +1,640 @@
+//! Rendering with `tiny-skia`, a rasterizer written in pure Rust, for builds
+//! that can't link cairo. Enabled by the `render-skia` feature in place of
+//! `render-png`, and provides the same `Image::render_png` and
+//! `Image::render_pixmap` methods. If both features are enabled, those use
+//! cairo, and tiny-skia stays available as `Image::render_png_skia` and
+//! `Image::render_pixmap_skia`.
+//!
+//! Every `RenderOptions` setting is supported except `strategy`, commands are
+//! always drawn one at a time. Features built on cairo surfaces, like
+//! `Image::phash` and progressive rendering, need `render-png`.
+
+use kurbo::{Affine, BezPath, PathEl, Point, Shape};
+use tiny_skia::{
+    FillRule, GradientStop, LinearGradient, Paint, PathBuilder, RadialGradient, Shader, SpreadMode,
+    Stroke, Transform,
+};
+
+use crate::error::{format_err, Context, Result};
+use crate::format::{Color, Command, Image, OutlineStyle, Style};
+use crate::geometry::{has_area, StrokeScaling};
+use crate::render::{bez_path, path_strokes, polygon_path, ARC_TOLERANCE};
+use crate::{
+    DebugOverlay, LineWidthOverrides, PixelFormat, RasterImage, RenderOptions, RenderPasses,
+    RenderSize, SnapMode,
+};
+
+/// Miter limit of strokes, the default of piet so that both backends join
+/// sharp corners alike
+const MITER_LIMIT: f32 = 10.0;
+
+/// A pixmap being drawn onto, with the transform from image units to pixels.
+/// Geometry is transformed before it is handed to tiny-skia, so that the
+/// transform can be composed with kurbo.
+struct Canvas {
+    pixmap: tiny_skia::Pixmap,
+    transform: Affine,
+}
+
+/// The unsuffixed methods render with tiny-skia unless `render-png` is also
+/// enabled, in which case cairo provides them
+#[cfg(not(feature = "render-png"))]
+impl Image {
+    /// Render PNG data to the given `std::io::Write`.
+    ///
+    /// ```
+    /// # use tinyvg::Decoder;
+    /// # use std::fs::File;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mut png = Vec::new();
+    /// image.render_png(&mut png).unwrap();
+    /// ```
+    pub fn render_png(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        self.render_png_with_options(writer, &RenderOptions::default())
+    }
+
+    /// Render PNG data to the given `std::io::Write`, using the given render
+    /// options.
+    pub fn render_png_with_options(
+        &self,
+        writer: &mut impl std::io::Write,
+        options: &RenderOptions,
+    ) -> Result<()> {
+        self.render_png_skia(writer, RenderSize::Scale(1.0), options)
+    }
+
+    /// Render PNG data to the given `std::io::Write` at a different size than
+    /// the one in the header, see `RenderSize`.
+    ///
+    /// ```
+    /// # use tinyvg::{Decoder, RenderOptions, RenderSize};
+    /// # use std::fs::File;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mut png = Vec::new();
+    /// image
+    ///     .render_png_scaled(&mut png, RenderSize::Scale(8.0), &RenderOptions::default())
+    ///     .unwrap();
+    /// ```
+    pub fn render_png_scaled(
+        &self,
+        writer: &mut impl std::io::Write,
+        size: RenderSize,
+        options: &RenderOptions,
+    ) -> Result<()> {
+        self.render_png_skia(writer, size, options)
+    }
+
+    /// Render this image at the size in its header to RGBA pixels in memory.
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use tinyvg::Decoder;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let pixmap = image.render_pixmap().unwrap();
+    ///
+    /// assert_eq!(pixmap.data.len(), 24 * 24 * 4);
+    ///
+    /// // The shield is blue next to its left edge
+    /// let [r, g, b, a] = pixmap.pixel(4, 8);
+    /// assert!(b > r && b > g && a == 255);
+    /// ```
+    pub fn render_pixmap(&self) -> Result<RasterImage> {
+        self.render_pixmap_with_options(&RenderOptions::default())
+    }
+
+    /// Render this image to RGBA pixels in memory, using the given render
+    /// options. See `Image::render_pixmap`.
+    pub fn render_pixmap_with_options(&self, options: &RenderOptions) -> Result<RasterImage> {
+        self.render_pixmap_skia(RenderSize::Scale(1.0), options)
+    }
+
+    /// Render this image to RGBA pixels in memory at a different size than
+    /// the one in the header, see `RenderSize`.
+    pub fn render_pixmap_scaled(
+        &self,
+        size: RenderSize,
+        options: &RenderOptions,
+    ) -> Result<RasterImage> {
+        self.render_pixmap_skia(size, options)
+    }
+}
+
+impl Image {
+    /// Render PNG data to the given `std::io::Write` with tiny-skia, at a
+    /// different size than the one in the header if `size` asks for it. This
+    /// picks the backend even if `render-png` is enabled as well.
+    ///
+    /// ```
+    /// # use tinyvg::{Decoder, RenderOptions, RenderSize};
+    /// # use std::fs::File;
+    /// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+    ///     .decode()
+    ///     .unwrap();
+    ///
+    /// let mut png = Vec::new();
+    /// image
+    ///     .render_png_skia(&mut png, RenderSize::Scale(2.0), &RenderOptions::default())
+    ///     .unwrap();
+    /// ```
+    pub fn render_png_skia(
+        &self,
+        writer: &mut impl std::io::Write,
+        size: RenderSize,
+        options: &RenderOptions,
+    ) -> Result<()> {
+        let (width, height) = size.output_size(self.header.width, self.header.height)?;
+        let (canvas, render_result) = self.render_canvas(width, height, options)?;
+
+        write_png(writer, &canvas)?;
+
+        render_result
+    }
+
+    /// Render this image to RGBA pixels in memory with tiny-skia, see
+    /// `Image::render_png_skia`.
+    pub fn render_pixmap_skia(
+        &self,
+        size: RenderSize,
+        options: &RenderOptions,
+    ) -> Result<RasterImage> {
+        let (width, height) = size.output_size(self.header.width, self.header.height)?;
+        let (canvas, render_result) = self.render_canvas(width, height, options)?;
+        render_result?;
+
+        Ok(canvas_pixmap(&canvas, options.premultiplied))
+    }
+
+    /// Render this image onto a new pixmap of the given size, scaling the
+    /// image to fill it. Errors from drawing are returned alongside the
+    /// pixmap, so that PNGs of partially rendered images can still be written.
+    fn render_canvas(
+        &self,
+        width: u32,
+        height: u32,
+        options: &RenderOptions,
+    ) -> Result<(Canvas, Result<()>)> {
+        options.check_output_size(width as u64, height as u64)?;
+
+        let mut pixmap = tiny_skia::Pixmap::new(width, height)
+            .ok_or_else(|| format_err!("failed to create a {}x{} pixmap", width, height))?;
+
+        // tiny-skia stores premultiplied RGBA, like the base surface
+        if let Some(base) = &options.base_surface {
+            for (pixel, base) in pixmap
+                .data_mut()
+                .chunks_exact_mut(4)
+                .zip(base.premultiplied_pixels(width, height)?)
+            {
+                pixel.copy_from_slice(&base);
+            }
+        }
+
+        let scale = |target: u32, source: u32| {
+            if source == 0 {
+                1.0
+            } else {
+                target as f64 / source as f64
+            }
+        };
+
+        let mut canvas = Canvas {
+            pixmap,
+            transform: Affine::scale_non_uniform(
+                scale(width, self.header.width),
+                scale(height, self.header.height),
+            ),
+        };
+
+        let result = self
+            .draw_skia(&mut canvas, options)
+            .wrap_err("failed to draw tinyvg file");
+
+        Ok((canvas, result))
+    }
+
+    /// Apply the options to the image and draw the selected commands, in the
+    /// same order as `Image::draw_with_options` does for piet
+    fn draw_skia(&self, canvas: &mut Canvas, options: &RenderOptions) -> Result<()> {
+        if options.transform != Affine::IDENTITY {
+            let inner = RenderOptions {
+                transform: Affine::IDENTITY,
+                ..options.clone()
+            };
+
+            let outer = canvas.transform;
+            canvas.transform = outer * options.transform;
+
+            let result = self.draw_skia(canvas, &inner);
+
+            canvas.transform = outer;
+
+            return result;
+        }
+
+        if options.clear || options.background.is_some() {
+            let background = options
+                .background
+                .clone()
+                .unwrap_or(Color::rgba8(0, 0, 0, 0));
+
+            if options.clear {
+                canvas.pixmap.fill(skia_color(&background));
+            } else {
+                canvas.fill(
+                    &self.bounds_rect().to_path(ARC_TOLERANCE),
+                    &solid_paint(&background),
+                );
+            }
+
+            let options = RenderOptions {
+                background: None,
+                clear: false,
+                ..options.clone()
+            };
+
+            return self.draw_skia(canvas, &options);
+        }
+
+        if options.padding > 0.0 || options.plate.is_some() {
+            if let Some(plate) = &options.plate {
+                let plate_path = self
+                    .bounds_rect()
+                    .to_rounded_rect(plate.corner_radius)
+                    .to_path(ARC_TOLERANCE);
+
+                canvas.fill(&plate_path, &solid_paint(&plate.color));
+            }
+
+            let inner = RenderOptions {
+                padding: 0.0,
+                plate: None,
+                ..options.clone()
+            };
+
+            let outer = canvas.transform;
+            if let Some(transform) = self.padding_transform(options.padding) {
+                canvas.transform = outer * transform;
+            }
+
+            let result = self.draw_skia(canvas, &inner);
+
+            canvas.transform = outer;
+
+            return result;
+        }
+
+        if !options.debug.is_empty() {
+            let scale = canvas.transform.determinant().abs().sqrt();
+            let overlay =
+                self.debug_overlay(options.debug, scale, self.selected_commands(options, None)?);
+
+            let options = RenderOptions {
+                debug: DebugOverlay::default(),
+                ..options.clone()
+            };
+            self.draw_skia(canvas, &options)?;
+
+            return overlay.draw_skia(canvas, &RenderOptions::default());
+        }
+
+        if options.adjusts_line_widths() {
+            let scale = canvas.transform.determinant().abs().sqrt();
+            let adjusted = self.with_adjusted_line_widths(options, scale);
+
+            let options = RenderOptions {
+                line_widths: LineWidthOverrides::default(),
+                stroke_scaling: StrokeScaling::Proportional,
+                ..options.clone()
+            };
+
+            return adjusted.draw_skia(canvas, &options);
+        }
+
+        if self.needs_gradient_fallback(options.degenerate_gradients) {
+            return self
+                .with_gradient_fallback(options.degenerate_gradients)
+                .draw_skia(canvas, options);
+        }
+
+        if options.snap == SnapMode::Pixel {
+            let snapped = self.snap_to_pixels(canvas.transform);
+            let options = RenderOptions {
+                snap: SnapMode::None,
+                ..options.clone()
+            };
+
+            return snapped.draw_skia(canvas, &options);
+        }
+
+        if !options.passes.is_default() {
+            let split =
+                self.with_render_passes(options.passes, self.selected_commands(options, None)?);
+            let options = RenderOptions {
+                passes: RenderPasses::default(),
+                selection: None,
+                viewport: None,
+                max_primitives: None,
+                ..options.clone()
+            };
+
+            return split.draw_skia(canvas, &options);
+        }
+
+        let selected = self.selected_commands(options, None)?;
+
+        for (index, cmd) in self.commands.iter().enumerate() {
+            if selected(index) {
+                self.draw_skia_command(canvas, cmd)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw_skia_command(&self, canvas: &mut Canvas, cmd: &Command) -> Result<()> {
+        match cmd {
+            Command::FillPath {
+                fill_style,
+                path,
+                outline,
+            } => {
+                let fill = self.paint(canvas, fill_style)?;
+                canvas.fill(&bez_path(path)?, &fill);
+
+                if let Some(OutlineStyle {
+                    line_width,
+                    line_style,
+                }) = outline
+                {
+                    let line = self.paint(canvas, line_style)?;
+
+                    for (stroke, line_width) in path_strokes(*line_width, path)? {
+                        canvas.stroke(&stroke, &line, line_width);
+                    }
+                }
+            }
+            Command::FillRectangles {
+                fill_style,
+                rectangles,
+                outline,
+            } => {
+                let fill = self.paint(canvas, fill_style)?;
+                let outline = self.outline_paint(canvas, outline)?;
+
+                for rect in rectangles.iter().filter(|r| has_area(r)) {
+                    let path = rect.to_path(ARC_TOLERANCE);
+
+                    canvas.fill(&path, &fill);
+                    if let Some((line_width, line)) = &outline {
+                        canvas.stroke(&path, line, *line_width);
+                    }
+                }
+            }
+            Command::FillPolygon {
+                fill_style,
+                polygon,
+                outline,
+            } => {
+                let fill = self.paint(canvas, fill_style)?;
+                let path = polygon_path(polygon);
+
+                canvas.fill(&path, &fill);
+                if let Some((line_width, line)) = self.outline_paint(canvas, outline)? {
+                    canvas.stroke(&path, &line, line_width);
+                }
+            }
+            Command::DrawLines {
+                line_style,
+                line_width,
+                lines,
+            } => {
+                let line = self.paint(canvas, line_style)?;
+
+                for segment in lines {
+                    canvas.stroke(&segment.to_path(ARC_TOLERANCE), &line, *line_width);
+                }
+            }
+            Command::DrawLineLoop {
+                line_style,
+                line_width,
+                close_path,
+                points,
+            } => {
+                let line = self.paint(canvas, line_style)?;
+
+                let mut path = polygon_path(points);
+                if *close_path {
+                    path.line_to(points[0]);
+                }
+
+                canvas.stroke(&path, &line, *line_width);
+            }
+            Command::DrawLinePath {
+                line_style,
+                line_width,
+                path,
+            } => {
+                let line = self.paint(canvas, line_style)?;
+
+                for (stroke, line_width) in path_strokes(*line_width, path)? {
+                    canvas.stroke(&stroke, &line, line_width);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn outline_paint(
+        &self,
+        canvas: &Canvas,
+        outline: &Option<OutlineStyle>,
+    ) -> Result<Option<(f64, Paint<'static>)>> {
+        outline
+            .as_ref()
+            .map(|style| Ok((style.line_width, self.paint(canvas, &style.line_style)?)))
+            .transpose()
+    }
+
+    /// Paint of a style, with gradient points transformed to pixels
+    fn paint(&self, canvas: &Canvas, style: &Style) -> Result<Paint<'static>> {
+        let (radial, point_0, point_1, color_index_0, color_index_1) = match style {
+            Style::FlatColor { color_index } => {
+                return Ok(solid_paint(&self.color(*color_index)?));
+            }
+            Style::LinearGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => (false, point_0, point_1, color_index_0, color_index_1),
+            Style::RadialGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => (true, point_0, point_1, color_index_0, color_index_1),
+        };
+
+        let color_0 = self.color(*color_index_0)?;
+        let color_1 = self.color(*color_index_1)?;
+        let stops = vec![
+            GradientStop::new(0.0, skia_color(&color_0)),
+            GradientStop::new(1.0, skia_color(&color_1)),
+        ];
+
+        let point_0 = canvas.transform * *point_0;
+        let point_1 = canvas.transform * *point_1;
+
+        let shader = if radial {
+            RadialGradient::new(
+                skia_point(point_0),
+                skia_point(point_0),
+                point_0.distance(point_1) as f32,
+                stops,
+                SpreadMode::Pad,
+                Transform::identity(),
+            )
+        } else {
+            LinearGradient::new(
+                skia_point(point_0),
+                skia_point(point_1),
+                stops,
+                SpreadMode::Pad,
+                Transform::identity(),
+            )
+        };
+
+        // tiny-skia has no gradient between identical points, so they are
+        // drawn with the first color
+        Ok(match shader {
+            Some(shader) => paint(shader),
+            None => solid_paint(&color_0),
+        })
+    }
+}
+
+impl Canvas {
+    fn fill(&mut self, path: &BezPath, paint: &Paint) {
+        if let Some(path) = skia_path(&(self.transform * path.clone())) {
+            self.pixmap
+                .fill_path(&path, paint, FillRule::Winding, Transform::identity(), None);
+        }
+    }
+
+    /// Stroke a path `width` image units wide. Widths are scaled by the
+    /// average scale of the transform, since geometry is transformed before
+    /// stroking.
+    fn stroke(&mut self, path: &BezPath, paint: &Paint, width: f64) {
+        let width = width * self.transform.determinant().abs().sqrt();
+        if width <= 0.0 {
+            return;
+        }
+
+        let stroke = Stroke {
+            width: width as f32,
+            miter_limit: MITER_LIMIT,
+            ..Default::default()
+        };
+
+        if let Some(path) = skia_path(&(self.transform * path.clone())) {
+            self.pixmap
+                .stroke_path(&path, paint, &stroke, Transform::identity(), None);
+        }
+    }
+}
+
+/// Copy the pixels of a canvas, which tiny-skia stores premultiplied
+fn canvas_pixmap(canvas: &Canvas, premultiplied: bool) -> RasterImage {
+    let data = if premultiplied {
+        canvas.pixmap.data().to_vec()
+    } else {
+        canvas
+            .pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect()
+    };
+
+    RasterImage {
+        width: canvas.pixmap.width(),
+        height: canvas.pixmap.height(),
+        stride: canvas.pixmap.width() as usize * 4,
+        format: if premultiplied {
+            PixelFormat::Rgba8Premultiplied
+        } else {
+            PixelFormat::Rgba8
+        },
+        data,
+    }
+}
+
+fn write_png(writer: &mut impl std::io::Write, canvas: &Canvas) -> Result<()> {
+    let png = canvas
+        .pixmap
+        .encode_png()
+        .map_err(|e| format_err!("{}", e))
+        .wrap_err("failed to encode PNG")?;
+    writer.write_all(&png)?;
+
+    Ok(())
+}
+
+fn paint(shader: Shader<'static>) -> Paint<'static> {
+    Paint {
+        shader,
+        anti_alias: true,
+        ..Default::default()
+    }
+}
+
+fn solid_paint(color: &Color) -> Paint<'static> {
+    paint(Shader::SolidColor(skia_color(color)))
+}
+
+fn skia_color(color: &Color) -> tiny_skia::Color {
+    let (red, green, blue, alpha) = color.as_rgba8();
+
+    tiny_skia::Color::from_rgba8(red, green, blue, alpha)
+}
+
+fn skia_point(point: Point) -> tiny_skia::Point {
+    tiny_skia::Point::from_xy(point.x as f32, point.y as f32)
+}
+
+/// Convert a path in pixels, `None` if it is empty
+fn skia_path(path: &BezPath) -> Option<tiny_skia::Path> {
+    let mut builder = PathBuilder::new();
+
+    for element in path.elements() {
+        match *element {
+            PathEl::MoveTo(p) => builder.move_to(p.x as f32, p.y as f32),
+            PathEl::LineTo(p) => builder.line_to(p.x as f32, p.y as f32),
+            PathEl::QuadTo(c, p) => builder.quad_to(c.x as f32, c.y as f32, p.x as f32, p.y as f32),
+            PathEl::CurveTo(c0, c1, p) => builder.cubic_to(
+                c0.x as f32,
+                c0.y as f32,
+                c1.x as f32,
+                c1.y as f32,
+                p.x as f32,
+                p.y as f32,
+            ),
+            PathEl::ClosePath => builder.close(),
+        }
+    }
+
+    builder.finish()
+}
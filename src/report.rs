@@ -0,0 +1,159 @@
+//! Single-page HTML report about a TinyVG file, combining the header, size
+//! statistics, lints and palette with a preview. Meant as the one artifact an
+//! asset reviewer looks at per icon.
+
+use crate::decode::{Decoder, TrailerCheck};
+use crate::error::{full_message, Result};
+use crate::lint::LintConfig;
+use crate::number::fixed;
+
+/// Generate an HTML report for the TinyVG file `source`. The preview is
+/// embedded as inline SVG, so the report has no external dependencies.
+///
+/// ```
+/// # use tinyvg::lint::LintConfig;
+/// # use tinyvg::report::html_report;
+/// let source = std::fs::read("data/everything.tvg").unwrap();
+///
+/// let html = html_report(&source, &LintConfig::default()).unwrap();
+///
+/// assert!(html.contains("<svg"));
+/// assert!(html.contains("outline_fill_rectangles"));
+/// ```
+pub fn html_report(source: &[u8], lint: &LintConfig) -> Result<String> {
+    let mut decoder = Decoder::new(source).with_trailer_check(TrailerCheck::Warn);
+    let mut image = decoder.decode_header()?;
+    let decode_result = decoder.decode_commands(&mut image);
+
+    let mut findings = Vec::new();
+
+    if let Err(e) = &decode_result {
+        findings.push(format!("decoding failed: {}", full_message(e)));
+    }
+
+    findings.extend(decoder.warnings().iter().map(|w| w.to_string()));
+
+    for (index, count) in image.palette_usage().into_iter().enumerate() {
+        if count == 0 {
+            findings.push(format!("color {} is never used", index));
+        }
+    }
+
+    for index in image.styles().flat_map(|s| s.color_indices()) {
+        if index >= image.color_table.len() {
+            findings.push(format!("a style uses missing color {}", index));
+        }
+    }
+
+    findings.extend(image.lint(lint).iter().map(|l| l.to_string()));
+
+    let header = &image.header;
+    let header_len = decoder
+        .source_map()
+        .first()
+        .map_or(source.len() - image.trailer.len(), |range| range.start);
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>TinyVG report</title>\n");
+    html.push_str(
+        "<style>body { font-family: sans-serif; } td, th { padding: 0 1em 0 0; text-align: left; } \
+         .swatch { display: inline-block; width: 1em; height: 1em; border: 1px solid #888; } \
+         .preview { border: 1px solid #ccc; max-width: 512px; }</style>\n",
+    );
+    html.push_str("</head>\n<body>\n<h1>TinyVG report</h1>\n");
+
+    html.push_str("<h2>Preview</h2>\n<div class=\"preview\">\n");
+    match crate::svg::write_svg(&image) {
+        Ok(svg) => html.push_str(&svg),
+        Err(e) => html.push_str(&format!(
+            "<p>no preview: {}</p>\n",
+            escape(&full_message(&e))
+        )),
+    }
+    html.push_str("</div>\n");
+
+    html.push_str("<h2>Header</h2>\n<table>\n");
+    for (name, value) in [
+        ("Version", header.version.to_string()),
+        ("Size", format!("{} x {}", header.width, header.height)),
+        ("Scale", format!("1/{}", 1u32 << header.scale)),
+        ("Color encoding", format!("{:?}", header.color_encoding)),
+        ("Coordinate range", format!("{:?}", header.coordinate_range)),
+    ] {
+        html.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", name, value));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Statistics</h2>\n<table>\n");
+    for (name, value) in [
+        ("File size", format!("{} bytes", source.len())),
+        ("Header and color table", format!("{} bytes", header_len)),
+        ("Commands", image.commands.len().to_string()),
+        ("Colors", image.color_table.len().to_string()),
+        ("Trailer", format!("{} bytes", image.trailer.len())),
+    ] {
+        html.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", name, value));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Findings</h2>\n");
+    if findings.is_empty() {
+        html.push_str("<p>No findings</p>\n");
+    } else {
+        html.push_str("<ul>\n");
+        for finding in &findings {
+            html.push_str(&format!("<li>{}</li>\n", escape(finding)));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    html.push_str(
+        "<h2>Palette</h2>\n<table>\n<tr><th>Index</th><th>Color</th><th>References</th></tr>\n",
+    );
+    for (index, (color, count)) in image
+        .color_table
+        .iter()
+        .zip(image.palette_usage())
+        .enumerate()
+    {
+        let (red, green, blue, alpha) = color.as_rgba8();
+        html.push_str(&format!(
+            "<tr><td>{}</td><td><span class=\"swatch\" style=\"background: rgba({}, {}, {}, {})\"></span> \
+             #{:02x}{:02x}{:02x}{:02x}</td><td>{}</td></tr>\n",
+            index,
+            red,
+            green,
+            blue,
+            fixed(alpha as f64 / 255.0, 3),
+            red,
+            green,
+            blue,
+            alpha,
+            count
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str(
+        "<h2>Commands</h2>\n<table>\n<tr><th>Index</th><th>Command</th><th>Size</th></tr>\n",
+    );
+    for (index, (command, range)) in image.commands.iter().zip(decoder.source_map()).enumerate() {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{} bytes</td></tr>\n",
+            index,
+            command.name(),
+            range.len()
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    Ok(html)
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,161 @@
+//! Rendering of a prefix of an image's commands, for scrubbing through the
+//! draw order in an editor. The surface is cached every few commands, so
+//! moving to any position only draws the commands since the closest cache.
+
+use cairo::{Format, ImageSurface, Operator};
+
+use crate::error::{ensure, Context, Result};
+use crate::format::Image;
+use crate::render::RenderOptions;
+
+/// Renders an image up to any number of its commands, caching the surface
+/// after every `interval` commands
+///
+/// ```
+/// # use std::fs::File;
+/// # use tinyvg::Decoder;
+/// # use tinyvg::scrub::ScrubRenderer;
+/// let image = Decoder::new(File::open("data/tiger.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let mut scrubber = ScrubRenderer::new(&image, 200, 200, 16, Default::default()).unwrap();
+///
+/// let mut png = Vec::new();
+/// scrubber.render_png(&mut png, 100).unwrap();
+///
+/// // Going back only draws from the cache at 32 commands
+/// png.clear();
+/// scrubber.render_png(&mut png, 40).unwrap();
+/// ```
+pub struct ScrubRenderer<'a> {
+    image: &'a Image,
+    width: i32,
+    height: i32,
+    interval: usize,
+    options: RenderOptions,
+
+    /// `checkpoints[i]` has the first `i * interval` commands drawn
+    checkpoints: Vec<ImageSurface>,
+}
+
+impl<'a> ScrubRenderer<'a> {
+    /// Create a renderer for surfaces of `width` by `height` pixels, the image
+    /// is scaled to fit. A smaller `interval` makes scrubbing faster at the
+    /// cost of memory, one surface is kept for every `interval` commands.
+    pub fn new(
+        image: &'a Image,
+        width: i32,
+        height: i32,
+        interval: usize,
+        options: RenderOptions,
+    ) -> Result<Self> {
+        ensure!(interval > 0, "scrub interval must be at least 1");
+        options.check_output_size(width as u32 as u64, height as u32 as u64)?;
+
+        Ok(Self {
+            image,
+            width,
+            height,
+            interval,
+            options,
+            checkpoints: Vec::new(),
+        })
+    }
+
+    /// Render the first `commands` commands of the image to a new surface.
+    /// Counts larger than the number of commands render the whole image.
+    pub fn surface(&mut self, commands: usize) -> Result<ImageSurface> {
+        let commands = commands.min(self.image.commands.len());
+        let checkpoint = commands / self.interval;
+
+        while self.checkpoints.len() <= checkpoint {
+            let index = self.checkpoints.len();
+
+            let surface = match index.checked_sub(1) {
+                Some(previous) => {
+                    let surface = copy_surface(&self.checkpoints[previous])?;
+                    self.draw(
+                        &surface,
+                        previous * self.interval,
+                        index * self.interval,
+                        false,
+                    )?;
+                    surface
+                }
+                None => {
+                    let surface = ImageSurface::create(Format::ARgb32, self.width, self.height)
+                        .wrap_err("failed to create cairo surface")?;
+                    self.draw(&surface, 0, 0, true)?;
+                    surface
+                }
+            };
+
+            self.checkpoints.push(surface);
+        }
+
+        let surface = copy_surface(&self.checkpoints[checkpoint])?;
+        self.draw(&surface, checkpoint * self.interval, commands, false)?;
+
+        Ok(surface)
+    }
+
+    /// Render the first `commands` commands of the image as PNG data to the
+    /// given `std::io::Write`
+    pub fn render_png(&mut self, writer: &mut impl std::io::Write, commands: usize) -> Result<()> {
+        self.surface(commands)?.write_to_png(writer)?;
+
+        Ok(())
+    }
+
+    /// Draw the commands in `start..end` onto the surface. The background
+    /// plate is only drawn onto the empty surface, otherwise it would cover
+    /// the commands drawn before.
+    fn draw(&self, surface: &ImageSurface, start: usize, end: usize, plate: bool) -> Result<()> {
+        // The selection refers to indices of the whole image, so it is
+        // applied here instead of on the part
+        let selection = self.options.selection.as_ref();
+        let part = Image {
+            header: self.image.header.clone(),
+            color_table: self.image.color_table.clone(),
+            commands: (start..end)
+                .filter(|index| selection.is_none_or(|selection| selection.contains(*index)))
+                .map(|index| self.image.commands[index].clone())
+                .collect(),
+            trailer: Vec::new(),
+            labels: Default::default(),
+            bounds: None,
+        };
+
+        let options = RenderOptions {
+            plate: if plate {
+                self.options.plate.clone()
+            } else {
+                None
+            },
+            selection: None,
+            ..self.options.clone()
+        };
+
+        part.render_to(surface, self.width as f64, self.height as f64, &options)??;
+        surface.flush();
+
+        Ok(())
+    }
+}
+
+fn copy_surface(surface: &ImageSurface) -> Result<ImageSurface> {
+    let copy = ImageSurface::create(Format::ARgb32, surface.width(), surface.height())
+        .wrap_err("failed to create cairo surface")?;
+
+    let cr = cairo::Context::new(&copy).wrap_err("failed to create cairo context")?;
+    cr.set_operator(Operator::Source);
+    cr.set_source_surface(surface, 0.0, 0.0)
+        .wrap_err("failed to set source surface")?;
+    cr.paint().wrap_err("failed to copy surface")?;
+
+    drop(cr);
+    copy.flush();
+
+    Ok(copy)
+}
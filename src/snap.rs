@@ -0,0 +1,246 @@
+//! Pixel grid snapping, used by `SnapMode::Pixel`. Axis-aligned edges are
+//! moved onto pixel boundaries (or pixel centers for odd stroke widths), and
+//! stroke widths are rounded to whole pixels, so that small icons render
+//! crisply instead of smearing edges across two pixels.
+
+use kurbo::{Affine, Point};
+
+use crate::format::{Command, Image, Segment, SegmentCommandKind};
+
+/// Maps one axis of user space onto device pixels
+#[derive(Clone, Copy)]
+struct Grid {
+    scale: f64,
+    offset: f64,
+}
+
+impl Grid {
+    fn snap(self, value: f64, center: bool) -> f64 {
+        let half = if center { 0.5 } else { 0.0 };
+        let device = value * self.scale + self.offset;
+
+        ((device - half).round() + half - self.offset) / self.scale
+    }
+}
+
+impl Image {
+    /// Return a copy of this image with its geometry snapped to the pixel grid
+    /// of the given device transform. Transforms that rotate or skew have no
+    /// meaningful pixel grid, in which case the image is returned unchanged.
+    pub(crate) fn snap_to_pixels(&self, transform: Affine) -> Image {
+        let mut image = self.clone();
+
+        let [a, b, c, d, e, f] = transform.as_coeffs();
+        if b != 0.0 || c != 0.0 || a == 0.0 || d == 0.0 {
+            return image;
+        }
+
+        let x = Grid {
+            scale: a,
+            offset: e,
+        };
+        let y = Grid {
+            scale: d,
+            offset: f,
+        };
+        let scale = (a.abs() + d.abs()) / 2.0;
+
+        for command in &mut image.commands {
+            snap_command(command, x, y, scale);
+        }
+
+        image
+    }
+}
+
+fn snap_command(command: &mut Command, x: Grid, y: Grid, scale: f64) {
+    // Strokes with an odd pixel width are only crisp if they are centered on
+    // a pixel, everything else is aligned to pixel boundaries
+    let center = snap_widths(command, scale);
+
+    if let Command::FillRectangles { rectangles, .. } = command {
+        for rect in rectangles {
+            rect.x0 = x.snap(rect.x0, center);
+            rect.x1 = x.snap(rect.x1, center);
+            rect.y0 = y.snap(rect.y0, center);
+            rect.y1 = y.snap(rect.y1, center);
+        }
+
+        return;
+    }
+
+    // Both ends of an axis-aligned edge share the exact same coordinate, so
+    // snapping every occurrence of that value keeps the edge intact
+    let (xs, ys) = axis_aligned_edges(command);
+
+    map_coordinates(
+        command,
+        |v| {
+            if xs.contains(&v) {
+                x.snap(v, center)
+            } else {
+                v
+            }
+        },
+        |v| {
+            if ys.contains(&v) {
+                y.snap(v, center)
+            } else {
+                v
+            }
+        },
+    );
+}
+
+/// Round every line width of the command to whole device pixels, at least one
+/// pixel wide. Returns whether the main line width is an odd number of pixels.
+fn snap_widths(command: &mut Command, scale: f64) -> bool {
+    let snap = |width: &mut f64| {
+        let device = (*width * scale).round().max(1.0);
+        *width = device / scale;
+
+        device % 2.0 == 1.0
+    };
+
+    let odd = match command {
+        Command::FillPolygon { outline, .. }
+        | Command::FillRectangles { outline, .. }
+        | Command::FillPath { outline, .. } => match outline {
+            Some(outline) => snap(&mut outline.line_width),
+            None => false,
+        },
+        Command::DrawLines { line_width, .. }
+        | Command::DrawLineLoop { line_width, .. }
+        | Command::DrawLinePath { line_width, .. } => snap(line_width),
+    };
+
+    if let Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } = command {
+        for segment in path {
+            for command in &mut segment.commands {
+                if let Some(width) = &mut command.line_width {
+                    snap(width);
+                }
+            }
+        }
+    }
+
+    odd
+}
+
+/// Collect the x coordinates of all vertical edges and the y coordinates of
+/// all horizontal edges of a command
+fn axis_aligned_edges(command: &Command) -> (Vec<f64>, Vec<f64>) {
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+
+    let mut edge = |p0: Point, p1: Point| {
+        if p0.x == p1.x {
+            xs.push(p0.x);
+        }
+
+        if p0.y == p1.y {
+            ys.push(p0.y);
+        }
+    };
+
+    match command {
+        Command::FillPolygon {
+            polygon: points, ..
+        }
+        | Command::DrawLineLoop { points, .. } => {
+            for pair in points.windows(2) {
+                edge(pair[0], pair[1]);
+            }
+
+            if let (Some(first), Some(last)) = (points.first(), points.last()) {
+                edge(*last, *first);
+            }
+        }
+        Command::DrawLines { lines, .. } => {
+            for line in lines {
+                edge(line.p0, line.p1);
+            }
+        }
+        Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } => {
+            for Segment { start, commands } in path {
+                let mut pen = *start;
+
+                for command in commands {
+                    let end = command.kind.end_point(pen, *start);
+
+                    if let SegmentCommandKind::Line { .. }
+                    | SegmentCommandKind::HorizontalLine { .. }
+                    | SegmentCommandKind::VerticalLine { .. }
+                    | SegmentCommandKind::ClosePath = command.kind
+                    {
+                        edge(pen, end);
+                    }
+
+                    pen = end;
+                }
+            }
+        }
+        Command::FillRectangles { .. } => {}
+    }
+
+    (xs, ys)
+}
+
+/// Apply `fx` to every x coordinate and `fy` to every y coordinate of the
+/// command's geometry. Gradient anchors and radii are left alone.
+fn map_coordinates(command: &mut Command, fx: impl Fn(f64) -> f64, fy: impl Fn(f64) -> f64) {
+    let point = |p: &mut Point| {
+        p.x = fx(p.x);
+        p.y = fy(p.y);
+    };
+
+    match command {
+        Command::FillPolygon {
+            polygon: points, ..
+        }
+        | Command::DrawLineLoop { points, .. } => points.iter_mut().for_each(point),
+        Command::FillRectangles { rectangles, .. } => {
+            for rect in rectangles {
+                rect.x0 = fx(rect.x0);
+                rect.x1 = fx(rect.x1);
+                rect.y0 = fy(rect.y0);
+                rect.y1 = fy(rect.y1);
+            }
+        }
+        Command::DrawLines { lines, .. } => {
+            for line in lines {
+                point(&mut line.p0);
+                point(&mut line.p1);
+            }
+        }
+        Command::FillPath { path, .. } | Command::DrawLinePath { path, .. } => {
+            for segment in path {
+                point(&mut segment.start);
+
+                for command in &mut segment.commands {
+                    match &mut command.kind {
+                        SegmentCommandKind::Line { end } => point(end),
+                        SegmentCommandKind::HorizontalLine { x } => *x = fx(*x),
+                        SegmentCommandKind::VerticalLine { y } => *y = fy(*y),
+                        SegmentCommandKind::CubicBezier {
+                            control_0,
+                            control_1,
+                            point_1,
+                        } => {
+                            point(control_0);
+                            point(control_1);
+                            point(point_1);
+                        }
+                        SegmentCommandKind::ArcCircle { target, .. }
+                        | SegmentCommandKind::ArcEllipse { target, .. } => point(target),
+                        SegmentCommandKind::ClosePath => {}
+                        SegmentCommandKind::QuadraticBezier { control, point_1 } => {
+                            point(control);
+                            point(point_1);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Instrumentation for performance work on the decoder. Wall-clock benchmarks
+//! don't show whether a change to buffering or allocation strategy did what
+//! it was meant to, these counters do.
+//!
+//! Allocations can only be counted if `CountingAllocator` is installed as the
+//! global allocator of the program. Without it, the allocation counters of
+//! `DecodeStats` are always zero.
+//!
+//! ```
+//! use std::fs::File;
+//! use tinyvg::stats::CountingAllocator;
+//! use tinyvg::Decoder;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator;
+//!
+//! fn main() {
+//!     let decoder = Decoder::new(File::open("data/shield.tvg").unwrap());
+//!     let (image, stats) = decoder.decode_with_stats().unwrap();
+//!
+//!     assert_eq!(stats.commands, image.commands.len());
+//!     assert!(stats.allocations > 0);
+//! }
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Global allocator that forwards to `std::alloc::System` and counts every
+/// allocation. Reallocations count as one allocation of the new size.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+fn record(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED_BYTES.fetch_add(size as u64, Ordering::Relaxed);
+}
+
+/// Snapshot of the global allocation counters
+pub(crate) fn allocation_counters() -> (u64, u64) {
+    (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+/// Counters collected by `Decoder::decode_with_stats`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodeStats {
+    /// Number of allocations made while decoding. The counters are global, so
+    /// allocations made by other threads at the same time are included.
+    pub allocations: u64,
+
+    /// Total size in bytes of the allocations made while decoding
+    pub allocated_bytes: u64,
+
+    /// Number of commands decoded
+    pub commands: usize,
+
+    /// Number of bytes consumed from the reader, including the trailer
+    pub bytes_read: usize,
+}
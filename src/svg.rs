@@ -0,0 +1,546 @@
+//! SVG export. Every command becomes one or more SVG elements, gradients are
+//! written as `<linearGradient>` and `<radialGradient>` definitions.
+//!
+//! TinyVG colors are sRGB, which is tagged explicitly with
+//! `color-interpolation="sRGB"` so that design tools don't interpolate
+//! gradients in linear RGB. Alpha is written as separate opacity attributes
+//! (or inside `rgba()`, see `SvgColorFormat`), including on gradient stops.
+//! Labels of commands (see `Image::labels`) are written as comments before
+//! their elements.
+//!
+//! ```
+//! # use std::fs::File;
+//! # use tinyvg::Decoder;
+//! # use tinyvg::svg::write_svg;
+//! let image = Decoder::new(File::open("data/everything.tvg").unwrap())
+//!     .decode()
+//!     .unwrap();
+//!
+//! let svg = write_svg(&image).unwrap();
+//!
+//! assert!(svg.starts_with("<svg"));
+//! assert!(svg.contains("<linearGradient"));
+//! ```
+
+use crate::error::{Error, Result};
+use crate::format::{Color, Command, Image, Point, Segment, SegmentCommandKind, Style};
+use crate::geometry::has_area;
+use crate::number::NumberFormat;
+
+/// How colors are written in SVG attributes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SvgColorFormat {
+    /// `#rrggbb`, with alpha in a separate `fill-opacity`, `stroke-opacity`
+    /// or `stop-opacity` attribute. Understood by every SVG consumer.
+    Hex,
+
+    /// `rgb(r, g, b)`, or `rgba(r, g, b, a)` for colors that are not opaque
+    Rgb,
+}
+
+/// Options for the SVG writer
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// How colors are written
+    pub color_format: SvgColorFormat,
+
+    /// How coordinates and widths are written. Limiting the precision makes
+    /// documents smaller, the default writes every number exactly.
+    pub number_format: NumberFormat,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            color_format: SvgColorFormat::Hex,
+            number_format: NumberFormat::default(),
+        }
+    }
+}
+
+/// Write an image as an SVG document
+pub fn write_svg(image: &Image) -> Result<String> {
+    write_svg_with_options(image, &SvgOptions::default())
+}
+
+/// Write an image as an SVG document, using the given options
+///
+/// ```
+/// # use std::fs::File;
+/// # use tinyvg::Decoder;
+/// # use tinyvg::number::NumberFormat;
+/// # use tinyvg::svg::{write_svg_with_options, SvgColorFormat, SvgOptions};
+/// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let options = SvgOptions {
+///     color_format: SvgColorFormat::Rgb,
+///     number_format: NumberFormat {
+///         precision: Some(2),
+///     },
+/// };
+/// let svg = write_svg_with_options(&image, &options).unwrap();
+///
+/// assert!(svg.contains(r#"fill="rgb(41, 173, 255)""#));
+/// ```
+pub fn write_svg_with_options(image: &Image, options: &SvgOptions) -> Result<String> {
+    let mut writer = SvgWriter {
+        image,
+        options,
+        defs: String::new(),
+        body: String::new(),
+        gradients: 0,
+    };
+
+    for (index, command) in image.commands.iter().enumerate() {
+        if let Some(label) = image.labels.get(&index) {
+            writer.comment(label);
+        }
+
+        writer.command(command)?;
+    }
+
+    let width = image.header.width;
+    let height = image.header.height;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" color-interpolation=\"sRGB\">\n",
+        width, height, width, height
+    );
+
+    if !writer.defs.is_empty() {
+        svg.push_str("  <defs>\n");
+        svg.push_str(&writer.defs);
+        svg.push_str("  </defs>\n");
+    }
+
+    svg.push_str(&writer.body);
+    svg.push_str("</svg>\n");
+
+    Ok(svg)
+}
+
+struct SvgWriter<'a> {
+    image: &'a Image,
+    options: &'a SvgOptions,
+    defs: String,
+    body: String,
+    gradients: usize,
+}
+
+impl<'a> SvgWriter<'a> {
+    fn command(&mut self, command: &Command) -> Result<()> {
+        let f = self.options.number_format;
+
+        match command {
+            Command::FillPolygon {
+                fill_style,
+                polygon,
+                outline,
+            } => {
+                let mut attributes = self.paint("fill", fill_style)?;
+                if let Some(outline) = outline {
+                    attributes += &self.stroke(&outline.line_style, outline.line_width)?;
+                }
+
+                self.element(
+                    "path",
+                    &format!("d=\"{}\"{}", polyline(polygon, true, f), attributes),
+                );
+            }
+            Command::FillRectangles {
+                fill_style,
+                rectangles,
+                outline,
+            } => {
+                let mut attributes = self.paint("fill", fill_style)?;
+                if let Some(outline) = outline {
+                    attributes += &self.stroke(&outline.line_style, outline.line_width)?;
+                }
+
+                for rect in rectangles.iter().filter(|r| has_area(r)) {
+                    self.element(
+                        "rect",
+                        &format!(
+                            "x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{}",
+                            f.format(rect.x0),
+                            f.format(rect.y0),
+                            f.format(rect.width()),
+                            f.format(rect.height()),
+                            attributes
+                        ),
+                    );
+                }
+            }
+            Command::FillPath {
+                fill_style,
+                path,
+                outline,
+            } => {
+                let mut attributes = self.paint("fill", fill_style)?;
+                if let Some(outline) = outline {
+                    attributes += &self.stroke(&outline.line_style, outline.line_width)?;
+                }
+
+                self.element(
+                    "path",
+                    &format!("d=\"{}\"{}", path_data(path, f), attributes),
+                );
+            }
+            Command::DrawLines {
+                line_style,
+                line_width,
+                lines,
+            } => {
+                let data = lines
+                    .iter()
+                    .map(|l| format!("M{} L{}", point(l.p0, f), point(l.p1, f)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let attributes = self.stroke(line_style, *line_width)?;
+
+                self.element(
+                    "path",
+                    &format!("d=\"{}\" fill=\"none\"{}", data, attributes),
+                );
+            }
+            Command::DrawLineLoop {
+                line_style,
+                line_width,
+                close_path,
+                points,
+            } => {
+                let attributes = self.stroke(line_style, *line_width)?;
+
+                self.element(
+                    "path",
+                    &format!(
+                        "d=\"{}\" fill=\"none\"{}",
+                        polyline(points, *close_path, f),
+                        attributes
+                    ),
+                );
+            }
+            Command::DrawLinePath {
+                line_style,
+                line_width,
+                path,
+            } => {
+                let varying = path
+                    .iter()
+                    .flat_map(|s| &s.commands)
+                    .any(|c| c.line_width.is_some());
+
+                if varying {
+                    self.varying_width_path(line_style, *line_width, path)?;
+                } else {
+                    let attributes = self.stroke(line_style, *line_width)?;
+
+                    self.element(
+                        "path",
+                        &format!("d=\"{}\" fill=\"none\"{}", path_data(path, f), attributes),
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// SVG has no per-segment stroke widths, so paths that change their width
+    /// are written as one element per path command. Like the renderer, a
+    /// width applies to its command and every command after it.
+    fn varying_width_path(
+        &mut self,
+        style: &Style,
+        mut line_width: f64,
+        path: &[Segment],
+    ) -> Result<()> {
+        let f = self.options.number_format;
+        let paint = self.paint("stroke", style)?;
+
+        for Segment { start, commands } in path {
+            let mut pen = *start;
+
+            for command in commands {
+                if let Some(width) = command.line_width {
+                    line_width = width;
+                }
+
+                // Each element is its own subpath, so closing the path has to
+                // be written as a line back to the start
+                let (data, end) = match command.kind {
+                    SegmentCommandKind::ClosePath => (format!("L{}", point(*start, f)), *start),
+                    _ => command_data(&command.kind, pen, *start, f),
+                };
+
+                self.element(
+                    "path",
+                    &format!(
+                        "d=\"M{} {}\" fill=\"none\"{}{}",
+                        point(pen, f),
+                        data,
+                        paint,
+                        stroke_width(line_width, f)
+                    ),
+                );
+
+                pen = end;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Comment before the elements of a command, showing its label. `--` is
+    /// not allowed inside of comments.
+    fn comment(&mut self, text: &str) {
+        let mut text = text.to_string();
+        while text.contains("--") {
+            text = text.replace("--", "- -");
+        }
+
+        self.body.push_str(&format!("  <!-- {} -->\n", text));
+    }
+
+    fn element(&mut self, name: &str, attributes: &str) {
+        self.body
+            .push_str(&format!("  <{} {}/>\n", name, attributes));
+    }
+
+    fn stroke(&mut self, style: &Style, line_width: f64) -> Result<String> {
+        Ok(format!(
+            "{}{}",
+            self.paint("stroke", style)?,
+            stroke_width(line_width, self.options.number_format)
+        ))
+    }
+
+    /// Attributes that paint the `fill` or `stroke` of an element with a
+    /// style, with a leading space
+    fn paint(&mut self, property: &str, style: &Style) -> Result<String> {
+        let (tag, point_0, point_1, color_index_0, color_index_1) = match style {
+            Style::FlatColor { color_index } => {
+                let color = self.color(*color_index)?;
+
+                return Ok(format!(
+                    " {}=\"{}\"{}",
+                    property,
+                    self.color_value(&color),
+                    self.opacity(&format!("{}-opacity", property), &color)
+                ));
+            }
+            Style::LinearGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => (
+                "linearGradient",
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            ),
+            Style::RadialGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => (
+                "radialGradient",
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            ),
+        };
+
+        let id = format!("gradient{}", self.gradients);
+        self.gradients += 1;
+
+        let f = self.options.number_format;
+        let geometry = if tag == "linearGradient" {
+            format!(
+                "x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"",
+                f.format(point_0.x),
+                f.format(point_0.y),
+                f.format(point_1.x),
+                f.format(point_1.y)
+            )
+        } else {
+            format!(
+                "cx=\"{}\" cy=\"{}\" r=\"{}\"",
+                f.format(point_0.x),
+                f.format(point_0.y),
+                f.format(point_0.distance(*point_1))
+            )
+        };
+
+        let mut gradient = format!(
+            "    <{} id=\"{}\" gradientUnits=\"userSpaceOnUse\" color-interpolation=\"sRGB\" {}>\n",
+            tag, id, geometry
+        );
+
+        for (offset, index) in [(0, color_index_0), (1, color_index_1)] {
+            let color = self.color(*index)?;
+
+            gradient.push_str(&format!(
+                "      <stop offset=\"{}\" stop-color=\"{}\"{}/>\n",
+                offset,
+                self.color_value(&color),
+                self.opacity("stop-opacity", &color)
+            ));
+        }
+
+        gradient.push_str(&format!("    </{}>\n", tag));
+        self.defs.push_str(&gradient);
+
+        Ok(format!(" {}=\"url(#{})\"", property, id))
+    }
+
+    fn color(&self, index: usize) -> Result<Color> {
+        self.image
+            .color_table
+            .get(index)
+            .cloned()
+            .ok_or_else(|| Error::ColorIndexOutOfRange {
+                index,
+                colors: self.image.color_table.len(),
+            })
+    }
+
+    fn color_value(&self, color: &Color) -> String {
+        let (red, green, blue, alpha) = color.as_rgba8();
+
+        match self.options.color_format {
+            SvgColorFormat::Hex => format!("#{:02x}{:02x}{:02x}", red, green, blue),
+            SvgColorFormat::Rgb if alpha == 255 => format!("rgb({}, {}, {})", red, green, blue),
+            SvgColorFormat::Rgb => {
+                format!("rgba({}, {}, {}, {})", red, green, blue, opacity(color))
+            }
+        }
+    }
+
+    /// Opacity attribute for a color, with a leading space. Empty for opaque
+    /// colors and for `SvgColorFormat::Rgb`, which carries alpha in the color.
+    fn opacity(&self, attribute: &str, color: &Color) -> String {
+        if self.options.color_format == SvgColorFormat::Rgb || color.as_rgba8().3 == 255 {
+            return String::new();
+        }
+
+        format!(" {}=\"{}\"", attribute, opacity(color))
+    }
+}
+
+/// Stroke width attributes, with a leading space. TinyVG lines always have
+/// round caps and joins.
+fn stroke_width(line_width: f64, f: NumberFormat) -> String {
+    format!(
+        " stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"",
+        f.format(line_width)
+    )
+}
+
+/// Alpha of a color, rounded to 3 digits
+fn opacity(color: &Color) -> String {
+    NumberFormat { precision: Some(3) }.format(color.as_rgba().3)
+}
+
+fn point(p: Point, f: NumberFormat) -> String {
+    format!("{} {}", f.format(p.x), f.format(p.y))
+}
+
+fn polyline(points: &[Point], close: bool, f: NumberFormat) -> String {
+    let mut data = points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| format!("{}{}", if i == 0 { "M" } else { "L" }, point(*p, f)))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if close {
+        data.push_str(" Z");
+    }
+
+    data
+}
+
+fn path_data(path: &[Segment], f: NumberFormat) -> String {
+    let mut data = Vec::new();
+
+    for Segment { start, commands } in path {
+        let mut pen = *start;
+        data.push(format!("M{}", point(pen, f)));
+
+        for command in commands {
+            let (command_data, end) = command_data(&command.kind, pen, *start, f);
+
+            data.push(command_data);
+            pen = end;
+        }
+    }
+
+    data.join(" ")
+}
+
+/// SVG path data for a single path command starting at `pen`, and the point
+/// the command ends on
+fn command_data(
+    kind: &SegmentCommandKind,
+    pen: Point,
+    start: Point,
+    f: NumberFormat,
+) -> (String, Point) {
+    let data = match kind {
+        SegmentCommandKind::Line { end } => format!("L{}", point(*end, f)),
+        SegmentCommandKind::HorizontalLine { x } => format!("H{}", f.format(*x)),
+        SegmentCommandKind::VerticalLine { y } => format!("V{}", f.format(*y)),
+        SegmentCommandKind::CubicBezier {
+            control_0,
+            control_1,
+            point_1,
+        } => format!(
+            "C{} {} {}",
+            point(*control_0, f),
+            point(*control_1, f),
+            point(*point_1, f)
+        ),
+        SegmentCommandKind::ArcCircle {
+            large,
+            sweep,
+            radius,
+            target,
+        } => format!(
+            "A{} {} 0 {} {} {}",
+            f.format(*radius),
+            f.format(*radius),
+            *large as u8,
+            *sweep as u8,
+            point(*target, f)
+        ),
+        SegmentCommandKind::ArcEllipse {
+            large,
+            sweep,
+            radius_x,
+            radius_y,
+            rotation,
+            target,
+        } => format!(
+            "A{} {} {} {} {} {}",
+            f.format(*radius_x),
+            f.format(*radius_y),
+            f.format(*rotation),
+            *large as u8,
+            *sweep as u8,
+            point(*target, f)
+        ),
+        SegmentCommandKind::ClosePath => "Z".to_string(),
+        SegmentCommandKind::QuadraticBezier { control, point_1 } => {
+            format!("Q{} {}", point(*control, f), point(*point_1, f))
+        }
+    };
+
+    (data, kind.end_point(pen, start))
+}
@@ -0,0 +1,925 @@
+//! The TinyVG text format (`.tvgt`), an s-expression representation of a
+//! TinyVG image used by the reference implementation. Useful for inspecting
+//! files and authoring images by hand.
+//!
+//! ```
+//! # use std::fs::File;
+//! # use tinyvg::Decoder;
+//! # use tinyvg::text_format::write_text;
+//! let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+//!     .decode()
+//!     .unwrap();
+//!
+//! let text = write_text(&image);
+//!
+//! assert_eq!(text, std::fs::read_to_string("data/shield.tvgt").unwrap());
+//! ```
+//!
+//! Besides the grammar written by the reference implementation, the parser
+//! accepts colors written inline wherever a style refers to a color index,
+//! e.g. `(flat (1.000 0.471 0.000))` or `(linear (0 0) (10 0) 0 (1 1 1 0.5) )`.
+//! Inline colors resolve to the first identical entry of the color table, or
+//! are appended to it if there is none.
+
+use crate::error::{bail, ensure, format_err, Context, Error, Result};
+use crate::format::{
+    Color, ColorEncoding, Command, CoordinateRange, Header, Image, Line, OutlineStyle, Point, Rect,
+    Segment, SegmentCommand, SegmentCommandKind, Style,
+};
+use crate::number::{fixed, shortest};
+
+/// Options for the text writer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextOptions {
+    /// Write colors inline in styles, e.g. `(flat (1.000 0.471 0.000))`,
+    /// instead of as an index into the color table. The color table is still
+    /// written. This is a convenience form of the text grammar that is easier
+    /// to read and edit by hand.
+    pub inline_colors: bool,
+}
+
+/// Write an image in the TinyVG text format. Coordinates and line widths are
+/// written with as many digits as needed to read back the exact same value, so
+/// line widths smaller than one unit survive a round trip through the text
+/// format.
+///
+/// ```
+/// # use std::fs::File;
+/// # use tinyvg::{Decoder, Image};
+/// # use tinyvg::format::{Color, Command, Line, Point, Style};
+/// # use tinyvg::text_format::{parse_text, write_text};
+/// let image = Decoder::new(File::open("data/everything.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let parsed = parse_text(&write_text(&image)).unwrap();
+/// assert_eq!(parsed.commands, image.commands);
+///
+/// let mut image = Image::empty(16, 16);
+/// image.header.scale = 9;
+/// image.color_table.push(Color::BLACK);
+/// image.commands.push(Command::DrawLines {
+///     line_style: Style::FlatColor { color_index: 0 },
+///     line_width: 1.0 / 512.0,
+///     lines: vec![Line::new(Point::new(0.0, 0.0), Point::new(16.0, 16.0))],
+/// });
+///
+/// let text = write_text(&image);
+/// assert!(text.contains("0.001953125"));
+/// assert_eq!(parse_text(&text).unwrap(), image);
+/// ```
+pub fn write_text(image: &Image) -> String {
+    write_text_with_options(image, &TextOptions::default())
+}
+
+/// Write an image in the TinyVG text format, using the given options
+///
+/// ```
+/// # use std::fs::File;
+/// # use tinyvg::Decoder;
+/// # use tinyvg::text_format::{write_text_with_options, TextOptions};
+/// let image = Decoder::new(File::open("data/shield.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// let options = TextOptions {
+///     inline_colors: true,
+/// };
+/// let text = write_text_with_options(&image, &options);
+///
+/// assert!(text.contains("(flat (0.161 0.678 1.000))"));
+/// ```
+pub fn write_text_with_options(image: &Image, options: &TextOptions) -> String {
+    let mut writer = TextWriter {
+        image,
+        options,
+        out: String::new(),
+    };
+
+    writer.image();
+
+    writer.out
+}
+
+struct TextWriter<'a> {
+    image: &'a Image,
+    options: &'a TextOptions,
+    out: String,
+}
+
+impl<'a> TextWriter<'a> {
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth {
+            self.out.push_str("  ");
+        }
+
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn image(&mut self) {
+        let header = &self.image.header;
+
+        self.line(0, &format!("(tvg {}", header.version));
+        self.line(
+            1,
+            &format!(
+                "({} {} 1/{} {} {})",
+                header.width,
+                header.height,
+                1u32 << header.scale,
+                color_encoding(header.color_encoding),
+                coordinate_range(header.coordinate_range),
+            ),
+        );
+
+        self.line(1, "(");
+        for color in &self.image.color_table {
+            self.line(2, &color_literal(color));
+        }
+        self.line(1, ")");
+
+        self.line(1, "(");
+        for command in &self.image.commands {
+            self.command(command);
+        }
+        self.line(1, ")");
+
+        self.line(0, ")");
+    }
+
+    fn command(&mut self, command: &Command) {
+        self.line(2, "(");
+
+        match command {
+            Command::FillPolygon {
+                fill_style,
+                polygon,
+                outline,
+            } => {
+                self.fill_header("fill_polygon", fill_style, outline);
+                self.list(polygon.iter().map(|p| point(*p)));
+            }
+            Command::FillRectangles {
+                fill_style,
+                rectangles,
+                outline,
+            } => {
+                self.fill_header("fill_rectangles", fill_style, outline);
+                self.list(rectangles.iter().map(|r| {
+                    format!(
+                        "({} {} {} {})",
+                        shortest(r.x0),
+                        shortest(r.y0),
+                        shortest(r.width()),
+                        shortest(r.height())
+                    )
+                }));
+            }
+            Command::FillPath {
+                fill_style,
+                path,
+                outline,
+            } => {
+                self.fill_header("fill_path", fill_style, outline);
+                self.path(path);
+            }
+            Command::DrawLines {
+                line_style,
+                line_width,
+                lines,
+            } => {
+                self.line_header("draw_lines", line_style, *line_width);
+                self.list(
+                    lines
+                        .iter()
+                        .map(|l| format!("({} {})", point(l.p0), point(l.p1))),
+                );
+            }
+            Command::DrawLineLoop {
+                line_style,
+                line_width,
+                close_path,
+                points,
+            } => {
+                let name = if *close_path {
+                    "draw_line_loop"
+                } else {
+                    "draw_line_strip"
+                };
+
+                self.line_header(name, line_style, *line_width);
+                self.list(points.iter().map(|p| point(*p)));
+            }
+            Command::DrawLinePath {
+                line_style,
+                line_width,
+                path,
+            } => {
+                self.line_header("draw_line_path", line_style, *line_width);
+                self.path(path);
+            }
+        }
+
+        self.line(2, ")");
+    }
+
+    fn fill_header(&mut self, name: &str, fill_style: &Style, outline: &Option<OutlineStyle>) {
+        match outline {
+            Some(outline) => {
+                self.line(3, &format!("outline_{}", name));
+                self.line(3, &self.style(fill_style));
+                self.line(3, &self.style(&outline.line_style));
+                self.line(3, &shortest(outline.line_width));
+            }
+            None => {
+                self.line(3, name);
+                self.line(3, &self.style(fill_style));
+            }
+        }
+    }
+
+    fn line_header(&mut self, name: &str, line_style: &Style, line_width: f64) {
+        self.line(3, name);
+        self.line(3, &self.style(line_style));
+        self.line(3, &shortest(line_width));
+    }
+
+    fn list(&mut self, items: impl Iterator<Item = String>) {
+        self.line(3, "(");
+        for item in items {
+            self.line(4, &item);
+        }
+        self.line(3, ")");
+    }
+
+    fn path(&mut self, path: &[Segment]) {
+        self.line(3, "(");
+
+        for segment in path {
+            self.line(4, &point(segment.start));
+            self.line(4, "(");
+
+            for command in &segment.commands {
+                let width = match command.line_width {
+                    Some(width) => shortest(width),
+                    None => "-".to_string(),
+                };
+
+                let text = match &command.kind {
+                    SegmentCommandKind::Line { end } => {
+                        format!("(line {} {})", width, coordinates(*end))
+                    }
+                    SegmentCommandKind::HorizontalLine { x } => {
+                        format!("(horiz {} {})", width, shortest(*x))
+                    }
+                    SegmentCommandKind::VerticalLine { y } => {
+                        format!("(vert {} {})", width, shortest(*y))
+                    }
+                    SegmentCommandKind::CubicBezier {
+                        control_0,
+                        control_1,
+                        point_1,
+                    } => format!(
+                        "(bezier {} {} {} {})",
+                        width,
+                        point(*control_0),
+                        point(*control_1),
+                        point(*point_1)
+                    ),
+                    // The decoder flips the sweep flag of the binary format,
+                    // the text format uses the binary value
+                    SegmentCommandKind::ArcCircle {
+                        large,
+                        sweep,
+                        radius,
+                        target,
+                    } => format!(
+                        "(arc_circle {} {} {} {} {})",
+                        width,
+                        shortest(*radius),
+                        large,
+                        !sweep,
+                        point(*target)
+                    ),
+                    SegmentCommandKind::ArcEllipse {
+                        large,
+                        sweep,
+                        radius_x,
+                        radius_y,
+                        rotation,
+                        target,
+                    } => format!(
+                        "(arc_ellipse {} {} {} {} {} {} {})",
+                        width,
+                        shortest(*radius_x),
+                        shortest(*radius_y),
+                        shortest(*rotation),
+                        large,
+                        !sweep,
+                        point(*target)
+                    ),
+                    SegmentCommandKind::ClosePath => format!("(close {})", width),
+                    SegmentCommandKind::QuadraticBezier { control, point_1 } => format!(
+                        "(quadratic_bezier {} {} {})",
+                        width,
+                        point(*control),
+                        point(*point_1)
+                    ),
+                };
+
+                self.line(5, &text);
+            }
+
+            self.line(4, ")");
+        }
+
+        self.line(3, ")");
+    }
+
+    fn style(&self, style: &Style) -> String {
+        match style {
+            Style::FlatColor { color_index } => format!("(flat {})", self.color(*color_index)),
+            Style::LinearGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => format!(
+                "(linear {} {} {} {} )",
+                point(*point_0),
+                point(*point_1),
+                self.color(*color_index_0),
+                self.color(*color_index_1)
+            ),
+            Style::RadialGradient {
+                point_0,
+                point_1,
+                color_index_0,
+                color_index_1,
+            } => format!(
+                "(radial {} {} {} {} )",
+                point(*point_0),
+                point(*point_1),
+                self.color(*color_index_0),
+                self.color(*color_index_1)
+            ),
+        }
+    }
+
+    /// A reference to a color, either the index into the color table or the
+    /// color itself if inline colors are enabled. Indexes outside of the color
+    /// table are always written as indexes.
+    fn color(&self, index: usize) -> String {
+        match self.image.color_table.get(index) {
+            Some(color) if self.options.inline_colors => color_literal(color),
+            _ => index.to_string(),
+        }
+    }
+}
+
+fn point(p: Point) -> String {
+    format!("({})", coordinates(p))
+}
+
+fn coordinates(p: Point) -> String {
+    format!("{} {}", shortest(p.x), shortest(p.y))
+}
+
+fn color_literal(color: &Color) -> String {
+    let (red, green, blue, alpha) = color.as_rgba();
+
+    if alpha == 1.0 {
+        format!("({} {} {})", fixed(red, 3), fixed(green, 3), fixed(blue, 3))
+    } else {
+        format!(
+            "({} {} {} {})",
+            fixed(red, 3),
+            fixed(green, 3),
+            fixed(blue, 3),
+            fixed(alpha, 3)
+        )
+    }
+}
+
+fn color_encoding(encoding: ColorEncoding) -> &'static str {
+    match encoding {
+        ColorEncoding::Rgba8888 => "u8888",
+        ColorEncoding::Rgb565 => "u565",
+        ColorEncoding::RgbaF32 => "f32",
+    }
+}
+
+fn coordinate_range(range: CoordinateRange) -> &'static str {
+    match range {
+        CoordinateRange::Default => "default",
+        CoordinateRange::Reduced => "reduced",
+        CoordinateRange::Enhanced => "enhanced",
+    }
+}
+
+/// Parse an image from the TinyVG text format. The trailer of the returned
+/// image is always empty.
+///
+/// ```
+/// # use std::fs::File;
+/// # use tinyvg::Decoder;
+/// # use tinyvg::text_format::parse_text;
+/// let text = std::fs::read_to_string("data/shield.tvgt").unwrap();
+/// let parsed = parse_text(&text).unwrap();
+///
+/// let decoded = Decoder::new(File::open("data/shield.tvg").unwrap())
+///     .decode()
+///     .unwrap();
+///
+/// assert_eq!(parsed.commands, decoded.commands);
+///
+/// // Inline colors are added to the color table
+/// let parsed = parse_text(
+///     "(tvg 1 (24 24 1/1 u8888 default) () (
+///         (fill_rectangles (flat (1 0 0)) ((0 0 24 24)))
+///     ))",
+/// )
+/// .unwrap();
+///
+/// assert_eq!(parsed.color_table.len(), 1);
+/// ```
+pub fn parse_text(text: &str) -> Result<Image> {
+    let mut nodes = Node::parse_all(text)?;
+    ensure!(
+        nodes.len() == 1,
+        "expected a single (tvg ...) expression, found {}",
+        nodes.len()
+    );
+
+    let document = nodes.remove(0);
+    let items = document.list()?;
+    ensure!(
+        items.len() == 5,
+        "expected (tvg <version> <header> <colors> <commands>), found {} items",
+        items.len()
+    );
+    ensure!(
+        items[0].atom()? == "tvg",
+        "document must start with tvg, found {}",
+        items[0].atom()?
+    );
+
+    let mut parser = TextParser {
+        color_table: Vec::new(),
+    };
+
+    let header = parser.header(items[1].number()?, &items[2])?;
+
+    for color in items[3].list()? {
+        let color = color_literal_value(color)?;
+        parser.color_table.push(color);
+    }
+
+    let commands = items[4]
+        .list()?
+        .iter()
+        .enumerate()
+        .map(|(index, command)| {
+            parser
+                .command(command)
+                .wrap_err_with(|| format!("invalid command {}", index))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Image {
+        header,
+        color_table: parser.color_table,
+        commands,
+        trailer: Vec::new(),
+        labels: Default::default(),
+        bounds: None,
+    })
+}
+
+impl Image {
+    /// Parse an image from the TinyVG text format, see `parse_text`
+    ///
+    /// ```
+    /// # use tinyvg::Image;
+    /// for path in [
+    ///     "data/shield.tvgt",
+    ///     "data/everything.tvgt",
+    ///     "data/app_icon.tvgt",
+    ///     "data/flowchart.tvgt",
+    /// ] {
+    ///     let text = std::fs::read_to_string(path).unwrap();
+    ///     let image = Image::from_text(&text).unwrap();
+    ///
+    ///     // Writing and parsing again doesn't change the image
+    ///     assert_eq!(Image::from_text(&image.to_text()).unwrap(), image);
+    /// }
+    /// ```
+    pub fn from_text(text: &str) -> Result<Image> {
+        parse_text(text)
+    }
+
+    /// Write this image in the TinyVG text format, see `write_text`
+    pub fn to_text(&self) -> String {
+        write_text(self)
+    }
+}
+
+/// An s-expression, either a bare word or a parenthesized list
+enum Node<'a> {
+    Atom(&'a str),
+    List(Vec<Node<'a>>),
+}
+
+impl<'a> Node<'a> {
+    fn parse_all(text: &'a str) -> Result<Vec<Node<'a>>> {
+        let mut stack = vec![Vec::new()];
+        let mut rest = text;
+
+        loop {
+            rest = rest.trim_start();
+
+            let next = match rest.chars().next() {
+                Some(next) => next,
+                None => break,
+            };
+
+            match next {
+                '(' => {
+                    stack.push(Vec::new());
+                    rest = &rest[1..];
+                }
+                ')' => {
+                    let list = stack.pop().filter(|_| !stack.is_empty());
+                    let list = list.ok_or_else(|| format_err!("unexpected )"))?;
+                    stack.last_mut().unwrap().push(Node::List(list));
+                    rest = &rest[1..];
+                }
+                _ => {
+                    let end = rest
+                        .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+                        .unwrap_or(rest.len());
+                    stack.last_mut().unwrap().push(Node::Atom(&rest[..end]));
+                    rest = &rest[end..];
+                }
+            }
+        }
+
+        ensure!(stack.len() == 1, "unexpected end of input, missing )");
+
+        Ok(stack.pop().unwrap())
+    }
+
+    fn atom(&self) -> Result<&'a str> {
+        match self {
+            Node::Atom(atom) => Ok(*atom),
+            Node::List(_) => bail!("expected a value, found a list"),
+        }
+    }
+
+    fn list(&self) -> Result<&[Node<'a>]> {
+        match self {
+            Node::List(items) => Ok(items),
+            Node::Atom(atom) => bail!("expected a list, found {}", atom),
+        }
+    }
+
+    fn number<T>(&self) -> Result<T>
+    where
+        T: std::str::FromStr,
+        T::Err: Into<Error>,
+    {
+        let atom = self.atom()?;
+
+        atom.parse()
+            .wrap_err_with(|| format!("invalid number {}", atom))
+    }
+
+    fn boolean(&self) -> Result<bool> {
+        match self.atom()? {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            other => bail!("expected true or false, found {}", other),
+        }
+    }
+
+    fn point(&self) -> Result<Point> {
+        match self.list()? {
+            [x, y] => Ok(Point {
+                x: x.number()?,
+                y: y.number()?,
+            }),
+            items => bail!("expected a point (x y), found {} items", items.len()),
+        }
+    }
+}
+
+struct TextParser {
+    color_table: Vec<Color>,
+}
+
+impl TextParser {
+    fn header(&self, version: u8, header: &Node) -> Result<Header> {
+        let (width, height, scale, color_encoding, coordinate_range) = match header.list()? {
+            [width, height, scale, color_encoding, coordinate_range] => {
+                (width, height, scale, color_encoding, coordinate_range)
+            }
+            items => bail!(
+                "expected header (width height scale encoding range), found {} items",
+                items.len()
+            ),
+        };
+
+        let scale = scale.atom()?;
+        let denominator: u32 = scale
+            .strip_prefix("1/")
+            .ok_or_else(|| format_err!("scale must be written as 1/N, found {}", scale))?
+            .parse()
+            .wrap_err_with(|| format!("invalid scale {}", scale))?;
+        ensure!(
+            denominator.is_power_of_two() && denominator <= 1 << 15,
+            "scale denominator must be a power of two up to 32768, found {}",
+            denominator
+        );
+
+        let color_encoding = match color_encoding.atom()? {
+            "u8888" => ColorEncoding::Rgba8888,
+            "u565" => ColorEncoding::Rgb565,
+            "f32" => ColorEncoding::RgbaF32,
+            other => bail!("unknown color encoding {}", other),
+        };
+
+        let coordinate_range = match coordinate_range.atom()? {
+            "default" => CoordinateRange::Default,
+            "reduced" => CoordinateRange::Reduced,
+            "enhanced" => CoordinateRange::Enhanced,
+            other => bail!("unknown coordinate range {}", other),
+        };
+
+        Ok(Header {
+            version,
+            scale: denominator.trailing_zeros() as u8,
+            color_encoding,
+            coordinate_range,
+            width: width.number()?,
+            height: height.number()?,
+        })
+    }
+
+    fn command(&mut self, command: &Node) -> Result<Command> {
+        let (name, args) = match command.list()? {
+            [name, args @ ..] => (name.atom()?, args),
+            [] => bail!("empty command"),
+        };
+
+        let (fill_name, outlined) = match name.strip_prefix("outline_") {
+            Some(fill_name) => (fill_name, true),
+            None => (name, false),
+        };
+
+        if fill_name.starts_with("fill_") {
+            let expected = if outlined { 4 } else { 2 };
+            ensure!(
+                args.len() == expected,
+                "{} expects {} arguments, found {}",
+                name,
+                expected,
+                args.len()
+            );
+
+            let fill_style = self.style(&args[0])?;
+            let outline = if outlined {
+                Some(OutlineStyle {
+                    line_style: self.style(&args[1])?,
+                    line_width: args[2].number()?,
+                })
+            } else {
+                None
+            };
+            let items = args[expected - 1].list()?;
+
+            return match fill_name {
+                "fill_polygon" => Ok(Command::FillPolygon {
+                    fill_style,
+                    polygon: items.iter().map(Node::point).collect::<Result<_>>()?,
+                    outline,
+                }),
+                "fill_rectangles" => Ok(Command::FillRectangles {
+                    fill_style,
+                    rectangles: items.iter().map(rectangle).collect::<Result<_>>()?,
+                    outline,
+                }),
+                "fill_path" => Ok(Command::FillPath {
+                    fill_style,
+                    path: path(items)?,
+                    outline,
+                }),
+                _ => bail!("unknown command {}", name),
+            };
+        }
+
+        ensure!(
+            args.len() == 3,
+            "{} expects 3 arguments, found {}",
+            name,
+            args.len()
+        );
+
+        let line_style = self.style(&args[0])?;
+        let line_width = args[1].number()?;
+        let items = args[2].list()?;
+
+        match name {
+            "draw_lines" => Ok(Command::DrawLines {
+                line_style,
+                line_width,
+                lines: items.iter().map(line).collect::<Result<_>>()?,
+            }),
+            "draw_line_loop" | "draw_line_strip" => Ok(Command::DrawLineLoop {
+                line_style,
+                line_width,
+                close_path: name == "draw_line_loop",
+                points: items.iter().map(Node::point).collect::<Result<_>>()?,
+            }),
+            "draw_line_path" => Ok(Command::DrawLinePath {
+                line_style,
+                line_width,
+                path: path(items)?,
+            }),
+            _ => bail!("unknown command {}", name),
+        }
+    }
+
+    fn style(&mut self, style: &Node) -> Result<Style> {
+        match style.list()? {
+            [kind, color] if kind.atom()? == "flat" => Ok(Style::FlatColor {
+                color_index: self.color(color)?,
+            }),
+            [kind, point_0, point_1, color_0, color_1] => {
+                let point_0 = point_0.point()?;
+                let point_1 = point_1.point()?;
+                let color_index_0 = self.color(color_0)?;
+                let color_index_1 = self.color(color_1)?;
+
+                match kind.atom()? {
+                    "linear" => Ok(Style::LinearGradient {
+                        point_0,
+                        point_1,
+                        color_index_0,
+                        color_index_1,
+                    }),
+                    "radial" => Ok(Style::RadialGradient {
+                        point_0,
+                        point_1,
+                        color_index_0,
+                        color_index_1,
+                    }),
+                    other => bail!("unknown style {}", other),
+                }
+            }
+            _ => bail!("expected (flat color), (linear ...) or (radial ...) style"),
+        }
+    }
+
+    /// Resolve a color reference, which is either an index into the color
+    /// table or an inline color
+    fn color(&mut self, color: &Node) -> Result<usize> {
+        if let Node::Atom(_) = color {
+            return color.number();
+        }
+
+        let color = color_literal_value(color)?;
+        let existing = self
+            .color_table
+            .iter()
+            .position(|c| c.as_rgba_u32() == color.as_rgba_u32());
+
+        Ok(existing.unwrap_or_else(|| {
+            self.color_table.push(color);
+            self.color_table.len() - 1
+        }))
+    }
+}
+
+fn color_literal_value(color: &Node) -> Result<Color> {
+    let channels = color
+        .list()?
+        .iter()
+        .map(Node::number)
+        .collect::<Result<Vec<f64>>>()?;
+
+    match channels[..] {
+        [red, green, blue] => Ok(Color::rgb(red, green, blue)),
+        [red, green, blue, alpha] => Ok(Color::rgba(red, green, blue, alpha)),
+        _ => bail!(
+            "expected a color (r g b) or (r g b a), found {} values",
+            channels.len()
+        ),
+    }
+}
+
+fn rectangle(rect: &Node) -> Result<Rect> {
+    match rect.list()? {
+        [x, y, width, height] => {
+            let x: f64 = x.number()?;
+            let y: f64 = y.number()?;
+
+            Ok(Rect::new(
+                x,
+                y,
+                x + width.number::<f64>()?,
+                y + height.number::<f64>()?,
+            ))
+        }
+        items => bail!(
+            "expected a rectangle (x y width height), found {} items",
+            items.len()
+        ),
+    }
+}
+
+fn line(line: &Node) -> Result<Line> {
+    match line.list()? {
+        [p0, p1] => Ok(Line::new(p0.point()?, p1.point()?)),
+        items => bail!("expected a line ((x y) (x y)), found {} items", items.len()),
+    }
+}
+
+fn path(items: &[Node]) -> Result<Vec<Segment>> {
+    ensure!(
+        items.len().is_multiple_of(2),
+        "path must consist of (start) (commands) pairs"
+    );
+
+    items
+        .chunks(2)
+        .map(|segment| {
+            Ok(Segment {
+                start: segment[0].point()?,
+                commands: segment[1]
+                    .list()?
+                    .iter()
+                    .map(segment_command)
+                    .collect::<Result<_>>()?,
+            })
+        })
+        .collect()
+}
+
+fn segment_command(command: &Node) -> Result<SegmentCommand> {
+    let (name, width, args) = match command.list()? {
+        [name, width, args @ ..] => (name.atom()?, width, args),
+        _ => bail!("expected a path command (name width ...)"),
+    };
+
+    let line_width = match width.atom()? {
+        "-" => None,
+        _ => Some(width.number()?),
+    };
+
+    // The decoder flips the sweep flag of the binary format, the text format
+    // uses the binary value
+    let kind = match (name, args) {
+        ("line", [x, y]) => SegmentCommandKind::Line {
+            end: Point {
+                x: x.number()?,
+                y: y.number()?,
+            },
+        },
+        ("horiz", [x]) => SegmentCommandKind::HorizontalLine { x: x.number()? },
+        ("vert", [y]) => SegmentCommandKind::VerticalLine { y: y.number()? },
+        ("bezier", [control_0, control_1, point_1]) => SegmentCommandKind::CubicBezier {
+            control_0: control_0.point()?,
+            control_1: control_1.point()?,
+            point_1: point_1.point()?,
+        },
+        ("quadratic_bezier", [control, point_1]) => SegmentCommandKind::QuadraticBezier {
+            control: control.point()?,
+            point_1: point_1.point()?,
+        },
+        ("arc_circle", [radius, large, sweep, target]) => SegmentCommandKind::ArcCircle {
+            large: large.boolean()?,
+            sweep: !sweep.boolean()?,
+            radius: radius.number()?,
+            target: target.point()?,
+        },
+        ("arc_ellipse", [radius_x, radius_y, rotation, large, sweep, target]) => {
+            SegmentCommandKind::ArcEllipse {
+                large: large.boolean()?,
+                sweep: !sweep.boolean()?,
+                radius_x: radius_x.number()?,
+                radius_y: radius_y.number()?,
+                rotation: rotation.number()?,
+                target: target.point()?,
+            }
+        }
+        ("close", []) => SegmentCommandKind::ClosePath,
+        _ => bail!(
+            "unknown path command {} with {} arguments",
+            name,
+            args.len()
+        ),
+    };
+
+    Ok(SegmentCommand { kind, line_width })
+}
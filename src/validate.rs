@@ -0,0 +1,167 @@
+//! Checks that an image conforms to the TinyVG specification. Decoding is
+//! lenient by default, so a file that breaks the rules in ways the decoder
+//! can work around decodes without complaint and may only fail later, e.g.
+//! when rendering references a color that doesn't exist.
+//!
+//! `Image::validate` checks an image in memory. `Decoder::with_strict` also
+//! checks how the file is encoded, and fails at the first violation with a
+//! `Error::Validation`.
+//!
+//! ```
+//! # use tinyvg::Image;
+//! # use tinyvg::format::{Command, Rect, Style};
+//! # use tinyvg::validate::ValidationError;
+//! let mut image = Image::empty(16, 0);
+//! image.commands.push(Command::FillRectangles {
+//!     fill_style: Style::FlatColor { color_index: 3 },
+//!     rectangles: vec![Rect::new(0.0, 0.0, 16.0, 16.0)],
+//!     outline: None,
+//! });
+//!
+//! assert_eq!(
+//!     image.validate().unwrap_err(),
+//!     [
+//!         ValidationError::EmptySize {
+//!             width: 16,
+//!             height: 0
+//!         },
+//!         ValidationError::ColorIndexOutOfRange {
+//!             command: 0,
+//!             index: 3,
+//!             colors: 0
+//!         },
+//!     ]
+//! );
+//! ```
+
+use std::fmt;
+
+use crate::format::{Command, Image};
+
+/// A violation of the TinyVG specification
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// The version in the header is not 1
+    UnsupportedVersion(u8),
+
+    /// The width or height in the header is zero
+    EmptySize {
+        /// Width in the header
+        width: u32,
+
+        /// Height in the header
+        height: u32,
+    },
+
+    /// A style of a command references a color past the end of the color
+    /// table
+    ColorIndexOutOfRange {
+        /// Index of the command
+        command: usize,
+
+        /// The referenced color index
+        index: usize,
+
+        /// Number of colors in the color table
+        colors: usize,
+    },
+
+    /// A variable-length integer ends in a zero byte, so it is not encoded
+    /// in the fewest bytes. Only checked by `Decoder::with_strict`.
+    NonCanonicalVarUint {
+        /// Offset of the integer's first byte from the start of the file
+        offset: usize,
+    },
+
+    /// Bits that the specification reserves are set. Only checked by
+    /// `Decoder::with_strict`.
+    ReservedBitsSet {
+        /// Offset of the byte from the start of the file
+        offset: usize,
+    },
+
+    /// The input ends without an end-of-document marker. Only checked by
+    /// `Decoder::with_strict`.
+    MissingEndOfDocument,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::UnsupportedVersion(version) => {
+                write!(f, "version is {}, but must be 1", version)
+            }
+            ValidationError::EmptySize { width, height } => {
+                write!(f, "size is {}x{}, but must not be zero", width, height)
+            }
+            ValidationError::ColorIndexOutOfRange {
+                command,
+                index,
+                colors,
+            } => write!(
+                f,
+                "command {} uses color {}, but the color table has {} colors",
+                command, index, colors
+            ),
+            ValidationError::NonCanonicalVarUint { offset } => write!(
+                f,
+                "variable-length integer at byte {} is longer than needed",
+                offset
+            ),
+            ValidationError::ReservedBitsSet { offset } => {
+                write!(f, "reserved bits are set in byte {}", offset)
+            }
+            ValidationError::MissingEndOfDocument => {
+                write!(f, "input ends without an end-of-document marker")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl Image {
+    /// Check that this image conforms to the TinyVG specification, and list
+    /// every violation if it doesn't. See the module documentation.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+
+        if self.header.version != 1 {
+            errors.push(ValidationError::UnsupportedVersion(self.header.version));
+        }
+
+        let (width, height) = (self.header.width, self.header.height);
+        if width == 0 || height == 0 {
+            errors.push(ValidationError::EmptySize { width, height });
+        }
+
+        for (index, command) in self.commands.iter().enumerate() {
+            errors.extend(check_color_indices(index, command, self.color_table.len()));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The first color index of a command that is past the end of a color table
+/// with `colors` colors
+pub(crate) fn check_color_indices(
+    index: usize,
+    command: &Command,
+    colors: usize,
+) -> Option<ValidationError> {
+    command
+        .styles()
+        .flat_map(|style| style.color_indices())
+        .find(|color| *color >= colors)
+        .map(|color| ValidationError::ColorIndexOutOfRange {
+            command: index,
+            index: color,
+            colors,
+        })
+}
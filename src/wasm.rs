@@ -0,0 +1,91 @@
+//! Bindings for JavaScript with `wasm-bindgen`, so that web apps can decode
+//! TinyVG files and draw them on a canvas. Enabled by the `wasm` feature,
+//! which renders with `tiny-skia` when built with `default-features = false`.
+//! Build the module with `cargo rustc --crate-type cdylib` for
+//! `wasm32-unknown-unknown` and generate `pkg/` with `wasm-bindgen`, see the
+//! README.
+//!
+//! ```js
+//! import init, { decode } from "./pkg/tinyvg.js";
+//!
+//! await init();
+//! const bytes = new Uint8Array(await (await fetch("icon.tvg")).arrayBuffer());
+//! const image = decode(bytes);
+//!
+//! const width = Math.round(image.width * 2), height = Math.round(image.height * 2);
+//! const pixels = image.renderRgba(2);
+//! canvas.getContext("2d").putImageData(new ImageData(pixels, width, height), 0, 0);
+//! ```
+
+use js_sys::Uint8ClampedArray;
+use wasm_bindgen::prelude::*;
+
+use crate::error::{full_message, Error};
+use crate::{Decoder, Image, RenderOptions, RenderSize};
+
+/// A decoded TinyVG image, `Image` in JavaScript
+#[wasm_bindgen(js_name = Image)]
+pub struct JsImage {
+    image: Image,
+}
+
+#[wasm_bindgen(js_class = Image)]
+impl JsImage {
+    /// Width from the header, in image units
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.image.header.width
+    }
+
+    /// Height from the header, in image units
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.image.header.height
+    }
+
+    /// Number of draw commands
+    #[wasm_bindgen(getter, js_name = commandCount)]
+    pub fn command_count(&self) -> usize {
+        self.image.commands.len()
+    }
+
+    /// Render at `scale` times the size in the header to straight RGBA
+    /// pixels, ready for `new ImageData(pixels, width, height)`
+    #[wasm_bindgen(js_name = renderRgba)]
+    pub fn render_rgba(&self, scale: f64) -> Result<Uint8ClampedArray, JsError> {
+        let pixmap = self
+            .image
+            .render_pixmap_scaled(RenderSize::Scale(scale), &RenderOptions::default())
+            .map_err(js_error)?;
+
+        Ok(Uint8ClampedArray::from(pixmap.as_rgba8().as_ref()))
+    }
+
+    /// Encode the image as TinyVG binary data
+    pub fn encode(&self) -> Result<Vec<u8>, JsError> {
+        let mut bytes = Vec::new();
+        self.image.encode(&mut bytes).map_err(js_error)?;
+
+        Ok(bytes)
+    }
+}
+
+/// Decode a TinyVG file, with the limits of `DecodeOptions::default`
+#[wasm_bindgen]
+pub fn decode(bytes: &[u8]) -> Result<JsImage, JsError> {
+    let image = Decoder::new(bytes).decode().map_err(js_error)?;
+
+    Ok(JsImage { image })
+}
+
+/// Decode a TinyVG file and render it at `scale` times the size in its
+/// header, see `Image.renderRgba`
+#[wasm_bindgen(js_name = renderRgba)]
+pub fn render_rgba(bytes: &[u8], scale: f64) -> Result<Uint8ClampedArray, JsError> {
+    decode(bytes)?.render_rgba(scale)
+}
+
+/// A JavaScript `Error` with the causes of an error, outermost first
+fn js_error(error: Error) -> JsError {
+    JsError::new(&full_message(&error))
+}